@@ -6,7 +6,7 @@
 // use indexer_sdk::client::drect::DirectClient;
 // use indexer_sdk::client::event::ClientEvent;
 // use indexer_sdk::client::Client;
-// use indexer_sdk::configuration::base::{IndexerConfiguration, NetConfiguration, ZMQConfiguration};
+// use indexer_sdk::configuration::base::{IndexerConfiguration, RpcConfiguration, ZMQConfiguration};
 // use indexer_sdk::event::TxIdType;
 // use indexer_sdk::factory::common::async_create_and_start_processor;
 // use indexer_sdk::storage::StorageProcessor;
@@ -29,7 +29,7 @@
 //                 zmq_url: "tcp://0.0.0.0:28332".to_string(),
 //                 zmq_topic: vec!["sequence".to_string(), "rawtx".to_string()],
 //             },
-//             net: NetConfiguration {
+//             net: RpcConfiguration {
 //                 url: "http://localhost:18443".to_string(),
 //                 username: "bitcoinrpc".to_string(),
 //                 password: "bitcoinrpc".to_string(),
@@ -168,10 +168,9 @@
 //     }
 // }
 
-use indexer_sdk::configuration::base::IndexerConfiguration;
-use indexer_sdk::configuration::base::ZMQConfiguration;
+use indexer_sdk::configuration::base::{IndexerConfiguration, StorageConfiguration};
 use indexer_sdk::factory::common::sync_create_and_start_processor;
-use log::LevelFilter;
+use indexer_sdk::logging::init_logging;
 use std::cell::RefCell;
 use std::rc::Rc;
 use std::sync::{Arc, Mutex};
@@ -186,20 +185,19 @@ use storage::MockStorage;
 use sync::MockSync;
 
 fn main() {
-    env_logger::builder()
-        .filter_level(LevelFilter::Debug)
-        .format_target(false)
-        .init();
-    let client = sync_create_and_start_processor(IndexerConfiguration {
-        mq: ZMQConfiguration {
-            zmq_url: "tcp://0.0.0.0:28332".to_string(),
-            zmq_topic: vec!["sequence".to_string(), "rawtx".to_string()],
+    // e.g. cargo run -- indexer.toml
+    let cfg = match std::env::args().nth(1) {
+        Some(path) => IndexerConfiguration::from_file(path).expect("invalid configuration"),
+        None => IndexerConfiguration {
+            storage: StorageConfiguration {
+                db_path: "./db".to_string(),
+                ..Default::default()
+            },
+            ..Default::default()
         },
-        net: Default::default(),
-        db_path: "./db".to_string(),
-        save_block_cache_count: 10,
-        log_configuration: Default::default(),
-    });
+    };
+    let _logger = init_logging(&cfg.log_configuration).expect("invalid log configuration");
+    let client = sync_create_and_start_processor(cfg).expect("start processor failed");
 
     let (notify_tx, notify_rx) = async_channel::unbounded();
     let storage = MockStorage::new(client.clone());
@@ -40,19 +40,20 @@ impl MockPending {
     fn handle_event(&mut self, event: ClientEvent) {
         match event {
             ClientEvent::Transaction(tx) => {
-                let response = self.simulate_tx(tx);
+                let response = self.simulate_tx(tx.tx);
                 // self.client.update_delta(response).unwrap();
             }
             ClientEvent::TxDroped(tx) => {
                 self.client.remove_tx_traces(vec![tx.clone()]).unwrap();
             }
-            ClientEvent::TxConfirmed(tx) => {}
+            ClientEvent::TxConfirmed(tx, _) => {}
             ClientEvent::GetHeight => {
                 let synchronizer = self.synchronizer.borrow();
                 let number = self.block_number.lock().unwrap();
                 let number = *number;
                 self.client.report_height(number).unwrap();
             }
+            _ => {}
         }
     }
     fn simulate_tx(&mut self, tx: Transaction) -> TransactionDelta {
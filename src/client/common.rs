@@ -1,23 +1,59 @@
-use crate::client::event::ClientEvent;
+use crate::client::cancel::CancellationToken;
+use crate::client::event::{delivered, ClientEvent};
+use crate::client::filter::EventFilter;
+use crate::client::stream::EventStream;
 use crate::client::Client;
+use crate::configuration::base::IndexerConfiguration;
+use crate::configuration::reload::reload;
 use crate::dispatcher::event::DispatchEvent;
-use crate::error::IndexerResult;
-use crate::event::{AddressType, BalanceType, IndexerEvent, TokenType};
+use crate::error::{IndexerError, IndexerResult};
+use crate::event::{
+    AddressType, BalanceSender, BalanceType, IndexerEvent, ReplySender, TokenType, TxIdType,
+};
+use crate::health::{ComponentHealth, HealthStatus};
 use crate::types::delta::TransactionDelta;
-use log::debug;
+use crate::types::request::{BlockId, ReorgReport};
+use crate::types::response::{SimulationResult, StatusResponse};
+use bitcoincore_rpc::bitcoin::{Block, Transaction};
+use log::{debug, error};
+use std::collections::VecDeque;
+use std::future::{poll_fn, Future};
+use std::pin::pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Wake};
+use std::thread::{self, Thread};
+use std::time::{Duration, Instant};
 
 #[repr(C)]
 #[derive(Clone)]
 pub struct CommonClient {
     pub(crate) rx: async_channel::Receiver<ClientEvent>,
+    // confirmations,removals and reorgs,always drained before rx
+    pub(crate) priority_rx: async_channel::Receiver<ClientEvent>,
     pub(crate) tx: async_channel::Sender<DispatchEvent>,
+    // index of this client in the processor,clients of one processor filter and replay apart
+    pub(crate) consumer: u32,
+    pub(crate) timeout: Option<Duration>,
+    pub(crate) cancel: CancellationToken,
+    // peeked and requeued events,taken before the channels.
+    // the flag is set once the event counted as delivered,a peek does not count
+    pub(crate) front: Arc<Mutex<VecDeque<(ClientEvent, bool)>>>,
 }
 
 impl Default for CommonClient {
     fn default() -> Self {
         let (tx, _) = async_channel::unbounded();
         let (_, rx) = async_channel::unbounded();
-        Self { rx, tx }
+        let (_, priority_rx) = async_channel::unbounded();
+        Self {
+            rx,
+            priority_rx,
+            tx,
+            consumer: 0,
+            timeout: None,
+            cancel: Default::default(),
+            front: Default::default(),
+        }
     }
 }
 
@@ -28,8 +64,7 @@ impl Client for CommonClient {
     }
 
     async fn push_event(&self, event: DispatchEvent) -> IndexerResult<()> {
-        self.tx.send(event).await.unwrap();
-        Ok(())
+        Ok(self.guard("push_event", self.tx.send(event)).await??)
     }
 
     async fn get_balance(
@@ -37,99 +72,602 @@ impl Client for CommonClient {
         address_type: AddressType,
         token_type: TokenType,
     ) -> IndexerResult<BalanceType> {
-        self.do_get_balance(address_type, token_type)
+        self.do_get_balance(address_type, token_type).await
+    }
+
+    async fn get_balances(
+        &mut self,
+        keys: Vec<(AddressType, TokenType)>,
+    ) -> IndexerResult<Vec<BalanceType>> {
+        let (tx, rx) = ReplySender::new();
+        self.guard("get_balances", async {
+            self.tx
+                .send(DispatchEvent::IndexerEvent(IndexerEvent::GetBalances(
+                    keys, tx,
+                )))
+                .await?;
+            rx.await?
+        })
+        .await?
+    }
+
+    async fn get_transaction(&mut self, tx_id: TxIdType) -> IndexerResult<Option<Transaction>> {
+        let (tx, rx) = ReplySender::new();
+        self.guard("get_transaction", async {
+            self.tx
+                .send(DispatchEvent::IndexerEvent(IndexerEvent::GetTransaction(
+                    tx_id, tx,
+                )))
+                .await?;
+            rx.await?
+        })
+        .await?
+    }
+
+    async fn get_block(&mut self, id: BlockId) -> IndexerResult<Option<Block>> {
+        let (tx, rx) = ReplySender::new();
+        self.guard("get_block", async {
+            self.tx
+                .send(DispatchEvent::IndexerEvent(IndexerEvent::GetBlock(id, tx)))
+                .await?;
+            rx.await?
+        })
+        .await?
+    }
+
+    async fn broadcast_transaction(&self, data: Transaction) -> IndexerResult<TxIdType> {
+        let (tx, rx) = ReplySender::new();
+        self.guard("broadcast_transaction", async {
+            self.tx
+                .send(DispatchEvent::IndexerEvent(
+                    IndexerEvent::BroadcastTransaction(data, tx),
+                ))
+                .await?;
+            rx.await?
+        })
+        .await?
+    }
+
+    async fn status(&self) -> IndexerResult<StatusResponse> {
+        let (tx, rx) = ReplySender::new();
+        self.guard("status", async {
+            self.tx
+                .send(DispatchEvent::IndexerEvent(IndexerEvent::GetStatus(tx)))
+                .await?;
+            Ok(rx.await?)
+        })
+        .await?
+    }
+
+    async fn reload(&self, cfg: IndexerConfiguration) -> IndexerResult<Vec<String>> {
+        self.guard("reload", reload(&self.tx, cfg)).await?
+    }
+
+    async fn health(&self) -> IndexerResult<Vec<ComponentHealth>> {
+        let (tx, rx) = ReplySender::new();
+        let ret = self
+            .guard("health", async {
+                self.tx
+                    .send(DispatchEvent::IndexerEvent(IndexerEvent::GetHealth(tx)))
+                    .await?;
+                Ok::<_, IndexerError>(rx.await?)
+            })
+            .await
+            .and_then(|v| v);
+        let health = ret.unwrap_or_else(|e| {
+            ComponentHealth::new("processor", HealthStatus::Failing(e.to_string()))
+        });
+        Ok(vec![health])
+    }
+
+    async fn simulate_delta(&mut self, delta: TransactionDelta) -> IndexerResult<SimulationResult> {
+        let (tx, rx) = ReplySender::new();
+        self.guard("simulate_delta", async {
+            self.tx
+                .send(DispatchEvent::IndexerEvent(IndexerEvent::SimulateDelta(
+                    delta, tx,
+                )))
+                .await?;
+            rx.await?
+        })
+        .await?
     }
 
     async fn update_delta(&mut self, result: TransactionDelta) -> IndexerResult<()> {
-        self.do_update_delta(result)
+        self.send_event("update_delta", IndexerEvent::UpdateDelta(result))
+            .await
+    }
+
+    async fn update_deltas(&mut self, results: Vec<TransactionDelta>) -> IndexerResult<()> {
+        self.send_event("update_deltas", IndexerEvent::UpdateDeltas(results))
+            .await
+    }
+    async fn replay_dead_letters(&self) -> IndexerResult<()> {
+        self.send_event("replay_dead_letters", IndexerEvent::ReplayDeadLetters)
+            .await
+    }
+    async fn replay_from(&self, seq: u64) -> IndexerResult<()> {
+        self.send_event(
+            "replay_from",
+            IndexerEvent::ReplayJournal(self.consumer, seq),
+        )
+        .await
+    }
+    async fn subscribe(&self, filter: EventFilter) -> IndexerResult<()> {
+        self.send_event("subscribe", IndexerEvent::Subscribe(self.consumer, filter))
+            .await
+    }
+    async fn commit_cursor(&self, seq: u64) -> IndexerResult<()> {
+        self.send_event(
+            "commit_cursor",
+            IndexerEvent::CommitCursor(self.consumer, seq),
+        )
+        .await
+    }
+    async fn ack(&self, tx_id: TxIdType) -> IndexerResult<()> {
+        self.send_event("ack", IndexerEvent::Ack(self.consumer, tx_id))
+            .await
+    }
+    async fn nack(&self, tx_id: TxIdType) -> IndexerResult<()> {
+        self.send_event("nack", IndexerEvent::Nack(self.consumer, tx_id))
+            .await
+    }
+    async fn pause(&self) -> IndexerResult<()> {
+        self.send_event("pause", IndexerEvent::Pause).await
+    }
+    async fn resume(&self) -> IndexerResult<()> {
+        self.send_event("resume", IndexerEvent::Resume).await
+    }
+    async fn peek_event(&self) -> IndexerResult<Option<ClientEvent>> {
+        self.do_peek_data()
+    }
+    async fn requeue(&self, event: ClientEvent) -> IndexerResult<()> {
+        self.do_requeue(event);
+        Ok(())
     }
     fn rx(&self) -> async_channel::Receiver<ClientEvent> {
         self.rx.clone()
     }
 
     async fn report_height(&self, height: u32) -> IndexerResult<()> {
-        self.tx
-            .send_blocking(DispatchEvent::IndexerEvent(IndexerEvent::ReportHeight(
-                height,
-            )))
-            .unwrap();
-        Ok(())
+        self.send_event("report_height", IndexerEvent::ReportHeight(height))
+            .await
     }
-    async fn report_reorg(&self, number: u32) -> IndexerResult<()> {
-        self.tx
-            .send_blocking(DispatchEvent::IndexerEvent(IndexerEvent::ReportReorg(
-                number,
-            )))
-            .unwrap();
-        Ok(())
+    async fn report_reorg(&self, report: ReorgReport) -> IndexerResult<()> {
+        self.send_event("report_reorg", IndexerEvent::ReportReorg(report))
+            .await
     }
 }
 
 impl CommonClient {
     pub fn new(
         rx: async_channel::Receiver<ClientEvent>,
+        priority_rx: async_channel::Receiver<ClientEvent>,
         tx: async_channel::Sender<DispatchEvent>,
+        consumer: u32,
     ) -> Self {
-        Self { rx, tx }
+        Self {
+            rx,
+            priority_rx,
+            tx,
+            consumer,
+            timeout: None,
+            cancel: Default::default(),
+            front: Default::default(),
+        }
+    }
+
+    // calls waiting on the processor fail with IndexerError::Timeout once it passed
+    pub fn with_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    // cancelling the token fails the pending and later calls with IndexerError::Cancelled
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancel = token;
+        self
+    }
+
+    pub fn cancellation_token(&self) -> CancellationToken {
+        self.cancel.clone()
+    }
+
+    pub fn consumer(&self) -> u32 {
+        self.consumer
+    }
+
+    async fn cancellable<F: Future>(&self, f: F) -> IndexerResult<F::Output> {
+        let cancelled = self.cancel.cancelled();
+        tokio::select! {
+            biased;
+            _ = cancelled => Err(IndexerError::Cancelled),
+            v = f => Ok(v),
+        }
+    }
+
+    async fn guard<F: Future>(&self, op: &str, f: F) -> IndexerResult<F::Output> {
+        let f = self.cancellable(f);
+        match self.timeout {
+            Some(timeout) => tokio::time::timeout(timeout, f)
+                .await
+                .map_err(|_| IndexerError::Timeout(op.to_string()))?,
+            None => f.await,
+        }
+    }
+
+    // guard for the sync api,no runtime needed
+    fn block_guard<F: Future>(&self, op: &str, f: F) -> IndexerResult<F::Output> {
+        block_on(self.cancellable(f), self.timeout)
+            .ok_or_else(|| IndexerError::Timeout(op.to_string()))?
     }
 
-    pub(crate) fn do_get_balance(
+    async fn send_event(&self, op: &str, event: IndexerEvent) -> IndexerResult<()> {
+        Ok(self
+            .guard(op, self.tx.send(DispatchEvent::IndexerEvent(event)))
+            .await??)
+    }
+
+    // never blocks the calling thread,safe to await on any executor
+    pub(crate) async fn do_get_balance(
         &self,
         address: AddressType,
-        _: TokenType,
+        token: TokenType,
     ) -> IndexerResult<BalanceType> {
-        let (tx, rx) = crossbeam::channel::bounded(1);
-        self.tx
-            .send_blocking(DispatchEvent::IndexerEvent(IndexerEvent::GetBalance(
-                address, tx,
-            )))
-            .unwrap();
-        let ret = rx.recv().unwrap();
-        Ok(ret)
+        let (tx, rx) = BalanceSender::new();
+        let ret = self
+            .guard("get_balance", async {
+                self.tx
+                    .send(DispatchEvent::IndexerEvent(IndexerEvent::GetBalance(
+                        address, token, tx,
+                    )))
+                    .await?;
+                Ok(rx.await?)
+            })
+            .await?;
+        ret
     }
     pub(crate) fn do_update_delta(&self, delta: TransactionDelta) -> IndexerResult<()> {
-        self.tx
-            .send_blocking(DispatchEvent::IndexerEvent(IndexerEvent::UpdateDelta(
-                delta,
-            )))
-            .unwrap();
-        Ok(())
+        self.sync_push_event(IndexerEvent::UpdateDelta(delta))
+    }
+    pub(crate) fn do_update_deltas(&self, deltas: Vec<TransactionDelta>) -> IndexerResult<()> {
+        self.sync_push_event(IndexerEvent::UpdateDeltas(deltas))
+    }
+    // the next event stays in place,get_event returns it later
+    pub(crate) fn do_peek_data(&self) -> IndexerResult<Option<ClientEvent>> {
+        if let Some((ret, _)) = self.front.lock().unwrap().front() {
+            return Ok(Some(ret.clone()));
+        }
+        let ret = self.try_recv_data()?;
+        if let Some(event) = &ret {
+            self.front
+                .lock()
+                .unwrap()
+                .push_front((event.clone(), false));
+        }
+        Ok(ret)
+    }
+    // the event is delivered again before anything else
+    pub(crate) fn do_requeue(&self, event: ClientEvent) {
+        self.front.lock().unwrap().push_front((event, true));
     }
     pub(crate) fn do_get_data(&self) -> IndexerResult<Option<ClientEvent>> {
-        let res = self.rx.try_recv();
-        return match res {
-            Ok(ret) => Ok(Some(ret)),
-            Err(_) => Ok(None),
+        let ret = match self.pop_front() {
+            Some(ret) => return Ok(Some(ret)),
+            None => self.try_recv_data()?,
         };
+        if let Some(event) = &ret {
+            delivered(event);
+        }
+        Ok(ret)
     }
     pub(crate) fn block_get_data(&self) -> IndexerResult<ClientEvent> {
-        let res = self.rx.recv_blocking();
-        return match res {
-            Ok(ret) => Ok(ret),
-            Err(_) => panic!("recv error"),
-        };
+        if let Some(ret) = self.pop_front() {
+            return Ok(ret);
+        }
+        let ret = self.block_guard("block_get_data", self.recv_data())??;
+        delivered(&ret);
+        Ok(ret)
+    }
+    // a peeked event is delivered when it is taken
+    fn pop_front(&self) -> Option<ClientEvent> {
+        let (ret, counted) = self.front.lock().unwrap().pop_front()?;
+        if !counted {
+            delivered(&ret);
+        }
+        Some(ret)
+    }
+    // the priority lane first,nothing counts as delivered yet
+    fn try_recv_data(&self) -> IndexerResult<Option<ClientEvent>> {
+        if let Ok(ret) = self.priority_rx.try_recv() {
+            return Ok(Some(ret));
+        }
+        match self.rx.try_recv() {
+            Ok(ret) => Ok(Some(ret)),
+            Err(async_channel::TryRecvError::Empty) => Ok(None),
+            Err(async_channel::TryRecvError::Closed) => Err(IndexerError::ChannelClosed),
+        }
+    }
+    // waits on both lanes,the priority one wins when both are ready
+    pub(crate) async fn recv_data(&self) -> Result<ClientEvent, async_channel::RecvError> {
+        let mut high = pin!(self.priority_rx.recv());
+        let mut low = pin!(self.rx.recv());
+        poll_fn(|cx| {
+            if let Poll::Ready(Ok(v)) = high.as_mut().poll(cx) {
+                return Poll::Ready(Ok(v));
+            }
+            low.as_mut().poll(cx)
+        })
+        .await
     }
 
-    pub fn sync_push_event(&self, event: IndexerEvent) {
-        self.tx
-            .send_blocking(DispatchEvent::IndexerEvent(event))
-            .unwrap();
+    // consumes the events with stream combinators instead of polling get_event,
+    // peeked and requeued events are not part of it
+    pub fn event_stream(&self) -> EventStream {
+        EventStream::new(self.priority_rx.clone(), self.rx.clone())
+    }
+
+    pub fn sync_push_event(&self, event: IndexerEvent) -> IndexerResult<()> {
+        self.block_guard(
+            "push_event",
+            self.tx.send(DispatchEvent::IndexerEvent(event)),
+        )??;
+        Ok(())
     }
 
+    // empty once the processor is gone
     pub fn get(&self) -> Vec<u8> {
-        let data = self.do_get_data().unwrap();
-        if data.is_none() {
-            return vec![];
-        }
-        let data = data.unwrap();
+        let data = match self.do_get_data() {
+            Ok(Some(data)) => data,
+            Ok(None) => return vec![],
+            Err(e) => {
+                error!("get event error:{:?}", e);
+                return vec![];
+            }
+        };
         let raw_data = data.to_bytes();
         raw_data
     }
 
+    // empty once the configured timeout passed without an event
     pub fn block_get(&self) -> Vec<u8> {
-        let data = self.block_get_data().unwrap();
+        let data = match self.block_get_data() {
+            Ok(data) => data,
+            Err(IndexerError::Timeout(_)) => return vec![],
+            Err(e) => {
+                error!("block get error:{:?}", e);
+                return vec![];
+            }
+        };
         debug!("get event:{:?}", &data);
         let raw_data = data.to_bytes();
         raw_data
     }
 }
+
+struct ThreadWaker(Thread);
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+// the sync api must not depend on a runtime being around,none once timeout passed
+fn block_on<F: Future>(f: F, timeout: Option<Duration>) -> Option<F::Output> {
+    let deadline = timeout.map(|v| Instant::now() + v);
+    let mut f = pin!(f);
+    let waker = Arc::new(ThreadWaker(thread::current())).into();
+    let mut cx = Context::from_waker(&waker);
+    loop {
+        if let Poll::Ready(v) = f.as_mut().poll(&mut cx) {
+            return Some(v);
+        }
+        match deadline {
+            Some(deadline) => {
+                let now = Instant::now();
+                if now >= deadline {
+                    return None;
+                }
+                thread::park_timeout(deadline - now);
+            }
+            None => thread::park(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_priority_lane() {
+        let (tx, rx) = async_channel::unbounded();
+        let (priority_tx, priority_rx) = async_channel::unbounded();
+        let (dispatch_tx, _dispatch_rx) = async_channel::unbounded();
+        let client = CommonClient::new(rx, priority_rx, dispatch_tx, 0);
+        for _ in 0..3 {
+            tx.send_blocking(ClientEvent::GetHeight).unwrap();
+        }
+        priority_tx
+            .send_blocking(ClientEvent::TxDroped(TxIdType::from_bytes(&[1u8; 32])))
+            .unwrap();
+        assert!(matches!(
+            client.block_get_data().unwrap(),
+            ClientEvent::TxDroped(_)
+        ));
+        assert!(matches!(
+            client.do_get_data().unwrap(),
+            Some(ClientEvent::GetHeight)
+        ));
+
+        // a blocked receiver wakes up for a priority event
+        let sender = std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(20));
+            priority_tx.send_blocking(ClientEvent::Resynced).unwrap();
+        });
+        client.do_get_data().unwrap();
+        client.do_get_data().unwrap();
+        assert!(matches!(
+            client.block_get_data().unwrap(),
+            ClientEvent::Resynced
+        ));
+        sender.join().unwrap();
+    }
+
+    #[test]
+    pub fn test_async_get_balance() {
+        let (_, rx) = async_channel::unbounded();
+        let (_, priority_rx) = async_channel::unbounded();
+        let (dispatch_tx, dispatch_rx) = async_channel::unbounded();
+        let mut client = CommonClient::new(rx, priority_rx, dispatch_tx, 0);
+
+        // the answering task shares the only worker thread with the caller
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let balance = rt.block_on(async move {
+            tokio::spawn(async move {
+                if let Ok(DispatchEvent::IndexerEvent(IndexerEvent::GetBalance(_, _, tx))) =
+                    dispatch_rx.recv().await
+                {
+                    tx.clone().send(BalanceType::from(7)).unwrap();
+                    assert!(tx.send(BalanceType::from(8)).is_err());
+                }
+            });
+            client
+                .get_balance(
+                    AddressType::from_bytes(&[1u8; 32]),
+                    TokenType::from_bytes(&[2u8; 20]),
+                )
+                .await
+                .unwrap()
+        });
+        assert_eq!(balance, BalanceType::from(7));
+    }
+
+    #[test]
+    pub fn test_timeout_and_cancel() {
+        let (_tx, rx) = async_channel::unbounded();
+        let (_priority_tx, priority_rx) = async_channel::unbounded();
+        let (dispatch_tx, _dispatch_rx) = async_channel::unbounded();
+        let token = CancellationToken::new();
+        let mut client = CommonClient::new(rx, priority_rx, dispatch_tx, 0)
+            .with_timeout(Some(Duration::from_millis(20)))
+            .with_cancellation(token.clone());
+
+        // nobody answers
+        assert!(matches!(
+            client.block_get_data(),
+            Err(IndexerError::Timeout(_))
+        ));
+        assert!(client.block_get().is_empty());
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .unwrap();
+        let address = AddressType::from_bytes(&[1u8; 32]);
+        let token_type = TokenType::from_bytes(&[2u8; 20]);
+        let ret = rt.block_on(client.get_balance(address.clone(), token_type.clone()));
+        assert!(matches!(ret, Err(IndexerError::Timeout(_))));
+
+        let mut client = client.with_timeout(None);
+        let canceller = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(20));
+            token.cancel();
+        });
+        assert!(matches!(
+            client.block_get_data(),
+            Err(IndexerError::Cancelled)
+        ));
+        canceller.join().unwrap();
+        let ret = rt.block_on(client.get_balance(address, token_type));
+        assert!(matches!(ret, Err(IndexerError::Cancelled)));
+        assert!(matches!(
+            client.sync_push_event(IndexerEvent::ReplayDeadLetters),
+            Err(IndexerError::Cancelled)
+        ));
+    }
+
+    #[test]
+    pub fn test_channel_closed() {
+        let (tx, rx) = async_channel::unbounded();
+        let (priority_tx, priority_rx) = async_channel::unbounded();
+        let (dispatch_tx, dispatch_rx) = async_channel::unbounded();
+        let mut client = CommonClient::new(rx, priority_rx, dispatch_tx, 0);
+
+        // the processor answers nothing and goes away
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let ret = rt.block_on(async {
+            tokio::spawn(async move {
+                drop(dispatch_rx.recv().await);
+            });
+            client
+                .get_balance(
+                    AddressType::from_bytes(&[1u8; 32]),
+                    TokenType::from_bytes(&[2u8; 20]),
+                )
+                .await
+        });
+        assert!(matches!(ret, Err(IndexerError::ChannelClosed)));
+        assert!(matches!(
+            client.sync_push_event(IndexerEvent::ReplayDeadLetters),
+            Err(IndexerError::ChannelClosed)
+        ));
+
+        drop(tx);
+        drop(priority_tx);
+        assert!(matches!(
+            client.do_get_data(),
+            Err(IndexerError::ChannelClosed)
+        ));
+        assert!(matches!(
+            client.block_get_data(),
+            Err(IndexerError::ChannelClosed)
+        ));
+        assert!(client.block_get().is_empty());
+    }
+
+    #[test]
+    pub fn test_peek_requeue() {
+        let (tx, rx) = async_channel::unbounded();
+        let (priority_tx, priority_rx) = async_channel::unbounded();
+        let (dispatch_tx, _dispatch_rx) = async_channel::unbounded();
+        let client = CommonClient::new(rx, priority_rx, dispatch_tx, 0);
+        let tx_id = TxIdType::from_bytes(&[1u8; 32]);
+        tx.send_blocking(ClientEvent::GetHeight).unwrap();
+        tx.send_blocking(ClientEvent::Resynced).unwrap();
+
+        assert!(matches!(
+            client.do_peek_data().unwrap(),
+            Some(ClientEvent::GetHeight)
+        ));
+        // a later priority event does not overtake the peeked one
+        priority_tx
+            .send_blocking(ClientEvent::TxDroped(tx_id.clone()))
+            .unwrap();
+        assert!(matches!(
+            client.do_peek_data().unwrap(),
+            Some(ClientEvent::GetHeight)
+        ));
+        assert!(matches!(
+            client.do_get_data().unwrap(),
+            Some(ClientEvent::GetHeight)
+        ));
+
+        let event = client.block_get_data().unwrap();
+        assert!(matches!(event, ClientEvent::TxDroped(_)));
+        client.do_requeue(event);
+        assert!(matches!(
+            client.block_get_data().unwrap(),
+            ClientEvent::TxDroped(_)
+        ));
+        assert!(matches!(
+            client.do_get_data().unwrap(),
+            Some(ClientEvent::Resynced)
+        ));
+        assert!(client.do_peek_data().unwrap().is_none());
+    }
+}
@@ -1,13 +1,34 @@
+use crate::client::cancel::CancellationToken;
 use crate::client::common::CommonClient;
 use crate::client::event::ClientEvent;
+use crate::client::filter::EventFilter;
+use crate::client::metrics::{observe, ClientMetrics};
+use crate::client::stream::EventStream;
 use crate::client::{Client, SyncClient};
+use crate::configuration::base::{IndexerConfiguration, RetryConfiguration};
 use crate::dispatcher::event::DispatchEvent;
 use crate::error::IndexerResult;
 use crate::event::{AddressType, BalanceType, IndexerEvent, TokenType, TxIdType};
-use crate::storage::StorageProcessor;
+use crate::health::{ComponentHealth, HealthCheck};
+use crate::net::{ChainDataSource, RpcSource};
+use crate::processor::lookup::{find_block, find_transaction};
+use crate::processor::simulate::simulate_delta;
+use crate::storage::block_cache::BlockCache;
+use crate::storage::kv::TransactionDeltaWrapper;
+use crate::storage::prefix::DeltaStatus;
+use crate::storage::{StorageProcessor, StorageStats};
+use crate::types::dead_letter::DeadLetter;
 use crate::types::delta::TransactionDelta;
-use crate::types::response::AllBalanceResponse;
+use crate::types::request::BlockId;
+use crate::types::request::Pagination;
+use crate::types::request::ReorgReport;
+use crate::types::response::{
+    AllBalanceResponse, BalanceDetailResponse, PendingTxResponse, SimulationResult, StatusResponse,
+};
+use crate::types::token::TokenMeta;
+use crate::types::transaction::Utxo;
 use async_channel::Receiver;
+use bitcoincore_rpc::bitcoin::{Block, Transaction};
 use std::sync::Arc;
 use tokio::runtime;
 use tokio::runtime::Runtime;
@@ -17,6 +38,11 @@ pub struct DirectClient<T: StorageProcessor + Clone> {
     rt: Arc<Runtime>,
     btc_client: Option<Arc<bitcoincore_rpc::Client>>,
     storage: T,
+    blocks: BlockCache,
+    metrics: Option<Arc<dyn ClientMetrics>>,
+    source: Option<Arc<dyn ChainDataSource>>,
+    // asked next to the processor and the storage
+    health_checks: Vec<Arc<dyn HealthCheck>>,
     pub(crate) base: CommonClient,
 }
 impl<T: StorageProcessor + Clone + Default> Default for DirectClient<T> {
@@ -29,6 +55,10 @@ impl<T: StorageProcessor + Clone + Default> Default for DirectClient<T> {
             rt: Arc::new(rt),
             btc_client: None,
             storage: T::default(),
+            blocks: Default::default(),
+            metrics: None,
+            source: None,
+            health_checks: vec![],
             base: CommonClient::default(),
         }
     }
@@ -38,21 +68,73 @@ impl<T: StorageProcessor + Clone> DirectClient<T> {
         rt: Arc<Runtime>,
         btc_client: Arc<bitcoincore_rpc::Client>,
         storage: T,
+        blocks: BlockCache,
         base: CommonClient,
     ) -> Self {
         Self {
             rt,
+            source: Some(Arc::new(RpcSource::new(
+                btc_client.clone(),
+                RetryConfiguration::default(),
+            ))),
             btc_client: Some(btc_client),
             storage,
+            blocks,
+            metrics: None,
+            health_checks: vec![],
             base,
         }
     }
+
+    pub fn with_health_check(mut self, check: Arc<dyn HealthCheck>) -> Self {
+        self.health_checks.push(check);
+        self
+    }
+
+    async fn do_health(&self) -> IndexerResult<Vec<ComponentHealth>> {
+        let mut ret = self.base.health().await?;
+        for check in &self.health_checks {
+            ret.push(check.health().await);
+        }
+        ret.push(self.storage.health().await);
+        Ok(ret)
+    }
+
+    // txs the sdk never saw and uncached blocks are fetched through it instead of rpc
+    pub fn with_source(mut self, source: Arc<dyn ChainDataSource>) -> Self {
+        self.source = Some(source);
+        self
+    }
+
+    // sat/vB to be mined within target blocks
+    pub async fn estimate_fee(&self, target_blocks: u16) -> IndexerResult<Option<f64>> {
+        match &self.source {
+            Some(source) => source.estimate_fee(target_blocks).await,
+            None => Ok(None),
+        }
+    }
+
+    // every event taken through get_event or block_get_event is reported
+    pub fn with_metrics(mut self, metrics: Arc<dyn ClientMetrics>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    fn observe(&self, event: &ClientEvent) {
+        if let Some(metrics) = &self.metrics {
+            observe(metrics.as_ref(), event);
+        }
+    }
 }
 
 #[async_trait::async_trait]
 impl<T: StorageProcessor + Clone> Client for DirectClient<T> {
     async fn get_event(&self) -> IndexerResult<Option<ClientEvent>> {
-        self.base.get_event().await
+        let ret = self.base.get_event().await?;
+        if let Some(event) = &ret {
+            self.observe(event);
+        }
+        Ok(ret)
     }
 
     async fn push_event(&self, event: DispatchEvent) -> IndexerResult<()> {
@@ -67,9 +149,78 @@ impl<T: StorageProcessor + Clone> Client for DirectClient<T> {
         self.storage.get_balance(&address_type, &token_type).await
     }
 
+    async fn get_balances(
+        &mut self,
+        keys: Vec<(AddressType, TokenType)>,
+    ) -> IndexerResult<Vec<BalanceType>> {
+        self.storage.get_balances(&keys).await
+    }
+
+    async fn get_transaction(&mut self, tx_id: TxIdType) -> IndexerResult<Option<Transaction>> {
+        self.do_get_transaction(&tx_id).await
+    }
+
+    async fn get_block(&mut self, id: BlockId) -> IndexerResult<Option<Block>> {
+        self.do_get_block(&id).await
+    }
+
+    async fn broadcast_transaction(&self, tx: Transaction) -> IndexerResult<TxIdType> {
+        self.base.broadcast_transaction(tx).await
+    }
+
+    async fn status(&self) -> IndexerResult<StatusResponse> {
+        self.base.status().await
+    }
+
+    async fn health(&self) -> IndexerResult<Vec<ComponentHealth>> {
+        self.do_health().await
+    }
+
+    async fn reload(&self, cfg: IndexerConfiguration) -> IndexerResult<Vec<String>> {
+        self.base.reload(cfg).await
+    }
+
+    async fn simulate_delta(&mut self, delta: TransactionDelta) -> IndexerResult<SimulationResult> {
+        simulate_delta(&mut self.storage, &delta).await
+    }
+
     async fn update_delta(&mut self, result: TransactionDelta) -> IndexerResult<()> {
         self.base.update_delta(result).await
     }
+
+    async fn update_deltas(&mut self, results: Vec<TransactionDelta>) -> IndexerResult<()> {
+        self.base.update_deltas(results).await
+    }
+    async fn replay_dead_letters(&self) -> IndexerResult<()> {
+        self.base.replay_dead_letters().await
+    }
+    async fn replay_from(&self, seq: u64) -> IndexerResult<()> {
+        self.base.replay_from(seq).await
+    }
+    async fn subscribe(&self, filter: EventFilter) -> IndexerResult<()> {
+        self.base.subscribe(filter).await
+    }
+    async fn commit_cursor(&self, seq: u64) -> IndexerResult<()> {
+        self.base.commit_cursor(seq).await
+    }
+    async fn ack(&self, tx_id: TxIdType) -> IndexerResult<()> {
+        self.base.ack(tx_id).await
+    }
+    async fn nack(&self, tx_id: TxIdType) -> IndexerResult<()> {
+        self.base.nack(tx_id).await
+    }
+    async fn pause(&self) -> IndexerResult<()> {
+        self.base.pause().await
+    }
+    async fn resume(&self) -> IndexerResult<()> {
+        self.base.resume().await
+    }
+    async fn peek_event(&self) -> IndexerResult<Option<ClientEvent>> {
+        self.base.peek_event().await
+    }
+    async fn requeue(&self, event: ClientEvent) -> IndexerResult<()> {
+        self.base.requeue(event).await
+    }
     fn rx(&self) -> async_channel::Receiver<ClientEvent> {
         self.base.rx()
     }
@@ -77,8 +228,8 @@ impl<T: StorageProcessor + Clone> Client for DirectClient<T> {
     async fn report_height(&self, height: u32) -> IndexerResult<()> {
         self.base.report_height(height).await
     }
-    async fn report_reorg(&self, number: u32) -> IndexerResult<()> {
-        self.base.report_reorg(number).await
+    async fn report_reorg(&self, report: ReorgReport) -> IndexerResult<()> {
+        self.base.report_reorg(report).await
     }
 }
 
@@ -89,48 +240,70 @@ impl<T: StorageProcessor + Clone> DirectClient<T> {
     pub fn block_get(&self) -> Vec<u8> {
         self.base.block_get()
     }
+    pub fn event_stream(&self) -> EventStream {
+        self.base.event_stream()
+    }
+    pub fn consumer(&self) -> u32 {
+        self.base.consumer()
+    }
+    pub fn cancellation_token(&self) -> CancellationToken {
+        self.base.cancellation_token()
+    }
 }
 impl<T: StorageProcessor + Clone> DirectClient<T> {
-    pub fn sync_push_event(&self, event: IndexerEvent) {
-        self.base.sync_push_event(event);
+    // without a bitcoind client only the stored txs are found
+    async fn do_get_block(&self, id: &BlockId) -> IndexerResult<Option<Block>> {
+        match &self.source {
+            Some(source) => find_block(&self.blocks, source.as_ref(), id).await,
+            None => Ok(self.blocks.get(id).map(|(_, block)| block)),
+        }
+    }
+    async fn do_get_transaction(&mut self, tx_id: &TxIdType) -> IndexerResult<Option<Transaction>> {
+        match self.source.clone() {
+            Some(source) => find_transaction(&mut self.storage, source.as_ref(), tx_id).await,
+            None => self.storage.get_raw_tx(tx_id).await,
+        }
+    }
+    pub fn sync_push_event(&self, event: IndexerEvent) -> IndexerResult<()> {
+        self.base.sync_push_event(event)
     }
 }
 
 impl<T: StorageProcessor + Clone> SyncClient for DirectClient<T> {
     fn get_event(&self) -> IndexerResult<Option<ClientEvent>> {
-        self.base.do_get_data()
+        let ret = self.base.do_get_data()?;
+        if let Some(event) = &ret {
+            self.observe(event);
+        }
+        Ok(ret)
     }
 
     fn block_get_event(&self) -> IndexerResult<ClientEvent> {
-        self.base.block_get_data()
+        let ret = self.base.block_get_data()?;
+        self.observe(&ret);
+        Ok(ret)
     }
 
-    fn report_height(&self, height: u32) -> IndexerResult<()> {
-        self.base
-            .tx
-            .send_blocking(DispatchEvent::IndexerEvent(IndexerEvent::ReportHeight(
-                height,
-            )))
-            .unwrap();
+    fn peek_event(&self) -> IndexerResult<Option<ClientEvent>> {
+        self.base.do_peek_data()
+    }
+
+    fn requeue(&self, event: ClientEvent) -> IndexerResult<()> {
+        self.base.do_requeue(event);
         Ok(())
     }
 
-    fn report_reorg(&self, org_number: u32) -> IndexerResult<()> {
+    fn report_height(&self, height: u32) -> IndexerResult<()> {
         self.base
-            .tx
-            .send_blocking(DispatchEvent::IndexerEvent(IndexerEvent::ReportReorg(
-                org_number,
-            )))
-            .unwrap();
-        Ok(())
+            .sync_push_event(IndexerEvent::ReportHeight(height))
+    }
+
+    fn report_reorg(&self, report: ReorgReport) -> IndexerResult<()> {
+        self.base.sync_push_event(IndexerEvent::ReportReorg(report))
     }
 
     fn push_event(&self, event: IndexerEvent) -> IndexerResult<()> {
-        self.base
-            .tx
-            .send_blocking(DispatchEvent::IndexerEvent(event))
-            .unwrap();
-        Ok(())
+        self.base.sync_push_event(event)
     }
 
     fn get_balance(
@@ -142,6 +315,40 @@ impl<T: StorageProcessor + Clone> SyncClient for DirectClient<T> {
             .block_on(async { self.storage.get_balance(&address_type, &token_type).await })
     }
 
+    fn get_balances(
+        &mut self,
+        keys: Vec<(AddressType, TokenType)>,
+    ) -> IndexerResult<Vec<BalanceType>> {
+        self.rt
+            .block_on(async { self.storage.get_balances(&keys).await })
+    }
+
+    fn get_transaction(&mut self, tx_id: TxIdType) -> IndexerResult<Option<Transaction>> {
+        self.rt
+            .clone()
+            .block_on(async { self.do_get_transaction(&tx_id).await })
+    }
+
+    fn get_block(&mut self, id: BlockId) -> IndexerResult<Option<Block>> {
+        self.rt.block_on(self.do_get_block(&id))
+    }
+
+    fn status(&self) -> IndexerResult<StatusResponse> {
+        self.rt.block_on(self.base.status())
+    }
+
+    fn health(&self) -> IndexerResult<Vec<ComponentHealth>> {
+        self.rt.block_on(self.do_health())
+    }
+
+    fn reload(&self, cfg: IndexerConfiguration) -> IndexerResult<Vec<String>> {
+        self.rt.block_on(self.base.reload(cfg))
+    }
+
+    fn broadcast_transaction(&self, tx: Transaction) -> IndexerResult<TxIdType> {
+        self.rt.block_on(self.base.broadcast_transaction(tx))
+    }
+
     fn get_all_balance(
         &mut self,
         address_type: AddressType,
@@ -151,10 +358,20 @@ impl<T: StorageProcessor + Clone> SyncClient for DirectClient<T> {
             .block_on(async { self.storage.get_all_balance(&address_type).await })?)
     }
 
+    fn simulate_delta(&mut self, delta: TransactionDelta) -> IndexerResult<SimulationResult> {
+        self.rt
+            .clone()
+            .block_on(simulate_delta(&mut self.storage, &delta))
+    }
+
     fn update_delta(&mut self, result: TransactionDelta) -> IndexerResult<()> {
         self.base.do_update_delta(result)
     }
 
+    fn update_deltas(&mut self, results: Vec<TransactionDelta>) -> IndexerResult<()> {
+        self.base.do_update_deltas(results)
+    }
+
     fn rx(&self) -> Receiver<ClientEvent> {
         self.base.rx.clone()
     }
@@ -180,4 +397,147 @@ impl<T: StorageProcessor + Clone> SyncClient for DirectClient<T> {
     fn get_btc_client(&self) -> Arc<bitcoincore_rpc::Client> {
         self.btc_client.clone().unwrap()
     }
+
+    fn export_storage(&mut self, path: &str) -> IndexerResult<()> {
+        self.rt.block_on(async { self.storage.export(path).await })
+    }
+
+    fn import_storage(&mut self, path: &str) -> IndexerResult<()> {
+        self.rt.block_on(async { self.storage.import(path).await })
+    }
+
+    fn get_storage_stats(&mut self) -> IndexerResult<StorageStats> {
+        self.rt.block_on(async { self.storage.stats().await })
+    }
+
+    fn get_balance_detail(
+        &mut self,
+        address_type: AddressType,
+        token_type: TokenType,
+    ) -> IndexerResult<BalanceDetailResponse> {
+        self.rt.block_on(async {
+            self.storage
+                .get_balance_detail(&address_type, &token_type)
+                .await
+        })
+    }
+
+    fn get_balance_at_height(
+        &mut self,
+        address_type: AddressType,
+        token_type: TokenType,
+        height: u32,
+    ) -> IndexerResult<BalanceType> {
+        self.rt.block_on(async {
+            self.storage
+                .get_balance_at_height(&address_type, &token_type, height)
+                .await
+        })
+    }
+
+    fn get_transaction_delta(
+        &mut self,
+        tx_id: TxIdType,
+    ) -> IndexerResult<Option<TransactionDeltaWrapper>> {
+        self.rt
+            .block_on(async { self.storage.get_transaction_delta(&tx_id).await })
+    }
+
+    fn list_deltas(
+        &mut self,
+        status: DeltaStatus,
+        pagination: Pagination,
+    ) -> IndexerResult<Vec<TransactionDeltaWrapper>> {
+        self.rt
+            .block_on(async { self.storage.list_deltas(status, &pagination).await })
+    }
+
+    fn register_token(&mut self, token_type: TokenType, meta: TokenMeta) -> IndexerResult<()> {
+        self.rt
+            .block_on(async { self.storage.register_token(&token_type, &meta).await })
+    }
+
+    fn get_token_meta(&mut self, token_type: TokenType) -> IndexerResult<Option<TokenMeta>> {
+        self.rt
+            .block_on(async { self.storage.get_token_meta(&token_type).await })
+    }
+
+    fn replay_dead_letters(&self) -> IndexerResult<()> {
+        self.sync_push_event(IndexerEvent::ReplayDeadLetters)
+    }
+
+    fn get_dead_letters(&mut self) -> IndexerResult<Vec<DeadLetter>> {
+        self.rt
+            .block_on(async { self.storage.get_dead_letters().await })
+    }
+
+    fn replay_from(&self, seq: u64) -> IndexerResult<()> {
+        self.sync_push_event(IndexerEvent::ReplayJournal(self.base.consumer, seq))
+    }
+
+    fn subscribe(&self, filter: EventFilter) -> IndexerResult<()> {
+        self.sync_push_event(IndexerEvent::Subscribe(self.base.consumer, filter))
+    }
+
+    fn commit_cursor(&self, seq: u64) -> IndexerResult<()> {
+        self.sync_push_event(IndexerEvent::CommitCursor(self.base.consumer, seq))
+    }
+
+    fn ack(&self, tx_id: TxIdType) -> IndexerResult<()> {
+        self.sync_push_event(IndexerEvent::Ack(self.base.consumer, tx_id))
+    }
+
+    fn nack(&self, tx_id: TxIdType) -> IndexerResult<()> {
+        self.sync_push_event(IndexerEvent::Nack(self.base.consumer, tx_id))
+    }
+
+    fn pause(&self) -> IndexerResult<()> {
+        self.sync_push_event(IndexerEvent::Pause)
+    }
+
+    fn resume(&self) -> IndexerResult<()> {
+        self.sync_push_event(IndexerEvent::Resume)
+    }
+
+    fn get_cursor(&mut self) -> IndexerResult<Option<u64>> {
+        let consumer = self.base.consumer;
+        self.rt
+            .block_on(async { self.storage.get_cursor(consumer).await })
+    }
+
+    fn get_pending_txs(&mut self) -> IndexerResult<Vec<PendingTxResponse>> {
+        self.rt
+            .block_on(async { self.storage.get_pending_txs().await })
+    }
+
+    fn get_journal(
+        &mut self,
+        from_seq: u64,
+        limit: usize,
+    ) -> IndexerResult<Vec<(u64, ClientEvent)>> {
+        let records = self
+            .rt
+            .block_on(async { self.storage.get_journal(from_seq, limit).await })?;
+        records
+            .into_iter()
+            .map(|(seq, data)| Ok((seq, ClientEvent::from_bytes(&data)?)))
+            .collect()
+    }
+
+    fn get_utxos(&mut self, address: AddressType) -> IndexerResult<Vec<Utxo>> {
+        self.rt
+            .block_on(async { self.storage.get_utxos(&address).await })
+    }
+
+    fn get_address_history(
+        &mut self,
+        address: AddressType,
+        pagination: Pagination,
+    ) -> IndexerResult<Vec<TxIdType>> {
+        self.rt.block_on(async {
+            self.storage
+                .get_address_history(&address, &pagination)
+                .await
+        })
+    }
 }
@@ -1,15 +1,50 @@
+use crate::error::{IndexerError, IndexerResult};
 use crate::event::{AddressType, IndexerEvent, TokenType, TxIdType};
 use crate::types::delta::TransactionDelta;
-use bitcoincore_rpc::bitcoin::consensus::serialize;
-use bitcoincore_rpc::bitcoin::Transaction;
+use crate::types::transaction::{MempoolTransaction, RichTransaction};
+use bitcoincore_rpc::bitcoin::consensus::{deserialize, serialize};
+use bitcoincore_rpc::bitcoin::BlockHash;
 use serde::{Deserialize, Serialize};
 
 #[derive(Clone, Debug)]
 pub enum ClientEvent {
-    Transaction(Transaction),
+    Transaction(MempoolTransaction),
+    // instead of Transaction when prevout enrichment is enabled
+    RichTransaction(RichTransaction),
     GetHeight,
     TxDroped(TxIdType),
-    TxConfirmed(TxIdType),
+    TxConfirmed(TxIdType, BlockContext),
+    // height,hash,txs of the block
+    BlockConnected(u32, BlockHash, Vec<TxIdType>),
+    BlockDisconnected(u32, BlockHash),
+    // old spent the same inputs as new and was dropped
+    TxReplaced { old: TxIdType, new: TxIdType },
+    // zmq messages were lost and the mempool has been synced again
+    Resynced,
+    // pending tx,mined tx spending one of its inputs
+    TxConflicted(TxIdType, TxIdType),
+    // the events of one block or one restore pass when batch delivery is enabled
+    Batch(Vec<ClientEvent>),
+    // the coinbase tx reached maturity,it was dispatched right before this event
+    CoinbaseMatured(TxIdType),
+    // processed height and the hex sha256 of the delta set at that height
+    Checkpoint { height: u32, state_root: String },
+    // blocks from from_height were replaced,the new branch up to to_height has been delivered
+    Reorg { from_height: u32, to_height: u32 },
+    // the zmq feed broke with this reason,events may be late until the restore
+    IngestDegraded(String),
+    // the feed is back,missed mempool txs come through the resync of the sequence gap
+    IngestRestored,
+    // a component of the pipeline failed with this reason,it is restarted unless the policy is never
+    ComponentFailed(String, String),
+    // a message of a zmq topic mapped to passthrough,the topic and the raw body
+    TopicMessage(String, Vec<u8>),
+}
+
+// a client took the event from its channels,the end of its lag with the metrics feature
+pub(crate) fn delivered(_event: &ClientEvent) {
+    #[cfg(feature = "metrics")]
+    crate::metrics::METRICS.delivered(_event);
 }
 
 impl ClientEvent {
@@ -18,29 +53,284 @@ impl ClientEvent {
             ClientEvent::Transaction(_) => 0,
             ClientEvent::GetHeight => 1,
             ClientEvent::TxDroped(_) => 2,
-            ClientEvent::TxConfirmed(_) => 3,
+            ClientEvent::TxConfirmed(_, _) => 3,
+            ClientEvent::BlockConnected(_, _, _) => 4,
+            ClientEvent::BlockDisconnected(_, _) => 5,
+            ClientEvent::TxReplaced { .. } => 6,
+            ClientEvent::Resynced => 7,
+            ClientEvent::RichTransaction(_) => 8,
+            ClientEvent::TxConflicted(_, _) => 9,
+            ClientEvent::Batch(_) => 10,
+            ClientEvent::CoinbaseMatured(_) => 11,
+            ClientEvent::Checkpoint { .. } => 12,
+            ClientEvent::Reorg { .. } => 13,
+            ClientEvent::IngestDegraded(_) => 14,
+            ClientEvent::IngestRestored => 15,
+            ClientEvent::ComponentFailed(_, _) => 16,
+            ClientEvent::TopicMessage(_, _) => 17,
         }
     }
+    // state changes of already delivered txs,delivered ahead of new txs
+    pub fn is_priority(&self) -> bool {
+        matches!(
+            self,
+            ClientEvent::TxConfirmed(_, _)
+                | ClientEvent::TxDroped(_)
+                | ClientEvent::TxReplaced { .. }
+                | ClientEvent::TxConflicted(_, _)
+                | ClientEvent::BlockDisconnected(_, _)
+                | ClientEvent::Reorg { .. }
+        )
+    }
     pub fn to_bytes(&self) -> Vec<u8> {
         match self {
             ClientEvent::Transaction(tx) => {
-                let mut ret = serialize(tx);
+                let mut ret = tx.to_bytes();
+                ret.push(self.get_suffix());
+                ret
+            }
+            ClientEvent::RichTransaction(tx) => {
+                let mut ret = tx.to_bytes();
                 ret.push(self.get_suffix());
                 ret
             }
             ClientEvent::GetHeight => self.get_suffix().to_le_bytes().to_vec(),
+            ClientEvent::Resynced => self.get_suffix().to_le_bytes().to_vec(),
+            ClientEvent::IngestRestored => self.get_suffix().to_le_bytes().to_vec(),
+            ClientEvent::IngestDegraded(reason) => {
+                let mut ret = reason.as_bytes().to_vec();
+                ret.push(self.get_suffix());
+                ret
+            }
+            // component|0|reason
+            ClientEvent::ComponentFailed(component, reason) => {
+                let mut ret = component.as_bytes().to_vec();
+                ret.push(0);
+                ret.extend_from_slice(reason.as_bytes());
+                ret.push(self.get_suffix());
+                ret
+            }
+            // topic|0|body
+            ClientEvent::TopicMessage(topic, body) => {
+                let mut ret = topic.as_bytes().to_vec();
+                ret.push(0);
+                ret.extend_from_slice(body);
+                ret.push(self.get_suffix());
+                ret
+            }
             ClientEvent::TxDroped(tx_id) => {
                 let mut ret = tx_id.to_bytes();
                 ret.push(self.get_suffix());
                 ret
             }
-            ClientEvent::TxConfirmed(tx_id) => {
+            // tx_id|height(le)|hash|position(le)
+            ClientEvent::TxConfirmed(tx_id, block) => {
                 let mut ret = tx_id.to_bytes();
+                ret.extend_from_slice(&block.height.to_le_bytes());
+                ret.extend_from_slice(&serialize(&block.hash));
+                ret.extend_from_slice(&block.position.to_le_bytes());
+                ret.push(self.get_suffix());
+                ret
+            }
+            ClientEvent::CoinbaseMatured(tx_id) => {
+                let mut ret = tx_id.to_bytes();
+                ret.push(self.get_suffix());
+                ret
+            }
+            // height(le)|hash|tx ids
+            ClientEvent::BlockConnected(height, hash, txs) => {
+                let mut ret = height.to_le_bytes().to_vec();
+                ret.extend_from_slice(&serialize(hash));
+                for tx_id in txs {
+                    ret.extend_from_slice(&tx_id.to_bytes());
+                }
+                ret.push(self.get_suffix());
+                ret
+            }
+            ClientEvent::BlockDisconnected(height, hash) => {
+                let mut ret = height.to_le_bytes().to_vec();
+                ret.extend_from_slice(&serialize(hash));
+                ret.push(self.get_suffix());
+                ret
+            }
+            ClientEvent::TxReplaced { old, new } => {
+                let mut ret = old.to_bytes();
+                ret.extend_from_slice(&new.to_bytes());
+                ret.push(self.get_suffix());
+                ret
+            }
+            ClientEvent::TxConflicted(tx_id, confirmed_tx_id) => {
+                let mut ret = tx_id.to_bytes();
+                ret.extend_from_slice(&confirmed_tx_id.to_bytes());
+                ret.push(self.get_suffix());
+                ret
+            }
+            ClientEvent::Checkpoint { height, state_root } => {
+                let mut ret = height.to_le_bytes().to_vec();
+                ret.extend_from_slice(state_root.as_bytes());
+                ret.push(self.get_suffix());
+                ret
+            }
+            ClientEvent::Reorg {
+                from_height,
+                to_height,
+            } => {
+                let mut ret = from_height.to_le_bytes().to_vec();
+                ret.extend_from_slice(&to_height.to_le_bytes());
+                ret.push(self.get_suffix());
+                ret
+            }
+            // len(le)|event,for each event
+            ClientEvent::Batch(events) => {
+                let mut ret = vec![];
+                for event in events {
+                    let data = event.to_bytes();
+                    ret.extend_from_slice(&(data.len() as u32).to_le_bytes());
+                    ret.extend_from_slice(&data);
+                }
                 ret.push(self.get_suffix());
                 ret
             }
         }
     }
+    // journal records come back through here,a corrupt one is an error instead of a panic
+    pub fn from_bytes(data: &[u8]) -> IndexerResult<Self> {
+        let Some((&suffix, body)) = data.split_last() else {
+            return Err(IndexerError::CodecError("empty client event".to_string()));
+        };
+        let invalid = || {
+            IndexerError::CodecError(format!(
+                "invalid client event,suffix:{},length:{}",
+                suffix,
+                body.len()
+            ))
+        };
+        let range = |from: usize, to: usize| body.get(from..to).ok_or_else(invalid);
+        let u32_at = |i: usize| range(i, i + 4).map(|v| u32::from_le_bytes(v.try_into().unwrap()));
+        let tx_id = |i: usize| range(i * 32, (i + 1) * 32).map(TxIdType::from_bytes);
+        // the fixed size events
+        let length = |len: usize| match body.len() == len {
+            true => Ok(()),
+            false => Err(invalid()),
+        };
+        let ret = match suffix {
+            0 => ClientEvent::Transaction(MempoolTransaction::from_bytes(body)?),
+            1 => ClientEvent::GetHeight,
+            2 => {
+                length(32)?;
+                ClientEvent::TxDroped(tx_id(0)?)
+            }
+            3 => {
+                length(72)?;
+                ClientEvent::TxConfirmed(
+                    tx_id(0)?,
+                    BlockContext {
+                        height: u32_at(32)?,
+                        hash: deserialize(range(36, 68)?)?,
+                        position: u32_at(68)?,
+                    },
+                )
+            }
+            4 => {
+                let height = u32_at(0)?;
+                let hash = deserialize(range(4, 36)?)?;
+                let txs = range(36, body.len())?;
+                if txs.len() % 32 != 0 {
+                    return Err(invalid());
+                }
+                let txs = txs.chunks(32).map(TxIdType::from_bytes).collect();
+                ClientEvent::BlockConnected(height, hash, txs)
+            }
+            5 => {
+                length(36)?;
+                ClientEvent::BlockDisconnected(u32_at(0)?, deserialize(range(4, 36)?)?)
+            }
+            6 => {
+                length(64)?;
+                ClientEvent::TxReplaced {
+                    old: tx_id(0)?,
+                    new: tx_id(1)?,
+                }
+            }
+            7 => ClientEvent::Resynced,
+            8 => ClientEvent::RichTransaction(RichTransaction::from_bytes(body)?),
+            9 => {
+                length(64)?;
+                ClientEvent::TxConflicted(tx_id(0)?, tx_id(1)?)
+            }
+            10 => {
+                let mut events = vec![];
+                let mut rest = body;
+                while !rest.is_empty() {
+                    if rest.len() < 4 {
+                        return Err(IndexerError::CodecError(
+                            "truncated batch event".to_string(),
+                        ));
+                    }
+                    let len = u32::from_le_bytes(rest[0..4].try_into().unwrap()) as usize;
+                    let Some(event) = rest.get(4..4 + len) else {
+                        return Err(IndexerError::CodecError(
+                            "truncated batch event".to_string(),
+                        ));
+                    };
+                    events.push(ClientEvent::from_bytes(event)?);
+                    rest = &rest[4 + len..];
+                }
+                ClientEvent::Batch(events)
+            }
+            11 => {
+                length(32)?;
+                ClientEvent::CoinbaseMatured(tx_id(0)?)
+            }
+            13 => {
+                length(8)?;
+                ClientEvent::Reorg {
+                    from_height: u32_at(0)?,
+                    to_height: u32_at(4)?,
+                }
+            }
+            12 => ClientEvent::Checkpoint {
+                height: u32_at(0)?,
+                state_root: String::from_utf8_lossy(range(4, body.len())?).to_string(),
+            },
+            14 => ClientEvent::IngestDegraded(String::from_utf8_lossy(body).to_string()),
+            15 => ClientEvent::IngestRestored,
+            16 => {
+                let split = body.iter().position(|v| *v == 0).ok_or_else(|| {
+                    IndexerError::CodecError("component failure without reason".to_string())
+                })?;
+                ClientEvent::ComponentFailed(
+                    String::from_utf8_lossy(&body[..split]).to_string(),
+                    String::from_utf8_lossy(&body[split + 1..]).to_string(),
+                )
+            }
+            17 => {
+                let split = body.iter().position(|v| *v == 0).ok_or_else(|| {
+                    IndexerError::CodecError("topic message without topic".to_string())
+                })?;
+                ClientEvent::TopicMessage(
+                    String::from_utf8_lossy(&body[..split]).to_string(),
+                    body[split + 1..].to_vec(),
+                )
+            }
+            _ => {
+                return Err(IndexerError::CodecError(format!(
+                    "unknown client event suffix:{}",
+                    suffix
+                )))
+            }
+        };
+        Ok(ret)
+    }
+}
+
+// the block a tx was mined in
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BlockContext {
+    pub height: u32,
+    pub hash: BlockHash,
+    // index of the tx in the block
+    pub position: u32,
 }
 
 #[derive(Clone, Debug)]
@@ -130,3 +420,70 @@ impl RequestEvent {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoincore_rpc::bitcoin::hashes::Hash;
+
+    #[test]
+    pub fn test_client_event_bytes() {
+        let id = |v: u8| TxIdType::from_bytes(&[v; 32]);
+        let hash = BlockHash::from_byte_array([7u8; 32]);
+        let events = vec![
+            ClientEvent::GetHeight,
+            ClientEvent::TxConfirmed(
+                id(1),
+                BlockContext {
+                    height: 9,
+                    hash,
+                    position: 3,
+                },
+            ),
+            ClientEvent::BlockConnected(9, hash, vec![id(1), id(2)]),
+            ClientEvent::BlockDisconnected(9, hash),
+            ClientEvent::TxReplaced {
+                old: id(1),
+                new: id(2),
+            },
+            ClientEvent::TxConflicted(id(3), id(4)),
+            ClientEvent::CoinbaseMatured(id(6)),
+            ClientEvent::Reorg {
+                from_height: 3,
+                to_height: 5,
+            },
+            ClientEvent::Checkpoint {
+                height: 10,
+                state_root: "ab".repeat(32),
+            },
+            ClientEvent::IngestDegraded("no message for 60s".to_string()),
+            ClientEvent::IngestRestored,
+            ClientEvent::ComponentFailed("zmq".to_string(), "panicked".to_string()),
+            ClientEvent::TopicMessage("mempool.fee".to_string(), vec![0, 1, 0]),
+            ClientEvent::Batch(vec![
+                ClientEvent::TxDroped(id(5)),
+                ClientEvent::Resynced,
+                ClientEvent::Batch(vec![]),
+            ]),
+        ];
+        for event in events {
+            let data = event.to_bytes();
+            let decoded = ClientEvent::from_bytes(&data).unwrap();
+            assert_eq!(format!("{:?}", decoded), format!("{:?}", event));
+            // a record cut anywhere fails without a panic,the text ones still decode
+            for cut in 0..data.len() - 1 {
+                let mut truncated = data[..cut].to_vec();
+                truncated.push(*data.last().unwrap());
+                let _ = ClientEvent::from_bytes(&truncated);
+            }
+        }
+        assert!(ClientEvent::from_bytes(&[]).is_err());
+        for suffix in [2u8, 3, 4, 5, 6, 9, 10, 11, 12, 13] {
+            assert!(
+                ClientEvent::from_bytes(&[1, 2, suffix]).is_err(),
+                "{}",
+                suffix
+            );
+        }
+    }
+}
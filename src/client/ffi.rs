@@ -2,32 +2,30 @@ use crate::client::drect::DirectClient;
 use crate::client::event::RequestEvent;
 use crate::client::SyncClient;
 use crate::configuration::base::{
-    IndexerConfiguration, LogConfiguration, NetConfiguration, ZMQConfiguration,
+    IndexerConfiguration, LogConfiguration, RpcConfiguration, StorageConfiguration,
+    ZMQConfiguration,
 };
 use crate::event::IndexerEvent;
 use crate::factory::common::sync_create_and_start_processor;
-use crate::storage::db::memory::MemoryDB;
-use crate::storage::db::thread_safe::ThreadSafeDB;
-use crate::storage::kv::KVStorageProcessor;
+use crate::storage::middleware::LayeredStorageProcessor;
+use bitcoincore_rpc::bitcoin::Network;
 use core::ffi::c_char;
-use log::{info, warn};
+use log::{error, info, warn};
 use once_cell::sync::Lazy;
 use std::ffi::CStr;
 use std::ffi::CString;
 use std::ops::DerefMut;
 
-static mut NOTIFIER: Lazy<Option<DirectClient<KVStorageProcessor<ThreadSafeDB<MemoryDB>>>>> =
-    Lazy::new(|| None);
+static mut NOTIFIER: Lazy<Option<DirectClient<LayeredStorageProcessor>>> = Lazy::new(|| None);
 
-fn get_notifier() -> &'static mut DirectClient<KVStorageProcessor<ThreadSafeDB<MemoryDB>>> {
+fn get_notifier() -> &'static mut DirectClient<LayeredStorageProcessor> {
     unsafe {
         let ret = NOTIFIER.deref_mut();
         ret.as_mut().unwrap()
     }
 }
 
-fn get_option_notifier(
-) -> &'static mut Option<DirectClient<KVStorageProcessor<ThreadSafeDB<MemoryDB>>>> {
+fn get_option_notifier() -> &'static mut Option<DirectClient<LayeredStorageProcessor>> {
     unsafe {
         let ret = NOTIFIER.deref_mut();
         ret
@@ -70,20 +68,44 @@ pub extern "C" fn start_processor() {
 
     info!("zmq_url: {}, zmq_topics: {}", zmq_url, zmq_topics);
     let zmq_topics: Vec<String> = zmq_topics.split(",").map(|v| v.to_string()).collect();
-    let ret = sync_create_and_start_processor(IndexerConfiguration {
-        mq: ZMQConfiguration {
+    let cfg = IndexerConfiguration {
+        network: Network::Regtest,
+        zmq: ZMQConfiguration {
             zmq_url,
             zmq_topic: zmq_topics,
+            ..Default::default()
         },
-        net: NetConfiguration {
+        rpc: RpcConfiguration {
             url: btc_rpc_url,
             username: btc_rpc_username,
             password: btc_rpc_password,
+            ..Default::default()
         },
-        db_path,
-        save_block_cache_count: cache_block,
-        log_configuration: LogConfiguration { log_level },
-    });
+        storage: StorageConfiguration {
+            db_path,
+            encryption_key: std::env::var("DB_ENCRYPTION_KEY").ok(),
+            save_block_cache_count: cache_block,
+            ..Default::default()
+        },
+        dispatcher: Default::default(),
+        client: Default::default(),
+        ingest: Default::default(),
+        log_configuration: LogConfiguration {
+            log_level,
+            ..Default::default()
+        },
+        metrics: Default::default(),
+        health: Default::default(),
+        track_utxos: false,
+        enrich_prevouts: false,
+        start_height: None,
+        watch_scripts: vec![],
+        confirmations: 1,
+    }
+    // INDEXER_SDK_* take precedence over the variables above
+    .with_env_overrides()
+    .expect("invalid configuration override");
+    let ret = sync_create_and_start_processor(cfg).expect("start processor failed");
     let old = get_option_notifier();
     *old = Some(ret);
 }
@@ -142,7 +164,9 @@ pub extern "C" fn push_event(data: *const u8, len: usize) {
     }
     let event = index_event.unwrap();
     let notifier = get_notifier();
-    notifier.sync_push_event(event);
+    if let Err(e) = notifier.sync_push_event(event) {
+        error!("push event error:{:?}", e);
+    }
 }
 //
 #[no_mangle]
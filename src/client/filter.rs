@@ -0,0 +1,190 @@
+use crate::client::event::ClientEvent;
+use crate::event::{AddressType, TokenType, TxIdType};
+use bitcoincore_rpc::bitcoin::{Address, Script};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+// what a client wants to receive,an empty set does not restrict
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct EventFilter {
+    // ClientEvent suffixes
+    pub kinds: HashSet<u8>,
+    // script pubkeys created or spent by a tx
+    pub scripts: HashSet<AddressType>,
+    // tokens touched by the delta of a tx,only known once the tx has been executed
+    pub tokens: HashSet<TokenType>,
+}
+
+impl EventFilter {
+    pub fn with_kinds(mut self, kinds: &[u8]) -> Self {
+        self.kinds.extend(kinds.iter().copied());
+        self
+    }
+    pub fn with_script(mut self, script: &Script) -> Self {
+        self.scripts
+            .insert(AddressType::from_bytes(script.as_bytes()));
+        self
+    }
+    pub fn with_address(self, address: &Address) -> Self {
+        self.with_script(&address.script_pubkey())
+    }
+    pub fn with_token(mut self, token: TokenType) -> Self {
+        self.tokens.insert(token);
+        self
+    }
+
+    // a plain tx event only carries its outputs,the spent scripts must be resolved
+    pub fn needs_prevouts(&self, event: &ClientEvent) -> bool {
+        !self.scripts.is_empty() && matches!(event, ClientEvent::Transaction(_))
+    }
+}
+
+// the tx a state change event is about
+pub fn subject_tx(event: &ClientEvent) -> Option<&TxIdType> {
+    match event {
+        ClientEvent::TxDroped(v)
+        | ClientEvent::TxConfirmed(v, _)
+        | ClientEvent::TxConflicted(v, _)
+        | ClientEvent::CoinbaseMatured(v) => Some(v),
+        ClientEvent::TxReplaced { old, .. } => Some(old),
+        _ => None,
+    }
+}
+
+// a filter and the matching txs which were delivered,state changes of a tx follow its delivery
+#[derive(Clone)]
+pub struct Subscription {
+    pub filter: EventFilter,
+    delivered: HashSet<TxIdType>,
+}
+
+impl Subscription {
+    pub fn new(filter: EventFilter) -> Self {
+        Self {
+            filter,
+            delivered: Default::default(),
+        }
+    }
+
+    // spent: scripts spent by a plain tx event,tokens: tokens of the subject tx delta
+    pub fn accept(
+        &mut self,
+        event: &ClientEvent,
+        spent: &[AddressType],
+        tokens: &[TokenType],
+    ) -> bool {
+        if !self.filter.kinds.is_empty() && !self.filter.kinds.contains(&event.get_suffix()) {
+            return false;
+        }
+        let scripts = &self.filter.scripts;
+        let tx = match event {
+            ClientEvent::Transaction(v) => Some((&v.tx, vec![])),
+            ClientEvent::RichTransaction(v) => Some((
+                &v.tx.tx,
+                v.prevouts.iter().map(|v| v.address.clone()).collect(),
+            )),
+            _ => None,
+        };
+        if let Some((tx, prevouts)) = tx {
+            if scripts.is_empty() {
+                return true;
+            }
+            let matched =
+                tx.output.iter().any(|v| {
+                    scripts.contains(&AddressType::from_bytes(v.script_pubkey.as_bytes()))
+                }) || spent
+                    .iter()
+                    .chain(prevouts.iter())
+                    .any(|v| scripts.contains(v));
+            if matched {
+                self.delivered.insert(tx.txid().into());
+            }
+            return matched;
+        }
+        let Some(tx_id) = subject_tx(event) else {
+            return true;
+        };
+        if !scripts.is_empty() && !self.delivered.contains(tx_id) {
+            return false;
+        }
+        if !matches!(event, ClientEvent::CoinbaseMatured(_)) {
+            // the tx reached its final state
+            self.delivered.remove(tx_id);
+        }
+        self.filter.tokens.is_empty() || tokens.iter().any(|v| self.filter.tokens.contains(v))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::event::BlockContext;
+    use crate::types::transaction::MempoolTransaction;
+    use bitcoincore_rpc::bitcoin::absolute::LockTime;
+    use bitcoincore_rpc::bitcoin::hashes::Hash;
+    use bitcoincore_rpc::bitcoin::{BlockHash, ScriptBuf, Transaction, TxOut};
+
+    #[test]
+    pub fn test_subscription() {
+        let watched = ScriptBuf::from_bytes(vec![0x51]);
+        let tx = |script: &ScriptBuf| Transaction {
+            version: 1,
+            lock_time: LockTime::ZERO,
+            input: vec![],
+            output: vec![TxOut {
+                value: 1,
+                script_pubkey: script.clone(),
+            }],
+        };
+        let hit = tx(&watched);
+        let miss = tx(&ScriptBuf::from_bytes(vec![0x52]));
+        let confirmed = |tx: &Transaction| {
+            ClientEvent::TxConfirmed(
+                tx.txid().into(),
+                BlockContext {
+                    height: 1,
+                    hash: BlockHash::all_zeros(),
+                    position: 0,
+                },
+            )
+        };
+        let token = TokenType::from_bytes(&[1u8; 20]);
+
+        let mut sub = Subscription::new(EventFilter::default().with_script(&watched));
+        assert!(sub.accept(
+            &ClientEvent::Transaction(MempoolTransaction::new(hit.clone())),
+            &[],
+            &[]
+        ));
+        assert!(!sub.accept(
+            &ClientEvent::Transaction(MempoolTransaction::new(miss.clone())),
+            &[],
+            &[]
+        ));
+        // the spent script matches too
+        let spent = [AddressType::from_bytes(watched.as_bytes())];
+        assert!(sub.accept(
+            &ClientEvent::Transaction(MempoolTransaction::new(tx(&ScriptBuf::new()))),
+            &spent,
+            &[]
+        ));
+        assert!(sub.accept(&confirmed(&hit), &[], &[]));
+        assert!(!sub.accept(&confirmed(&hit), &[], &[]));
+        assert!(!sub.accept(&confirmed(&miss), &[], &[]));
+        assert!(sub.accept(&ClientEvent::Resynced, &[], &[]));
+
+        let mut sub = Subscription::new(
+            EventFilter::default()
+                .with_kinds(&[0, 3])
+                .with_token(token.clone()),
+        );
+        assert!(sub.accept(
+            &ClientEvent::Transaction(MempoolTransaction::new(miss.clone())),
+            &[],
+            &[]
+        ));
+        assert!(!sub.accept(&ClientEvent::Resynced, &[], &[]));
+        assert!(!sub.accept(&confirmed(&miss), &[], &[]));
+        assert!(sub.accept(&confirmed(&miss), &[], &[token]));
+    }
+}
@@ -0,0 +1,120 @@
+use crate::client::event::ClientEvent;
+use chrono::Local;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+// bridges the events a client takes to the metrics system of the user.
+// kind is the suffix of the event,the inner events of a batch are reported one by one
+pub trait ClientMetrics: Send + Sync {
+    fn on_event(&self, kind: u8, lag: Option<Duration>);
+}
+
+// how long ago the event was created,only known for txs with a mempool entry time
+pub fn event_lag(event: &ClientEvent) -> Option<Duration> {
+    let entry_time = match event {
+        ClientEvent::Transaction(tx) => tx.entry_time,
+        ClientEvent::RichTransaction(tx) => tx.tx.entry_time,
+        _ => None,
+    }?;
+    let now = Local::now().timestamp() as u64;
+    Some(Duration::from_secs(now.saturating_sub(entry_time)))
+}
+
+pub(crate) fn observe(metrics: &dyn ClientMetrics, event: &ClientEvent) {
+    if let ClientEvent::Batch(events) = event {
+        events.iter().for_each(|v| observe(metrics, v));
+        return;
+    }
+    metrics.on_event(event.get_suffix(), event_lag(event));
+}
+
+// in process counters for users without a metrics system
+#[derive(Default)]
+pub struct CountingMetrics {
+    received: AtomicU64,
+    by_kind: Mutex<HashMap<u8, u64>>,
+    lag_samples: AtomicU64,
+    lag_total_ms: AtomicU64,
+    lag_max_ms: AtomicU64,
+}
+
+impl ClientMetrics for CountingMetrics {
+    fn on_event(&self, kind: u8, lag: Option<Duration>) {
+        self.received.fetch_add(1, Ordering::Relaxed);
+        *self.by_kind.lock().unwrap().entry(kind).or_default() += 1;
+        if let Some(lag) = lag {
+            let ms = lag.as_millis() as u64;
+            self.lag_samples.fetch_add(1, Ordering::Relaxed);
+            self.lag_total_ms.fetch_add(ms, Ordering::Relaxed);
+            self.lag_max_ms.fetch_max(ms, Ordering::Relaxed);
+        }
+    }
+}
+
+impl CountingMetrics {
+    pub fn received(&self) -> u64 {
+        self.received.load(Ordering::Relaxed)
+    }
+
+    pub fn received_by_kind(&self, kind: u8) -> u64 {
+        self.by_kind
+            .lock()
+            .unwrap()
+            .get(&kind)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    pub fn average_lag(&self) -> Option<Duration> {
+        let samples = self.lag_samples.load(Ordering::Relaxed);
+        if samples == 0 {
+            return None;
+        }
+        Some(Duration::from_millis(
+            self.lag_total_ms.load(Ordering::Relaxed) / samples,
+        ))
+    }
+
+    pub fn max_lag(&self) -> Duration {
+        Duration::from_millis(self.lag_max_ms.load(Ordering::Relaxed))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::TxIdType;
+    use crate::types::transaction::MempoolTransaction;
+    use bitcoincore_rpc::bitcoin::absolute::LockTime;
+    use bitcoincore_rpc::bitcoin::Transaction;
+
+    #[test]
+    pub fn test_counting_metrics() {
+        let metrics = CountingMetrics::default();
+        let mut tx = MempoolTransaction::new(Transaction {
+            version: 2,
+            lock_time: LockTime::ZERO,
+            input: vec![],
+            output: vec![],
+        });
+        tx.entry_time = Some(Local::now().timestamp() as u64 - 10);
+        let dropped = ClientEvent::TxDroped(TxIdType::from_bytes(&[1u8; 32]));
+        observe(
+            &metrics,
+            &ClientEvent::Batch(vec![ClientEvent::Transaction(tx), dropped.clone()]),
+        );
+        observe(&metrics, &dropped);
+
+        assert_eq!(metrics.received(), 3);
+        assert_eq!(metrics.received_by_kind(dropped.get_suffix()), 2);
+        assert_eq!(
+            metrics.received_by_kind(ClientEvent::Resynced.get_suffix()),
+            0
+        );
+        let lag = metrics.average_lag().unwrap();
+        assert!(lag >= Duration::from_secs(10) && lag <= Duration::from_secs(11));
+        assert_eq!(metrics.max_lag(), lag);
+    }
+}
@@ -1,29 +1,86 @@
 use crate::client::event::ClientEvent;
+use crate::client::filter::EventFilter;
+use crate::configuration::base::IndexerConfiguration;
 use crate::dispatcher::event::DispatchEvent;
 use crate::error::IndexerResult;
 use crate::event::{AddressType, BalanceType, IndexerEvent, TokenType, TxIdType};
+use crate::health::ComponentHealth;
+use crate::storage::kv::TransactionDeltaWrapper;
+use crate::storage::prefix::DeltaStatus;
+use crate::storage::StorageStats;
+use crate::types::dead_letter::DeadLetter;
 use crate::types::delta::TransactionDelta;
-use crate::types::response::AllBalanceResponse;
+use crate::types::request::{BlockId, Pagination, ReorgReport};
+use crate::types::response::{
+    AllBalanceResponse, BalanceDetailResponse, PendingTxResponse, SimulationResult, StatusResponse,
+};
+use crate::types::token::TokenMeta;
+use crate::types::transaction::Utxo;
+use bitcoincore_rpc::bitcoin::{Block, Transaction};
 use std::sync::Arc;
 
+pub mod cancel;
 pub mod common;
 
 pub mod drect;
 pub mod event;
 pub mod ffi;
+pub mod filter;
+pub mod metrics;
+pub mod stream;
 
 #[async_trait::async_trait]
 pub trait Client: Send + Sync {
     async fn get_event(&self) -> IndexerResult<Option<ClientEvent>>;
     async fn report_height(&self, height: u32) -> IndexerResult<()>;
-    async fn report_reorg(&self, number: u32) -> IndexerResult<()>;
+    async fn report_reorg(&self, report: ReorgReport) -> IndexerResult<()>;
     async fn push_event(&self, event: DispatchEvent) -> IndexerResult<()>;
     async fn get_balance(
         &mut self,
         address_type: AddressType,
         token_type: TokenType,
     ) -> IndexerResult<BalanceType>;
+    // one round trip for all the pairs,the balances come back in the same order
+    async fn get_balances(
+        &mut self,
+        keys: Vec<(AddressType, TokenType)>,
+    ) -> IndexerResult<Vec<BalanceType>>;
+    // from the sdk storage,falls back to rpc
+    async fn get_transaction(&mut self, tx_id: TxIdType) -> IndexerResult<Option<Transaction>>;
+    // by height on the best chain or by hash,the latest save_block_cache_count blocks are cached
+    async fn get_block(&mut self, id: BlockId) -> IndexerResult<Option<Block>>;
+    // sendrawtransaction,the tx is dispatched and stored as seen so its zmq announcement is skipped
+    async fn broadcast_transaction(&self, tx: Transaction) -> IndexerResult<TxIdType>;
+    // tip of bitcoind against the indexed height,answered by the processor
+    async fn status(&self) -> IndexerResult<StatusResponse>;
+    // status of every component,a processor that does not answer is failing
+    async fn health(&self) -> IndexerResult<Vec<ComponentHealth>>;
+    // applies the tunables without a restart,returns the changed fields that need one
+    async fn reload(&self, cfg: IndexerConfiguration) -> IndexerResult<Vec<String>>;
+    // the balances the delta would leave,nothing is persisted
+    async fn simulate_delta(&mut self, delta: TransactionDelta) -> IndexerResult<SimulationResult>;
     async fn update_delta(&mut self, result: TransactionDelta) -> IndexerResult<()>;
+    async fn update_deltas(&mut self, results: Vec<TransactionDelta>) -> IndexerResult<()>;
+    // re-handles the events that failed before,in the order they failed
+    async fn replay_dead_letters(&self) -> IndexerResult<()>;
+    // re-sends the journaled events with a sequence number >= seq
+    async fn replay_from(&self, seq: u64) -> IndexerResult<()>;
+    // only the events matching filter are delivered from now on,the default filter matches all
+    async fn subscribe(&self, filter: EventFilter) -> IndexerResult<()>;
+    // persists the journal seq this client processed up to,a restarted client replays after it
+    async fn commit_cursor(&self, seq: u64) -> IndexerResult<()>;
+    // with ack_timeout_secs set a delivered tx is redelivered until it is acked,
+    // a nack redelivers it right away
+    async fn ack(&self, tx_id: TxIdType) -> IndexerResult<()>;
+    async fn nack(&self, tx_id: TxIdType) -> IndexerResult<()>;
+    // the processor persists the incoming events until the resume,e.g. during a migration
+    async fn pause(&self) -> IndexerResult<()>;
+    async fn resume(&self) -> IndexerResult<()>;
+
+    // the next event without taking it
+    async fn peek_event(&self) -> IndexerResult<Option<ClientEvent>>;
+    // puts a taken event back in front of the queue,e.g. while its parent tx is not executed yet
+    async fn requeue(&self, event: ClientEvent) -> IndexerResult<()>;
 
     fn rx(&self) -> async_channel::Receiver<ClientEvent>;
 }
@@ -32,8 +89,10 @@ pub trait Client: Send + Sync {
 pub trait SyncClient: Send + Sync {
     fn get_event(&self) -> IndexerResult<Option<ClientEvent>>;
     fn block_get_event(&self) -> IndexerResult<ClientEvent>;
+    fn peek_event(&self) -> IndexerResult<Option<ClientEvent>>;
+    fn requeue(&self, event: ClientEvent) -> IndexerResult<()>;
     fn report_height(&self, height: u32) -> IndexerResult<()>;
-    fn report_reorg(&self, org_number: u32) -> IndexerResult<()>;
+    fn report_reorg(&self, report: ReorgReport) -> IndexerResult<()>;
     fn push_event(&self, event: IndexerEvent) -> IndexerResult<()>;
     fn get_balance(
         &mut self,
@@ -41,13 +100,34 @@ pub trait SyncClient: Send + Sync {
         token_type: TokenType,
     ) -> IndexerResult<BalanceType>;
 
+    fn get_balances(
+        &mut self,
+        keys: Vec<(AddressType, TokenType)>,
+    ) -> IndexerResult<Vec<BalanceType>>;
+
+    fn get_transaction(&mut self, tx_id: TxIdType) -> IndexerResult<Option<Transaction>>;
+
+    fn get_block(&mut self, id: BlockId) -> IndexerResult<Option<Block>>;
+
+    fn status(&self) -> IndexerResult<StatusResponse>;
+
+    fn health(&self) -> IndexerResult<Vec<ComponentHealth>>;
+
+    fn reload(&self, cfg: IndexerConfiguration) -> IndexerResult<Vec<String>>;
+
+    fn broadcast_transaction(&self, tx: Transaction) -> IndexerResult<TxIdType>;
+
     fn get_all_balance(
         &mut self,
         address_type: AddressType,
     ) -> IndexerResult<Vec<AllBalanceResponse>>;
 
+    fn simulate_delta(&mut self, delta: TransactionDelta) -> IndexerResult<SimulationResult>;
+
     fn update_delta(&mut self, result: TransactionDelta) -> IndexerResult<()>;
 
+    fn update_deltas(&mut self, results: Vec<TransactionDelta>) -> IndexerResult<()>;
+
     fn rx(&self) -> async_channel::Receiver<ClientEvent>;
 
     fn simple_set(&mut self, tx_id: &TxIdType, key: &[u8], value: Vec<u8>) -> IndexerResult<()>;
@@ -57,4 +137,74 @@ pub trait SyncClient: Send + Sync {
     fn remove_tx_traces(&mut self, tx_id: Vec<TxIdType>) -> IndexerResult<()>;
 
     fn get_btc_client(&self) -> Arc<bitcoincore_rpc::Client>;
+
+    fn export_storage(&mut self, path: &str) -> IndexerResult<()>;
+
+    fn import_storage(&mut self, path: &str) -> IndexerResult<()>;
+
+    fn get_storage_stats(&mut self) -> IndexerResult<StorageStats>;
+
+    fn get_balance_detail(
+        &mut self,
+        address_type: AddressType,
+        token_type: TokenType,
+    ) -> IndexerResult<BalanceDetailResponse>;
+
+    fn get_balance_at_height(
+        &mut self,
+        address_type: AddressType,
+        token_type: TokenType,
+        height: u32,
+    ) -> IndexerResult<BalanceType>;
+
+    fn get_transaction_delta(
+        &mut self,
+        tx_id: TxIdType,
+    ) -> IndexerResult<Option<TransactionDeltaWrapper>>;
+
+    fn list_deltas(
+        &mut self,
+        status: DeltaStatus,
+        pagination: Pagination,
+    ) -> IndexerResult<Vec<TransactionDeltaWrapper>>;
+
+    fn register_token(&mut self, token_type: TokenType, meta: TokenMeta) -> IndexerResult<()>;
+
+    fn get_token_meta(&mut self, token_type: TokenType) -> IndexerResult<Option<TokenMeta>>;
+
+    fn get_utxos(&mut self, address: AddressType) -> IndexerResult<Vec<Utxo>>;
+
+    fn replay_dead_letters(&self) -> IndexerResult<()>;
+
+    fn get_dead_letters(&mut self) -> IndexerResult<Vec<DeadLetter>>;
+
+    fn replay_from(&self, seq: u64) -> IndexerResult<()>;
+
+    fn subscribe(&self, filter: EventFilter) -> IndexerResult<()>;
+
+    fn commit_cursor(&self, seq: u64) -> IndexerResult<()>;
+
+    fn get_cursor(&mut self) -> IndexerResult<Option<u64>>;
+
+    fn ack(&self, tx_id: TxIdType) -> IndexerResult<()>;
+
+    fn nack(&self, tx_id: TxIdType) -> IndexerResult<()>;
+
+    fn pause(&self) -> IndexerResult<()>;
+
+    fn resume(&self) -> IndexerResult<()>;
+
+    fn get_pending_txs(&mut self) -> IndexerResult<Vec<PendingTxResponse>>;
+
+    fn get_journal(
+        &mut self,
+        from_seq: u64,
+        limit: usize,
+    ) -> IndexerResult<Vec<(u64, ClientEvent)>>;
+
+    fn get_address_history(
+        &mut self,
+        address: AddressType,
+        pagination: Pagination,
+    ) -> IndexerResult<Vec<TxIdType>>;
 }
@@ -0,0 +1,84 @@
+use crate::client::event::{delivered, ClientEvent};
+use futures_core::Stream;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+// client events as a stream,the priority lane is polled first.
+// ends once the processor dropped both senders
+pub struct EventStream {
+    priority_rx: async_channel::Receiver<ClientEvent>,
+    rx: async_channel::Receiver<ClientEvent>,
+    priority_closed: bool,
+}
+
+impl EventStream {
+    pub fn new(
+        priority_rx: async_channel::Receiver<ClientEvent>,
+        rx: async_channel::Receiver<ClientEvent>,
+    ) -> Self {
+        Self {
+            priority_rx,
+            rx,
+            priority_closed: false,
+        }
+    }
+}
+
+impl Stream for EventStream {
+    type Item = ClientEvent;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if !self.priority_closed {
+            match Pin::new(&mut self.priority_rx).poll_next(cx) {
+                Poll::Ready(Some(v)) => {
+                    delivered(&v);
+                    return Poll::Ready(Some(v));
+                }
+                Poll::Ready(None) => self.priority_closed = true,
+                Poll::Pending => {}
+            }
+        }
+        match Pin::new(&mut self.rx).poll_next(cx) {
+            Poll::Ready(None) if !self.priority_closed => Poll::Pending,
+            Poll::Ready(Some(v)) => {
+                delivered(&v);
+                Poll::Ready(Some(v))
+            }
+            v => v,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::TxIdType;
+    use std::future::poll_fn;
+
+    #[test]
+    pub fn test_event_stream() {
+        let (tx, rx) = async_channel::unbounded();
+        let (priority_tx, priority_rx) = async_channel::unbounded();
+        let mut stream = EventStream::new(priority_rx, rx);
+        tx.send_blocking(ClientEvent::GetHeight).unwrap();
+        priority_tx
+            .send_blocking(ClientEvent::TxDroped(TxIdType::from_bytes(&[1u8; 32])))
+            .unwrap();
+        drop(tx);
+        drop(priority_tx);
+
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let events = rt.block_on(async move {
+            let mut ret = vec![];
+            while let Some(v) = poll_fn(|cx| Pin::new(&mut stream).poll_next(cx)).await {
+                ret.push(v);
+            }
+            ret
+        });
+        assert_eq!(events.len(), 2);
+        assert!(matches!(events[0], ClientEvent::TxDroped(_)));
+        assert!(matches!(events[1], ClientEvent::GetHeight));
+    }
+}
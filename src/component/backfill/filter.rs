@@ -0,0 +1,88 @@
+use crate::error::{IndexerError, IndexerResult};
+use bitcoincore_rpc::bitcoin::address::NetworkUnchecked;
+use bitcoincore_rpc::bitcoin::bip158::BlockFilter;
+use bitcoincore_rpc::bitcoin::{Address, BlockHash, Network, ScriptBuf};
+use std::str::FromStr;
+
+// the scripts a bip158 basic filter is matched against
+#[derive(Clone, Debug, Default)]
+pub struct ScriptWatchList {
+    scripts: Vec<ScriptBuf>,
+}
+
+impl ScriptWatchList {
+    // addresses of the network or hex encoded output scripts
+    pub fn parse(items: &[String], network: Network) -> IndexerResult<Self> {
+        let scripts = items
+            .iter()
+            .map(|v| {
+                if let Ok(address) = Address::<NetworkUnchecked>::from_str(v) {
+                    let address = address.require_network(network).map_err(|_| {
+                        IndexerError::InvalidConfiguration(format!(
+                            "watched address {} is not a {} address",
+                            v, network
+                        ))
+                    })?;
+                    return Ok(address.script_pubkey());
+                }
+                hex::decode(v).map(ScriptBuf::from_bytes).map_err(|_| {
+                    IndexerError::InvalidConfiguration(format!(
+                        "watched script {} is neither an address nor hex",
+                        v
+                    ))
+                })
+            })
+            .collect::<IndexerResult<_>>()?;
+        Ok(Self { scripts })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.scripts.is_empty()
+    }
+
+    // false positives are possible,false negatives are not
+    pub fn matches(&self, hash: &BlockHash, filter: &BlockFilter) -> IndexerResult<bool> {
+        filter
+            .match_any(hash, self.scripts.iter().map(|v| v.as_bytes()))
+            .map_err(|e| IndexerError::InvalidConfiguration(format!("invalid block filter:{}", e)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoincore_rpc::bitcoin::absolute::LockTime;
+    use bitcoincore_rpc::bitcoin::blockdata::constants::genesis_block;
+    use bitcoincore_rpc::bitcoin::{Transaction, TxOut};
+
+    #[test]
+    pub fn test_script_watch_list() {
+        let mut block = genesis_block(Network::Regtest);
+        let watched = ScriptBuf::from_bytes(vec![0x51]);
+        block.txdata.push(Transaction {
+            version: 2,
+            lock_time: LockTime::ZERO,
+            input: vec![],
+            output: vec![TxOut {
+                value: 1000,
+                script_pubkey: watched.clone(),
+            }],
+        });
+        let hash = block.block_hash();
+        let filter = BlockFilter::new_script_filter(&block, |_| {
+            Err::<ScriptBuf, _>(bitcoincore_rpc::bitcoin::bip158::Error::UtxoMissing(
+                Default::default(),
+            ))
+        })
+        .unwrap();
+
+        let list = ScriptWatchList::parse(&["51".to_string()], Network::Regtest).unwrap();
+        assert!(list.matches(&hash, &filter).unwrap());
+        let address = "bcrt1qw508d6qejxtdg4y5r3zarvary0c5xw7kygt080".to_string();
+        let other =
+            ScriptWatchList::parse(std::slice::from_ref(&address), Network::Regtest).unwrap();
+        assert!(!other.matches(&hash, &filter).unwrap());
+        assert!(ScriptWatchList::parse(&[address], Network::Bitcoin).is_err());
+        assert!(ScriptWatchList::parse(&["not a script".to_string()], Network::Regtest).is_err());
+    }
+}
@@ -0,0 +1,115 @@
+use crate::component::backfill::filter::ScriptWatchList;
+use crate::configuration::base::{IndexerConfiguration, RetryConfiguration};
+use crate::dispatcher::event::DispatchEvent;
+use crate::error::IndexerResult;
+use crate::event::IndexerEvent;
+use crate::processor::retry::with_retry;
+use crate::{Component, HookComponent};
+use async_channel::{Receiver, Sender};
+use async_trait::async_trait;
+use bitcoincore_rpc::bitcoin::block::Header;
+use bitcoincore_rpc::bitcoin::BlockHash;
+use bitcoincore_rpc::{Client, RpcApi};
+use log::info;
+use std::sync::Arc;
+use wg::AsyncWaitGroup;
+
+pub mod filter;
+
+// walks the blocks from start_height to the tip on startup,
+// the processor only handles live zmq messages once they are queued
+#[derive(Clone)]
+pub struct BackfillComponent {
+    btc_client: Arc<Client>,
+    start_height: u32,
+    retry: RetryConfiguration,
+    wg: AsyncWaitGroup,
+    // only the blocks whose compact filter matches are fetched,all of them when empty
+    watch: ScriptWatchList,
+
+    tx: Sender<DispatchEvent>,
+}
+
+#[async_trait]
+impl Component<DispatchEvent> for BackfillComponent {
+    async fn init(&mut self, cfg: &IndexerConfiguration) -> IndexerResult<()> {
+        self.watch = ScriptWatchList::parse(&cfg.watch_scripts, cfg.network)?;
+        Ok(())
+    }
+
+    async fn interest(&self, _: &DispatchEvent) -> bool {
+        false
+    }
+}
+
+#[async_trait]
+impl HookComponent<DispatchEvent> for BackfillComponent {
+    async fn before_start(
+        &mut self,
+        _: Sender<DispatchEvent>,
+        _: Receiver<DispatchEvent>,
+    ) -> IndexerResult<()> {
+        let tip = with_retry(&self.retry, "getblockcount", || {
+            self.btc_client.get_block_count()
+        })
+        .await?;
+        info!("backfill blocks,from:{},to:{}", self.start_height, tip);
+        let mut skipped = 0;
+        for height in self.start_height as u64..tip + 1 {
+            // blocks are fetched by the processor,so only hashes are queued
+            let hash = with_retry(&self.retry, "getblockhash", || {
+                self.btc_client.get_block_hash(height)
+            })
+            .await?;
+            let event = match self.skip(&hash).await? {
+                Some(header) => {
+                    skipped += 1;
+                    IndexerEvent::HeaderConnected(header, height as u32)
+                }
+                None => IndexerEvent::BlockConnected(hash),
+            };
+            let _ = self.tx.send(DispatchEvent::IndexerEvent(event)).await;
+        }
+        info!("backfill done,tip:{},skipped by filters:{}", tip, skipped);
+        self.wg.done();
+        Ok(())
+    }
+}
+
+impl BackfillComponent {
+    // the header of a block none of the watched scripts are in,needs -blockfilterindex
+    async fn skip(&self, hash: &BlockHash) -> IndexerResult<Option<Header>> {
+        if self.watch.is_empty() {
+            return Ok(None);
+        }
+        let filter = with_retry(&self.retry, "getblockfilter", || {
+            self.btc_client.get_block_filter(hash)
+        })
+        .await?;
+        if self.watch.matches(hash, &filter.to_filter())? {
+            return Ok(None);
+        }
+        let header = with_retry(&self.retry, "getblockheader", || {
+            self.btc_client.get_block_header(hash)
+        })
+        .await?;
+        Ok(Some(header))
+    }
+
+    pub fn new(
+        btc_client: Arc<Client>,
+        start_height: u32,
+        retry: RetryConfiguration,
+        wg: AsyncWaitGroup,
+        tx: Sender<DispatchEvent>,
+    ) -> Self {
+        Self {
+            btc_client,
+            start_height,
+            retry,
+            wg,
+            watch: Default::default(),
+            tx,
+        }
+    }
+}
@@ -0,0 +1,161 @@
+use crate::dispatcher::event::DispatchEvent;
+use crate::error::{IndexerError, IndexerResult};
+use crate::health::{HealthCheck, HealthStatus};
+use crate::net::http::{serve, HttpResponse};
+use crate::{Component, HookComponent};
+use async_trait::async_trait;
+use bitcoincore_rpc::{Client, RpcApi};
+use log::info;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tokio::net::TcpListener;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+
+// the probes of kubernetes:/healthz answers while the process runs,/readyz once the restore
+// completed,the ingest is connected and the node answers rpc
+#[derive(Clone)]
+pub struct HealthComponent {
+    listen: String,
+    flag: Arc<AtomicBool>,
+    // none for the poller,which has no connection to lose
+    ingest: Option<Arc<dyn HealthCheck>>,
+    btc_client: Arc<Client>,
+}
+
+impl HealthComponent {
+    pub fn new(
+        listen: String,
+        flag: Arc<AtomicBool>,
+        ingest: Option<Arc<dyn HealthCheck>>,
+        btc_client: Arc<Client>,
+    ) -> Self {
+        Self {
+            listen,
+            flag,
+            ingest,
+            btc_client,
+        }
+    }
+
+    // whether every check passed,and a line per check
+    pub async fn readiness(&self) -> (bool, String) {
+        let mut checks = vec![];
+        checks.push((
+            "restore".to_string(),
+            match self.flag.load(Ordering::Relaxed) {
+                true => Ok(()),
+                false => Err("in progress".to_string()),
+            },
+        ));
+        if let Some(ingest) = &self.ingest {
+            let health = ingest.health().await;
+            let status = match health.status {
+                HealthStatus::Failing(reason) => Err(reason),
+                _ => Ok(()),
+            };
+            checks.push((health.component, status));
+        }
+        let client = self.btc_client.clone();
+        let rpc = match tokio::task::spawn_blocking(move || client.get_block_count()).await {
+            Ok(Ok(_)) => Ok(()),
+            Ok(Err(e)) => Err(e.to_string()),
+            Err(e) => Err(e.to_string()),
+        };
+        checks.push(("rpc".to_string(), rpc));
+        let ready = checks.iter().all(|(_, v)| v.is_ok());
+        let body = checks
+            .into_iter()
+            .map(|(name, v)| match v {
+                Ok(()) => format!("{}:ok\n", name),
+                Err(e) => format!("{}:{}\n", name, e),
+            })
+            .collect();
+        (ready, body)
+    }
+
+    async fn respond(&self, path: &str) -> HttpResponse {
+        match path {
+            "/healthz" => HttpResponse {
+                status: 200,
+                body: b"ok\n".to_vec(),
+            },
+            "/readyz" => {
+                let (ready, body) = self.readiness().await;
+                HttpResponse {
+                    status: if ready { 200 } else { 503 },
+                    body: body.into_bytes(),
+                }
+            }
+            _ => HttpResponse {
+                status: 404,
+                body: b"not found".to_vec(),
+            },
+        }
+    }
+}
+
+#[async_trait]
+impl Component<DispatchEvent> for HealthComponent {
+    async fn start(&mut self, exit: watch::Receiver<()>) -> IndexerResult<Vec<JoinHandle<()>>> {
+        let listener = TcpListener::bind(&self.listen)
+            .await
+            .map_err(|e| IndexerError::InvalidConfiguration(format!("health.listen:{}", e)))?;
+        info!("health probes listen on {}", self.listen);
+        let node = self.clone();
+        let handle = serve(listener, exit, move |path: String| {
+            let node = node.clone();
+            async move { node.respond(&path).await }
+        });
+        Ok(vec![handle])
+    }
+
+    async fn interest(&self, _: &DispatchEvent) -> bool {
+        false
+    }
+}
+
+#[async_trait]
+impl HookComponent<DispatchEvent> for HealthComponent {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::health::ComponentHealth;
+    use bitcoincore_rpc::Auth;
+
+    struct Disconnected;
+
+    #[async_trait]
+    impl HealthCheck for Disconnected {
+        async fn health(&self) -> ComponentHealth {
+            ComponentHealth::new("zmq", HealthStatus::Failing("not connected".to_string()))
+        }
+    }
+
+    #[tokio::test]
+    pub async fn test_probes() {
+        // nothing listens there
+        let client = Arc::new(Client::new("http://127.0.0.1:1", Auth::None).unwrap());
+        let flag = Arc::new(AtomicBool::new(false));
+        let node = HealthComponent::new(
+            "127.0.0.1:0".to_string(),
+            flag.clone(),
+            Some(Arc::new(Disconnected)),
+            client,
+        );
+        assert_eq!(node.respond("/healthz").await.status, 200);
+        assert_eq!(node.respond("/other").await.status, 404);
+        let ret = node.respond("/readyz").await;
+        assert_eq!(ret.status, 503);
+        let body = String::from_utf8(ret.body).unwrap();
+        let lines: Vec<&str> = body.lines().collect();
+        assert_eq!(lines[..2], ["restore:in progress", "zmq:not connected"]);
+        assert!(lines[2].starts_with("rpc:") && lines[2] != "rpc:ok");
+
+        flag.store(true, Ordering::Relaxed);
+        let (ready, body) = node.readiness().await;
+        assert!(!ready);
+        assert!(body.starts_with("restore:ok\n"));
+    }
+}
@@ -1,4 +1,8 @@
+pub mod backfill;
 pub mod catchup;
+pub mod health;
 pub mod org;
+pub mod p2p;
+pub mod polling;
 pub mod waitsync;
 pub mod zmq;
@@ -0,0 +1,292 @@
+use crate::component::zmq::event::Deduplicator;
+use crate::configuration::base::{IndexerConfiguration, P2pConfiguration};
+use crate::dispatcher::event::DispatchEvent;
+use crate::error::{IndexerError, IndexerResult};
+use crate::event::IndexerEvent;
+use crate::{Component, HookComponent};
+use bitcoincore_rpc::bitcoin::consensus::{deserialize, serialize};
+use bitcoincore_rpc::bitcoin::network::constants::ServiceFlags;
+use bitcoincore_rpc::bitcoin::network::message::{NetworkMessage, RawNetworkMessage};
+use bitcoincore_rpc::bitcoin::network::message_blockdata::Inventory;
+use bitcoincore_rpc::bitcoin::network::message_network::VersionMessage;
+use bitcoincore_rpc::bitcoin::network::Address;
+use bitcoincore_rpc::bitcoin::Network;
+use chrono::Local;
+use log::{error, info};
+use std::net::{Ipv4Addr, SocketAddr};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::sync::watch::Receiver;
+use tokio::task::JoinHandle;
+use wg::AsyncWaitGroup;
+
+// witness data is served from 70012 on
+const P2P_PROTOCOL_VERSION: u32 = 70015;
+const HEADER_LEN: usize = 24;
+const MAX_PAYLOAD_LEN: usize = 4_000_000 + 1_000;
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(60);
+
+// ingestion straight from a peer of the bitcoin network:txs and blocks announced by inv are
+// fetched and queued like zmq messages,the feed needs neither zmq nor rpc of the node
+// (the processor still asks rpc on restores and lookups).
+// mempool removals are not announced on p2p,they are only noticed through the blocks
+#[derive(Clone)]
+pub struct P2pComponent {
+    config: P2pConfiguration,
+    network: Network,
+    sender: async_channel::Sender<DispatchEvent>,
+    flag: Arc<AtomicBool>,
+    connected: Arc<AtomicBool>,
+    wg: AsyncWaitGroup,
+}
+
+#[async_trait::async_trait]
+impl HookComponent<DispatchEvent> for P2pComponent {}
+
+#[async_trait::async_trait]
+impl Component<DispatchEvent> for P2pComponent {
+    async fn init(&mut self, cfg: &IndexerConfiguration) -> IndexerResult<()> {
+        self.config = cfg.ingest.p2p.clone();
+        self.network = cfg.network;
+        Ok(())
+    }
+
+    async fn start(&mut self, _: Receiver<()>) -> IndexerResult<Vec<JoinHandle<()>>> {
+        let node = self.clone();
+        Ok(vec![tokio::task::spawn(async move { node.run().await })])
+    }
+
+    async fn interest(&self, _: &DispatchEvent) -> bool {
+        false
+    }
+}
+
+impl P2pComponent {
+    pub fn new(
+        wg: AsyncWaitGroup,
+        config: P2pConfiguration,
+        network: Network,
+        sender: async_channel::Sender<DispatchEvent>,
+        flag: Arc<AtomicBool>,
+    ) -> Self {
+        Self {
+            config,
+            network,
+            sender,
+            flag,
+            connected: Default::default(),
+            wg,
+        }
+    }
+
+    // whether the handshake with the peer is done,shared with the processor status
+    pub fn connected(&self) -> Arc<AtomicBool> {
+        self.connected.clone()
+    }
+
+    async fn run(&self) {
+        let mut wg = Some(self.wg.clone());
+        let mut dedup = Deduplicator::new(10_000);
+        let mut sequence = 0u32;
+        let mut delay = Duration::from_secs(1);
+        loop {
+            let reason = match self.session(&mut wg, &mut dedup, &mut sequence).await {
+                Ok(_) => "peer closed the connection".to_string(),
+                Err(e) => e.to_string(),
+            };
+            let reason = format!("{}:{}", self.config.peer, reason);
+            if self.connected.swap(false, Ordering::Relaxed) {
+                delay = Duration::from_secs(1);
+                self.send(IndexerEvent::IngestDegraded(reason.clone()))
+                    .await;
+            }
+            error!("p2p feed degraded,{},reconnect in {:?}", reason, delay);
+            tokio::time::sleep(delay).await;
+            delay = (delay * 2).min(MAX_RECONNECT_DELAY);
+        }
+    }
+
+    async fn session(
+        &self,
+        wg: &mut Option<AsyncWaitGroup>,
+        dedup: &mut Deduplicator,
+        sequence: &mut u32,
+    ) -> IndexerResult<()> {
+        let magic = self.network.magic();
+        let mut stream = TcpStream::connect(self.config.peer.as_str()).await?;
+        let version = self.version_message(stream.peer_addr()?);
+        write_message(&mut stream, magic, NetworkMessage::Version(version)).await?;
+        loop {
+            let message = match read_message(&mut stream).await? {
+                Some(v) if v.magic == magic => v.payload,
+                Some(v) => {
+                    return Err(IndexerError::CodecError(format!(
+                        "p2p message of another network,magic:{}",
+                        v.magic
+                    )))
+                }
+                None => return Ok(()),
+            };
+            match message {
+                NetworkMessage::Version(v) => {
+                    info!("p2p peer version:{},agent:{}", v.version, v.user_agent);
+                    write_message(&mut stream, magic, NetworkMessage::Verack).await?;
+                }
+                NetworkMessage::Verack => {
+                    info!("p2p handshake with {} done", self.config.peer);
+                    if !self.connected.swap(true, Ordering::Relaxed) && wg.is_none() {
+                        self.send(IndexerEvent::IngestRestored).await;
+                    }
+                    if let Some(wg) = wg.take() {
+                        wg.done();
+                    }
+                }
+                NetworkMessage::Ping(nonce) => {
+                    write_message(&mut stream, magic, NetworkMessage::Pong(nonce)).await?;
+                }
+                NetworkMessage::Inv(items) => {
+                    let wanted = wanted_inventory(&items, dedup);
+                    if !wanted.is_empty() {
+                        write_message(&mut stream, magic, NetworkMessage::GetData(wanted)).await?;
+                    }
+                }
+                NetworkMessage::Tx(tx) => {
+                    self.wait_synced().await;
+                    *sequence = sequence.wrapping_add(1);
+                    self.send(IndexerEvent::NewTxComing(serialize(&tx), *sequence))
+                        .await;
+                }
+                NetworkMessage::Block(block) => {
+                    self.wait_synced().await;
+                    *sequence = sequence.wrapping_add(1);
+                    info!("receive p2p block:{}", block.block_hash());
+                    self.send(IndexerEvent::RawBlockComing(block, *sequence))
+                        .await;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn version_message(&self, peer: SocketAddr) -> VersionMessage {
+        let local = SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), 0);
+        let mut ret = VersionMessage::new(
+            ServiceFlags::NONE,
+            Local::now().timestamp(),
+            Address::new(&peer, ServiceFlags::NONE),
+            Address::new(&local, ServiceFlags::NONE),
+            Local::now().timestamp_nanos_opt().unwrap_or_default() as u64,
+            self.config.user_agent.clone(),
+            0,
+        );
+        ret.version = P2P_PROTOCOL_VERSION;
+        ret.relay = true;
+        ret
+    }
+
+    async fn wait_synced(&self) {
+        while !self.flag.load(Ordering::Relaxed) {
+            info!("processor is not synced yet,wait 3s");
+            tokio::time::sleep(Duration::from_secs(3)).await
+        }
+    }
+
+    async fn send(&self, event: IndexerEvent) {
+        let _ = self.sender.send(DispatchEvent::IndexerEvent(event)).await;
+    }
+}
+
+// the announced txs and blocks not requested before,asked for with their witnesses
+fn wanted_inventory(items: &[Inventory], dedup: &mut Deduplicator) -> Vec<Inventory> {
+    items
+        .iter()
+        .filter_map(|v| match v {
+            Inventory::Transaction(tx_id) | Inventory::WitnessTransaction(tx_id) => dedup
+                .first_seen("tx", &serialize(tx_id))
+                .then_some(Inventory::WitnessTransaction(*tx_id)),
+            Inventory::Block(hash) | Inventory::WitnessBlock(hash) => dedup
+                .first_seen("block", &serialize(hash))
+                .then_some(Inventory::WitnessBlock(*hash)),
+            _ => None,
+        })
+        .collect()
+}
+
+async fn write_message<W: AsyncWrite + Unpin>(
+    stream: &mut W,
+    magic: bitcoincore_rpc::bitcoin::network::Magic,
+    payload: NetworkMessage,
+) -> IndexerResult<()> {
+    let data = serialize(&RawNetworkMessage { magic, payload });
+    stream.write_all(&data).await?;
+    Ok(())
+}
+
+// magic(4)|command(12)|length(4,le)|checksum(4)|payload,none when the stream ended
+async fn read_message<R: AsyncRead + Unpin>(
+    stream: &mut R,
+) -> IndexerResult<Option<RawNetworkMessage>> {
+    let mut data = vec![0u8; HEADER_LEN];
+    if let Err(e) = stream.read_exact(&mut data).await {
+        if e.kind() == std::io::ErrorKind::UnexpectedEof {
+            return Ok(None);
+        }
+        return Err(e.into());
+    }
+    let len = u32::from_le_bytes(data[16..20].try_into().unwrap()) as usize;
+    if len > MAX_PAYLOAD_LEN {
+        return Err(IndexerError::CodecError(format!(
+            "p2p payload too large:{}",
+            len
+        )));
+    }
+    data.resize(HEADER_LEN + len, 0);
+    stream.read_exact(&mut data[HEADER_LEN..]).await?;
+    Ok(Some(deserialize(&data)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoincore_rpc::bitcoin::hashes::Hash;
+    use bitcoincore_rpc::bitcoin::{Network, Txid};
+
+    #[tokio::test]
+    pub async fn test_p2p_framing() {
+        let magic = Network::Regtest.magic();
+        let (mut a, mut b) = tokio::io::duplex(1024);
+        write_message(&mut a, magic, NetworkMessage::Ping(7))
+            .await
+            .unwrap();
+        write_message(&mut a, magic, NetworkMessage::Verack)
+            .await
+            .unwrap();
+        drop(a);
+
+        let message = read_message(&mut b).await.unwrap().unwrap();
+        assert_eq!(message.magic, magic);
+        assert_eq!(message.payload, NetworkMessage::Ping(7));
+        let message = read_message(&mut b).await.unwrap().unwrap();
+        assert_eq!(message.payload, NetworkMessage::Verack);
+        assert!(read_message(&mut b).await.unwrap().is_none());
+    }
+
+    #[test]
+    pub fn test_wanted_inventory() {
+        let tx_id = Txid::from_byte_array([1u8; 32]);
+        let mut dedup = Deduplicator::new(16);
+        let items = vec![
+            Inventory::Transaction(tx_id),
+            Inventory::WitnessTransaction(tx_id),
+            Inventory::Error,
+        ];
+        assert_eq!(
+            wanted_inventory(&items, &mut dedup),
+            vec![Inventory::WitnessTransaction(tx_id)]
+        );
+        assert!(wanted_inventory(&items, &mut dedup).is_empty());
+    }
+}
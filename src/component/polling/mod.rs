@@ -0,0 +1,227 @@
+use crate::configuration::base::RetryConfiguration;
+use crate::dispatcher::event::DispatchEvent;
+use crate::error::IndexerResult;
+use crate::event::{IndexerEvent, TxIdType};
+use crate::processor::retry::with_retry;
+use crate::{Component, HookComponent};
+use async_channel::{Receiver, Sender};
+use async_trait::async_trait;
+use bitcoincore_rpc::bitcoin::consensus::serialize;
+use bitcoincore_rpc::bitcoin::{BlockHash, Txid};
+use bitcoincore_rpc::{Client, RpcApi};
+use log::{info, warn};
+use std::collections::{HashSet, VecDeque};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use wg::AsyncWaitGroup;
+
+// the blocks remembered to detect a reorg of the tip
+const RECENT_BLOCKS: usize = 16;
+
+// ingestion for nodes without zmq:the mempool and the tip are polled and diffed,
+// the differences are queued as the events the zmq component would have sent
+#[derive(Clone)]
+pub struct PollingComponent {
+    btc_client: Arc<Client>,
+    interval: Duration,
+    retry: RetryConfiguration,
+    flag: Arc<AtomicBool>,
+    // none when zmq is enabled as well,the zmq component signals the start then
+    wg: Option<AsyncWaitGroup>,
+
+    // none until the first poll after the processor synced
+    mempool: Option<HashSet<Txid>>,
+    recent: VecDeque<(u64, BlockHash)>,
+    sequence: u32,
+
+    tx: Sender<DispatchEvent>,
+}
+
+#[async_trait]
+impl Component<DispatchEvent> for PollingComponent {
+    async fn interest(&self, _: &DispatchEvent) -> bool {
+        false
+    }
+}
+
+#[async_trait]
+impl HookComponent<DispatchEvent> for PollingComponent {
+    async fn before_start(
+        &mut self,
+        _: Sender<DispatchEvent>,
+        _: Receiver<DispatchEvent>,
+    ) -> IndexerResult<()> {
+        if let Some(wg) = self.wg.take() {
+            wg.done();
+        }
+        Ok(())
+    }
+
+    fn interval(&self) -> Option<Duration> {
+        Some(self.interval)
+    }
+
+    async fn handle_tick_event(&mut self) -> IndexerResult<()> {
+        // the processor restores the mempool on start,polling takes over from there
+        if !self.flag.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+        let mined = self.poll_blocks().await?;
+        self.poll_mempool(mined).await
+    }
+}
+
+impl PollingComponent {
+    pub fn new(
+        btc_client: Arc<Client>,
+        interval: Duration,
+        retry: RetryConfiguration,
+        flag: Arc<AtomicBool>,
+        wg: Option<AsyncWaitGroup>,
+        tx: Sender<DispatchEvent>,
+    ) -> Self {
+        Self {
+            btc_client,
+            interval,
+            retry,
+            flag,
+            wg,
+            mempool: None,
+            recent: Default::default(),
+            sequence: 0,
+            tx,
+        }
+    }
+
+    // returns the txs of the new blocks,they left the mempool without being removed
+    async fn poll_blocks(&mut self) -> IndexerResult<HashSet<Txid>> {
+        let tip = with_retry(&self.retry, "getblockcount", || {
+            self.btc_client.get_block_count()
+        })
+        .await?;
+        if self.recent.is_empty() {
+            let hash = with_retry(&self.retry, "getblockhash", || {
+                self.btc_client.get_block_hash(tip)
+            })
+            .await?;
+            self.recent.push_back((tip, hash));
+            return Ok(HashSet::new());
+        }
+        while let Some((height, hash)) = self.recent.back().cloned() {
+            if height <= tip {
+                let current = with_retry(&self.retry, "getblockhash", || {
+                    self.btc_client.get_block_hash(height)
+                })
+                .await?;
+                if current == hash {
+                    break;
+                }
+            }
+            warn!("polled tip changed,disconnect block:{},{}", height, hash);
+            self.recent.pop_back();
+            self.send(IndexerEvent::BlockDisconnected(hash)).await;
+        }
+        // deeper than the remembered blocks,continue from the tip
+        let from = match self.recent.back() {
+            Some((height, _)) => height + 1,
+            None => tip,
+        };
+        let mut mined = HashSet::new();
+        for height in from..tip + 1 {
+            let hash = with_retry(&self.retry, "getblockhash", || {
+                self.btc_client.get_block_hash(height)
+            })
+            .await?;
+            let block = with_retry(&self.retry, "getblock", || {
+                self.btc_client.get_block_info(&hash)
+            })
+            .await?;
+            mined.extend(block.tx);
+            info!("polled new block:{},{}", height, hash);
+            self.send(IndexerEvent::BlockConnected(hash)).await;
+            self.recent.push_back((height, hash));
+            if self.recent.len() > RECENT_BLOCKS {
+                self.recent.pop_front();
+            }
+        }
+        Ok(mined)
+    }
+
+    async fn poll_mempool(&mut self, mined: HashSet<Txid>) -> IndexerResult<()> {
+        let current = with_retry(&self.retry, "getrawmempool", || {
+            self.btc_client.get_raw_mempool()
+        })
+        .await?;
+        let Some(known) = self.mempool.take() else {
+            self.mempool = Some(current.into_iter().collect());
+            return Ok(());
+        };
+        let (added, removed) = diff_mempool(&known, &current, &mined);
+        let mut next: HashSet<Txid> = current.into_iter().collect();
+        for tx_id in added {
+            let tx = match self.btc_client.get_raw_transaction(&tx_id, None) {
+                Ok(tx) => tx,
+                // mined or evicted since the poll,the next block poll covers it
+                Err(e) => {
+                    warn!("get polled tx {} failed:{:?}", tx_id, e);
+                    next.remove(&tx_id);
+                    continue;
+                }
+            };
+            self.sequence = self.sequence.wrapping_add(1);
+            self.send(IndexerEvent::NewTxComing(serialize(&tx), self.sequence))
+                .await;
+        }
+        for tx_id in removed {
+            self.send(IndexerEvent::TxRemoved(TxIdType::from(tx_id)))
+                .await;
+        }
+        self.mempool = Some(next);
+        Ok(())
+    }
+
+    async fn send(&self, event: IndexerEvent) {
+        let _ = self.tx.send(DispatchEvent::IndexerEvent(event)).await;
+    }
+}
+
+// txs new to the mempool,and the ones gone from it for another reason than being mined
+fn diff_mempool(
+    known: &HashSet<Txid>,
+    current: &[Txid],
+    mined: &HashSet<Txid>,
+) -> (Vec<Txid>, Vec<Txid>) {
+    let added = current
+        .iter()
+        .filter(|v| !known.contains(*v))
+        .cloned()
+        .collect();
+    let current: HashSet<&Txid> = current.iter().collect();
+    let mut removed: Vec<Txid> = known
+        .iter()
+        .filter(|v| !current.contains(v) && !mined.contains(*v))
+        .cloned()
+        .collect();
+    removed.sort();
+    (added, removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoincore_rpc::bitcoin::hashes::Hash;
+
+    #[test]
+    pub fn test_diff_mempool() {
+        let id = |v: u8| Txid::from_byte_array([v; 32]);
+        let known: HashSet<Txid> = [id(1), id(2), id(3)].into_iter().collect();
+        let mined: HashSet<Txid> = [id(2)].into_iter().collect();
+        let (added, removed) = diff_mempool(&known, &[id(1), id(4), id(5)], &mined);
+        assert_eq!(added, vec![id(4), id(5)]);
+        assert_eq!(removed, vec![id(3)]);
+
+        let (added, removed) = diff_mempool(&known, &[id(1), id(2), id(3)], &HashSet::new());
+        assert!(added.is_empty() && removed.is_empty());
+    }
+}
@@ -1,17 +1,17 @@
-use crate::configuration::base::IndexerConfiguration;
+use crate::component::zmq::event::{Deduplicator, SequenceEvent, SequenceTracker};
+use crate::configuration::base::{IndexerConfiguration, TopicHandler, ZMQConfiguration};
 use crate::dispatcher::event::DispatchEvent;
 use crate::error::IndexerResult;
-use crate::event::{IndexerEvent, TxIdType};
-use crate::factory::common::create_client_from_configuration;
+use crate::event::IndexerEvent;
 use crate::{Component, HookComponent};
 use bitcoincore_rpc::bitcoin::consensus::{deserialize, serialize};
-use bitcoincore_rpc::bitcoin::hashes::Hash;
+use bitcoincore_rpc::bitcoin::hashes::{sha256, Hash};
 use bitcoincore_rpc::bitcoin::{Block, BlockHash, Transaction};
 use bitcoincore_rpc::RpcApi;
 use log::{error, info, warn};
-use may::go;
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 use std::vec;
 use tokio::sync::watch::Receiver;
@@ -22,9 +22,11 @@ use zeromq::{Socket, ZmqMessage};
 
 #[derive(Clone)]
 pub struct ZeroMQComponent {
-    config: IndexerConfiguration,
+    config: ZMQConfiguration,
+    client: Arc<bitcoincore_rpc::Client>,
     sender: async_channel::Sender<DispatchEvent>,
     flag: Arc<AtomicBool>,
+    connected: Arc<AtomicBool>,
     wg: AsyncWaitGroup,
 }
 
@@ -33,15 +35,31 @@ impl HookComponent<DispatchEvent> for ZeroMQComponent {}
 
 #[async_trait::async_trait]
 impl Component<DispatchEvent> for ZeroMQComponent {
-    async fn init(&mut self, cfg: IndexerConfiguration) -> IndexerResult<()> {
-        self.config = cfg.clone();
+    async fn init(&mut self, cfg: &IndexerConfiguration) -> IndexerResult<()> {
+        self.config = cfg.zmq.clone();
         Ok(())
     }
 
     async fn start(&mut self, exit: Receiver<()>) -> IndexerResult<Vec<JoinHandle<()>>> {
         let mut ret = vec![];
-        let node = ZeroMQNode::new(self.config.clone(), self.sender.clone(), self.flag.clone());
-        ret.push(node.start(exit.clone(), self.wg.clone()).await);
+        let urls = self.config.urls();
+        let dedup = (urls.len() > 1).then(|| Arc::new(Mutex::new(Deduplicator::new(DEDUP_WINDOW))));
+        let live = Arc::new(AtomicUsize::new(0));
+        for (i, url) in urls.into_iter().enumerate() {
+            let mut node = ZeroMQNode::new(
+                self.config.clone(),
+                self.client.clone(),
+                self.sender.clone(),
+                self.flag.clone(),
+            );
+            node.url = url;
+            node.connected = self.connected.clone();
+            node.live = live.clone();
+            node.dedup = dedup.clone();
+            // the start waits for the primary node only
+            let wg = (i == 0).then(|| self.wg.clone());
+            ret.push(node.start(exit.clone(), wg).await);
+        }
         Ok(ret)
     }
 
@@ -56,84 +74,217 @@ impl Component<DispatchEvent> for ZeroMQComponent {
 impl ZeroMQComponent {
     pub fn new(
         mq_wg: AsyncWaitGroup,
-        config: IndexerConfiguration,
+        config: ZMQConfiguration,
+        client: Arc<bitcoincore_rpc::Client>,
         sender: async_channel::Sender<DispatchEvent>,
         flag: Arc<AtomicBool>,
     ) -> Self {
         Self {
             config,
+            client,
             sender,
             flag,
+            connected: Default::default(),
             wg: mq_wg,
         }
     }
+
+    // whether any of the sockets is subscribed and receiving,shared with the processor status
+    pub fn connected(&self) -> Arc<AtomicBool> {
+        self.connected.clone()
+    }
 }
 
+const DEDUP_WINDOW: usize = 10_000;
+
 #[derive(Clone)]
 struct ZeroMQNode {
-    config: IndexerConfiguration,
+    config: ZMQConfiguration,
+    url: String,
     sender: async_channel::Sender<DispatchEvent>,
     flag: Arc<AtomicBool>,
+    connected: Arc<AtomicBool>,
+    // whether this node is up,and how many of the nodes are
+    link: Arc<AtomicBool>,
+    live: Arc<AtomicUsize>,
     client: Arc<bitcoincore_rpc::Client>,
+    sequences: Arc<Mutex<SequenceTracker>>,
+    // shared by the nodes when there are several
+    dedup: Option<Arc<Mutex<Deduplicator>>>,
 }
 
 impl ZeroMQNode {
     pub fn new(
-        config: IndexerConfiguration,
+        config: ZMQConfiguration,
+        client: Arc<bitcoincore_rpc::Client>,
         sender: async_channel::Sender<DispatchEvent>,
         flag: Arc<AtomicBool>,
     ) -> Self {
-        let client = create_client_from_configuration(config.clone());
         Self {
+            url: config.zmq_url.clone(),
             config,
             sender,
             flag,
-            client: Arc::new(client),
+            connected: Default::default(),
+            link: Default::default(),
+            live: Default::default(),
+            client,
+            sequences: Default::default(),
+            dedup: None,
         }
     }
-    async fn start(&self, _: Receiver<()>, wg: AsyncWaitGroup) -> JoinHandle<()> {
+    async fn start(&self, _: Receiver<()>, wg: Option<AsyncWaitGroup>) -> JoinHandle<()> {
         let node = self.clone();
         let flag = self.flag.clone();
         tokio::task::spawn(async move {
-            let mut socket = zeromq::SubSocket::new();
-            socket
-                .connect(node.config.mq.zmq_url.clone().as_str())
-                .await
-                .expect("Failed to connect");
-            // for topic in &node.config.mq.zmq_topic {
-            //     socket.subscribe(topic).await.unwrap();
-            // }
-            socket.subscribe("sequence").await.unwrap();
-            socket.subscribe("rawblock").await.unwrap();
-            wg.done();
-            loop {
-                tokio::select! {
-                        event=socket.recv()=>{
-                            if let Err(e)=event{
-                                error!("receive msg failed:{:?}",e);
-                                continue
-                            }
-
-                            loop{
-                                let synced=flag.load(Ordering::Relaxed);
-                                if synced{
-                                    break;
-                                }
-                                info!("processor is not synced yet,wait 3s");
-                                tokio::time::sleep(Duration::from_secs(3)).await
-                            }
+            let socket = match node.connect().await {
+                Ok(socket) => socket,
+                // the primary node has to be up on start,the backups may come later
+                Err(e) if wg.is_none() => {
+                    warn!("connect zmq backup {} failed:{:?}", node.url, e);
+                    node.reconnect().await
+                }
+                Err(e) => panic!("Failed to connect:{:?}", e),
+            };
+            node.set_connected(true);
+            if let Some(wg) = wg {
+                wg.done();
+            }
+            // the socket is drained by its own task,a slow handler drops messages at the receive
+            // high-water mark instead of stalling the socket.the lost ones show up as a sequence gap
+            let (queue_tx, queue_rx) = async_channel::bounded(node.config.recv_hwm.max(1));
+            tokio::task::spawn(node.clone().read(socket, queue_tx));
+            // the gauge of the metrics feature goes with the queue
+            let queue_rx = Arc::new(queue_rx);
+            #[cfg(feature = "metrics")]
+            {
+                let queue = Arc::downgrade(&queue_rx);
+                crate::metrics::METRICS.register_channel(&format!("zmq_{}", node.url), move || {
+                    queue.upgrade().map(|v| v.len())
+                });
+            }
+            while let Ok(message) = queue_rx.recv().await {
+                loop {
+                    let synced = flag.load(Ordering::Relaxed);
+                    if synced {
+                        break;
+                    }
+                    info!("processor is not synced yet,wait 3s");
+                    tokio::time::sleep(Duration::from_secs(3)).await
+                }
 
-                            let message=event.unwrap();
-                            if let Err(e)=node.handle_message(&message).await{
-                                error!("handle message failed:{:?}",e);
-                                continue
-                        }
-                        }
+                if let Err(e) = node.handle_message(&message).await {
+                    error!("handle message failed:{:?}", e);
                 }
             }
         })
     }
 
+    async fn read(self, mut socket: zeromq::SubSocket, queue: async_channel::Sender<ZmqMessage>) {
+        loop {
+            match self.recv(&mut socket).await {
+                Ok(message) => match queue.try_send(message) {
+                    Ok(_) => {}
+                    Err(async_channel::TrySendError::Full(_)) => {
+                        warn!(
+                            "zmq receive queue of {} is full({}),message dropped",
+                            self.url, self.config.recv_hwm
+                        );
+                    }
+                    Err(async_channel::TrySendError::Closed(_)) => return,
+                },
+                Err(reason) => {
+                    let reason = format!("{}:{}", self.url, reason);
+                    error!("zmq feed degraded,{},reconnecting", reason);
+                    self.set_connected(false);
+                    self.send(IndexerEvent::IngestDegraded(reason)).await;
+                    socket = self.reconnect().await;
+                    self.set_connected(true);
+                    self.send(IndexerEvent::IngestRestored).await;
+                }
+            }
+        }
+    }
+
+    async fn connect(&self) -> Result<zeromq::SubSocket, zeromq::ZmqError> {
+        let mut socket = zeromq::SubSocket::new();
+        socket.connect(self.url.as_str()).await?;
+        for topic in self.config.topics() {
+            socket.subscribe(&topic).await?;
+        }
+        Ok(socket)
+    }
+
+    // a silent socket counts as dead once the liveness timeout passed
+    async fn recv(&self, socket: &mut zeromq::SubSocket) -> Result<ZmqMessage, String> {
+        let timeout = self.config.liveness_timeout_secs;
+        if timeout == 0 {
+            return socket.recv().await.map_err(|e| e.to_string());
+        }
+        match tokio::time::timeout(Duration::from_secs(timeout), socket.recv()).await {
+            Ok(ret) => ret.map_err(|e| e.to_string()),
+            Err(_) => Err(format!("no message for {}s", timeout)),
+        }
+    }
+
+    // the messages of the outage are lost,the sequence tracker reports them as a gap afterwards
+    async fn reconnect(&self) -> zeromq::SubSocket {
+        let mut attempt = 0;
+        loop {
+            let delay = reconnect_delay(&self.config, attempt);
+            tokio::time::sleep(delay).await;
+            match self.connect().await {
+                Ok(socket) => {
+                    info!("zmq reconnected after {} attempts", attempt + 1);
+                    return socket;
+                }
+                Err(e) => {
+                    warn!(
+                        "zmq reconnect failed,attempt:{},retry in {:?},err:{:?}",
+                        attempt + 1,
+                        reconnect_delay(&self.config, attempt + 1),
+                        e
+                    );
+                    attempt += 1;
+                }
+            }
+        }
+    }
+
+    fn set_connected(&self, up: bool) {
+        if self.link.swap(up, Ordering::Relaxed) != up {
+            if up {
+                self.live.fetch_add(1, Ordering::Relaxed);
+            } else {
+                self.live.fetch_sub(1, Ordering::Relaxed);
+            }
+        }
+        self.connected
+            .store(self.live.load(Ordering::Relaxed) > 0, Ordering::Relaxed);
+    }
+
+    // the id of the message across the nodes,the per node sequence numbers left out
+    fn first_seen(&self, topic: &str, body: &[u8]) -> bool {
+        let Some(dedup) = &self.dedup else {
+            return true;
+        };
+        let id = match self.config.handler(topic) {
+            Some(TopicHandler::Sequence) => body[..body.len().min(33)].to_vec(),
+            Some(TopicHandler::Transaction | TopicHandler::Block) => {
+                sha256::Hash::hash(body).to_byte_array().to_vec()
+            }
+            _ => body.to_vec(),
+        };
+        dedup.lock().unwrap().first_seen(topic, &id)
+    }
+
+    async fn send(&self, event: IndexerEvent) {
+        self.sender
+            .send(DispatchEvent::IndexerEvent(event))
+            .await
+            .expect("unreachable");
+    }
+
     async fn handle_message(&self, message: &ZmqMessage) -> IndexerResult<()> {
         let data = message.clone().into_vec();
         if data.is_empty() {
@@ -148,115 +299,110 @@ impl ZeroMQNode {
         let body = data.get(1).unwrap();
         let sequence = data.get(2).unwrap();
         let topic = String::from_utf8_lossy(&topic[..]).to_string();
-        let events = if topic == "rawtx" {
-            let raw_tx_data = body.to_vec();
-            let transaction: Transaction =
-                deserialize(&raw_tx_data).expect("Failed to deserialize transaction");
-            let sequence_number =
-                u32::from_le_bytes(sequence.to_vec().as_slice().try_into().unwrap());
-            info!(
-                "receive new raw tx,tx_id:{},sequence:{}",
-                transaction.txid(),
-                sequence_number
-            );
-            let event = IndexerEvent::NewTxComing(raw_tx_data, sequence_number);
-            vec![event]
-        } else if topic == "hashblock" {
-            let data = body.to_vec();
-            let block_hash = hex::encode(&data);
-            let sequence_number =
-                u32::from_le_bytes(sequence.to_vec().as_slice().try_into().unwrap());
-            info!(
-                "receive new block hash:{},sequence:{}",
-                block_hash, sequence_number
-            );
-            let client = self.client.clone();
-            let mut block_info = None;
-            loop {
-                let block = client.get_block(&BlockHash::from_slice(&data).unwrap());
-                if let Err(e) = block {
-                    error!("get block info failed:{:?},have to sleep", e);
-                    tokio::time::sleep(Duration::from_secs(3)).await;
-                    continue;
-                }
-                block_info = Some(block.unwrap());
-                break;
-            }
-            let block_info: Block = block_info.unwrap();
-            let txs: Vec<TxIdType> = block_info
-                .txdata
-                .into_iter()
-                .map(|v| v.txid().into())
-                .collect();
-            info!(
-                "block txs,block_hash:{:?},txs count:{:?}",
-                block_hash,
-                txs.len()
-            );
-            let sender = self.sender.clone();
-            go!(move || {
-                for tx in txs {
-                    sender
-                        .send_blocking(DispatchEvent::IndexerEvent(IndexerEvent::NewTxComing(
-                            tx.to_bytes(),
-                            sequence_number,
-                        )))
-                        .expect("unreachable")
-                }
-            });
-
-            vec![]
-        } else if topic == "hashtx" {
-            let tx_hash = hex::encode(&body.to_vec());
-            let sequence_number =
-                u32::from_le_bytes(sequence.to_vec().as_slice().try_into().unwrap());
-            info!(
-                "receive new tx hash:{},sequence:{}",
-                tx_hash, sequence_number
-            );
-            vec![]
-        } else if topic == "rawblock" {
-            let sequence_number =
-                u32::from_le_bytes(sequence.to_vec().as_slice().try_into().unwrap());
-            let body = &body.to_vec();
-            let new_block = deserialize::<Block>(&body).expect("Failed to deserialize block");
-            info!(
-                "receive new raw block,sequence:{},hash:{:?}",
-                sequence_number,
-                new_block.block_hash()
-            );
-            let events = new_block
-                .txdata
-                .iter()
-                .map(|tx| IndexerEvent::TxConfirmed(tx.txid().into()))
-                .collect::<Vec<IndexerEvent>>();
-            events
-        } else if topic == "sequence" {
-            let hash = hex::encode(&body[..32]);
-            let label = body[32] as char;
-            info!(
-                "receive sequence topic:{:?},tx_hash:{},label:{}",
-                topic, hash, label
-            );
-            if label == 'R' {
-                vec![IndexerEvent::TxRemoved(TxIdType::from(hash))]
-            } else if label == 'A' {
-                let tx_hash = TxIdType::from(hash).into();
-                let tx = self.client.get_raw_transaction(&tx_hash, None)?;
-                let tx = serialize(&tx);
-                vec![IndexerEvent::NewTxComing(tx, 0)]
-            } else if label == 'C' {
-                vec![IndexerEvent::TxConfirmed(TxIdType::from(hash))]
-            } else {
+        #[cfg(feature = "metrics")]
+        crate::metrics::METRICS.zmq_messages.inc(&topic);
+        if let Ok(number) = sequence
+            .to_vec()
+            .as_slice()
+            .try_into()
+            .map(u32::from_le_bytes)
+        {
+            let gap = self.sequences.lock().unwrap().track(&topic, number);
+            if let Some((expected, got)) = gap {
                 warn!(
-                    "receive unknown label:{:?},maybe we need to handle it",
-                    label
+                    "zmq messages lost,topic:{},expected sequence:{},got:{}",
+                    topic, expected, got
+                );
+                self.sender
+                    .send(DispatchEvent::IndexerEvent(IndexerEvent::SequenceGap(
+                        expected, got,
+                    )))
+                    .await
+                    .expect("unreachable");
+            }
+        }
+        if !self.first_seen(&topic, body) {
+            return Ok(());
+        }
+        let events = match self.config.handler(&topic) {
+            Some(TopicHandler::Transaction) => {
+                let raw_tx_data = body.to_vec();
+                let transaction: Transaction =
+                    deserialize(&raw_tx_data).expect("Failed to deserialize transaction");
+                received(|| format!("tx:{}", transaction.txid()));
+                let sequence_number =
+                    u32::from_le_bytes(sequence.to_vec().as_slice().try_into().unwrap());
+                info!(
+                    "receive new raw tx,tx_id:{},sequence:{}",
+                    transaction.txid(),
+                    sequence_number
                 );
+                let event = IndexerEvent::NewTxComing(raw_tx_data, sequence_number);
+                vec![event]
+            }
+            Some(TopicHandler::BlockHash) => {
+                let block_hash = hex::encode(body);
+                let sequence_number =
+                    u32::from_le_bytes(sequence.to_vec().as_slice().try_into().unwrap());
+                info!(
+                    "receive new block hash:{},sequence:{}",
+                    block_hash, sequence_number
+                );
+                let hash = BlockHash::from_str(&block_hash).expect("invalid block hash");
+                received(|| format!("block:{}", hash));
+                vec![IndexerEvent::BlockConnected(hash)]
+            }
+            Some(TopicHandler::TxHash) => {
+                let tx_hash = hex::encode(&body.to_vec());
+                let sequence_number =
+                    u32::from_le_bytes(sequence.to_vec().as_slice().try_into().unwrap());
+                info!(
+                    "receive new tx hash:{},sequence:{}",
+                    tx_hash, sequence_number
+                );
+                vec![]
+            }
+            Some(TopicHandler::Block) => {
+                let sequence_number =
+                    u32::from_le_bytes(sequence.to_vec().as_slice().try_into().unwrap());
+                let body = &body.to_vec();
+                let new_block = deserialize::<Block>(&body).expect("Failed to deserialize block");
+                info!(
+                    "receive new raw block,sequence:{},hash:{:?}",
+                    sequence_number,
+                    new_block.block_hash()
+                );
+                received(|| format!("block:{}", new_block.block_hash()));
+                vec![IndexerEvent::RawBlockComing(new_block, sequence_number)]
+            }
+            Some(TopicHandler::Sequence) => {
+                let event = SequenceEvent::parse(body)?;
+                info!("receive sequence topic,event:{:?}", event);
+                match event {
+                    SequenceEvent::MempoolAdded(tx_id, _) => {
+                        received(|| format!("tx:{}", tx_id.0));
+                        let tx = self.client.get_raw_transaction(&tx_id.into(), None)?;
+                        let sequence_number =
+                            u32::from_le_bytes(sequence.to_vec().as_slice().try_into().unwrap());
+                        vec![IndexerEvent::NewTxComing(serialize(&tx), sequence_number)]
+                    }
+                    SequenceEvent::MempoolRemoved(tx_id, _) => vec![IndexerEvent::TxRemoved(tx_id)],
+                    SequenceEvent::BlockConnected(hash) => {
+                        received(|| format!("block:{}", hash));
+                        vec![IndexerEvent::BlockConnected(hash)]
+                    }
+                    SequenceEvent::BlockDisconnected(hash) => {
+                        vec![IndexerEvent::BlockDisconnected(hash)]
+                    }
+                }
+            }
+            Some(TopicHandler::Passthrough) => {
+                vec![IndexerEvent::TopicMessage(topic.clone(), body.to_vec())]
+            }
+            None => {
+                warn!("receive unknown topic:{:?}", topic);
                 vec![]
             }
-        } else {
-            warn!("receive unknown topic:{:?}", topic);
-            vec![]
         };
         for event in events {
             self.sender
@@ -268,20 +414,102 @@ impl ZeroMQNode {
     }
 }
 
+// the start of the lag of a tx or block,see Metrics::delivered
+fn received(_key: impl FnOnce() -> String) {
+    #[cfg(feature = "metrics")]
+    crate::metrics::METRICS.received(_key());
+}
+
+fn reconnect_delay(cfg: &ZMQConfiguration, attempt: u32) -> Duration {
+    Duration::from_millis(
+        cfg.reconnect_base_delay_ms
+            .saturating_mul(1 << attempt.min(32))
+            .min(cfg.reconnect_max_delay_ms),
+    )
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::configuration::base::ZMQConfiguration;
+    use crate::factory::common::create_client_from_configuration;
     use std::thread::sleep;
     use tokio::sync::watch;
 
+    #[test]
+    pub fn test_reconnect_delay() {
+        let cfg = ZMQConfiguration {
+            reconnect_base_delay_ms: 100,
+            reconnect_max_delay_ms: 1000,
+            ..Default::default()
+        };
+        assert_eq!(reconnect_delay(&cfg, 0), Duration::from_millis(100));
+        assert_eq!(reconnect_delay(&cfg, 2), Duration::from_millis(400));
+        assert_eq!(reconnect_delay(&cfg, 4), Duration::from_millis(1000));
+        assert_eq!(reconnect_delay(&cfg, 100), Duration::from_millis(1000));
+    }
+
+    fn client() -> Arc<bitcoincore_rpc::Client> {
+        Arc::new(create_client_from_configuration(&Default::default()))
+    }
+
+    #[tokio::test]
+    pub async fn test_topic_handlers() {
+        let mut config = ZMQConfiguration::default();
+        config
+            .topic_handlers
+            .insert("proxy.fee".to_string(), TopicHandler::Passthrough);
+        config
+            .topic_handlers
+            .insert("proxy.block".to_string(), TopicHandler::BlockHash);
+        assert_eq!(config.topics().len(), 5);
+        assert_eq!(config.handler("rawtx"), Some(TopicHandler::Transaction));
+        assert_eq!(config.handler("other"), None);
+
+        let (tx, rx) = async_channel::unbounded();
+        let node = ZeroMQNode::new(config, client(), tx, Arc::new(AtomicBool::new(true)));
+        let message = |topic: &str, body: Vec<u8>, seq: u32| {
+            let mut message = ZmqMessage::from(topic.to_string());
+            message.push_back(body.into());
+            message.push_back(seq.to_le_bytes().to_vec().into());
+            message
+        };
+        node.handle_message(&message("proxy.fee", vec![1, 2, 3], 0))
+            .await
+            .unwrap();
+        node.handle_message(&message("proxy.block", vec![7u8; 32], 0))
+            .await
+            .unwrap();
+        node.handle_message(&message("other", vec![1], 0))
+            .await
+            .unwrap();
+
+        let DispatchEvent::IndexerEvent(IndexerEvent::TopicMessage(topic, body)) =
+            rx.recv().await.unwrap()
+        else {
+            panic!("expect a topic message");
+        };
+        assert_eq!((topic.as_str(), body), ("proxy.fee", vec![1, 2, 3]));
+        let DispatchEvent::IndexerEvent(IndexerEvent::BlockConnected(hash)) =
+            rx.recv().await.unwrap()
+        else {
+            panic!("expect a connected block");
+        };
+        assert_eq!(hash.to_byte_array(), [7u8; 32]);
+        assert!(rx.is_empty());
+    }
+
     #[tokio::test]
     pub async fn test_asd() {
-        let config = IndexerConfiguration::default();
+        let config = ZMQConfiguration::default();
         let (tx, _) = async_channel::unbounded();
         let wg = AsyncWaitGroup::new();
-        let mut component =
-            ZeroMQComponent::new(wg, config, tx.clone(), Arc::new(AtomicBool::new(true)));
+        let mut component = ZeroMQComponent::new(
+            wg,
+            config,
+            client(),
+            tx.clone(),
+            Arc::new(AtomicBool::new(true)),
+        );
         let (exit_tx, exit_rx) = watch::channel(());
         let nodes = component.start(exit_rx.clone()).await.unwrap();
         for node in nodes {
@@ -298,13 +526,15 @@ mod tests {
             .format_target(false)
             .init();
         let (exit_tx, exit_rx) = watch::channel(());
-        let mut config = IndexerConfiguration::default();
-        config.mq.zmq_url = "tcp://0.0.0.0:28332".to_string();
+        let config = ZMQConfiguration {
+            zmq_url: "tcp://0.0.0.0:28332".to_string(),
+            ..Default::default()
+        };
 
         let (tx, _) = async_channel::unbounded();
-        let node = ZeroMQNode::new(config, tx, Arc::new(AtomicBool::new(true)));
+        let node = ZeroMQNode::new(config, client(), tx, Arc::new(AtomicBool::new(true)));
         let wg = AsyncWaitGroup::new();
-        let handler = node.start(exit_rx, wg).await;
+        let handler = node.start(exit_rx, Some(wg)).await;
         handler.await.expect("TODO: panic message");
         sleep(Duration::from_secs(10000000000));
         drop(exit_tx)
@@ -1,6 +1,169 @@
+use crate::error::{IndexerError, IndexerResult};
+use crate::event::TxIdType;
 use crate::Event;
+use bitcoincore_rpc::bitcoin::BlockHash;
+use lru::LruCache;
+use std::collections::HashMap;
+use std::num::NonZeroUsize;
+use std::str::FromStr;
 
 #[derive(Clone, Debug)]
 pub enum ZeroMQEvent {}
 
 impl Event for ZeroMQEvent {}
+
+// body of the `sequence` topic: hash(32)|label(1)|mempool sequence(8,le,only for A and R)
+#[derive(Clone, Debug, PartialEq)]
+pub enum SequenceEvent {
+    MempoolAdded(TxIdType, u64),
+    MempoolRemoved(TxIdType, u64),
+    BlockConnected(BlockHash),
+    BlockDisconnected(BlockHash),
+}
+
+impl SequenceEvent {
+    pub fn parse(body: &[u8]) -> IndexerResult<Self> {
+        if body.len() < 33 {
+            return Err(IndexerError::InvalidZmqMessage(format!(
+                "sequence body too short:{}",
+                body.len()
+            )));
+        }
+        let hash = hex::encode(&body[..32]);
+        let label = body[32] as char;
+        let mempool_sequence = || -> IndexerResult<u64> {
+            if body.len() != 41 {
+                return Err(IndexerError::InvalidZmqMessage(format!(
+                    "sequence body of label {} must be 41 bytes,got:{}",
+                    label,
+                    body.len()
+                )));
+            }
+            Ok(u64::from_le_bytes(body[33..41].try_into().unwrap()))
+        };
+        let block_hash = || {
+            BlockHash::from_str(&hash).map_err(|e| IndexerError::InvalidZmqMessage(e.to_string()))
+        };
+        match label {
+            'A' => Ok(SequenceEvent::MempoolAdded(
+                TxIdType::from(hash.clone()),
+                mempool_sequence()?,
+            )),
+            'R' => Ok(SequenceEvent::MempoolRemoved(
+                TxIdType::from(hash.clone()),
+                mempool_sequence()?,
+            )),
+            'C' => Ok(SequenceEvent::BlockConnected(block_hash()?)),
+            'D' => Ok(SequenceEvent::BlockDisconnected(block_hash()?)),
+            _ => Err(IndexerError::InvalidZmqMessage(format!(
+                "unknown sequence label:{}",
+                label
+            ))),
+        }
+    }
+}
+
+// bitcoind numbers the messages of every topic,a skipped number means lost messages
+#[derive(Clone, Debug, Default)]
+pub struct SequenceTracker {
+    last: HashMap<String, u32>,
+}
+
+impl SequenceTracker {
+    // returns the expected and received number on a gap
+    pub fn track(&mut self, topic: &str, sequence: u32) -> Option<(u32, u32)> {
+        let last = self.last.insert(topic.to_string(), sequence)?;
+        let expected = last.wrapping_add(1);
+        if sequence == expected {
+            return None;
+        }
+        Some((expected, sequence))
+    }
+}
+
+// every redundant node delivers the same messages,only the first copy is handled.
+// the ids are remembered in a bounded window,a copy arriving later than that passes again
+pub struct Deduplicator {
+    seen: LruCache<(String, Vec<u8>), ()>,
+}
+
+impl Deduplicator {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            seen: LruCache::new(NonZeroUsize::new(capacity.max(1)).unwrap()),
+        }
+    }
+
+    pub fn first_seen(&mut self, topic: &str, id: &[u8]) -> bool {
+        self.seen
+            .put((topic.to_string(), id.to_vec()), ())
+            .is_none()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_sequence_gap() {
+        let mut tracker = SequenceTracker::default();
+        assert_eq!(tracker.track("rawtx", 5), None);
+        assert_eq!(tracker.track("rawtx", 6), None);
+        assert_eq!(tracker.track("rawblock", 0), None);
+        assert_eq!(tracker.track("rawtx", 9), Some((7, 9)));
+        assert_eq!(tracker.track("rawtx", 10), None);
+        assert_eq!(tracker.track("rawblock", 1), None);
+
+        tracker.track("sequence", u32::MAX);
+        assert_eq!(tracker.track("sequence", 0), None);
+    }
+
+    #[test]
+    pub fn test_deduplicator() {
+        let mut dedup = Deduplicator::new(2);
+        assert!(dedup.first_seen("rawtx", b"a"));
+        assert!(!dedup.first_seen("rawtx", b"a"));
+        assert!(dedup.first_seen("rawblock", b"a"));
+        assert!(dedup.first_seen("rawtx", b"b"));
+        // evicted
+        assert!(dedup.first_seen("rawtx", b"a"));
+    }
+
+    #[test]
+    pub fn test_parse_sequence() {
+        let hash = [7u8; 32];
+        let mut body = hash.to_vec();
+        body.push(b'A');
+        body.extend_from_slice(&42u64.to_le_bytes());
+        assert_eq!(
+            SequenceEvent::parse(&body).unwrap(),
+            SequenceEvent::MempoolAdded(TxIdType::from_bytes(&hash), 42)
+        );
+        body[32] = b'R';
+        assert_eq!(
+            SequenceEvent::parse(&body).unwrap(),
+            SequenceEvent::MempoolRemoved(TxIdType::from_bytes(&hash), 42)
+        );
+
+        let mut body = hash.to_vec();
+        body.push(b'C');
+        let block_hash = BlockHash::from_str(&hex::encode(hash)).unwrap();
+        assert_eq!(
+            SequenceEvent::parse(&body).unwrap(),
+            SequenceEvent::BlockConnected(block_hash)
+        );
+        body[32] = b'D';
+        assert_eq!(
+            SequenceEvent::parse(&body).unwrap(),
+            SequenceEvent::BlockDisconnected(block_hash)
+        );
+
+        // mempool events need the sequence number
+        body[32] = b'A';
+        assert!(SequenceEvent::parse(&body).is_err());
+        body[32] = b'X';
+        assert!(SequenceEvent::parse(&body).is_err());
+        assert!(SequenceEvent::parse(&hash).is_err());
+    }
+}
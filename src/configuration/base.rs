@@ -1,60 +1,585 @@
+use crate::storage::middleware::MiddlewareStack;
+use crate::storage::prune::PruneRetention;
+use bitcoincore_rpc::bitcoin::Network;
 use log::Level;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct IndexerConfiguration {
-    pub mq: ZMQConfiguration,
-    pub net: NetConfiguration,
+    // the chain of the node,checked on start. addresses and p2p messages of other networks are refused
+    #[serde(deserialize_with = "deserialize_network")]
+    pub network: Network,
+    // the sections handed to the component of the same name on init
+    pub zmq: ZMQConfiguration,
+    pub rpc: RpcConfiguration,
+    pub storage: StorageConfiguration,
+    pub dispatcher: DispatcherConfiguration,
+    pub client: ClientConfiguration,
+    pub ingest: IngestConfiguration,
+    pub log_configuration: LogConfiguration,
+    pub metrics: MetricsConfiguration,
+    pub health: HealthConfiguration,
+    // the processor takes the rest,record created/spent outputs of every mempool tx
+    pub track_utxos: bool,
+    // resolve the prevouts of dispatched txs and deliver them as RichTransaction
+    pub enrich_prevouts: bool,
+    // index historical blocks from this height before going live
+    pub start_height: Option<u32>,
+    // addresses or hex scripts,the backfill skips the blocks whose bip158 filter matches none
+    // of them (needs -blockfilterindex on the node)
+    pub watch_scripts: Vec<String>,
+    // blocks on top of (and including) the one a tx was mined in before it is confirmed
+    pub confirmations: u32,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StorageConfiguration {
     pub db_path: String,
+    // hex encoded 32 bytes key,the stored values are encrypted with it (EncryptedDB) when set
+    pub encryption_key: Option<String>,
     pub save_block_cache_count: u32,
-    pub log_configuration: LogConfiguration,
+    pub snapshot: SnapshotConfiguration,
+    pub prune: PruneConfiguration,
+    // layers wrapped around the storage,outermost first
+    #[serde(skip)]
+    pub middlewares: MiddlewareStack,
+}
+
+impl Default for StorageConfiguration {
+    fn default() -> Self {
+        Self {
+            db_path: "./indexerdb".to_string(),
+            encryption_key: None,
+            save_block_cache_count: 10,
+            snapshot: Default::default(),
+            prune: Default::default(),
+            middlewares: Default::default(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DispatcherConfiguration {
+    // applies to every component the dispatcher runs
+    pub restart_policy: RestartPolicy,
+}
+
+// how events reach the clients
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ClientConfiguration {
+    // persist every client event so consumers can replay them
+    pub event_journal: bool,
+    // keep the last n journal records,0 keeps them all
+    pub journal_retention: u64,
+    // coalesce the events of one block or one restore pass into a ClientEvent::Batch
+    pub batch_delivery: bool,
+    // emit ClientEvent::Checkpoint every n reported heights,0 disables it
+    pub checkpoint_interval: u32,
+    // delivered txs are redelivered until the client acks them,0 disables acknowledgments
+    pub ack_timeout_secs: u64,
+    // client calls waiting on the processor fail with IndexerError::Timeout after it,0 waits forever
+    pub timeout_ms: u64,
+}
+
+// the prometheus exporter,needs the metrics feature
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MetricsConfiguration {
+    // e.g. 127.0.0.1:9100,/metrics is served there. none disables the exporter
+    pub listen: Option<String>,
 }
 
-#[derive(Clone, Debug)]
+// the http probes of component::health::HealthComponent
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HealthConfiguration {
+    // e.g. 0.0.0.0:8080 for /healthz and /readyz. none disables the probes
+    pub listen: Option<String>,
+}
+
+// what happens to a component whose task failed or panicked
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RestartPolicy {
+    Never,
+    Always,
+    // base_delay_ms doubled per failure in a row,up to max_delay_ms
+    Backoff {
+        base_delay_ms: u64,
+        max_delay_ms: u64,
+    },
+}
+
+impl Default for RestartPolicy {
+    fn default() -> Self {
+        RestartPolicy::Backoff {
+            base_delay_ms: 1000,
+            max_delay_ms: 60_000,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct LogConfiguration {
     pub log_level: log::LevelFilter,
+    // see logging::init_logging
+    pub stderr: bool,
+    pub file: Option<LogFileConfiguration>,
+    // of both the stderr and the file output
+    pub format: LogFormat,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LogFormat {
+    #[default]
+    Text,
+    // a json object per line,with the txid,height,component and event fields when known
+    Json,
 }
 impl Default for LogConfiguration {
     fn default() -> Self {
         Self {
             log_level: log::LevelFilter::Debug,
+            stderr: true,
+            file: None,
+            format: LogFormat::Text,
+        }
+    }
+}
+
+// the file is rotated into path.1(.gz) once it grew above max_size_mb or rotate_interval_secs
+// passed,the oldest of the retention rotated files is deleted on the next rotation
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LogFileConfiguration {
+    pub path: String,
+    // 0 disables the size based rotation
+    pub max_size_mb: u64,
+    // e.g. 86400 for a daily rotation,0 disables the time based rotation
+    pub rotate_interval_secs: u64,
+    // rotated files kept,0 deletes them right away
+    pub retention: u32,
+    pub compress: bool,
+}
+
+impl Default for LogFileConfiguration {
+    fn default() -> Self {
+        Self {
+            path: "./logs/indexer.log".to_string(),
+            max_size_mb: 100,
+            rotate_interval_secs: 0,
+            retention: 7,
+            compress: true,
         }
     }
 }
+// bitcoin,testnet,signet,regtest as well as the names of bitcoind,e.g. main and test
+fn deserialize_network<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Network, D::Error> {
+    let name = String::deserialize(deserializer)?;
+    match name.to_lowercase().as_str() {
+        "mainnet" | "main" => Ok(Network::Bitcoin),
+        "test" => Ok(Network::Testnet),
+        v => v.parse().map_err(serde::de::Error::custom),
+    }
+}
+
 impl Default for IndexerConfiguration {
     fn default() -> Self {
         Self {
-            mq: ZMQConfiguration {
-                zmq_url: "tcp://0.0.0.0:28332".to_string(),
-                zmq_topic: vec!["sequence".to_string(), "rawtx".to_string()],
-            },
-            net: Default::default(),
-            db_path: "./indexerdb".to_string(),
-            save_block_cache_count: 10,
+            network: Network::Regtest,
+            zmq: Default::default(),
+            rpc: Default::default(),
+            storage: Default::default(),
+            dispatcher: Default::default(),
+            client: Default::default(),
+            ingest: Default::default(),
             log_configuration: LogConfiguration {
                 log_level: Level::Debug.to_level_filter(),
+                ..Default::default()
             },
+            metrics: Default::default(),
+            health: Default::default(),
+            track_utxos: false,
+            enrich_prevouts: false,
+            start_height: None,
+            watch_scripts: vec![],
+            confirmations: 1,
+        }
+    }
+}
+impl IndexerConfiguration {
+    // the sections the processor is handed
+    pub fn processor(&self) -> ProcessorConfiguration {
+        ProcessorConfiguration {
+            rpc: self.rpc.clone(),
+            storage: self.storage.clone(),
+            client: self.client.clone(),
+            track_utxos: self.track_utxos,
+            enrich_prevouts: self.enrich_prevouts,
+            confirmations: self.confirmations,
+        }
+    }
+}
+
+// the rpc,storage and client sections and the processor fields of the configuration,
+// named as in IndexerConfiguration so the reload paths match
+#[derive(Clone, Debug, Serialize)]
+pub struct ProcessorConfiguration {
+    pub rpc: RpcConfiguration,
+    pub storage: StorageConfiguration,
+    pub client: ClientConfiguration,
+    pub track_utxos: bool,
+    pub enrich_prevouts: bool,
+    pub confirmations: u32,
+}
+
+impl Default for ProcessorConfiguration {
+    fn default() -> Self {
+        IndexerConfiguration::default().processor()
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RpcConfiguration {
+    pub url: String,
+    pub username: String,
+    pub password: String,
+    // path of the .cookie of bitcoind,used instead of username/password when set
+    pub cookie_file: Option<String>,
+    // tcp://host:port of an electrum server,tx and prevout lookups go there instead of rpc
+    pub electrum_url: Option<String>,
+    // http base url of an esplora api,e.g. http://localhost:3000/api,txs and blocks are
+    // fetched there instead of rpc
+    pub esplora_url: Option<String>,
+    // rpc connections restores fetch the mempool txs over in parallel,in json-rpc batches
+    // of rpc_batch_size txs
+    pub rpc_pool_size: usize,
+    pub max_concurrent_rpc: usize,
+    pub rpc_batch_size: usize,
+    // bounds connecting,sending and reading of every rpc call,a hung node fails the call instead
+    // of stalling the processor
+    pub rpc_timeout_ms: u64,
+    // blocks are only handled once enough nodes agree on their hash at that height
+    pub quorum: Option<QuorumConfiguration>,
+    // caps the lookups sent to a shared node
+    pub rate_limit: RateLimitConfiguration,
+    pub retry: RetryConfiguration,
+}
+
+impl RpcConfiguration {
+    pub fn endpoint(&self) -> RpcEndpoint {
+        RpcEndpoint {
+            url: self.url.clone(),
+            username: self.username.clone(),
+            password: self.password.clone(),
+            cookie_file: self.cookie_file.clone(),
         }
     }
 }
-#[derive(Clone, Debug)]
-pub struct NetConfiguration {
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RpcEndpoint {
     pub url: String,
     pub username: String,
     pub password: String,
+    pub cookie_file: Option<String>,
+}
+
+// requests per second,0 is unlimited.
+// a lookup takes a token of its class and one of per_second
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RateLimitConfiguration {
+    pub per_second: u32,
+    // tx lookups,e.g. restores and prevouts
+    pub txs_per_second: u32,
+    // blocks,block hashes and heights
+    pub blocks_per_second: u32,
+    pub fees_per_second: u32,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct QuorumConfiguration {
+    // the nodes next to url,url itself votes for the blocks it announces
+    pub nodes: Vec<RpcEndpoint>,
+    // agreeing nodes including url
+    pub required: usize,
+    // lagging nodes are asked again before the block fails into the dead letters
+    pub attempts: u32,
+    pub retry_delay_ms: u64,
+}
+
+impl Default for QuorumConfiguration {
+    fn default() -> Self {
+        Self {
+            nodes: vec![],
+            required: 2,
+            attempts: 5,
+            retry_delay_ms: 2000,
+        }
+    }
 }
 
-impl Default for NetConfiguration {
+impl Default for RpcConfiguration {
     fn default() -> Self {
         Self {
             url: "http://localhost:18443".to_string(),
             username: "bitcoinrpc".to_string(),
             password: "bitcoinrpc".to_string(),
+            cookie_file: None,
+            electrum_url: None,
+            esplora_url: None,
+            rpc_pool_size: 4,
+            max_concurrent_rpc: 16,
+            rpc_batch_size: 100,
+            rpc_timeout_ms: 15_000,
+            quorum: None,
+            rate_limit: Default::default(),
+            retry: Default::default(),
         }
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct ZMQConfiguration {
     pub zmq_url: String,
     pub zmq_topic: Vec<String>,
+    // redundant bitcoind nodes subscribed next to zmq_url,a message is handled once whichever
+    // node delivers it first
+    pub backup_urls: Vec<String>,
+    // the socket is reconnected when no message came for this long,0 disables the check.
+    // set it above the longest quiet period of the node,regtest can be silent for hours
+    pub liveness_timeout_secs: u64,
+    // exponential backoff between reconnect attempts,they go on until one succeeds
+    pub reconnect_base_delay_ms: u64,
+    pub reconnect_max_delay_ms: u64,
+    // messages buffered between the socket and the handler,the ones beyond are dropped
+    pub recv_hwm: usize,
+    // extra topics and how their messages are handled,e.g. a proxy republishing rawtx as
+    // "mempool.tx". they are subscribed next to sequence,rawblock and hashblock,and a topic
+    // listed here overrides the built in handler of the same name
+    pub topic_handlers: HashMap<String, TopicHandler>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TopicHandler {
+    Transaction,
+    Block,
+    BlockHash,
+    TxHash,
+    Sequence,
+    // the raw body is forwarded to the client untouched
+    Passthrough,
+}
+
+impl Default for ZMQConfiguration {
+    fn default() -> Self {
+        Self {
+            zmq_url: "tcp://0.0.0.0:28332".to_string(),
+            zmq_topic: vec!["sequence".to_string(), "rawtx".to_string()],
+            backup_urls: vec![],
+            liveness_timeout_secs: 0,
+            reconnect_base_delay_ms: 500,
+            reconnect_max_delay_ms: 30_000,
+            recv_hwm: 10_000,
+            topic_handlers: HashMap::new(),
+        }
+    }
+}
+
+impl ZMQConfiguration {
+    pub fn topics(&self) -> Vec<String> {
+        let mut ret: Vec<String> = ["sequence", "rawblock", "hashblock"]
+            .iter()
+            .map(|v| v.to_string())
+            .collect();
+        for topic in self.topic_handlers.keys() {
+            if !ret.contains(topic) {
+                ret.push(topic.clone());
+            }
+        }
+        ret
+    }
+
+    pub fn handler(&self, topic: &str) -> Option<TopicHandler> {
+        if let Some(handler) = self.topic_handlers.get(topic) {
+            return Some(handler.clone());
+        }
+        match topic {
+            "rawtx" => Some(TopicHandler::Transaction),
+            "rawblock" => Some(TopicHandler::Block),
+            "hashblock" => Some(TopicHandler::BlockHash),
+            "hashtx" => Some(TopicHandler::TxHash),
+            "sequence" => Some(TopicHandler::Sequence),
+            _ => None,
+        }
+    }
+
+    pub fn urls(&self) -> Vec<String> {
+        let mut ret = vec![self.zmq_url.clone()];
+        for url in &self.backup_urls {
+            if !ret.contains(url) {
+                ret.push(url.clone());
+            }
+        }
+        ret
+    }
+}
+
+// where the live mempool and block events come from
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IngestMode {
+    Zmq,
+    // polls the rpc of nodes which don't expose zmq,e.g. managed providers
+    Polling,
+    // polling catches what zmq dropped
+    Both,
+    // listens to a peer of the bitcoin network,see P2pConfiguration
+    P2p,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct IngestConfiguration {
+    pub mode: IngestMode,
+    pub poll_interval_ms: u64,
+    pub p2p: P2pConfiguration,
+}
+
+impl Default for IngestConfiguration {
+    fn default() -> Self {
+        Self {
+            mode: IngestMode::Zmq,
+            poll_interval_ms: 5_000,
+            p2p: Default::default(),
+        }
+    }
+}
+
+impl IngestConfiguration {
+    pub fn zmq(&self) -> bool {
+        matches!(self.mode, IngestMode::Zmq | IngestMode::Both)
+    }
+
+    pub fn polling(&self) -> bool {
+        matches!(self.mode, IngestMode::Polling | IngestMode::Both)
+    }
+
+    pub fn p2p(&self) -> bool {
+        self.mode == IngestMode::P2p
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct P2pConfiguration {
+    // host:port of the peer,it has to be on IndexerConfiguration::network
+    pub peer: String,
+    pub user_agent: String,
+}
+
+impl Default for P2pConfiguration {
+    fn default() -> Self {
+        Self {
+            peer: "127.0.0.1:18444".to_string(),
+            user_agent: "/indexer-sdk:0.1.0/".to_string(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SnapshotConfiguration {
+    // checkpoints are disabled when none
+    pub snapshot_dir: Option<String>,
+    // take a checkpoint every n processed blocks
+    pub block_interval: u32,
+    // keep the latest n checkpoints in snapshot_dir,0 keeps them all
+    pub keep: u32,
+    // load the latest snapshot in snapshot_dir before starting
+    pub boot_from_snapshot: bool,
+    // object store (mounted bucket) checkpoints are uploaded to and booted from.
+    // there is no s3/gcs client,mount the bucket with s3fs or gcsfuse or pass an ObjectStore
+    // to the builder,which replaces this one
+    pub upload_dir: Option<String>,
+    pub upload_retries: u32,
+}
+
+impl Default for SnapshotConfiguration {
+    fn default() -> Self {
+        Self {
+            snapshot_dir: None,
+            block_interval: 100,
+            keep: 3,
+            boot_from_snapshot: false,
+            upload_dir: None,
+            upload_retries: 3,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PruneConfiguration {
+    // pruning is disabled when none
+    pub retention: Option<PruneRetention>,
+    // move inactive deltas into the audit log on every pass
+    pub gc_inactive: bool,
+    pub interval_secs: u64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RetryConfiguration {
+    // retries after the first attempt,0 disables retrying
+    pub max_retries: u32,
+    pub base_delay_ms: u64,
+    pub max_delay_ms: u64,
+    // max_retries of single rpc methods,e.g. getrawtransaction:0 for lookups that are retried later anyway
+    pub method_retries: HashMap<String, u32>,
+}
+
+impl RetryConfiguration {
+    pub fn max_retries(&self, method: &str) -> u32 {
+        self.method_retries
+            .get(method)
+            .copied()
+            .unwrap_or(self.max_retries)
+    }
+}
+
+impl Default for RetryConfiguration {
+    fn default() -> Self {
+        Self {
+            max_retries: 5,
+            base_delay_ms: 200,
+            max_delay_ms: 10_000,
+            method_retries: Default::default(),
+        }
+    }
+}
+
+impl Default for PruneConfiguration {
+    fn default() -> Self {
+        Self {
+            retention: None,
+            gc_inactive: true,
+            interval_secs: 60 * 10,
+        }
+    }
 }
@@ -0,0 +1,263 @@
+use crate::configuration::base::{IndexerConfiguration, RpcEndpoint};
+use crate::error::{ConfigurationError, ConfigurationErrors};
+use std::path::Path;
+
+const ZMQ_TOPICS: [&str; 5] = ["rawtx", "rawblock", "hashtx", "hashblock", "sequence"];
+
+// builds a configuration that is checked before any component starts,the INDEXER_SDK_*
+// environment variables are applied on build
+#[derive(Clone, Default)]
+pub struct IndexerConfigurationBuilder {
+    cfg: IndexerConfiguration,
+}
+
+impl IndexerConfigurationBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // e.g. the result of IndexerConfiguration::from_file
+    pub fn from_configuration(cfg: IndexerConfiguration) -> Self {
+        Self { cfg }
+    }
+
+    pub fn with_zmq_url(mut self, url: &str) -> Self {
+        self.cfg.zmq.zmq_url = url.to_string();
+        self
+    }
+
+    pub fn with_zmq_topics(mut self, topics: &[&str]) -> Self {
+        self.cfg.zmq.zmq_topic = topics.iter().map(|v| v.to_string()).collect();
+        self
+    }
+
+    pub fn with_rpc(mut self, url: &str, username: &str, password: &str) -> Self {
+        self.cfg.rpc.url = url.to_string();
+        self.cfg.rpc.username = username.to_string();
+        self.cfg.rpc.password = password.to_string();
+        self
+    }
+
+    pub fn with_cookie_file(mut self, path: &str) -> Self {
+        self.cfg.rpc.cookie_file = Some(path.to_string());
+        self
+    }
+
+    pub fn with_db_path(mut self, path: &str) -> Self {
+        self.cfg.storage.db_path = path.to_string();
+        self
+    }
+
+    pub fn with_encryption_key(mut self, key: &str) -> Self {
+        self.cfg.storage.encryption_key = Some(key.to_string());
+        self
+    }
+
+    pub fn with_log_level(mut self, level: log::LevelFilter) -> Self {
+        self.cfg.log_configuration.log_level = level;
+        self
+    }
+
+    // for the fields without a setter
+    pub fn with(mut self, f: impl FnOnce(&mut IndexerConfiguration)) -> Self {
+        f(&mut self.cfg);
+        self
+    }
+
+    pub fn build(self) -> Result<IndexerConfiguration, ConfigurationErrors> {
+        let cfg = self.cfg.with_env_overrides().map_err(|e| {
+            ConfigurationErrors(vec![ConfigurationError::InvalidOverride(e.to_string())])
+        })?;
+        cfg.validate()?;
+        Ok(cfg)
+    }
+}
+
+impl IndexerConfiguration {
+    pub fn validate(&self) -> Result<(), ConfigurationErrors> {
+        let mut errors = vec![];
+        check_endpoint("rpc", &self.rpc.endpoint(), &mut errors);
+        if let Some(quorum) = &self.rpc.quorum {
+            for (i, node) in quorum.nodes.iter().enumerate() {
+                check_endpoint(&format!("rpc.quorum.nodes[{}]", i), node, &mut errors);
+            }
+        }
+        // resolved again on start,a reference checked here may still break until then
+        if let Err(e) = self.clone().resolve_secrets() {
+            errors.extend(e.0);
+        }
+        if let Some(url) = &self.rpc.electrum_url {
+            check_url(url, &["tcp", "ssl"], &mut errors);
+        }
+        if let Some(url) = &self.rpc.esplora_url {
+            check_url(url, &["http", "https"], &mut errors);
+        }
+        if self.ingest.zmq() {
+            for url in self.zmq.urls() {
+                check_url(&url, &["tcp", "ipc"], &mut errors);
+            }
+            for topic in &self.zmq.zmq_topic {
+                if !ZMQ_TOPICS.contains(&topic.as_str())
+                    && !self.zmq.topic_handlers.contains_key(topic)
+                {
+                    errors.push(ConfigurationError::UnknownTopic(topic.clone()));
+                }
+            }
+            if self.zmq.topic_handlers.contains_key("") {
+                errors.push(ConfigurationError::UnknownTopic("".to_string()));
+            }
+        }
+        if let Err(e) = check_writable(Path::new(&self.storage.db_path)) {
+            errors.push(ConfigurationError::UnwritableDbPath(
+                self.storage.db_path.clone(),
+                e.to_string(),
+            ));
+        }
+        if let Some(key) = &self.storage.encryption_key {
+            match hex::decode(key) {
+                Ok(v) if v.len() == 32 => {}
+                Ok(v) => errors.push(ConfigurationError::InvalidEncryptionKey(format!(
+                    "{} bytes instead of 32",
+                    v.len()
+                ))),
+                Err(e) => errors.push(ConfigurationError::InvalidEncryptionKey(e.to_string())),
+            }
+        }
+        if errors.is_empty() {
+            return Ok(());
+        }
+        Err(ConfigurationErrors(errors))
+    }
+}
+
+fn check_endpoint(name: &str, endpoint: &RpcEndpoint, errors: &mut Vec<ConfigurationError>) {
+    check_url(&endpoint.url, &["http", "https"], errors);
+    match &endpoint.cookie_file {
+        Some(path) => {
+            if let Err(e) = std::fs::metadata(path) {
+                errors.push(ConfigurationError::UnreadableCookieFile(
+                    path.clone(),
+                    e.to_string(),
+                ));
+            }
+        }
+        None => {
+            if endpoint.username.is_empty() || endpoint.password.is_empty() {
+                errors.push(ConfigurationError::MissingCredentials(name.to_string()));
+            }
+        }
+    }
+}
+
+// scheme://host:port,ipc urls take a path instead
+fn check_url(url: &str, schemes: &[&str], errors: &mut Vec<ConfigurationError>) {
+    let invalid =
+        |reason: &str| ConfigurationError::InvalidUrl(url.to_string(), reason.to_string());
+    let Some((scheme, rest)) = url.split_once("://") else {
+        errors.push(invalid("missing scheme"));
+        return;
+    };
+    if !schemes.contains(&scheme) {
+        errors.push(invalid(&format!("expect {}", schemes.join(" or "))));
+        return;
+    }
+    if scheme == "ipc" {
+        if rest.is_empty() {
+            errors.push(invalid("missing path"));
+        }
+        return;
+    }
+    let authority = rest.split('/').next().unwrap_or_default();
+    let authority = authority.rsplit('@').next().unwrap_or_default();
+    let (host, port) = match authority.rsplit_once(':') {
+        Some((host, port)) if !port.contains(']') => (host, Some(port)),
+        _ => (authority, None),
+    };
+    if host.is_empty() {
+        errors.push(invalid("missing host"));
+    }
+    match port {
+        Some(port) if port.parse::<u16>().is_err() => errors.push(invalid("invalid port")),
+        None if scheme == "tcp" || scheme == "ssl" => errors.push(invalid("missing port")),
+        _ => {}
+    }
+}
+
+// the db directory is created on open,so its closest existing ancestor has to be writable
+fn check_writable(path: &Path) -> std::io::Result<()> {
+    let mut dir = path;
+    while !dir.exists() {
+        dir = match dir.parent() {
+            Some(v) if !v.as_os_str().is_empty() => v,
+            _ => Path::new("."),
+        };
+    }
+    if !dir.is_dir() {
+        return Err(std::io::Error::other(format!(
+            "{} is not a directory",
+            dir.display()
+        )));
+    }
+    let probe = dir.join(".indexer_sdk_probe");
+    std::fs::write(&probe, [])?;
+    std::fs::remove_file(&probe)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_validate_configuration() {
+        let cfg = IndexerConfigurationBuilder::new()
+            .with_zmq_url("tcp://127.0.0.1:28332")
+            .with_rpc("http://user@127.0.0.1:8332/wallet/w", "user", "pass")
+            .with_db_path("./test_validate/db")
+            .build()
+            .unwrap();
+        assert_eq!(cfg.storage.db_path, "./test_validate/db");
+
+        let errors = IndexerConfigurationBuilder::new()
+            .with_zmq_url("127.0.0.1:28332")
+            .with_zmq_topics(&["rawtx", "rawtxx"])
+            .with_rpc("ftp://127.0.0.1", "", "")
+            .with_db_path("./Cargo.toml/db")
+            .with_encryption_key("abcd")
+            .with(|cfg| cfg.zmq.backup_urls = vec!["tcp://host:port".to_string()])
+            .build()
+            .unwrap_err();
+        assert_eq!(
+            errors.0,
+            vec![
+                ConfigurationError::InvalidUrl(
+                    "ftp://127.0.0.1".to_string(),
+                    "expect http or https".to_string()
+                ),
+                ConfigurationError::MissingCredentials("rpc".to_string()),
+                ConfigurationError::InvalidUrl(
+                    "127.0.0.1:28332".to_string(),
+                    "missing scheme".to_string()
+                ),
+                ConfigurationError::InvalidUrl(
+                    "tcp://host:port".to_string(),
+                    "invalid port".to_string()
+                ),
+                ConfigurationError::UnknownTopic("rawtxx".to_string()),
+                ConfigurationError::UnwritableDbPath(
+                    "./Cargo.toml/db".to_string(),
+                    "./Cargo.toml is not a directory".to_string()
+                ),
+                ConfigurationError::InvalidEncryptionKey("2 bytes instead of 32".to_string()),
+            ]
+        );
+
+        let errors = IndexerConfigurationBuilder::new()
+            .with_cookie_file("./test_validate_missing.cookie")
+            .build()
+            .unwrap_err();
+        assert!(matches!(
+            errors.0.as_slice(),
+            [ConfigurationError::UnreadableCookieFile(_, _)]
+        ));
+    }
+}
@@ -0,0 +1,131 @@
+use crate::configuration::base::IndexerConfiguration;
+use crate::error::{IndexerError, IndexerResult};
+use serde_json::Value;
+
+pub const ENV_PREFIX: &str = "INDEXER_SDK_";
+
+impl IndexerConfiguration {
+    // INDEXER_SDK_<FIELD> overrides a field,nested fields are joined by a double underscore,
+    // e.g. INDEXER_SDK_CONFIRMATIONS,INDEXER_SDK_ZMQ__ZMQ_URL or INDEXER_SDK_RPC__PASSWORD.
+    // lists and tables are given as json,e.g. INDEXER_SDK_ZMQ__BACKUP_URLS='["tcp://b:28332"]'
+    pub fn with_env_overrides(self) -> IndexerResult<Self> {
+        self.with_overrides(std::env::vars())
+    }
+
+    pub fn with_overrides<I: IntoIterator<Item = (String, String)>>(
+        self,
+        vars: I,
+    ) -> IndexerResult<Self> {
+        let mut vars: Vec<(String, String)> = vars
+            .into_iter()
+            .filter(|(k, _)| k.starts_with(ENV_PREFIX))
+            .collect();
+        if vars.is_empty() {
+            return Ok(self);
+        }
+        vars.sort();
+        let middlewares = self.storage.middlewares.clone();
+        let mut value = serde_json::to_value(&self)?;
+        for (name, raw) in vars {
+            let path: Vec<String> = name[ENV_PREFIX.len()..]
+                .split("__")
+                .map(|v| v.to_lowercase())
+                .collect();
+            let err = |e: String| IndexerError::InvalidConfiguration(format!("{}:{}", name, e));
+            let before = value.clone();
+            override_field(&mut value, &path, &raw).map_err(err)?;
+            // a number given for an optional string,e.g. an all digits password
+            if serde_json::from_value::<IndexerConfiguration>(value.clone()).is_err() {
+                value = before;
+                override_field(&mut value, &path, &serde_json::to_string(&raw)?).map_err(err)?;
+            }
+        }
+        let mut ret: IndexerConfiguration = serde_json::from_value(value)
+            .map_err(|e| IndexerError::InvalidConfiguration(e.to_string()))?;
+        ret.storage.middlewares = middlewares;
+        Ok(ret)
+    }
+}
+
+fn override_field(root: &mut Value, path: &[String], raw: &str) -> Result<(), String> {
+    let (last, parent) = path.split_last().unwrap();
+    let mut table = root;
+    for key in parent {
+        let field = table
+            .get_mut(key)
+            .ok_or_else(|| format!("unknown field {}", key))?;
+        // a none table,e.g. rpc.quorum,is created with its defaults filled in on deserialize
+        if field.is_null() {
+            *field = Value::Object(Default::default());
+        }
+        table = field;
+    }
+    let table = table
+        .as_object_mut()
+        .ok_or_else(|| format!("{} is not a table", parent.join(".")))?;
+    let current = table.get(last);
+    let value = match current {
+        Some(Value::String(_)) => Value::String(raw.to_string()),
+        // an option,resolved by the caller
+        Some(Value::Null) | None => {
+            serde_json::from_str(raw).unwrap_or_else(|_| Value::String(raw.to_string()))
+        }
+        Some(_) => serde_json::from_str(raw).map_err(|e| format!("invalid value,{}", e))?,
+    };
+    table.insert(last.clone(), value);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::configuration::base::IngestMode;
+
+    #[test]
+    pub fn test_env_overrides() {
+        let vars = |v: &[(&str, &str)]| {
+            v.iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect::<Vec<_>>()
+        };
+        let cfg = IndexerConfiguration::default()
+            .with_overrides(vars(&[
+                ("INDEXER_SDK_STORAGE__DB_PATH", "/var/lib/indexer"),
+                ("INDEXER_SDK_ZMQ__ZMQ_URL", "tcp://node:28332"),
+                ("INDEXER_SDK_RPC__USERNAME", "alice"),
+                ("INDEXER_SDK_RPC__PASSWORD", "1234"),
+                ("INDEXER_SDK_STORAGE__ENCRYPTION_KEY", "1234"),
+                ("INDEXER_SDK_CONFIRMATIONS", "6"),
+                ("INDEXER_SDK_START_HEIGHT", "800000"),
+                ("INDEXER_SDK_ZMQ__BACKUP_URLS", "[\"tcp://b:28332\"]"),
+                ("INDEXER_SDK_INGEST__MODE", "both"),
+                ("INDEXER_SDK_RPC__QUORUM__REQUIRED", "3"),
+                ("INDEXER_SDK_LOG_CONFIGURATION__LOG_LEVEL", "warn"),
+                ("INDEXER_SDK_NETWORK", "mainnet"),
+                ("OTHER_DB_PATH", "ignored"),
+            ]))
+            .unwrap();
+        assert_eq!(cfg.storage.db_path, "/var/lib/indexer");
+        assert_eq!(cfg.zmq.zmq_url, "tcp://node:28332");
+        assert_eq!(cfg.zmq.backup_urls, vec!["tcp://b:28332".to_string()]);
+        assert_eq!(cfg.rpc.username, "alice");
+        assert_eq!(cfg.rpc.password, "1234");
+        assert_eq!(cfg.storage.encryption_key, Some("1234".to_string()));
+        assert_eq!(cfg.confirmations, 6);
+        assert_eq!(cfg.start_height, Some(800_000));
+        assert_eq!(cfg.ingest.mode, IngestMode::Both);
+        assert_eq!(cfg.rpc.quorum.unwrap().required, 3);
+        assert_eq!(cfg.log_configuration.log_level, log::LevelFilter::Warn);
+        assert_eq!(cfg.network, bitcoincore_rpc::bitcoin::Network::Bitcoin);
+
+        for invalid in [
+            ("INDEXER_SDK_STORAGE__DB_PTH", "x"),
+            ("INDEXER_SDK_RPCC__URL", "x"),
+            ("INDEXER_SDK_CONFIRMATIONS", "six"),
+        ] {
+            assert!(IndexerConfiguration::default()
+                .with_overrides(vars(&[invalid]))
+                .is_err());
+        }
+    }
+}
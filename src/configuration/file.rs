@@ -0,0 +1,143 @@
+use crate::configuration::base::IndexerConfiguration;
+use crate::configuration::toml;
+use crate::error::{IndexerError, IndexerResult};
+use std::path::Path;
+
+impl IndexerConfiguration {
+    // .toml,.yaml/.yml or .json,the keys are the field names and the missing ones keep their
+    // defaults. storage_middlewares can only be set in code.
+    // INDEXER_SDK_* environment variables override the file,see with_env_overrides
+    pub fn from_file<P: AsRef<Path>>(path: P) -> IndexerResult<Self> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path)?;
+        let ext = path
+            .extension()
+            .and_then(|v| v.to_str())
+            .unwrap_or_default();
+        let cfg = match ext {
+            "toml" => Self::from_toml(&content)?,
+            "yaml" | "yml" | "json" => Self::from_yaml(&content)?,
+            _ => {
+                return Err(IndexerError::InvalidConfiguration(format!(
+                    "unknown configuration format:{}",
+                    path.display()
+                )))
+            }
+        };
+        cfg.with_env_overrides()
+    }
+
+    pub fn from_toml(content: &str) -> IndexerResult<Self> {
+        serde_json::from_value(toml::parse(content)?)
+            .map_err(|e| IndexerError::InvalidConfiguration(e.to_string()))
+    }
+
+    // json is accepted as well,it is a subset of yaml
+    pub fn from_yaml(content: &str) -> IndexerResult<Self> {
+        serde_yaml::from_str(content).map_err(|e| IndexerError::InvalidConfiguration(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::configuration::base::{IngestMode, RestartPolicy, TopicHandler};
+    use crate::storage::prune::PruneRetention;
+    use bitcoincore_rpc::bitcoin::Network;
+
+    #[test]
+    pub fn test_from_file() {
+        let toml = r#"
+network = "signet"
+confirmations = 3
+dispatcher.restart_policy = "never"
+
+[log_configuration]
+log_level = "warn"
+
+[rpc]
+url = "http://node:8332"
+cookie_file = "/root/.bitcoin/.cookie"
+
+[rpc.rate_limit]
+txs_per_second = 50
+
+[[rpc.quorum.nodes]]
+url = "http://backup:8332"
+
+[zmq]
+zmq_url = "tcp://node:28332"
+topic_handlers = { "proxy.fee" = "passthrough" }
+
+[ingest]
+mode = "both"
+p2p.peer = "node:38333"
+
+[storage]
+db_path = "./data"
+prune.retention = { height = 1000 }
+"#;
+        let yaml = r#"
+network: signet
+confirmations: 3
+dispatcher:
+  restart_policy: never
+log_configuration:
+  log_level: warn
+rpc:
+  url: http://node:8332
+  cookie_file: /root/.bitcoin/.cookie
+  rate_limit:
+    txs_per_second: 50
+  quorum:
+    nodes:
+      - url: http://backup:8332
+zmq:
+  zmq_url: tcp://node:28332
+  topic_handlers:
+    proxy.fee: passthrough
+ingest:
+  mode: both
+  p2p:
+    peer: node:38333
+storage:
+  db_path: ./data
+  prune:
+    retention:
+      height: 1000
+"#;
+        for (name, content) in [("test_config.toml", toml), ("test_config.yaml", yaml)] {
+            std::fs::write(name, content).unwrap();
+            let cfg = IndexerConfiguration::from_file(name).unwrap();
+            std::fs::remove_file(name).unwrap();
+
+            assert_eq!(cfg.storage.db_path, "./data");
+            assert_eq!(cfg.confirmations, 3);
+            assert_eq!(cfg.dispatcher.restart_policy, RestartPolicy::Never);
+            assert_eq!(cfg.log_configuration.log_level, log::LevelFilter::Warn);
+            assert_eq!(cfg.rpc.url, "http://node:8332");
+            assert_eq!(cfg.rpc.rate_limit.txs_per_second, 50);
+            assert_eq!(cfg.rpc.rpc_timeout_ms, 15_000);
+            let quorum = cfg.rpc.quorum.unwrap();
+            assert_eq!(quorum.nodes[0].url, "http://backup:8332");
+            assert_eq!(quorum.required, 2);
+            assert_eq!(cfg.zmq.zmq_url, "tcp://node:28332");
+            assert_eq!(
+                cfg.zmq.handler("proxy.fee"),
+                Some(TopicHandler::Passthrough)
+            );
+            assert_eq!(cfg.ingest.mode, IngestMode::Both);
+            assert_eq!(cfg.network, Network::Signet);
+            assert_eq!(cfg.ingest.p2p.peer, "node:38333");
+            assert_eq!(
+                cfg.storage.prune.retention,
+                Some(PruneRetention::Height(1000))
+            );
+            assert!(cfg.storage.prune.gc_inactive);
+        }
+
+        IndexerConfiguration::from_file("demo/rust/client/indexer.toml").unwrap();
+        assert!(IndexerConfiguration::from_toml("db_pth = \"./data\"").is_err());
+        assert!(IndexerConfiguration::from_file("test_config.ini").is_err());
+    }
+}
@@ -1 +1,7 @@
 pub mod base;
+pub mod builder;
+pub mod env;
+pub mod file;
+pub mod reload;
+pub mod secret;
+mod toml;
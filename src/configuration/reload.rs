@@ -0,0 +1,217 @@
+use crate::configuration::base::{IndexerConfiguration, ProcessorConfiguration};
+use crate::dispatcher::event::DispatchEvent;
+use crate::error::IndexerResult;
+use crate::event::{IndexerEvent, ReplySender};
+use async_channel::Sender;
+use log::{error, info, warn};
+use serde::Serialize;
+use serde_json::Value;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+
+// the fields a reload applies to the running indexer,the others only apply after a restart
+pub const TUNABLES: [&str; 4] = [
+    "log_configuration.log_level",
+    "confirmations",
+    "client.checkpoint_interval",
+    "rpc.rate_limit",
+];
+
+impl IndexerConfiguration {
+    // dotted paths of the fields which differ,e.g. rpc.rate_limit.per_second
+    pub fn changed_fields(&self, other: &IndexerConfiguration) -> Vec<String> {
+        changed_fields(self, other)
+    }
+
+    // the changed fields a reload can't apply
+    pub fn restart_required(&self, other: &IndexerConfiguration) -> Vec<String> {
+        restart_required(self, other)
+    }
+
+    // copies the tunables of other
+    pub fn apply_tunables(&mut self, other: &IndexerConfiguration) {
+        self.log_configuration.log_level = other.log_configuration.log_level;
+        self.confirmations = other.confirmations;
+        self.client.checkpoint_interval = other.client.checkpoint_interval;
+        self.rpc.rate_limit = other.rpc.rate_limit.clone();
+    }
+}
+
+impl ProcessorConfiguration {
+    pub fn restart_required(&self, other: &ProcessorConfiguration) -> Vec<String> {
+        restart_required(self, other)
+    }
+
+    pub fn apply_tunables(&mut self, other: &ProcessorConfiguration) {
+        self.confirmations = other.confirmations;
+        self.client.checkpoint_interval = other.client.checkpoint_interval;
+        self.rpc.rate_limit = other.rpc.rate_limit.clone();
+    }
+}
+
+fn changed_fields<T: Serialize>(old: &T, new: &T) -> Vec<String> {
+    let mut ret = vec![];
+    diff(
+        "",
+        &serde_json::to_value(old).unwrap(),
+        &serde_json::to_value(new).unwrap(),
+        &mut ret,
+    );
+    ret
+}
+
+fn restart_required<T: Serialize>(old: &T, new: &T) -> Vec<String> {
+    changed_fields(old, new)
+        .into_iter()
+        .filter(|field| !is_tunable(field))
+        .collect()
+}
+
+fn is_tunable(field: &str) -> bool {
+    TUNABLES
+        .iter()
+        .any(|v| field == *v || field.starts_with(&format!("{}.", v)))
+}
+
+fn diff(path: &str, old: &Value, new: &Value, ret: &mut Vec<String>) {
+    match (old, new) {
+        (Value::Object(old), Value::Object(new)) => {
+            let mut keys: Vec<&String> = old.keys().chain(new.keys()).collect();
+            keys.sort();
+            keys.dedup();
+            for key in keys {
+                let field = if path.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{}.{}", path, key)
+                };
+                diff(
+                    &field,
+                    old.get(key).unwrap_or(&Value::Null),
+                    new.get(key).unwrap_or(&Value::Null),
+                    ret,
+                );
+            }
+        }
+        _ if old != new => ret.push(path.to_string()),
+        _ => {}
+    }
+}
+
+// sends a reload to the processor whenever the modification time of the file changes,
+// files which fail to load are skipped until they change again
+pub fn watch_configuration(
+    path: PathBuf,
+    interval: Duration,
+    tx: Sender<DispatchEvent>,
+    mut exit: watch::Receiver<()>,
+) -> JoinHandle<()> {
+    tokio::task::spawn(async move {
+        let mut last = modified(&path);
+        // the processor only knows its own sections,the others are diffed here
+        let mut applied = IndexerConfiguration::from_file(&path).ok();
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            tokio::select! {
+                _ = ticker.tick() => {}
+                _ = exit.changed() => {
+                    info!("configuration watcher exit");
+                    return;
+                }
+            }
+            let current = modified(&path);
+            if current == last {
+                continue;
+            }
+            last = current;
+            let cfg = match IndexerConfiguration::from_file(&path) {
+                Ok(v) => v,
+                Err(e) => {
+                    error!("reload {} failed:{}", path.display(), e);
+                    continue;
+                }
+            };
+            let ret = reload(&tx, cfg.clone()).await.map(|mut restart| {
+                if let Some(previous) = &applied {
+                    restart.extend(previous.restart_required(&cfg));
+                    restart.sort();
+                    restart.dedup();
+                }
+                restart
+            });
+            if ret.is_ok() {
+                applied = Some(cfg);
+            }
+            match ret {
+                Ok(restart) if restart.is_empty() => info!("reloaded {}", path.display()),
+                Ok(restart) => warn!(
+                    "reloaded {},changes of {} apply after a restart",
+                    path.display(),
+                    restart.join(",")
+                ),
+                Err(e) => error!("reload {} failed:{}", path.display(), e),
+            }
+        }
+    })
+}
+
+pub(crate) async fn reload(
+    tx: &Sender<DispatchEvent>,
+    cfg: IndexerConfiguration,
+) -> IndexerResult<Vec<String>> {
+    let (reply, rx) = ReplySender::new();
+    tx.send(DispatchEvent::IndexerEvent(IndexerEvent::Reload(
+        Box::new(cfg),
+        reply,
+    )))
+    .await?;
+    rx.await?
+}
+
+fn modified(path: &PathBuf) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|v| v.modified()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_restart_required() {
+        let old = IndexerConfiguration::default();
+        let mut new = old.clone();
+        new.confirmations = 6;
+        new.rpc.rate_limit.txs_per_second = 10;
+        new.log_configuration.log_level = log::LevelFilter::Warn;
+        assert!(old.restart_required(&new).is_empty());
+
+        new.zmq.zmq_url = "tcp://other:28332".to_string();
+        new.rpc.quorum = Some(Default::default());
+        assert_eq!(
+            old.restart_required(&new),
+            vec!["rpc.quorum".to_string(), "zmq.zmq_url".to_string()]
+        );
+
+        let mut applied = old.clone();
+        applied.apply_tunables(&new);
+        assert_eq!(applied.confirmations, 6);
+        assert_eq!(applied.rpc.rate_limit.txs_per_second, 10);
+        assert_eq!(applied.zmq.zmq_url, old.zmq.zmq_url);
+        assert_eq!(
+            applied.changed_fields(&new),
+            vec!["rpc.quorum".to_string(), "zmq.zmq_url".to_string()]
+        );
+
+        // the processor only sees its sections
+        let mut section = old.processor();
+        assert_eq!(
+            section.restart_required(&new.processor()),
+            vec!["rpc.quorum".to_string()]
+        );
+        section.apply_tunables(&new.processor());
+        assert_eq!(section.confirmations, 6);
+        assert_eq!(section.rpc.rate_limit.txs_per_second, 10);
+    }
+}
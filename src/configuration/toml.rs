@@ -0,0 +1,378 @@
+use crate::error::{IndexerError, IndexerResult};
+use serde_json::{Map, Value};
+
+// the toml the configuration needs: tables,arrays of tables,inline tables,strings,integers,
+// floats,booleans and arrays. dates and multi line strings are rejected
+pub(crate) fn parse(input: &str) -> IndexerResult<Value> {
+    let mut parser = Parser {
+        chars: input.chars().collect(),
+        pos: 0,
+    };
+    let mut root = Map::new();
+    let mut current: Vec<String> = vec![];
+    loop {
+        parser.skip_blank();
+        match parser.peek() {
+            None => return Ok(Value::Object(root)),
+            Some('[') => {
+                parser.pos += 1;
+                let array = parser.eat('[');
+                let path = parser.key()?;
+                parser.expect(']')?;
+                if array {
+                    parser.expect(']')?;
+                    let (last, parent) = path.split_last().unwrap();
+                    let table = parser.table(&mut root, parent)?;
+                    let entry = table
+                        .entry(last.clone())
+                        .or_insert_with(|| Value::Array(vec![]));
+                    let Value::Array(tables) = entry else {
+                        return Err(parser.error(&format!("{} is not an array of tables", last)));
+                    };
+                    tables.push(Value::Object(Map::new()));
+                } else {
+                    parser.table(&mut root, &path)?;
+                }
+                current = path;
+            }
+            Some(_) => {
+                let path = parser.key()?;
+                parser.skip_space();
+                parser.expect('=')?;
+                let value = parser.value()?;
+                let (last, parent) = path.split_last().unwrap();
+                let full: Vec<String> = current.iter().chain(parent).cloned().collect();
+                let table = parser.table(&mut root, &full)?;
+                if table.insert(last.clone(), value).is_some() {
+                    return Err(parser.error(&format!("duplicate key {}", last)));
+                }
+            }
+        }
+        parser.skip_space();
+        parser.skip_comment();
+        if !matches!(parser.peek(), None | Some('\n') | Some('\r')) {
+            return Err(parser.error("expect the end of the line"));
+        }
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn error(&self, msg: &str) -> IndexerError {
+        let line = self.chars[..self.pos.min(self.chars.len())]
+            .iter()
+            .filter(|v| **v == '\n')
+            .count()
+            + 1;
+        IndexerError::InvalidConfiguration(format!("toml line {}:{}", line, msg))
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            return true;
+        }
+        false
+    }
+
+    fn expect(&mut self, c: char) -> IndexerResult<()> {
+        self.skip_space();
+        if !self.eat(c) {
+            return Err(self.error(&format!("expect '{}'", c)));
+        }
+        Ok(())
+    }
+
+    fn skip_space(&mut self) {
+        while matches!(self.peek(), Some(' ') | Some('\t')) {
+            self.pos += 1;
+        }
+    }
+
+    fn skip_comment(&mut self) {
+        if self.peek() == Some('#') {
+            while !matches!(self.peek(), None | Some('\n')) {
+                self.pos += 1;
+            }
+        }
+    }
+
+    // spaces,newlines and comments
+    fn skip_blank(&mut self) {
+        loop {
+            self.skip_space();
+            self.skip_comment();
+            match self.peek() {
+                Some('\n') | Some('\r') => self.pos += 1,
+                _ => return,
+            }
+        }
+    }
+
+    // the table at path,created on the way. an array of tables resolves to its last table
+    fn table<'a>(
+        &self,
+        root: &'a mut Map<String, Value>,
+        path: &[String],
+    ) -> IndexerResult<&'a mut Map<String, Value>> {
+        let mut table = root;
+        for key in path {
+            let mut entry = table
+                .entry(key.clone())
+                .or_insert_with(|| Value::Object(Map::new()));
+            if let Value::Array(tables) = entry {
+                entry = tables
+                    .last_mut()
+                    .ok_or_else(|| self.error(&format!("{} is not a table", key)))?;
+            }
+            table = match entry {
+                Value::Object(v) => v,
+                _ => return Err(self.error(&format!("{} is not a table", key))),
+            };
+        }
+        Ok(table)
+    }
+
+    // a.b."c d"
+    fn key(&mut self) -> IndexerResult<Vec<String>> {
+        let mut ret = vec![];
+        loop {
+            self.skip_space();
+            let part = match self.peek() {
+                Some('"') => self.basic_string()?,
+                Some('\'') => self.literal_string()?,
+                _ => {
+                    let start = self.pos;
+                    while matches!(self.peek(), Some(c) if c.is_ascii_alphanumeric() || c == '_' || c == '-')
+                    {
+                        self.pos += 1;
+                    }
+                    if start == self.pos {
+                        return Err(self.error("expect a key"));
+                    }
+                    self.chars[start..self.pos].iter().collect()
+                }
+            };
+            ret.push(part);
+            self.skip_space();
+            if !self.eat('.') {
+                return Ok(ret);
+            }
+        }
+    }
+
+    fn value(&mut self) -> IndexerResult<Value> {
+        self.skip_space();
+        match self.peek() {
+            Some('"') => Ok(Value::String(self.basic_string()?)),
+            Some('\'') => Ok(Value::String(self.literal_string()?)),
+            Some('[') => {
+                self.pos += 1;
+                let mut ret = vec![];
+                loop {
+                    self.skip_blank();
+                    if self.eat(']') {
+                        return Ok(Value::Array(ret));
+                    }
+                    ret.push(self.value()?);
+                    self.skip_blank();
+                    if !self.eat(',') {
+                        self.skip_blank();
+                        self.expect(']')?;
+                        return Ok(Value::Array(ret));
+                    }
+                }
+            }
+            Some('{') => {
+                self.pos += 1;
+                let mut ret = Map::new();
+                self.skip_space();
+                if self.eat('}') {
+                    return Ok(Value::Object(ret));
+                }
+                loop {
+                    let path = self.key()?;
+                    self.expect('=')?;
+                    let value = self.value()?;
+                    let (last, parent) = path.split_last().unwrap();
+                    if self
+                        .table(&mut ret, parent)?
+                        .insert(last.clone(), value)
+                        .is_some()
+                    {
+                        return Err(self.error(&format!("duplicate key {}", last)));
+                    }
+                    self.skip_space();
+                    if !self.eat(',') {
+                        self.expect('}')?;
+                        return Ok(Value::Object(ret));
+                    }
+                }
+            }
+            _ => {
+                let start = self.pos;
+                while matches!(self.peek(), Some(c) if c.is_ascii_alphanumeric() || "+-._".contains(c))
+                {
+                    self.pos += 1;
+                }
+                let word: String = self.chars[start..self.pos].iter().collect();
+                self.scalar(&word)
+            }
+        }
+    }
+
+    fn scalar(&self, word: &str) -> IndexerResult<Value> {
+        match word {
+            "true" => return Ok(Value::Bool(true)),
+            "false" => return Ok(Value::Bool(false)),
+            "" => return Err(self.error("expect a value")),
+            _ => {}
+        }
+        let number = word.replace('_', "");
+        if let Ok(v) = number.parse::<i64>() {
+            return Ok(Value::from(v));
+        }
+        if let Ok(v) = number.parse::<u64>() {
+            return Ok(Value::from(v));
+        }
+        if number.contains(['.', 'e', 'E']) {
+            if let Some(v) = number
+                .parse::<f64>()
+                .ok()
+                .and_then(serde_json::Number::from_f64)
+            {
+                return Ok(Value::Number(v));
+            }
+        }
+        Err(self.error(&format!("unsupported value {}", word)))
+    }
+
+    fn basic_string(&mut self) -> IndexerResult<String> {
+        self.pos += 1;
+        let mut ret = String::new();
+        loop {
+            let Some(c) = self.peek() else {
+                return Err(self.error("unterminated string"));
+            };
+            self.pos += 1;
+            match c {
+                '"' => return Ok(ret),
+                '\n' => return Err(self.error("unterminated string")),
+                '\\' => {
+                    let escaped = self
+                        .peek()
+                        .ok_or_else(|| self.error("unterminated string"))?;
+                    self.pos += 1;
+                    match escaped {
+                        'n' => ret.push('\n'),
+                        't' => ret.push('\t'),
+                        'r' => ret.push('\r'),
+                        '"' => ret.push('"'),
+                        '\\' => ret.push('\\'),
+                        'u' | 'U' => {
+                            let len = if escaped == 'u' { 4 } else { 8 };
+                            let end = (self.pos + len).min(self.chars.len());
+                            let hex: String = self.chars[self.pos..end].iter().collect();
+                            let c = u32::from_str_radix(&hex, 16)
+                                .ok()
+                                .and_then(char::from_u32)
+                                .ok_or_else(|| self.error("invalid unicode escape"))?;
+                            ret.push(c);
+                            self.pos = end;
+                        }
+                        _ => return Err(self.error(&format!("invalid escape \\{}", escaped))),
+                    }
+                }
+                _ => ret.push(c),
+            }
+        }
+    }
+
+    fn literal_string(&mut self) -> IndexerResult<String> {
+        self.pos += 1;
+        let start = self.pos;
+        loop {
+            match self.peek() {
+                None | Some('\n') => return Err(self.error("unterminated string")),
+                Some('\'') => {
+                    let ret = self.chars[start..self.pos].iter().collect();
+                    self.pos += 1;
+                    return Ok(ret);
+                }
+                _ => self.pos += 1,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    pub fn test_parse_toml() {
+        let value = parse(
+            r#"
+# top level keys
+db_path = "./db" # trailing comment
+confirmations = 6
+ratio = 1.5
+flags = [true, false,]
+
+[net]
+url = 'http://127.0.0.1:8332'
+rate_limit.per_second = 1_000
+
+[[net.quorum.nodes]]
+url = "http://a"
+[[net.quorum.nodes]]
+url = "http://b!"
+
+[mq]
+backup_urls = [
+    "tcp://a", # first
+    "tcp://b",
+]
+reconnect = { initial_ms = 100, max_ms = "x" }
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            value,
+            json!({
+                "db_path": "./db",
+                "confirmations": 6,
+                "ratio": 1.5,
+                "flags": [true, false],
+                "net": {
+                    "url": "http://127.0.0.1:8332",
+                    "rate_limit": {"per_second": 1000},
+                    "quorum": {"nodes": [{"url": "http://a"}, {"url": "http://b!"}]},
+                },
+                "mq": {
+                    "backup_urls": ["tcp://a", "tcp://b"],
+                    "reconnect": {"initial_ms": 100, "max_ms": "x"},
+                },
+            })
+        );
+
+        for invalid in [
+            "a = ",
+            "a = 1\na = 2",
+            "a = \"open",
+            "[a\nb = 1",
+            "a = 1 b = 2",
+        ] {
+            assert!(parse(invalid).is_err(), "{}", invalid);
+        }
+    }
+}
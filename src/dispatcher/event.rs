@@ -16,7 +16,14 @@ unsafe impl Send for DispatchEvent {}
 
 unsafe impl Sync for DispatchEvent {}
 
-impl Event for DispatchEvent {}
+impl Event for DispatchEvent {
+    fn component_failed(component: &str, reason: &str) -> Option<Self> {
+        Some(DispatchEvent::IndexerEvent(IndexerEvent::ComponentFailed(
+            component.to_string(),
+            reason.to_string(),
+        )))
+    }
+}
 
 impl DispatchEvent {
     pub fn get_indexer_event(&self) -> Option<&IndexerEvent> {
@@ -29,9 +29,9 @@ impl<E: Event + Clone> Dispatcher<E> {
     }
 }
 impl<E: Event + Clone> Dispatcher<E> {
-    pub async fn init(&mut self, config: IndexerConfiguration) -> IndexerResult<()> {
+    pub async fn init(&mut self, config: &IndexerConfiguration) -> IndexerResult<()> {
         for component in self.components.iter_mut() {
-            component.init(config.clone()).await?;
+            component.init(config).await?;
         }
         Ok(())
     }
@@ -45,6 +45,7 @@ impl<E: Event + Clone> Dispatcher<E> {
     ) -> IndexerResult<Vec<JoinHandle<()>>> {
         let mut handles = vec![];
         for component in self.components.iter_mut() {
+            component.report_failures_to(self.tx.clone());
             let handle = component.start(exit.clone()).await?;
             handles.extend(handle);
         }
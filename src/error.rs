@@ -16,6 +16,148 @@ pub enum IndexerError {
 
     #[error("level db error,msg:{0}")]
     RustLevelDBError(String),
+
+    #[error("io error:{0}")]
+    IoError(#[from] std::io::Error),
+
+    #[error("serde json error:{0}")]
+    SerdeJsonError(#[from] serde_json::Error),
+
+    #[error("unsupported export version:{0}")]
+    UnsupportedExportVersion(u32),
+
+    #[error("schema version:{0} is newer than supported version:{1}")]
+    SchemaVersionTooNew(u32, u32),
+
+    #[error("no migration registered from schema version:{0}")]
+    MissingMigration(u32),
+
+    #[error("encryption error:{0}")]
+    EncryptionError(String),
+
+    #[error("invalid snapshot path:{0}")]
+    InvalidSnapshotPath(String),
+
+    #[error("checksum mismatch for snapshot:{0}")]
+    ChecksumMismatch(String),
+
+    #[error("codec error:{0}")]
+    CodecError(String),
+
+    #[error("storage transaction already started")]
+    TransactionAlreadyStarted,
+
+    #[error("no active storage transaction")]
+    NoActiveTransaction,
+
+    #[error("invalid zmq message:{0}")]
+    InvalidZmqMessage(String),
+
+    #[error("unknown consumer:{0}")]
+    UnknownConsumer(u32),
+
+    #[error("{0} timed out")]
+    Timeout(String),
+
+    #[error("cancelled")]
+    Cancelled,
+
+    #[error("channel closed")]
+    ChannelClosed,
+
+    #[error("invalid configuration:{0}")]
+    InvalidConfiguration(String),
+
+    #[error("electrum error:{0}")]
+    ElectrumError(String),
+
+    #[error("esplora error:{0}")]
+    EsploraError(String),
+
+    #[error("{0} is not supported")]
+    Unsupported(String),
+
+    #[error("quorum not reached for block {0} at height {1},{2} of {3} required nodes agree")]
+    QuorumNotReached(String, u32, usize, usize),
+
+    #[error("{0}")]
+    ConfigurationErrors(#[from] ConfigurationErrors),
+
+    #[error("logging error:{0}")]
+    LoggingError(String),
+
+    #[error("configured for {0} but the node runs {1}")]
+    WrongNetwork(bitcoincore_rpc::bitcoin::Network, String),
+
+    #[error("header {0} is not tracked")]
+    UnknownHeader(String),
+
+    #[error("invalid event:{0}")]
+    InvalidEvent(String),
+
+    #[error("{0} is neither empty nor a read only copy")]
+    InvalidCopyPath(String),
+
+    #[error("{0} kept changing while it was copied")]
+    UnstableCopy(String),
+}
+
+// a problem of the configuration found before any component started
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum ConfigurationError {
+    #[error("{0} is not a valid url:{1}")]
+    InvalidUrl(String, String),
+
+    #[error("unknown zmq topic:{0}")]
+    UnknownTopic(String),
+
+    #[error("db path {0} is not writable:{1}")]
+    UnwritableDbPath(String, String),
+
+    #[error("missing credentials for {0}")]
+    MissingCredentials(String),
+
+    #[error("cookie file {0} is not readable:{1}")]
+    UnreadableCookieFile(String, String),
+
+    #[error("invalid encryption key:{0}")]
+    InvalidEncryptionKey(String),
+
+    #[error("invalid override:{0}")]
+    InvalidOverride(String),
+
+    #[error("secret {0} can not be resolved:{1}")]
+    UnresolvedSecret(String, String),
+}
+
+// every problem at once,so a deployment is fixed in one go
+#[derive(Debug, Clone, PartialEq, Error)]
+pub struct ConfigurationErrors(pub Vec<ConfigurationError>);
+
+impl std::fmt::Display for ConfigurationErrors {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let errors: Vec<String> = self.0.iter().map(|v| v.to_string()).collect();
+        write!(f, "invalid configuration:{}", errors.join(";"))
+    }
+}
+
+// the other side of a channel is gone,e.g. the processor stopped
+impl<T> From<async_channel::SendError<T>> for IndexerError {
+    fn from(_: async_channel::SendError<T>) -> Self {
+        Self::ChannelClosed
+    }
+}
+
+impl From<async_channel::RecvError> for IndexerError {
+    fn from(_: async_channel::RecvError) -> Self {
+        Self::ChannelClosed
+    }
+}
+
+impl From<tokio::sync::oneshot::error::RecvError> for IndexerError {
+    fn from(_: tokio::sync::oneshot::error::RecvError) -> Self {
+        Self::ChannelClosed
+    }
 }
 
 impl From<Status> for IndexerError {
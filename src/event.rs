@@ -1,52 +1,276 @@
+use crate::client::filter::EventFilter;
+use crate::configuration::base::IndexerConfiguration;
+use crate::error::{IndexerError, IndexerResult};
+use crate::health::ComponentHealth;
 use crate::types::delta::TransactionDelta;
+use crate::types::request::{BlockId, ReorgReport};
+use crate::types::response::{SimulationResult, StatusResponse};
 use crate::Event;
 use bigdecimal::num_bigint::{BigInt, ToBigInt};
 use bigdecimal::num_traits::FromBytes;
 use bigdecimal::num_traits::ToBytes;
-use bitcoincore_rpc::bitcoin::Txid;
+use bitcoincore_rpc::bitcoin::block::Header;
+use bitcoincore_rpc::bitcoin::consensus::{deserialize, serialize};
+use bitcoincore_rpc::bitcoin::{Block, BlockHash, Transaction, Txid};
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::fmt::{Debug, Formatter};
 use std::str::FromStr;
+use std::sync::{Arc, Mutex};
 
 #[derive(Clone)]
 pub enum IndexerEvent {
     NewTxComing(Vec<u8>, u32),
     TxFromRestoreByTxId(TxIdType),
 
-    // RawBlockComing(Block, u32),
-    GetBalance(AddressType, crossbeam::channel::Sender<BalanceType>),
+    RawBlockComing(Block, u32),
+    GetBalance(AddressType, TokenType, BalanceSender),
+    GetBalances(
+        Vec<(AddressType, TokenType)>,
+        ReplySender<IndexerResult<Vec<BalanceType>>>,
+    ),
+
+    // from the storage,or rpc for txs the sdk never stored
+    GetTransaction(TxIdType, ReplySender<IndexerResult<Option<Transaction>>>),
+
+    // from the block cache,falls back to rpc
+    GetBlock(BlockId, ReplySender<IndexerResult<Option<Block>>>),
+
+    GetStatus(ReplySender<StatusResponse>),
+
+    GetHealth(ReplySender<ComponentHealth>),
+
+    // applies the tunables of the configuration,answered with the changed fields that need a restart
+    Reload(
+        Box<IndexerConfiguration>,
+        ReplySender<IndexerResult<Vec<String>>>,
+    ),
+
+    // sent through bitcoind and marked as seen,the later zmq announcement is skipped
+    BroadcastTransaction(Transaction, ReplySender<IndexerResult<TxIdType>>),
+
+    SimulateDelta(
+        TransactionDelta,
+        ReplySender<IndexerResult<SimulationResult>>,
+    ),
 
     UpdateDelta(TransactionDelta),
 
+    UpdateDeltas(Vec<TransactionDelta>),
+
     TxRemoved(TxIdType),
 
     TxConfirmed(TxIdType),
 
     ReportHeight(u32),
 
-    ReportReorg(u32),
+    ReportReorg(ReorgReport),
+
+    BlockConnected(BlockHash),
+
+    BlockDisconnected(BlockHash),
+
+    // expected and received zmq sequence number
+    SequenceGap(u32, u32),
+
+    // re-handle the events that failed before
+    ReplayDeadLetters,
+
+    // re-send the journaled client events from this sequence number to the consumer
+    ReplayJournal(u32, u64),
+
+    // deliver the client events buffered since the restore pass started
+    FlushBatch,
+
+    // replaces the filter of the consumer
+    Subscribe(u32, EventFilter),
+
+    // the consumer processed the journaled events up to this sequence number
+    CommitCursor(u32, u64),
+
+    // the consumer handled the tx,or failed to and wants it again
+    Ack(u32, TxIdType),
+    Nack(u32, TxIdType),
+
+    // deliver the unacknowledged txs again once they timed out
+    RedeliverExpired,
+
+    // incoming events are persisted instead of handled until the resume,then handled in order
+    Pause,
+    Resume,
+
+    // the zmq socket died or went silent and is being reconnected,and the reason why
+    IngestDegraded(String),
+    IngestRestored,
+
+    // a backfilled block whose compact filter matched none of the watched scripts,
+    // only its header and height are tracked
+    HeaderConnected(Header, u32),
+
+    // reported by the supervisor,the component and why its task stopped
+    ComponentFailed(String, String),
+
+    // a zmq message of a passthrough topic,the topic and the raw body
+    TopicMessage(String, Vec<u8>),
 }
 impl Event for IndexerEvent {}
 impl IndexerEvent {
+    // answered through a reply sender,not persisted
+    pub fn is_query(&self) -> bool {
+        matches!(
+            self,
+            IndexerEvent::GetBalance(_, _, _)
+                | IndexerEvent::GetBalances(_, _)
+                | IndexerEvent::GetTransaction(_, _)
+                | IndexerEvent::GetBlock(_, _)
+                | IndexerEvent::GetStatus(_)
+                | IndexerEvent::GetHealth(_)
+                | IndexerEvent::Reload(_, _)
+                | IndexerEvent::BroadcastTransaction(_, _)
+                | IndexerEvent::SimulateDelta(_, _)
+        )
+    }
     pub fn get_suffix(&self) -> u8 {
         match self {
             IndexerEvent::NewTxComing(_, _) => 0,
-            IndexerEvent::GetBalance(_, _) => 1,
+            IndexerEvent::GetBalance(_, _, _) => 1,
             IndexerEvent::UpdateDelta(_) => 2,
             IndexerEvent::TxConfirmed(_) => 3,
-            // IndexerEvent::RawBlockComing(_, _) => 4,
+            IndexerEvent::RawBlockComing(_, _) => 4,
             IndexerEvent::TxFromRestoreByTxId(_) => 5,
             IndexerEvent::TxRemoved(_) => 6,
             IndexerEvent::ReportHeight(_) => 7,
             IndexerEvent::ReportReorg(_) => 8,
+            IndexerEvent::UpdateDeltas(_) => 9,
+            IndexerEvent::BlockConnected(_) => 10,
+            IndexerEvent::BlockDisconnected(_) => 11,
+            IndexerEvent::SequenceGap(_, _) => 12,
+            IndexerEvent::ReplayDeadLetters => 13,
+            IndexerEvent::ReplayJournal(_, _) => 14,
+            IndexerEvent::FlushBatch => 15,
+            IndexerEvent::Subscribe(_, _) => 16,
+            IndexerEvent::CommitCursor(_, _) => 17,
+            IndexerEvent::Ack(_, _) => 18,
+            IndexerEvent::Nack(_, _) => 19,
+            IndexerEvent::RedeliverExpired => 20,
+            IndexerEvent::GetTransaction(_, _) => 21,
+            IndexerEvent::GetBlock(_, _) => 22,
+            IndexerEvent::GetStatus(_) => 23,
+            IndexerEvent::Pause => 24,
+            IndexerEvent::Resume => 25,
+            IndexerEvent::GetBalances(_, _) => 26,
+            IndexerEvent::BroadcastTransaction(_, _) => 27,
+            IndexerEvent::SimulateDelta(_, _) => 28,
+            IndexerEvent::IngestDegraded(_) => 29,
+            IndexerEvent::IngestRestored => 30,
+            IndexerEvent::HeaderConnected(_, _) => 31,
+            IndexerEvent::ComponentFailed(_, _) => 32,
+            IndexerEvent::GetHealth(_) => 33,
+            IndexerEvent::TopicMessage(_, _) => 34,
+            IndexerEvent::Reload(_, _) => 35,
+        }
+    }
+    // the variant,e.g. the event field of the json logs
+    pub fn name(&self) -> &'static str {
+        match self {
+            IndexerEvent::NewTxComing(_, _) => "NewTxComing",
+            IndexerEvent::GetBalance(_, _, _) => "GetBalance",
+            IndexerEvent::UpdateDelta(_) => "UpdateDelta",
+            IndexerEvent::TxConfirmed(_) => "TxConfirmed",
+            IndexerEvent::RawBlockComing(_, _) => "RawBlockComing",
+            IndexerEvent::TxFromRestoreByTxId(_) => "TxFromRestoreByTxId",
+            IndexerEvent::TxRemoved(_) => "TxRemoved",
+            IndexerEvent::ReportHeight(_) => "ReportHeight",
+            IndexerEvent::ReportReorg(_) => "ReportReorg",
+            IndexerEvent::UpdateDeltas(_) => "UpdateDeltas",
+            IndexerEvent::BlockConnected(_) => "BlockConnected",
+            IndexerEvent::BlockDisconnected(_) => "BlockDisconnected",
+            IndexerEvent::SequenceGap(_, _) => "SequenceGap",
+            IndexerEvent::ReplayDeadLetters => "ReplayDeadLetters",
+            IndexerEvent::ReplayJournal(_, _) => "ReplayJournal",
+            IndexerEvent::FlushBatch => "FlushBatch",
+            IndexerEvent::Subscribe(_, _) => "Subscribe",
+            IndexerEvent::CommitCursor(_, _) => "CommitCursor",
+            IndexerEvent::Ack(_, _) => "Ack",
+            IndexerEvent::Nack(_, _) => "Nack",
+            IndexerEvent::RedeliverExpired => "RedeliverExpired",
+            IndexerEvent::GetTransaction(_, _) => "GetTransaction",
+            IndexerEvent::GetBlock(_, _) => "GetBlock",
+            IndexerEvent::GetStatus(_) => "GetStatus",
+            IndexerEvent::Pause => "Pause",
+            IndexerEvent::Resume => "Resume",
+            IndexerEvent::GetBalances(_, _) => "GetBalances",
+            IndexerEvent::BroadcastTransaction(_, _) => "BroadcastTransaction",
+            IndexerEvent::SimulateDelta(_, _) => "SimulateDelta",
+            IndexerEvent::IngestDegraded(_) => "IngestDegraded",
+            IndexerEvent::IngestRestored => "IngestRestored",
+            IndexerEvent::HeaderConnected(_, _) => "HeaderConnected",
+            IndexerEvent::ComponentFailed(_, _) => "ComponentFailed",
+            IndexerEvent::GetHealth(_) => "GetHealth",
+            IndexerEvent::TopicMessage(_, _) => "TopicMessage",
+            IndexerEvent::Reload(_, _) => "Reload",
         }
     }
     pub fn to_bytes(&self) -> Vec<u8> {
         let mut data = match self {
+            IndexerEvent::NewTxComing(data, seq) => {
+                let mut data = data.clone();
+                data.extend_from_slice(&seq.to_le_bytes());
+                data
+            }
+            IndexerEvent::RawBlockComing(block, seq) => {
+                let mut data = serialize(block);
+                data.extend_from_slice(&seq.to_le_bytes());
+                data
+            }
+            IndexerEvent::BlockConnected(hash) => serialize(hash),
+            IndexerEvent::HeaderConnected(header, height) => {
+                let mut data = serialize(header);
+                data.extend_from_slice(&height.to_le_bytes());
+                data
+            }
+            IndexerEvent::BlockDisconnected(hash) => serialize(hash),
+            IndexerEvent::SequenceGap(expected, got) => {
+                let mut data = expected.to_le_bytes().to_vec();
+                data.extend_from_slice(&got.to_le_bytes());
+                data
+            }
+            IndexerEvent::ReplayDeadLetters => vec![],
+            IndexerEvent::ReplayJournal(consumer, seq)
+            | IndexerEvent::CommitCursor(consumer, seq) => {
+                let mut data = consumer.to_le_bytes().to_vec();
+                data.extend_from_slice(&seq.to_le_bytes());
+                data
+            }
+            IndexerEvent::FlushBatch => vec![],
+            IndexerEvent::Ack(consumer, tx_id) | IndexerEvent::Nack(consumer, tx_id) => {
+                let mut data = consumer.to_le_bytes().to_vec();
+                data.extend_from_slice(&tx_id.to_bytes());
+                data
+            }
+            IndexerEvent::RedeliverExpired
+            | IndexerEvent::Pause
+            | IndexerEvent::Resume
+            | IndexerEvent::IngestRestored => vec![],
+            IndexerEvent::IngestDegraded(reason) => reason.as_bytes().to_vec(),
+            IndexerEvent::ComponentFailed(component, reason) => {
+                serde_json::to_vec(&(component, reason)).unwrap()
+            }
+            IndexerEvent::TopicMessage(topic, body) => {
+                let mut data = topic.as_bytes().to_vec();
+                data.push(0);
+                data.extend_from_slice(body);
+                data
+            }
+            IndexerEvent::Subscribe(consumer, filter) => {
+                let mut data = consumer.to_le_bytes().to_vec();
+                data.extend_from_slice(&serde_json::to_vec(filter).unwrap());
+                data
+            }
             IndexerEvent::UpdateDelta(tx) => {
                 let data = serde_json::to_vec(&tx).unwrap();
                 data
             }
+            IndexerEvent::UpdateDeltas(txs) => serde_json::to_vec(&txs).unwrap(),
             IndexerEvent::TxConfirmed(tx_id) => {
                 let data = tx_id.to_bytes();
                 data
@@ -63,10 +287,7 @@ impl IndexerEvent {
                 let data = height.to_le_bytes().to_vec();
                 data
             }
-            IndexerEvent::ReportReorg(height) => {
-                let data = height.to_le_bytes().to_vec();
-                data
-            }
+            IndexerEvent::ReportReorg(report) => serde_json::to_vec(report).unwrap(),
 
             _ => {
                 panic!("not support")
@@ -75,49 +296,173 @@ impl IndexerEvent {
         data.push(self.get_suffix());
         data
     }
-    pub fn from_bytes(data: &[u8]) -> Self {
-        let suffix = data[data.len() - 1];
-        match suffix {
+    // persisted events may be corrupt or of a newer version,neither panics
+    pub fn from_bytes(data: &[u8]) -> IndexerResult<Self> {
+        let (suffix, body) = data
+            .split_last()
+            .ok_or_else(|| invalid_event("empty event"))?;
+        let ret = match suffix {
             0 => {
-                let tx: TransactionDelta =
-                    serde_json::from_slice(&data[0..data.len() - 1]).unwrap();
-                IndexerEvent::UpdateDelta(tx)
+                let (tx, seq) = split_tail(body, 4)?;
+                IndexerEvent::NewTxComing(tx.to_vec(), u32::from_le_bytes(read_array(seq, 0)?))
             }
             2 => {
-                let tx_id = TxIdType::from_bytes(&data[0..data.len() - 1]);
+                let tx: TransactionDelta = serde_json::from_slice(body)?;
+                IndexerEvent::UpdateDelta(tx)
+            }
+            3 => {
+                let tx_id = TxIdType::from_bytes(body);
                 IndexerEvent::TxConfirmed(tx_id)
             }
+            4 => {
+                let (block, seq) = split_tail(body, 4)?;
+                IndexerEvent::RawBlockComing(
+                    deserialize(block)?,
+                    u32::from_le_bytes(read_array(seq, 0)?),
+                )
+            }
             5 => {
-                let tx_id = TxIdType::from_bytes(&data[0..data.len() - 1]);
-                IndexerEvent::TxRemoved(tx_id)
+                let tx_id = TxIdType::from_bytes(body);
+                IndexerEvent::TxFromRestoreByTxId(tx_id)
             }
             6 => {
-                let height = u32::from_le_bytes(data[0..data.len() - 1].try_into().unwrap());
-                IndexerEvent::ReportHeight(height)
+                let tx_id = TxIdType::from_bytes(body);
+                IndexerEvent::TxRemoved(tx_id)
             }
             7 => {
-                let height = u32::from_le_bytes(data[0..data.len() - 1].try_into().unwrap());
-                IndexerEvent::ReportReorg(height)
+                let height = u32::from_le_bytes(read_array(body, 0)?);
+                IndexerEvent::ReportHeight(height)
             }
-            _ => {
-                panic!("unknown suffix:{}", suffix);
+            8 => {
+                let report: ReorgReport = serde_json::from_slice(body)?;
+                IndexerEvent::ReportReorg(report)
             }
-        }
+            9 => {
+                let txs: Vec<TransactionDelta> = serde_json::from_slice(body)?;
+                IndexerEvent::UpdateDeltas(txs)
+            }
+            10 => IndexerEvent::BlockConnected(deserialize(body)?),
+            11 => IndexerEvent::BlockDisconnected(deserialize(body)?),
+            12 => IndexerEvent::SequenceGap(
+                u32::from_le_bytes(read_array(body, 0)?),
+                u32::from_le_bytes(read_array(body, 4)?),
+            ),
+            13 => IndexerEvent::ReplayDeadLetters,
+            14 => IndexerEvent::ReplayJournal(
+                u32::from_le_bytes(read_array(body, 0)?),
+                u64::from_le_bytes(read_array(body, 4)?),
+            ),
+            15 => IndexerEvent::FlushBatch,
+            16 => IndexerEvent::Subscribe(
+                u32::from_le_bytes(read_array(body, 0)?),
+                serde_json::from_slice(body.get(4..).unwrap_or_default())?,
+            ),
+            17 => IndexerEvent::CommitCursor(
+                u32::from_le_bytes(read_array(body, 0)?),
+                u64::from_le_bytes(read_array(body, 4)?),
+            ),
+            18 => IndexerEvent::Ack(
+                u32::from_le_bytes(read_array(body, 0)?),
+                TxIdType::from_bytes(body.get(4..).unwrap_or_default()),
+            ),
+            19 => IndexerEvent::Nack(
+                u32::from_le_bytes(read_array(body, 0)?),
+                TxIdType::from_bytes(body.get(4..).unwrap_or_default()),
+            ),
+            20 => IndexerEvent::RedeliverExpired,
+            24 => IndexerEvent::Pause,
+            25 => IndexerEvent::Resume,
+            29 => IndexerEvent::IngestDegraded(String::from_utf8_lossy(body).to_string()),
+            30 => IndexerEvent::IngestRestored,
+            32 => {
+                let (component, reason) = serde_json::from_slice(body)?;
+                IndexerEvent::ComponentFailed(component, reason)
+            }
+            31 => {
+                let (header, height) = split_tail(body, 4)?;
+                IndexerEvent::HeaderConnected(
+                    deserialize(header)?,
+                    u32::from_le_bytes(read_array(height, 0)?),
+                )
+            }
+            34 => {
+                let split = body
+                    .iter()
+                    .position(|v| *v == 0)
+                    .ok_or_else(|| invalid_event("topic message without topic"))?;
+                IndexerEvent::TopicMessage(
+                    String::from_utf8_lossy(&body[..split]).to_string(),
+                    body[split + 1..].to_vec(),
+                )
+            }
+            _ => return Err(invalid_event(&format!("unknown suffix:{}", suffix))),
+        };
+        Ok(ret)
     }
 }
 
+fn invalid_event(reason: &str) -> IndexerError {
+    IndexerError::InvalidEvent(reason.to_string())
+}
+
+// the n bytes at offset
+fn read_array<const N: usize>(data: &[u8], offset: usize) -> IndexerResult<[u8; N]> {
+    data.get(offset..offset + N)
+        .and_then(|v| v.try_into().ok())
+        .ok_or_else(|| invalid_event("event is too short"))
+}
+
+// the body and its last n bytes
+fn split_tail(data: &[u8], n: usize) -> IndexerResult<(&[u8], &[u8])> {
+    let at = data
+        .len()
+        .checked_sub(n)
+        .ok_or_else(|| invalid_event("event is too short"))?;
+    Ok(data.split_at(at))
+}
+
 impl Debug for IndexerEvent {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
             IndexerEvent::NewTxComing(_, _) => {
                 write!(f, "NewTxComing")
             }
-            IndexerEvent::GetBalance(_, _) => {
+            IndexerEvent::GetBalance(_, _, _) => {
                 write!(f, "GetBalance")
             }
+            IndexerEvent::GetBalances(v, _) => {
+                write!(f, "GetBalances: {}", v.len())
+            }
+            IndexerEvent::GetTransaction(v, _) => {
+                write!(f, "GetTransaction:{:?}", v)
+            }
+            IndexerEvent::GetBlock(v, _) => {
+                write!(f, "GetBlock:{:?}", v)
+            }
+            IndexerEvent::GetStatus(_) => {
+                write!(f, "GetStatus")
+            }
+            IndexerEvent::GetHealth(_) => {
+                write!(f, "GetHealth")
+            }
+            IndexerEvent::Reload(_, _) => {
+                write!(f, "Reload")
+            }
+            IndexerEvent::TopicMessage(topic, body) => {
+                write!(f, "TopicMessage:{},{} bytes", topic, body.len())
+            }
+            IndexerEvent::BroadcastTransaction(v, _) => {
+                write!(f, "BroadcastTransaction:{}", v.txid())
+            }
+            IndexerEvent::SimulateDelta(v, _) => {
+                write!(f, "SimulateDelta:{:?}", v.tx_id)
+            }
             IndexerEvent::UpdateDelta(_) => {
                 write!(f, "UpdateDelta")
             }
+            IndexerEvent::UpdateDeltas(v) => {
+                write!(f, "UpdateDeltas: {}", v.len())
+            }
             IndexerEvent::TxConfirmed(v) => {
                 write!(f, "TxConfirmed :{:?}", v)
             }
@@ -131,12 +476,99 @@ impl Debug for IndexerEvent {
                 write!(f, "ReportHeight: {}", v)
             }
             IndexerEvent::ReportReorg(v) => {
-                write!(f, "ReportReorg,to:{:?}", v)
+                write!(
+                    f,
+                    "ReportReorg,from:{},to:{}",
+                    v.old_tip.height, v.new_tip.height
+                )
+            }
+            IndexerEvent::RawBlockComing(v, _) => {
+                write!(f, "RawBlockComing: {}", v.block_hash())
+            }
+            IndexerEvent::BlockConnected(v) => {
+                write!(f, "BlockConnected: {}", v)
+            }
+            IndexerEvent::BlockDisconnected(v) => {
+                write!(f, "BlockDisconnected: {}", v)
+            }
+            IndexerEvent::SequenceGap(expected, got) => {
+                write!(f, "SequenceGap,expected:{},got:{}", expected, got)
+            }
+            IndexerEvent::ReplayDeadLetters => {
+                write!(f, "ReplayDeadLetters")
+            }
+            IndexerEvent::ReplayJournal(consumer, v) => {
+                write!(f, "ReplayJournal,consumer:{},from:{}", consumer, v)
+            }
+            IndexerEvent::FlushBatch => {
+                write!(f, "FlushBatch")
+            }
+            IndexerEvent::Subscribe(consumer, v) => {
+                write!(f, "Subscribe,consumer:{},filter:{:?}", consumer, v)
+            }
+            IndexerEvent::CommitCursor(consumer, v) => {
+                write!(f, "CommitCursor,consumer:{},seq:{}", consumer, v)
+            }
+            IndexerEvent::Ack(consumer, v) => {
+                write!(f, "Ack,consumer:{},tx_id:{:?}", consumer, v)
+            }
+            IndexerEvent::Nack(consumer, v) => {
+                write!(f, "Nack,consumer:{},tx_id:{:?}", consumer, v)
+            }
+            IndexerEvent::RedeliverExpired => {
+                write!(f, "RedeliverExpired")
+            }
+            IndexerEvent::Pause => {
+                write!(f, "Pause")
+            }
+            IndexerEvent::Resume => {
+                write!(f, "Resume")
+            }
+            IndexerEvent::IngestDegraded(v) => {
+                write!(f, "IngestDegraded: {}", v)
+            }
+            IndexerEvent::IngestRestored => {
+                write!(f, "IngestRestored")
+            }
+            IndexerEvent::ComponentFailed(component, reason) => {
+                write!(
+                    f,
+                    "ComponentFailed,component:{},reason:{}",
+                    component, reason
+                )
+            }
+            IndexerEvent::HeaderConnected(v, height) => {
+                write!(f, "HeaderConnected: {},height:{}", v.block_hash(), height)
             }
         }
     }
 }
 
+// reply side of a query,events are cloned so the oneshot sender is shared and taken once
+pub struct ReplySender<T>(Arc<Mutex<Option<tokio::sync::oneshot::Sender<T>>>>);
+
+impl<T> Clone for ReplySender<T> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<T> ReplySender<T> {
+    pub fn new() -> (Self, tokio::sync::oneshot::Receiver<T>) {
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        (Self(Arc::new(Mutex::new(Some(tx)))), rx)
+    }
+    // gives the value back when the receiver is gone or a clone already replied
+    pub fn send(&self, value: T) -> Result<(), T> {
+        match self.0.lock().unwrap().take() {
+            Some(tx) => tx.send(value),
+            None => Err(value),
+        }
+    }
+}
+
+pub type BalanceSender = ReplySender<BalanceType>;
+
 #[derive(Clone, Debug, Default, Serialize, Deserialize, Eq, Hash, PartialEq)]
 pub struct BalanceType(pub bigdecimal::BigDecimal);
 
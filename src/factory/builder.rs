@@ -0,0 +1,525 @@
+use crate::client::cancel::CancellationToken;
+use crate::client::common::CommonClient;
+use crate::client::drect::DirectClient;
+use crate::client::filter::{EventFilter, Subscription};
+use crate::client::metrics::ClientMetrics;
+use crate::component::backfill::BackfillComponent;
+use crate::component::catchup::CacheUpComponent;
+use crate::component::health::HealthComponent;
+use crate::component::p2p::P2pComponent;
+use crate::component::polling::PollingComponent;
+use crate::component::zmq::component::ZeroMQComponent;
+use crate::configuration::base::{
+    IndexerConfiguration, SnapshotConfiguration, StorageConfiguration,
+};
+use crate::configuration::reload::watch_configuration;
+use crate::dispatcher::event::DispatchEvent;
+use crate::dispatcher::Dispatcher;
+use crate::error::IndexerResult;
+use crate::factory::common::{boot_from_snapshot, create_client_from_configuration};
+use crate::health::HealthCheck;
+#[cfg(feature = "metrics")]
+use crate::metrics::{serve as serve_metrics, METRICS};
+use crate::net::limit::{RateLimitedSource, RateLimiter};
+use crate::net::pool::RpcPool;
+use crate::net::quorum::QuorumVerifier;
+use crate::net::{check_network, source_from_configuration, ChainDataSource};
+use crate::processor::common::IndexerProcessorImpl;
+use crate::processor::consumer::Consumer;
+use crate::processor::inflight::start_redelivery;
+use crate::storage::db::encrypted::EncryptedDB;
+use crate::storage::db::memory::MemoryDB;
+use crate::storage::db::thread_safe::ThreadSafeDB;
+use crate::storage::db::DB;
+use crate::storage::kv::KVStorageProcessor;
+use crate::storage::middleware::LayeredStorageProcessor;
+use crate::storage::prune::start_pruner;
+use crate::storage::snapshot::remote::{ObjectStore, RemoteSnapshotStore};
+use crate::storage::StorageProcessor;
+use crate::{ComponentTemplate, HookComponent};
+use async_channel::Sender;
+use log::{error, info};
+use std::panic;
+use std::path::PathBuf;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::runtime;
+use tokio::runtime::Runtime;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use wg::AsyncWaitGroup;
+
+// wires the processor and its clients,every option falls back to what the configuration says.
+// the storage middlewares of the configuration are layered over the given storage as well
+pub struct DirectClientBuilder {
+    cfg: IndexerConfiguration,
+    storage: Option<Box<dyn StorageProcessor>>,
+    channel_capacity: Option<usize>,
+    timeout: Option<Duration>,
+    filter: Option<EventFilter>,
+    cancel: Option<CancellationToken>,
+    metrics: Option<Arc<dyn ClientMetrics>>,
+    components: Vec<ComponentFactory>,
+    config_file: Option<PathBuf>,
+    object_store: Option<Arc<dyn ObjectStore>>,
+}
+
+const CONFIG_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+type ComponentFactory =
+    Box<dyn FnOnce(Sender<DispatchEvent>) -> Box<dyn HookComponent<DispatchEvent>> + Send>;
+
+impl DirectClientBuilder {
+    pub fn new(cfg: IndexerConfiguration) -> Self {
+        let timeout = Some(Duration::from_millis(cfg.client.timeout_ms)).filter(|v| !v.is_zero());
+        Self {
+            cfg,
+            storage: None,
+            channel_capacity: None,
+            timeout,
+            filter: None,
+            cancel: None,
+            metrics: None,
+            components: vec![],
+            config_file: None,
+            object_store: None,
+        }
+    }
+
+    // replaces the in memory kv storage,migrations are up to the caller then
+    pub fn with_storage<T: StorageProcessor + 'static>(mut self, storage: T) -> Self {
+        self.storage = Some(Box::new(storage));
+        self
+    }
+
+    // bounds the event channels of every client,the processor waits for the client once full
+    pub fn with_channel_capacity(mut self, capacity: usize) -> Self {
+        self.channel_capacity = Some(capacity);
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    // the filter every client starts with,the clients can still subscribe to another one
+    pub fn with_filter(mut self, filter: EventFilter) -> Self {
+        self.filter = Some(filter);
+        self
+    }
+
+    // shared by all the clients
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancel = Some(token);
+        self
+    }
+
+    // shared by all the clients
+    pub fn with_metrics(mut self, metrics: Arc<dyn ClientMetrics>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    // a component initialized,started and fed by the dispatcher like the built in ones,
+    // e.g. an archiver or an alerter. the factory gets the sender of the dispatcher
+    pub fn with_component<T, F>(mut self, factory: F) -> Self
+    where
+        T: HookComponent<DispatchEvent> + Clone + 'static,
+        F: FnOnce(Sender<DispatchEvent>) -> T + Send + 'static,
+    {
+        self.components.push(Box::new(move |tx| {
+            Box::new(ComponentTemplate::new(factory(tx))) as Box<dyn HookComponent<DispatchEvent>>
+        }));
+        self
+    }
+
+    // checkpoints are uploaded to and booted from it instead of snapshot.upload_dir,e.g. an s3 client
+    pub fn with_object_store(mut self, store: Arc<dyn ObjectStore>) -> Self {
+        self.object_store = Some(store);
+        self
+    }
+
+    // the tunables of the file are applied whenever it changes,see configuration::reload
+    pub fn with_config_file<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.config_file = Some(path.into());
+        self
+    }
+
+    pub async fn build(
+        self,
+        exit: watch::Receiver<()>,
+    ) -> IndexerResult<(
+        DirectClient<LayeredStorageProcessor>,
+        Vec<JoinHandle<()>>,
+        Arc<Runtime>,
+    )> {
+        let (mut clients, handles, rt) = self.build_clients(exit, 1).await?;
+        Ok((clients.remove(0), handles, rt))
+    }
+
+    // every client gets its own event channels,filter and cursor,all of them share the ingest pipeline.
+    // the first one answers the height queries of the processor
+    pub async fn build_clients(
+        self,
+        origin_exit: watch::Receiver<()>,
+        client_count: u32,
+    ) -> IndexerResult<(
+        Vec<DirectClient<LayeredStorageProcessor>>,
+        Vec<JoinHandle<()>>,
+        Arc<Runtime>,
+    )> {
+        self.cfg.validate()?;
+        let origin_cfg = self.cfg.clone().resolve_secrets()?;
+        info!(
+            "effective configuration:{}",
+            serde_json::to_string(&origin_cfg.redacted()).unwrap()
+        );
+        let rt = Arc::new(
+            runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()?,
+        );
+
+        // panicked components are restarted by their supervisor
+        panic::set_hook(Box::new(|panic_info| {
+            error!("panic occurred: {:?}", panic_info);
+        }));
+        let flag = Arc::new(AtomicBool::new(false));
+        let snapshot = &origin_cfg.storage.snapshot;
+        let remote = match &self.object_store {
+            Some(store) => Some(RemoteSnapshotStore::new(
+                store.clone(),
+                snapshot.upload_retries,
+            )),
+            None => RemoteSnapshotStore::from_configuration(snapshot),
+        };
+        let processor: Box<dyn StorageProcessor> = match self.storage {
+            Some(mut storage) => {
+                boot(snapshot, remote.as_ref(), &mut storage).await?;
+                storage
+            }
+            None => {
+                // let db = LevelDB::new(origin_cfg.storage.db_path.as_str()).unwrap();
+                let db = ThreadSafeDB::new(MemoryDB::default());
+                open_storage(&origin_cfg.storage, remote.as_ref(), db).await?
+            }
+        };
+        let processor = origin_cfg.storage.middlewares.apply(processor);
+        let client = Arc::new(create_client_from_configuration(&origin_cfg.rpc));
+        check_network(&client, origin_cfg.network)?;
+        // also without limits,a reload can set them
+        let limiter = Arc::new(RateLimiter::from_configuration(&origin_cfg.rpc.rate_limit));
+        let source =
+            source_from_configuration(&origin_cfg.rpc, &origin_cfg.rpc.retry, client.clone())?;
+        let source: Arc<dyn ChainDataSource> =
+            Arc::new(RateLimitedSource::new(source, limiter.clone()));
+        // only when the txs come from rpc
+        let net = &origin_cfg.rpc;
+        let rpc_pool = (net.electrum_url.is_none() && net.esplora_url.is_none()).then(|| {
+            let clients = (0..net.rpc_pool_size.max(1))
+                .map(|_| create_client_from_configuration(&origin_cfg.rpc))
+                .collect();
+            Arc::new(
+                RpcPool::new(clients, net.max_concurrent_rpc).with_limiter(Some(limiter.clone())),
+            )
+        });
+        let quorum = net.quorum.as_ref().map(|v| {
+            Arc::new(QuorumVerifier::new(
+                v,
+                Duration::from_millis(net.rpc_timeout_ms),
+            ))
+        });
+        let channel = || match self.channel_capacity {
+            Some(capacity) => async_channel::bounded(capacity),
+            None => async_channel::unbounded(),
+        };
+        let channels: Vec<_> = (0..client_count.max(1))
+            .map(|_| (channel(), channel()))
+            .collect();
+        let consumers = channels
+            .iter()
+            .map(|((tx, _), (priority_tx, _))| {
+                let mut consumer = Consumer::new(tx.clone(), priority_tx.clone());
+                consumer.subscription = self.filter.clone().map(Subscription::new);
+                consumer
+            })
+            .collect();
+        let notify_tx = channels[0].0 .0.clone();
+        // the gauges hold a receiver,they are dropped once the processor dropped its senders
+        #[cfg(feature = "metrics")]
+        for (i, ((_, rx), (_, priority_rx))) in channels.iter().enumerate() {
+            for (name, rx) in [
+                (format!("client_{}", i), rx.clone()),
+                (format!("client_{}_priority", i), priority_rx.clone()),
+            ] {
+                METRICS.register_channel(&name, move || (rx.sender_count() > 0).then(|| rx.len()));
+            }
+        }
+
+        let dispatcher = Box::leak(Box::new(Dispatcher::default()));
+        let tx = dispatcher.tx();
+        #[cfg(feature = "metrics")]
+        {
+            let tx = tx.clone();
+            METRICS.register_channel("dispatcher", move || Some(tx.len()));
+        }
+
+        let wg = AsyncWaitGroup::new();
+        let mq_wg = wg.add(1);
+        let catch_up_wg = wg.add(1);
+        let backfill_wg = origin_cfg.start_height.map(|_| wg.add(1));
+
+        // the zmq component signals the start when enabled,the p2p one or the poller otherwise
+        let ingest = &origin_cfg.ingest;
+        let (zmq_wg, p2p_wg, poll_wg) = if ingest.zmq() {
+            (Some(mq_wg), None, None)
+        } else if ingest.p2p() {
+            (None, Some(mq_wg), None)
+        } else {
+            (None, None, Some(mq_wg))
+        };
+        let zmq = zmq_wg.map(|wg| {
+            ZeroMQComponent::new(
+                wg,
+                origin_cfg.zmq.clone(),
+                client.clone(),
+                tx.clone(),
+                flag.clone(),
+            )
+        });
+        let p2p = p2p_wg.map(|wg| {
+            P2pComponent::new(
+                wg,
+                ingest.p2p.clone(),
+                origin_cfg.network,
+                tx.clone(),
+                flag.clone(),
+            )
+        });
+        let ingest_health: Option<Arc<dyn HealthCheck>> = match (&zmq, &p2p) {
+            (Some(zmq), _) => Some(Arc::new(zmq.clone())),
+            (_, Some(p2p)) => Some(Arc::new(p2p.clone())),
+            _ => None,
+        };
+        let zmq_connected = match (&zmq, &p2p) {
+            (Some(zmq), _) => zmq.connected(),
+            (_, Some(p2p)) => p2p.connected(),
+            _ => Default::default(),
+        };
+
+        let (index_processor, block_cache) = {
+            let (tx, rx) = async_channel::unbounded();
+            let indexer_processor = IndexerProcessorImpl::new(
+                origin_cfg.processor(),
+                wg.clone(),
+                consumers,
+                processor.clone(),
+                client.clone(),
+                notify_tx.clone(),
+                flag.clone(),
+                tx.clone(),
+                rx.clone(),
+            )
+            .with_zmq_state(zmq_connected)
+            .with_source(source.clone())
+            .with_rpc_pool(rpc_pool)
+            .with_quorum(quorum)
+            .with_limiter(limiter.clone())
+            .with_snapshot_store(remote);
+            let block_cache = indexer_processor.block_cache();
+            let indexer =
+                ComponentTemplate::new_with_tx_rx(indexer_processor, tx.clone(), rx.clone());
+            (indexer, block_cache)
+        };
+
+        // let wait_cachup = ComponentTemplate::new(WaitIndexerCatchupComponent::new(
+        //     catch_up_wg,
+        //     client.clone(),
+        //     notify_tx.clone(),
+        // ));
+        let catchup = ComponentTemplate::new(CacheUpComponent::new(
+            client.clone(),
+            catch_up_wg,
+            tx.clone(),
+        ));
+
+        dispatcher.register_component(Box::new(index_processor));
+        dispatcher.register_component(Box::new(catchup));
+        if let Some(zmq) = zmq {
+            dispatcher.register_component(Box::new(ComponentTemplate::new(zmq)));
+        }
+        if let Some(p2p) = p2p {
+            dispatcher.register_component(Box::new(ComponentTemplate::new(p2p)));
+        }
+        if origin_cfg.ingest.polling() {
+            dispatcher.register_component(Box::new(ComponentTemplate::new(PollingComponent::new(
+                client.clone(),
+                Duration::from_millis(origin_cfg.ingest.poll_interval_ms),
+                origin_cfg.rpc.retry.clone(),
+                flag.clone(),
+                poll_wg,
+                tx.clone(),
+            ))));
+        }
+        if let (Some(start_height), Some(backfill_wg)) = (origin_cfg.start_height, backfill_wg) {
+            dispatcher.register_component(Box::new(ComponentTemplate::new(
+                BackfillComponent::new(
+                    client.clone(),
+                    start_height,
+                    origin_cfg.rpc.retry.clone(),
+                    backfill_wg,
+                    tx.clone(),
+                ),
+            )));
+        }
+
+        if let Some(listen) = &origin_cfg.health.listen {
+            dispatcher.register_component(Box::new(ComponentTemplate::new(HealthComponent::new(
+                listen.clone(),
+                flag.clone(),
+                ingest_health.clone(),
+                client.clone(),
+            ))));
+        }
+
+        for factory in self.components {
+            dispatcher.register_component(factory(tx.clone()));
+        }
+
+        dispatcher.init(&origin_cfg).await?;
+        let mut ret = dispatcher.start(origin_exit.clone()).await?;
+        if origin_cfg.client.ack_timeout_secs > 0 {
+            ret.push(start_redelivery(
+                tx.clone(),
+                origin_cfg.client.ack_timeout_secs,
+                origin_exit.clone(),
+            ));
+        }
+        if let Some(listen) = &origin_cfg.metrics.listen {
+            #[cfg(feature = "metrics")]
+            ret.push(serve_metrics(listen, origin_exit.clone()).await?);
+            #[cfg(not(feature = "metrics"))]
+            log::warn!(
+                "metrics.listen is {},but the metrics feature is disabled",
+                listen
+            );
+        }
+        if let Some(path) = self.config_file {
+            ret.push(watch_configuration(
+                path,
+                CONFIG_POLL_INTERVAL,
+                tx.clone(),
+                origin_exit.clone(),
+            ));
+        }
+        if origin_cfg.storage.prune.retention.is_some() || origin_cfg.storage.prune.gc_inactive {
+            ret.push(start_pruner(
+                processor.clone(),
+                origin_cfg.storage.prune.clone(),
+                origin_exit.clone(),
+            ));
+        }
+
+        let clients = channels
+            .into_iter()
+            .enumerate()
+            .map(|(i, ((_, rx), (_, priority_rx)))| {
+                let mut inner_client = CommonClient::new(rx, priority_rx, tx.clone(), i as u32)
+                    .with_timeout(self.timeout);
+                if let Some(token) = &self.cancel {
+                    inner_client = inner_client.with_cancellation(token.clone());
+                }
+                let client = DirectClient::new(
+                    rt.clone(),
+                    client.clone(),
+                    processor.clone(),
+                    block_cache.clone(),
+                    inner_client,
+                )
+                .with_source(source.clone());
+                let client = match &ingest_health {
+                    Some(check) => client.with_health_check(check.clone()),
+                    None => client,
+                };
+                match &self.metrics {
+                    Some(metrics) => client.with_metrics(metrics.clone()),
+                    None => client,
+                }
+            })
+            .collect();
+        Ok((clients, ret, rt.clone()))
+    }
+}
+
+// the values are encrypted at rest once storage.encryption_key is set
+async fn open_storage<D: DB + Send + Sync + Clone + 'static>(
+    cfg: &StorageConfiguration,
+    remote: Option<&RemoteSnapshotStore>,
+    db: D,
+) -> IndexerResult<Box<dyn StorageProcessor>> {
+    match &cfg.encryption_key {
+        Some(_) => open_kv_storage(cfg, remote, EncryptedDB::from_configuration(db, cfg)?).await,
+        None => open_kv_storage(cfg, remote, db).await,
+    }
+}
+
+async fn open_kv_storage<D: DB + Send + Sync + Clone + 'static>(
+    cfg: &StorageConfiguration,
+    remote: Option<&RemoteSnapshotStore>,
+    db: D,
+) -> IndexerResult<Box<dyn StorageProcessor>> {
+    let mut processor = KVStorageProcessor::new(db);
+    boot(&cfg.snapshot, remote, &mut processor).await?;
+    processor.migrate()?;
+    Ok(Box::new(processor))
+}
+
+async fn boot<T: StorageProcessor>(
+    cfg: &SnapshotConfiguration,
+    remote: Option<&RemoteSnapshotStore>,
+    storage: &mut T,
+) -> IndexerResult<()> {
+    if !cfg.boot_from_snapshot {
+        return Ok(());
+    }
+    if let Some(dir) = &cfg.snapshot_dir {
+        if let Some(remote) = remote {
+            remote.download_latest(dir).await?;
+        }
+        boot_from_snapshot(storage, dir).await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::prefix::KeyPrefix;
+
+    #[tokio::test]
+    pub async fn test_open_encrypted_storage() {
+        let key = KeyPrefix::build_event_journal_key(0);
+        let mut cfg = StorageConfiguration::default();
+        let mut raw = MemoryDB::default();
+        let mut storage = open_storage(&cfg, None, raw.clone()).await.unwrap();
+        storage.append_journal(b"plain").await.unwrap();
+        assert_eq!(raw.get(&key).unwrap(), Some(b"plain".to_vec()));
+
+        cfg.encryption_key = Some("07".repeat(32));
+        let mut raw = MemoryDB::default();
+        let mut storage = open_storage(&cfg, None, raw.clone()).await.unwrap();
+        storage.append_journal(b"plain").await.unwrap();
+        let stored = raw.get(&key).unwrap().unwrap();
+        assert_ne!(stored, b"plain".to_vec());
+        assert_eq!(
+            EncryptedDB::new(raw, &[7u8; 32]).get(&key).unwrap(),
+            Some(b"plain".to_vec())
+        );
+        assert_eq!(
+            storage.get_journal(0, 1).await.unwrap(),
+            vec![(0, b"plain".to_vec())]
+        );
+    }
+}
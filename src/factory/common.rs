@@ -1,126 +1,99 @@
-use crate::client::common::CommonClient;
 use crate::client::drect::DirectClient;
-use crate::component::catchup::CacheUpComponent;
-use crate::component::zmq::component::ZeroMQComponent;
-use crate::configuration::base::IndexerConfiguration;
-use crate::dispatcher::Dispatcher;
-use crate::processor::common::IndexerProcessorImpl;
-use crate::storage::db::memory::MemoryDB;
-use crate::storage::db::thread_safe::ThreadSafeDB;
-use crate::storage::kv::KVStorageProcessor;
-use crate::{wait_exit_signal, ComponentTemplate};
-use bitcoincore_rpc::{Auth, Client};
-use log::error;
-use std::process::exit;
-use std::sync::atomic::AtomicBool;
+use crate::configuration::base::{IndexerConfiguration, RpcConfiguration, RpcEndpoint};
+use crate::error::IndexerResult;
+use crate::factory::builder::DirectClientBuilder;
+use crate::net::cookie::CookieTransport;
+use crate::storage::middleware::LayeredStorageProcessor;
+use crate::storage::snapshot::Snapshot;
+use crate::storage::StorageProcessor;
+use crate::wait_exit_signal;
+use bitcoincore_rpc::jsonrpc::{self, simple_http};
+use bitcoincore_rpc::Client;
+use log::info;
 use std::sync::Arc;
-use std::{panic, thread};
-use tokio::runtime;
+use std::thread;
+use std::time::Duration;
 use tokio::runtime::Runtime;
 use tokio::sync::watch;
 use tokio::task::JoinHandle;
-use wg::AsyncWaitGroup;
 
 pub async fn async_create_and_start_processor(
     origin_exit: watch::Receiver<()>,
     origin_cfg: IndexerConfiguration,
-) -> (
-    DirectClient<KVStorageProcessor<ThreadSafeDB<MemoryDB>>>,
+) -> IndexerResult<(
+    DirectClient<LayeredStorageProcessor>,
     Vec<JoinHandle<()>>,
     Arc<Runtime>,
-) {
-    let rt = Arc::new(
-        runtime::Builder::new_current_thread()
-            .enable_all()
-            .build()
-            .unwrap(),
-    );
-
-    panic::set_hook(Box::new(|panic_info| {
-        println!("panic occurred: {:?}", panic_info);
-        error!("panic occurred: {:?}", panic_info);
-        exit(-1);
-    }));
-    let flag = Arc::new(AtomicBool::new(false));
-    // let db = LevelDB::new(origin_cfg.db_path.as_str()).unwrap();
-    let db = ThreadSafeDB::new(MemoryDB::default());
-    let processor = KVStorageProcessor::new(db);
-    let client = Arc::new(create_client_from_configuration(origin_cfg.clone()));
-    let (notify_tx, notify_rx) = async_channel::unbounded();
-
-    let dispatcher = Box::leak(Box::new(Dispatcher::default()));
-    let tx = dispatcher.tx();
-
-    let wg = AsyncWaitGroup::new();
-    let mq_wg = wg.add(1);
-    let catch_up_wg = wg.add(1);
-
-    let index_processor = {
-        let (tx, rx) = async_channel::unbounded();
-        let indexer_processor = IndexerProcessorImpl::new(
-            origin_cfg.clone(),
-            wg.clone(),
-            notify_tx.clone(),
-            processor.clone(),
-            client.clone(),
-            notify_tx.clone(),
-            flag.clone(),
-            tx.clone(),
-            rx.clone(),
-        );
-        let indexer = ComponentTemplate::new_with_tx_rx(indexer_processor, tx.clone(), rx.clone());
-        indexer
-    };
-
-    // let wait_cachup = ComponentTemplate::new(WaitIndexerCatchupComponent::new(
-    //     catch_up_wg,
-    //     client.clone(),
-    //     notify_tx.clone(),
-    // ));
-    let catchup = ComponentTemplate::new(CacheUpComponent::new(
-        client.clone(),
-        catch_up_wg,
-        tx.clone(),
-    ));
-
-    let zmq = ComponentTemplate::new(ZeroMQComponent::new(
-        mq_wg,
-        origin_cfg.clone(),
-        tx.clone(),
-        flag.clone(),
-    ));
-
-    dispatcher.register_component(Box::new(index_processor));
-    dispatcher.register_component(Box::new(catchup));
-    dispatcher.register_component(Box::new(zmq));
+)> {
+    DirectClientBuilder::new(origin_cfg)
+        .build(origin_exit)
+        .await
+}
 
-    dispatcher.init(origin_cfg.clone()).await.unwrap();
-    let ret = dispatcher.start(origin_exit.clone()).await.unwrap();
+// every client gets its own event channels,filter and cursor,all of them share the ingest pipeline
+pub async fn async_create_and_start_processor_with_clients(
+    origin_exit: watch::Receiver<()>,
+    origin_cfg: IndexerConfiguration,
+    client_count: u32,
+) -> IndexerResult<(
+    Vec<DirectClient<LayeredStorageProcessor>>,
+    Vec<JoinHandle<()>>,
+    Arc<Runtime>,
+)> {
+    DirectClientBuilder::new(origin_cfg)
+        .build_clients(origin_exit, client_count)
+        .await
+}
 
-    let inner_client = CommonClient::new(notify_rx.clone(), tx.clone());
-    (
-        DirectClient::new(rt.clone(), client.clone(), processor.clone(), inner_client),
-        ret,
-        rt.clone(),
-    )
+pub async fn boot_from_snapshot<T: StorageProcessor>(
+    storage: &mut T,
+    dir: &str,
+) -> IndexerResult<Option<u32>> {
+    let snapshot = Snapshot::latest(dir)?;
+    if snapshot.is_none() {
+        info!("no snapshot found in {},start from scratch", dir);
+        return Ok(None);
+    }
+    let snapshot = snapshot.unwrap();
+    storage.restore_snapshot(&snapshot).await?;
+    info!("boot from snapshot,height:{}", snapshot.height);
+    Ok(Some(snapshot.height))
 }
 
 pub(crate) fn create_client_from_configuration(
-    config: IndexerConfiguration,
+    config: &RpcConfiguration,
 ) -> bitcoincore_rpc::Client {
-    Client::new(
-        config.net.url.as_str(),
-        Auth::UserPass(config.net.username.clone(), config.net.password.clone()),
+    create_client_for_endpoint(
+        &config.endpoint(),
+        Duration::from_millis(config.rpc_timeout_ms),
     )
-    .unwrap()
+}
+
+pub(crate) fn create_client_for_endpoint(
+    endpoint: &RpcEndpoint,
+    timeout: Duration,
+) -> bitcoincore_rpc::Client {
+    if let Some(cookie_file) = &endpoint.cookie_file {
+        return CookieTransport::new(endpoint.url.as_str(), cookie_file)
+            .unwrap()
+            .with_timeout(timeout)
+            .client();
+    }
+    let transport = simple_http::Builder::new()
+        .url(endpoint.url.as_str())
+        .unwrap()
+        .timeout(timeout)
+        .auth(endpoint.username.as_str(), Some(endpoint.password.as_str()))
+        .build();
+    Client::from_jsonrpc(jsonrpc::Client::with_transport(transport))
 }
 
 pub fn sync_create_and_start_processor(
     origin_cfg: IndexerConfiguration,
-) -> DirectClient<KVStorageProcessor<ThreadSafeDB<MemoryDB>>> {
+) -> IndexerResult<DirectClient<LayeredStorageProcessor>> {
     let (tx, rx) = watch::channel(());
-    let rt = Runtime::new().unwrap();
-    let ret = rt.block_on(async_create_and_start_processor(rx, origin_cfg));
+    let rt = Runtime::new()?;
+    let ret = rt.block_on(async_create_and_start_processor(rx, origin_cfg))?;
     thread::spawn(move || {
         rt.block_on(async {
             let handlers = ret.1;
@@ -132,5 +105,5 @@ pub fn sync_create_and_start_processor(
         });
     });
 
-    ret.0
+    Ok(ret.0)
 }
@@ -0,0 +1,95 @@
+use crate::component::p2p::P2pComponent;
+use crate::component::zmq::component::ZeroMQComponent;
+use crate::storage::StorageProcessor;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::Ordering;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum HealthStatus {
+    Connected,
+    // working but behind,and why
+    Lagging(String),
+    Failing(String),
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ComponentHealth {
+    pub component: String,
+    pub status: HealthStatus,
+}
+
+impl ComponentHealth {
+    pub fn new(component: &str, status: HealthStatus) -> Self {
+        Self {
+            component: component.to_string(),
+            status,
+        }
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.status == HealthStatus::Connected
+    }
+}
+
+#[async_trait]
+pub trait HealthCheck: Send + Sync {
+    async fn health(&self) -> ComponentHealth;
+}
+
+#[async_trait]
+impl HealthCheck for ZeroMQComponent {
+    async fn health(&self) -> ComponentHealth {
+        ingest_health("zmq", self.connected().load(Ordering::Relaxed))
+    }
+}
+
+#[async_trait]
+impl HealthCheck for P2pComponent {
+    async fn health(&self) -> ComponentHealth {
+        ingest_health("p2p", self.connected().load(Ordering::Relaxed))
+    }
+}
+
+fn ingest_health(component: &str, connected: bool) -> ComponentHealth {
+    let status = match connected {
+        true => HealthStatus::Connected,
+        false => HealthStatus::Failing("not connected,reconnecting".to_string()),
+    };
+    ComponentHealth::new(component, status)
+}
+
+// dead letters are events the processor failed to handle
+#[async_trait]
+impl<T: StorageProcessor + Clone> HealthCheck for T {
+    async fn health(&self) -> ComponentHealth {
+        let status = match self.clone().get_dead_letters().await {
+            Ok(letters) if letters.is_empty() => HealthStatus::Connected,
+            Ok(letters) => HealthStatus::Lagging(format!("{} dead letters", letters.len())),
+            Err(e) => HealthStatus::Failing(e.to_string()),
+        };
+        ComponentHealth::new("storage", status)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::db::memory::MemoryDB;
+    use crate::storage::db::thread_safe::ThreadSafeDB;
+    use crate::storage::kv::KVStorageProcessor;
+
+    #[tokio::test]
+    pub async fn test_storage_health() {
+        let mut storage = KVStorageProcessor::new(ThreadSafeDB::new(MemoryDB::default()));
+        assert!(storage.health().await.is_connected());
+        storage.add_dead_letter(&[1, 2], "failed").await.unwrap();
+        assert_eq!(
+            storage.health().await,
+            ComponentHealth::new(
+                "storage",
+                HealthStatus::Lagging("1 dead letters".to_string())
+            )
+        );
+    }
+}
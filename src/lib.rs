@@ -1,5 +1,7 @@
-use crate::configuration::base::IndexerConfiguration;
+use crate::configuration::base::{IndexerConfiguration, RestartPolicy};
 use crate::error::IndexerResult;
+use crate::logging::{component_field, with_log_fields};
+use crate::supervisor::supervise;
 use async_channel::{Receiver, Sender};
 use downcast_rs::{impl_downcast, Downcast};
 use log::{info, warn};
@@ -7,7 +9,6 @@ use std::fmt::Debug;
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::sync::watch;
-use tokio::task;
 use tokio::task::JoinHandle;
 
 pub mod client;
@@ -17,8 +18,14 @@ pub mod dispatcher;
 pub mod error;
 pub mod event;
 pub mod factory;
+pub mod health;
+pub mod logging;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+pub mod net;
 pub mod processor;
 pub mod storage;
+pub mod supervisor;
 pub mod types;
 
 #[derive(Clone, Debug)]
@@ -31,6 +38,14 @@ pub trait Event: Downcast + Send + Sync + Debug {
     fn is_async(&self) -> bool {
         true
     }
+
+    // what the supervisor reports when a component fails,nothing by default
+    fn component_failed(_component: &str, _reason: &str) -> Option<Self>
+    where
+        Self: Sized,
+    {
+        None
+    }
 }
 impl_downcast!(Event);
 
@@ -42,7 +57,8 @@ pub trait Component<T: Event + Clone>: Send + Sync {
         std::any::type_name::<Self>().to_string()
     }
 
-    async fn init(&mut self, _cfg: IndexerConfiguration) -> IndexerResult<()> {
+    // a component keeps the section of the configuration it needs,e.g. cfg.zmq
+    async fn init(&mut self, _cfg: &IndexerConfiguration) -> IndexerResult<()> {
         Ok(())
     }
 
@@ -66,6 +82,8 @@ pub struct ComponentTemplate<T: HookComponent<E> + Clone + 'static, E: Clone + E
     internal: T,
     rx: async_channel::Receiver<E>,
     tx: Sender<E>,
+    policy: RestartPolicy,
+    report: Option<Sender<E>>,
 }
 
 impl<T: HookComponent<E> + Clone + 'static, E: Clone + Event> ComponentTemplate<T, E> {
@@ -76,10 +94,16 @@ impl<T: HookComponent<E> + Clone + 'static, E: Clone + Event> ComponentTemplate<
 impl<T: HookComponent<E> + Clone, E: Clone + Event> ComponentTemplate<T, E> {
     pub fn new(internal: T) -> Self {
         let (tx, rx) = async_channel::unbounded();
-        Self { internal, rx, tx }
+        Self::new_with_tx_rx(internal, tx, rx)
     }
     pub fn new_with_tx_rx(internal: T, tx: Sender<E>, rx: Receiver<E>) -> Self {
-        Self { internal, rx, tx }
+        Self {
+            internal,
+            rx,
+            tx,
+            policy: Default::default(),
+            report: None,
+        }
     }
 }
 
@@ -87,17 +111,36 @@ impl<T: HookComponent<E> + Clone, E: Clone + Event> ComponentTemplate<T, E> {
 impl<T: HookComponent<E> + Clone + 'static, E: Clone + Event> Component<E>
     for ComponentTemplate<T, E>
 {
-    async fn init(&mut self, cfg: IndexerConfiguration) -> IndexerResult<()> {
+    async fn init(&mut self, cfg: &IndexerConfiguration) -> IndexerResult<()> {
         info!("component {} init", self.component_name());
+        self.policy = cfg.dispatcher.restart_policy.clone();
         self.internal.init(cfg).await
     }
 
     async fn start(&mut self, exit: watch::Receiver<()>) -> IndexerResult<Vec<JoinHandle<()>>> {
         let mut ret = self.internal.start(exit.clone()).await?;
-        let mut node = self.clone();
-        let task = task::spawn(async move {
-            node.on_start(exit.clone()).await.unwrap();
-        });
+        // every restart begins with the state the component had before its first start
+        let node = self.clone();
+        let node_exit = exit.clone();
+        let component = component_field(&self.component_name());
+        // the events waiting for the component,e.g. the processor
+        #[cfg(feature = "metrics")]
+        {
+            let tx = self.tx.clone();
+            crate::metrics::METRICS.register_channel(&component, move || Some(tx.len()));
+        }
+        let task = supervise(
+            self.component_name(),
+            self.policy.clone(),
+            self.report.clone(),
+            exit,
+            move || {
+                let mut node = node.clone();
+                let exit = node_exit.clone();
+                let fields = vec![("component", component.clone().into())];
+                async move { with_log_fields(fields, node.on_start(exit)).await }
+            },
+        );
         ret.push(task);
         Ok(ret)
     }
@@ -128,6 +171,9 @@ pub trait HookComponent<E: Clone + Event>: Component<E> {
     async fn push_event(&mut self, _: &E) -> IndexerResult<()> {
         Ok(())
     }
+
+    // where failures of the component are reported,e.g. the dispatcher
+    fn report_failures_to(&mut self, _: Sender<E>) {}
 }
 
 #[async_trait::async_trait]
@@ -152,6 +198,10 @@ impl<T: HookComponent<E> + Clone, E: Clone + Event> HookComponent<E> for Compone
         let _ = self.tx.send(event.clone()).await;
         Ok(())
     }
+
+    fn report_failures_to(&mut self, report: Sender<E>) {
+        self.report = Some(report);
+    }
 }
 impl<T: HookComponent<E> + Clone, E: Clone + Event> ComponentTemplate<T, E> {
     async fn on_start(&mut self, _: watch::Receiver<()>) -> IndexerResult<()> {
@@ -240,7 +290,7 @@ mod tests {
 
     #[test]
     pub fn test_notifier() {
-        let notifier = sync_create_and_start_processor(IndexerConfiguration::default());
+        let notifier = sync_create_and_start_processor(IndexerConfiguration::default()).unwrap();
         loop {
             let data = notifier.get();
             if data.len() > 0 {
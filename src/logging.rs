@@ -0,0 +1,273 @@
+use crate::configuration::base::{LogConfiguration, LogFileConfiguration, LogFormat};
+use crate::error::{IndexerError, IndexerResult};
+use chrono::{Local, SecondsFormat};
+use log::{LevelFilter, Record};
+use log4rs::append::console::{ConsoleAppender, Target};
+use log4rs::append::rolling_file::policy::compound::roll::delete::DeleteRoller;
+use log4rs::append::rolling_file::policy::compound::roll::fixed_window::FixedWindowRoller;
+use log4rs::append::rolling_file::policy::compound::roll::Roll;
+use log4rs::append::rolling_file::policy::compound::trigger::Trigger;
+use log4rs::append::rolling_file::policy::compound::CompoundPolicy;
+use log4rs::append::rolling_file::{LogFile, RollingFileAppender};
+use log4rs::config::{Appender, Config, Root};
+use log4rs::encode::pattern::PatternEncoder;
+use log4rs::encode::Encode;
+use serde_json::{Map, Value};
+use std::cell::RefCell;
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+const PATTERN: &str = "{d(%Y-%m-%d %H:%M:%S%.3f)} {l} {t} - {m}{n}";
+
+tokio::task_local! {
+    static LOG_FIELDS: RefCell<Map<String, Value>>;
+}
+
+// runs f with the fields added to the ones of the caller,every json record f logs carries them.
+// tasks spawned by f don't inherit them
+pub async fn with_log_fields<F: Future>(fields: Vec<(&str, Value)>, f: F) -> F::Output {
+    let mut current = log_fields();
+    for (key, value) in fields {
+        current.insert(key.to_string(), value);
+    }
+    LOG_FIELDS.scope(RefCell::new(current), f).await
+}
+
+// e.g. the txid once the raw tx is decoded,ignored outside of with_log_fields
+pub fn set_log_field(key: &str, value: impl Into<Value>) {
+    let _ = LOG_FIELDS.try_with(|v| v.borrow_mut().insert(key.to_string(), value.into()));
+}
+
+// the type without its path and generics,e.g. IndexerProcessorImpl
+pub(crate) fn component_field(type_name: &str) -> String {
+    let name = type_name.split('<').next().unwrap_or_default();
+    name.rsplit("::").next().unwrap_or_default().to_string()
+}
+
+pub fn log_fields() -> Map<String, Value> {
+    LOG_FIELDS
+        .try_with(|v| v.borrow().clone())
+        .unwrap_or_default()
+}
+
+// {"time":..,"level":..,"target":..,"message":..} plus the log fields of the task
+#[derive(Debug)]
+pub struct JsonEncoder;
+
+impl Encode for JsonEncoder {
+    fn encode(&self, w: &mut dyn log4rs::encode::Write, record: &Record) -> anyhow::Result<()> {
+        let mut line = log_fields();
+        line.insert(
+            "time".to_string(),
+            Local::now()
+                .to_rfc3339_opts(SecondsFormat::Millis, false)
+                .into(),
+        );
+        line.insert("level".to_string(), record.level().as_str().into());
+        line.insert("target".to_string(), record.target().into());
+        line.insert("message".to_string(), record.args().to_string().into());
+        serde_json::to_writer(&mut *w, &line)?;
+        w.write_all(b"\n")?;
+        Ok(())
+    }
+}
+
+fn encoder(format: LogFormat) -> Box<dyn Encode> {
+    match format {
+        LogFormat::Text => Box::new(PatternEncoder::new(PATTERN)),
+        LogFormat::Json => Box::new(JsonEncoder),
+    }
+}
+
+// installs the global logger,instead of env_logger. the appenders take every level and
+// log::max_level filters,so a reload of the configuration can raise the level as well
+pub fn init_logging(cfg: &LogConfiguration) -> IndexerResult<log4rs::Handle> {
+    let handle = log4rs::init_config(logging_config(cfg)?)
+        .map_err(|e| IndexerError::LoggingError(e.to_string()))?;
+    log::set_max_level(cfg.log_level);
+    Ok(handle)
+}
+
+pub fn logging_config(cfg: &LogConfiguration) -> IndexerResult<Config> {
+    let mut builder = Config::builder();
+    let mut root = Root::builder();
+    if cfg.stderr {
+        let console = ConsoleAppender::builder()
+            .target(Target::Stderr)
+            .encoder(encoder(cfg.format))
+            .build();
+        builder = builder.appender(Appender::builder().build("stderr", Box::new(console)));
+        root = root.appender("stderr");
+    }
+    if let Some(file) = &cfg.file {
+        builder = builder.appender(
+            Appender::builder().build("file", Box::new(file_appender(file, cfg.format)?)),
+        );
+        root = root.appender("file");
+    }
+    builder
+        .build(root.build(LevelFilter::Trace))
+        .map_err(|e| IndexerError::LoggingError(e.to_string()))
+}
+
+pub fn file_appender(
+    cfg: &LogFileConfiguration,
+    format: LogFormat,
+) -> IndexerResult<RollingFileAppender> {
+    let err = |e: anyhow::Error| IndexerError::LoggingError(format!("{}:{}", cfg.path, e));
+    let roller: Box<dyn Roll> = if cfg.retention == 0 {
+        Box::new(DeleteRoller::new())
+    } else {
+        let pattern = match cfg.compress {
+            true => format!("{}.{{}}.gz", cfg.path),
+            false => format!("{}.{{}}", cfg.path),
+        };
+        Box::new(
+            FixedWindowRoller::builder()
+                .base(1)
+                .build(&pattern, cfg.retention)
+                .map_err(err)?,
+        )
+    };
+    let trigger = RotationTrigger::new(cfg);
+    RollingFileAppender::builder()
+        .encoder(encoder(format))
+        .build(
+            &cfg.path,
+            Box::new(CompoundPolicy::new(Box::new(trigger), roller)),
+        )
+        .map_err(|e| IndexerError::LoggingError(format!("{}:{}", cfg.path, e)))
+}
+
+// by size or by age,the age counts from the creation of the file,or the start when unknown
+#[derive(Debug)]
+struct RotationTrigger {
+    max_size: u64,
+    interval: Option<Duration>,
+    next: Mutex<SystemTime>,
+}
+
+impl RotationTrigger {
+    fn new(cfg: &LogFileConfiguration) -> Self {
+        let interval = Some(Duration::from_secs(cfg.rotate_interval_secs)).filter(|v| !v.is_zero());
+        let created = std::fs::metadata(&cfg.path)
+            .and_then(|v| v.created())
+            .unwrap_or_else(|_| SystemTime::now());
+        Self {
+            max_size: cfg.max_size_mb * 1024 * 1024,
+            interval,
+            next: Mutex::new(created + interval.unwrap_or_default()),
+        }
+    }
+}
+
+impl Trigger for RotationTrigger {
+    fn trigger(&self, file: &LogFile) -> anyhow::Result<bool> {
+        if self.max_size > 0 && file.len_estimate() > self.max_size {
+            return Ok(true);
+        }
+        let Some(interval) = self.interval else {
+            return Ok(false);
+        };
+        let mut next = self.next.lock().unwrap();
+        let now = SystemTime::now();
+        if now < *next {
+            return Ok(false);
+        }
+        *next = now + interval;
+        // an empty file is kept
+        Ok(file.len_estimate() > 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use log4rs::append::Append;
+    use log4rs::encode::writer::simple::SimpleWriter;
+
+    #[test]
+    pub fn test_file_rotation() {
+        let dir = "./test_file_rotation";
+        let _ = std::fs::remove_dir_all(dir);
+        let cfg = LogFileConfiguration {
+            path: format!("{}/indexer.log", dir),
+            max_size_mb: 0,
+            rotate_interval_secs: 1,
+            retention: 2,
+            compress: false,
+        };
+        let appender = file_appender(&cfg, LogFormat::Text).unwrap();
+        let append = |msg: &str| {
+            appender
+                .append(&Record::builder().args(format_args!("{}", msg)).build())
+                .unwrap();
+            appender.flush();
+        };
+        append("first");
+        for round in ["second", "third", "fourth"] {
+            std::thread::sleep(Duration::from_millis(1100));
+            append(round);
+        }
+        let read = |name: &str| std::fs::read_to_string(format!("{}/{}", dir, name)).unwrap();
+        let exists = |name: &str| std::path::Path::new(&format!("{}/{}", dir, name)).exists();
+        // the first record after the window ended is the last one of the rotated file,
+        // the next record opens a new file
+        assert!(!exists("indexer.log"));
+        assert!(read("indexer.log.1").contains("fourth"));
+        assert!(read("indexer.log.2").contains("third"));
+        assert!(!exists("indexer.log.3"));
+
+        let cfg = LogConfiguration {
+            file: Some(cfg),
+            ..Default::default()
+        };
+        logging_config(&cfg).unwrap();
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    pub async fn test_json_encoder() {
+        let encode = || {
+            let mut out = SimpleWriter(vec![]);
+            JsonEncoder
+                .encode(
+                    &mut out,
+                    &Record::builder()
+                        .level(log::Level::Warn)
+                        .target("indexer_sdk::processor")
+                        .args(format_args!("tx seen"))
+                        .build(),
+                )
+                .unwrap();
+            let line = String::from_utf8(out.0).unwrap();
+            assert!(line.ends_with('\n'));
+            serde_json::from_str::<Map<String, Value>>(&line).unwrap()
+        };
+        let line = encode();
+        assert_eq!(line["level"], "WARN");
+        assert_eq!(line["target"], "indexer_sdk::processor");
+        assert_eq!(line["message"], "tx seen");
+        assert!(!line.contains_key("component"));
+
+        let inner = with_log_fields(vec![("component", "processor".into())], async {
+            with_log_fields(vec![("event", "NewTxComing".into())], async {
+                set_log_field("txid", "ab");
+                set_log_field("height", 800_000);
+                encode()
+            })
+            .await
+        })
+        .await;
+        assert_eq!(inner["component"], "processor");
+        assert_eq!(inner["event"], "NewTxComing");
+        assert_eq!(inner["txid"], "ab");
+        assert_eq!(inner["height"], 800_000);
+        assert_eq!(log_fields(), Map::new());
+        assert_eq!(
+            component_field("indexer_sdk::processor::common::IndexerProcessorImpl<a::b::C>"),
+            "IndexerProcessorImpl"
+        );
+    }
+}
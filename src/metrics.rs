@@ -0,0 +1,403 @@
+use crate::client::event::ClientEvent;
+use crate::error::{IndexerError, IndexerResult};
+use crate::net::http::{serve as serve_http, HttpResponse};
+use log::info;
+use once_cell::sync::Lazy;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::net::TcpListener;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+
+// seconds,the ones of the prometheus client libraries
+const BUCKETS: [f64; 12] = [
+    0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+// the receipts waiting for their delivery,the ones older than LAG_MAX_AGE are dropped once
+// there are more,e.g. txs the processor had seen or a filter skipped
+const MAX_PENDING_RECEIPTS: usize = 100_000;
+const LAG_MAX_AGE: Duration = Duration::from_secs(600);
+// the quantiles of the lag are the ones of the last LAG_WINDOW deliveries
+const LAG_WINDOW: usize = 4096;
+
+pub static METRICS: Lazy<Metrics> = Lazy::new(Metrics::new);
+
+// none once the channel is gone,the gauge is dropped then
+type Gauge = Box<dyn Fn() -> Option<usize> + Send + Sync>;
+
+pub struct Metrics {
+    pub zmq_messages: Counter,
+    pub events_dispatched: Counter,
+    pub rpc_latency: Histogram,
+    pub storage_write_latency: Histogram,
+    pub event_lag: Summary,
+    channels: Mutex<BTreeMap<String, Gauge>>,
+    receipts: Mutex<HashMap<String, Instant>>,
+}
+
+impl Metrics {
+    fn new() -> Self {
+        Self {
+            zmq_messages: Counter::new(
+                "indexer_zmq_messages_total",
+                "zmq messages received",
+                "topic",
+            ),
+            events_dispatched: Counter::new(
+                "indexer_events_dispatched_total",
+                "events handled by the processor",
+                "event",
+            ),
+            rpc_latency: Histogram::new(
+                "indexer_rpc_latency_seconds",
+                "latency of the rpc calls,per attempt",
+                "method",
+            ),
+            storage_write_latency: Histogram::new(
+                "indexer_storage_write_latency_seconds",
+                "latency of the storage writes",
+                "op",
+            ),
+            event_lag: Summary::new(
+                "indexer_event_lag_seconds",
+                "time from the zmq receipt of a tx or block to its delivery to a client",
+            ),
+            channels: Default::default(),
+            receipts: Default::default(),
+        }
+    }
+
+    // depth is read on every scrape,a channel registered again under the same name replaces
+    // the previous one
+    pub fn register_channel(
+        &self,
+        name: &str,
+        depth: impl Fn() -> Option<usize> + Send + Sync + 'static,
+    ) {
+        self.channels
+            .lock()
+            .unwrap()
+            .insert(name.to_string(), Box::new(depth));
+    }
+
+    // e.g. tx:<txid> or block:<hash>,the first receipt counts
+    pub fn received(&self, key: String) {
+        let now = Instant::now();
+        let mut receipts = self.receipts.lock().unwrap();
+        if receipts.len() >= MAX_PENDING_RECEIPTS {
+            receipts.retain(|_, v| now.duration_since(*v) < LAG_MAX_AGE);
+            if receipts.len() >= MAX_PENDING_RECEIPTS {
+                return;
+            }
+        }
+        receipts.entry(key).or_insert(now);
+    }
+
+    // the first client taking the event ends its lag
+    pub fn delivered(&self, event: &ClientEvent) {
+        let key = match event {
+            ClientEvent::Transaction(v) => format!("tx:{}", v.tx.txid()),
+            ClientEvent::RichTransaction(v) => format!("tx:{}", v.tx.tx.txid()),
+            ClientEvent::BlockConnected(_, hash, _) => format!("block:{}", hash),
+            ClientEvent::Batch(events) => {
+                events.iter().for_each(|v| self.delivered(v));
+                return;
+            }
+            _ => return,
+        };
+        let received = self.receipts.lock().unwrap().remove(&key);
+        if let Some(received) = received {
+            self.event_lag.observe(received.elapsed());
+        }
+    }
+
+    // the prometheus text format
+    pub fn render(&self) -> String {
+        let mut ret = String::new();
+        self.zmq_messages.render(&mut ret);
+        self.events_dispatched.render(&mut ret);
+        self.rpc_latency.render(&mut ret);
+        self.storage_write_latency.render(&mut ret);
+        self.event_lag.render(&mut ret);
+        let mut channels = self.channels.lock().unwrap();
+        let depths: Vec<(String, usize)> = channels
+            .iter()
+            .filter_map(|(name, depth)| depth().map(|v| (name.clone(), v)))
+            .collect();
+        channels.retain(|name, _| depths.iter().any(|(v, _)| v == name));
+        header(
+            &mut ret,
+            "indexer_channel_depth",
+            "events waiting in the channel",
+            "gauge",
+        );
+        for (name, depth) in depths {
+            let _ = writeln!(
+                ret,
+                "indexer_channel_depth{{channel=\"{}\"}} {}",
+                escape(&name),
+                depth
+            );
+        }
+        ret
+    }
+}
+
+pub struct Counter {
+    name: &'static str,
+    help: &'static str,
+    label: &'static str,
+    values: Mutex<BTreeMap<String, u64>>,
+}
+
+impl Counter {
+    fn new(name: &'static str, help: &'static str, label: &'static str) -> Self {
+        Self {
+            name,
+            help,
+            label,
+            values: Default::default(),
+        }
+    }
+
+    pub fn inc(&self, label: &str) {
+        *self
+            .values
+            .lock()
+            .unwrap()
+            .entry(label.to_string())
+            .or_default() += 1;
+    }
+
+    fn render(&self, out: &mut String) {
+        header(out, self.name, self.help, "counter");
+        for (label, value) in self.values.lock().unwrap().iter() {
+            let _ = writeln!(
+                out,
+                "{}{{{}=\"{}\"}} {}",
+                self.name,
+                self.label,
+                escape(label),
+                value
+            );
+        }
+    }
+}
+
+#[derive(Clone, Default)]
+struct Observations {
+    buckets: [u64; BUCKETS.len()],
+    count: u64,
+    sum: f64,
+}
+
+pub struct Histogram {
+    name: &'static str,
+    help: &'static str,
+    label: &'static str,
+    values: Mutex<BTreeMap<String, Observations>>,
+}
+
+impl Histogram {
+    fn new(name: &'static str, help: &'static str, label: &'static str) -> Self {
+        Self {
+            name,
+            help,
+            label,
+            values: Default::default(),
+        }
+    }
+
+    pub fn observe(&self, label: &str, elapsed: Duration) {
+        let seconds = elapsed.as_secs_f64();
+        let mut values = self.values.lock().unwrap();
+        let v = values.entry(label.to_string()).or_default();
+        if let Some(i) = BUCKETS.iter().position(|le| seconds <= *le) {
+            v.buckets[i] += 1;
+        }
+        v.count += 1;
+        v.sum += seconds;
+    }
+
+    // observes the duration of f
+    pub fn time<T>(&self, label: &str, f: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let ret = f();
+        self.observe(label, start.elapsed());
+        ret
+    }
+
+    fn render(&self, out: &mut String) {
+        header(out, self.name, self.help, "histogram");
+        for (label, v) in self.values.lock().unwrap().iter() {
+            let label = format!("{}=\"{}\"", self.label, escape(label));
+            let mut cumulative = 0;
+            for (le, n) in BUCKETS.iter().zip(v.buckets) {
+                cumulative += n;
+                let _ = writeln!(
+                    out,
+                    "{}_bucket{{{},le=\"{}\"}} {}",
+                    self.name, label, le, cumulative
+                );
+            }
+            let _ = writeln!(
+                out,
+                "{}_bucket{{{},le=\"+Inf\"}} {}",
+                self.name, label, v.count
+            );
+            let _ = writeln!(out, "{}_sum{{{}}} {}", self.name, label, v.sum);
+            let _ = writeln!(out, "{}_count{{{}}} {}", self.name, label, v.count);
+        }
+    }
+}
+
+// the quantiles over a window of the latest observations,the sum and count over all of them
+pub struct Summary {
+    name: &'static str,
+    help: &'static str,
+    values: Mutex<(VecDeque<f64>, u64, f64)>,
+}
+
+impl Summary {
+    fn new(name: &'static str, help: &'static str) -> Self {
+        Self {
+            name,
+            help,
+            values: Mutex::new((VecDeque::with_capacity(LAG_WINDOW), 0, 0.0)),
+        }
+    }
+
+    pub fn observe(&self, elapsed: Duration) {
+        let seconds = elapsed.as_secs_f64();
+        let mut values = self.values.lock().unwrap();
+        if values.0.len() == LAG_WINDOW {
+            values.0.pop_front();
+        }
+        values.0.push_back(seconds);
+        values.1 += 1;
+        values.2 += seconds;
+    }
+
+    // nearest rank,none without observations
+    pub fn quantile(&self, q: f64) -> Option<f64> {
+        let mut window: Vec<f64> = self.values.lock().unwrap().0.iter().copied().collect();
+        if window.is_empty() {
+            return None;
+        }
+        window.sort_by(f64::total_cmp);
+        let rank = ((q * window.len() as f64).ceil() as usize).clamp(1, window.len());
+        Some(window[rank - 1])
+    }
+
+    fn render(&self, out: &mut String) {
+        header(out, self.name, self.help, "summary");
+        for q in [0.5, 0.99] {
+            if let Some(v) = self.quantile(q) {
+                let _ = writeln!(out, "{}{{quantile=\"{}\"}} {}", self.name, q, v);
+            }
+        }
+        let (_, count, sum) = *self.values.lock().unwrap();
+        let _ = writeln!(out, "{}_sum {}", self.name, sum);
+        let _ = writeln!(out, "{}_count {}", self.name, count);
+    }
+}
+
+fn header(out: &mut String, name: &str, help: &str, kind: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+fn escape(label: &str) -> String {
+    label
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+// serves METRICS on /metrics until the exit signal
+pub async fn serve(listen: &str, exit: watch::Receiver<()>) -> IndexerResult<JoinHandle<()>> {
+    let listener = TcpListener::bind(listen)
+        .await
+        .map_err(|e| IndexerError::InvalidConfiguration(format!("metrics.listen:{}", e)))?;
+    info!("metrics exporter listen on {}", listen);
+    Ok(serve_http(listener, exit, |path: String| async move {
+        match path.as_str() {
+            "/metrics" => HttpResponse {
+                status: 200,
+                body: METRICS.render().into_bytes(),
+            },
+            _ => HttpResponse {
+                status: 404,
+                body: b"not found".to_vec(),
+            },
+        }
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoincore_rpc::bitcoin::hashes::Hash;
+    use bitcoincore_rpc::bitcoin::BlockHash;
+
+    #[test]
+    pub fn test_render() {
+        let metrics = Metrics::new();
+        metrics.zmq_messages.inc("rawtx");
+        metrics.zmq_messages.inc("rawtx");
+        metrics.zmq_messages.inc("raw\"block");
+        metrics
+            .rpc_latency
+            .observe("getrawtransaction", Duration::from_millis(20));
+        metrics
+            .rpc_latency
+            .observe("getrawtransaction", Duration::from_secs(20));
+        metrics.register_channel("processor", || Some(3));
+        metrics.register_channel("closed", || None);
+        let text = metrics.render();
+        for line in [
+            "# TYPE indexer_zmq_messages_total counter",
+            "indexer_zmq_messages_total{topic=\"rawtx\"} 2",
+            "indexer_zmq_messages_total{topic=\"raw\\\"block\"} 1",
+            "# TYPE indexer_events_dispatched_total counter",
+            "indexer_rpc_latency_seconds_bucket{method=\"getrawtransaction\",le=\"0.01\"} 0",
+            "indexer_rpc_latency_seconds_bucket{method=\"getrawtransaction\",le=\"0.025\"} 1",
+            "indexer_rpc_latency_seconds_bucket{method=\"getrawtransaction\",le=\"10\"} 1",
+            "indexer_rpc_latency_seconds_bucket{method=\"getrawtransaction\",le=\"+Inf\"} 2",
+            "indexer_rpc_latency_seconds_count{method=\"getrawtransaction\"} 2",
+            "indexer_channel_depth{channel=\"processor\"} 3",
+        ] {
+            assert!(text.lines().any(|v| v == line), "{} not in\n{}", line, text);
+        }
+        assert!(!text.contains("closed"));
+        assert_eq!(metrics.channels.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    pub fn test_event_lag() {
+        let metrics = Metrics::new();
+        assert_eq!(metrics.event_lag.quantile(0.5), None);
+        for ms in 1..=100 {
+            metrics.event_lag.observe(Duration::from_millis(ms));
+        }
+        assert_eq!(metrics.event_lag.quantile(0.5), Some(0.05));
+        assert_eq!(metrics.event_lag.quantile(0.99), Some(0.099));
+
+        let hash = BlockHash::all_zeros();
+        metrics.received(format!("block:{}", hash));
+        metrics.delivered(&ClientEvent::Batch(vec![ClientEvent::BlockConnected(
+            1,
+            hash,
+            vec![],
+        )]));
+        // a second client gets it as well
+        metrics.delivered(&ClientEvent::BlockConnected(1, hash, vec![]));
+        assert!(metrics.receipts.lock().unwrap().is_empty());
+        let text = metrics.render();
+        assert!(text.contains("indexer_event_lag_seconds_count 101\n"));
+        assert!(text.contains("indexer_event_lag_seconds{quantile=\"0.99\"} "));
+    }
+}
@@ -0,0 +1,188 @@
+use crate::error::{IndexerError, IndexerResult};
+use bitcoincore_rpc::jsonrpc;
+use bitcoincore_rpc::jsonrpc::simple_http::{self, SimpleHttpTransport};
+use bitcoincore_rpc::jsonrpc::{Request, Response, Transport};
+use log::info;
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::RwLock;
+use std::time::{Duration, SystemTime};
+
+// authenticates with the .cookie bitcoind writes on every start.
+// the file is reread once it changes or the node rejects the credentials
+pub struct CookieTransport {
+    url: String,
+    path: PathBuf,
+    timeout: Option<Duration>,
+    // modification time of the cookie the transport was built with
+    state: RwLock<Option<(SystemTime, SimpleHttpTransport)>>,
+}
+
+impl CookieTransport {
+    pub fn new(url: &str, path: impl Into<PathBuf>) -> IndexerResult<Self> {
+        simple_http::Builder::new()
+            .url(url)
+            .map_err(|e| IndexerError::InvalidConfiguration(format!("rpc url {}:{}", url, e)))?;
+        Ok(Self {
+            url: url.to_string(),
+            path: path.into(),
+            timeout: None,
+            state: RwLock::new(None),
+        })
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn client(self) -> bitcoincore_rpc::Client {
+        bitcoincore_rpc::Client::from_jsonrpc(jsonrpc::Client::with_transport(self))
+    }
+
+    fn transport(&self, reload: bool) -> Result<SimpleHttpTransport, jsonrpc::Error> {
+        let modified = std::fs::metadata(&self.path)
+            .and_then(|v| v.modified())
+            .map_err(transport_error)?;
+        if let Some((at, transport)) = self.state.read().unwrap().as_ref() {
+            if *at == modified && !reload {
+                return Ok(transport.clone());
+            }
+        }
+        let cookie = std::fs::read_to_string(&self.path).map_err(transport_error)?;
+        let mut builder = simple_http::Builder::new()
+            .url(&self.url)?
+            .cookie_auth(cookie.trim());
+        if let Some(timeout) = self.timeout {
+            builder = builder.timeout(timeout);
+        }
+        let transport = builder.build();
+        info!("loaded rpc cookie from {}", self.path.display());
+        *self.state.write().unwrap() = Some((modified, transport.clone()));
+        Ok(transport)
+    }
+
+    fn send<T>(
+        &self,
+        f: impl Fn(&SimpleHttpTransport) -> Result<T, jsonrpc::Error>,
+    ) -> Result<T, jsonrpc::Error> {
+        match f(&self.transport(false)?) {
+            // rotated within the resolution of the modification time
+            Err(e) if is_unauthorized(&e) => f(&self.transport(true)?),
+            ret => ret,
+        }
+    }
+}
+
+fn transport_error(e: std::io::Error) -> jsonrpc::Error {
+    jsonrpc::Error::Transport(Box::new(e))
+}
+
+fn is_unauthorized(e: &jsonrpc::Error) -> bool {
+    matches!(e,jsonrpc::Error::Transport(e)
+        if matches!(e.downcast_ref::<simple_http::Error>(),Some(simple_http::Error::HttpErrorCode(401))))
+}
+
+impl Transport for CookieTransport {
+    fn send_request(&self, request: Request) -> Result<Response, jsonrpc::Error> {
+        self.send(|v| v.send_request(request.clone()))
+    }
+
+    fn send_batch(&self, requests: &[Request]) -> Result<Vec<Response>, jsonrpc::Error> {
+        self.send(|v| v.send_batch(requests))
+    }
+
+    fn fmt_target(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.url)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoincore_rpc::RpcApi;
+    use serde_json::{json, Value};
+    use std::sync::{Arc, Mutex};
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpListener;
+
+    #[tokio::test(flavor = "multi_thread")]
+    pub async fn test_cookie_rotation() {
+        let dir = std::env::temp_dir().join("test_cookie_rotation");
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(".cookie");
+        std::fs::write(&path, "__cookie__:first\n").unwrap();
+
+        // only the current cookie is accepted
+        let accepted = Arc::new(Mutex::new("first".to_string()));
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let server_accepted = accepted.clone();
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                let accepted = server_accepted.clone();
+                tokio::spawn(async move {
+                    let mut stream = BufReader::new(stream);
+                    loop {
+                        let (mut len, mut auth) = (0, String::new());
+                        loop {
+                            let mut line = String::new();
+                            if stream.read_line(&mut line).await.unwrap_or(0) == 0 {
+                                return;
+                            }
+                            if line == "\r\n" {
+                                break;
+                            }
+                            let lower = line.to_lowercase();
+                            if let Some(v) = lower.strip_prefix("content-length:") {
+                                len = v.trim().parse().unwrap();
+                            }
+                            if lower.starts_with("authorization:") {
+                                auth = line.trim().to_string();
+                            }
+                        }
+                        let mut body = vec![0u8; len];
+                        stream.read_exact(&mut body).await.unwrap();
+                        let request: Value = serde_json::from_slice(&body).unwrap();
+                        let expected = match accepted.lock().unwrap().as_str() {
+                            // base64 of __cookie__:first and __cookie__:second
+                            "first" => "Basic X19jb29raWVfXzpmaXJzdA==",
+                            _ => "Basic X19jb29raWVfXzpzZWNvbmQ=",
+                        };
+                        let response = if auth.ends_with(expected) {
+                            let body = json!({"result": 7, "error": null, "id": request["id"]})
+                                .to_string();
+                            format!(
+                                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{}",
+                                body.len(),
+                                body
+                            )
+                        } else {
+                            "HTTP/1.1 401 Unauthorized\r\nContent-Length: 0\r\n\r\n".to_string()
+                        };
+                        stream
+                            .get_mut()
+                            .write_all(response.as_bytes())
+                            .await
+                            .unwrap();
+                    }
+                });
+            }
+        });
+
+        let client = Arc::new(CookieTransport::new(&url, &path).unwrap().client());
+        let get_count = |client: Arc<bitcoincore_rpc::Client>| {
+            tokio::task::spawn_blocking(move || client.get_block_count())
+        };
+        assert_eq!(get_count(client.clone()).await.unwrap().unwrap(), 7);
+
+        // bitcoind restarted with a new cookie
+        *accepted.lock().unwrap() = "second".to_string();
+        std::fs::write(&path, "__cookie__:second").unwrap();
+        assert_eq!(get_count(client.clone()).await.unwrap().unwrap(), 7);
+
+        std::fs::remove_file(&path).unwrap();
+        assert!(get_count(client).await.unwrap().is_err());
+    }
+}
@@ -0,0 +1,266 @@
+use crate::error::{IndexerError, IndexerResult};
+use crate::net::{unsupported, ChainDataSource};
+use crate::types::transaction::{MempoolEntry, MempoolListing};
+use async_trait::async_trait;
+use bitcoincore_rpc::bitcoin::block::Header;
+use bitcoincore_rpc::bitcoin::consensus::deserialize;
+use bitcoincore_rpc::bitcoin::hashes::{sha256, Hash};
+use bitcoincore_rpc::bitcoin::{Block, BlockHash, Script, Transaction, Txid};
+use log::warn;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::sync::atomic::{AtomicU64, Ordering};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio::sync::Mutex;
+
+// a confirmed (height > 0) or mempool (height <= 0) tx touching a script
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct HistoryEntry {
+    pub tx_hash: String,
+    pub height: i64,
+}
+
+// line delimited json-rpc to an electrum server over plain tcp,connected on the first call
+// and again after a failure
+pub struct ElectrumClient {
+    addr: String,
+    conn: Mutex<Option<BufReader<TcpStream>>>,
+    next_id: AtomicU64,
+}
+
+impl ElectrumClient {
+    // tcp://host:port or host:port,ssl endpoints need a tunnel
+    pub fn new(url: &str) -> IndexerResult<Self> {
+        if url.starts_with("ssl://") {
+            return Err(IndexerError::InvalidConfiguration(format!(
+                "electrum over ssl is not supported:{}",
+                url
+            )));
+        }
+        Ok(Self {
+            addr: url.trim_start_matches("tcp://").to_string(),
+            conn: Mutex::new(None),
+            next_id: AtomicU64::new(0),
+        })
+    }
+
+    pub async fn script_history(&self, script: &Script) -> IndexerResult<Vec<HistoryEntry>> {
+        let ret = self
+            .call(
+                "blockchain.scripthash.get_history",
+                json!([script_hash(script)]),
+            )
+            .await?;
+        Ok(serde_json::from_value(ret)?)
+    }
+
+    async fn call(&self, method: &str, params: Value) -> IndexerResult<Value> {
+        let mut conn = self.conn.lock().await;
+        let ret = self.do_call(&mut conn, method, params).await;
+        if let Err(e) = &ret {
+            if !matches!(e, IndexerError::ElectrumError(_)) {
+                warn!(
+                    "electrum {} failed,reconnect on the next call:{:?}",
+                    method, e
+                );
+                *conn = None;
+            }
+        }
+        ret
+    }
+
+    async fn do_call(
+        &self,
+        conn: &mut Option<BufReader<TcpStream>>,
+        method: &str,
+        params: Value,
+    ) -> IndexerResult<Value> {
+        if conn.is_none() {
+            *conn = Some(BufReader::new(TcpStream::connect(&self.addr).await?));
+        }
+        let stream = conn.as_mut().unwrap();
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let mut request = serde_json::to_vec(&json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": method,
+            "params": params,
+        }))?;
+        request.push(b'\n');
+        stream.get_mut().write_all(&request).await?;
+        loop {
+            let mut line = String::new();
+            if stream.read_line(&mut line).await? == 0 {
+                return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+            }
+            let response: Value = serde_json::from_str(&line)?;
+            // notifications of subscriptions carry no id
+            if response.get("id").and_then(Value::as_u64) != Some(id) {
+                continue;
+            }
+            if let Some(error) = response.get("error").filter(|v| !v.is_null()) {
+                let message = error
+                    .get("message")
+                    .and_then(Value::as_str)
+                    .map(|v| v.to_string())
+                    .unwrap_or_else(|| error.to_string());
+                return Err(IndexerError::ElectrumError(message));
+            }
+            return Ok(response.get("result").cloned().unwrap_or(Value::Null));
+        }
+    }
+}
+
+// blocks are not served by electrum,see SplitSource
+#[async_trait]
+impl ChainDataSource for ElectrumClient {
+    async fn get_transaction(&self, txid: &Txid) -> IndexerResult<Option<Transaction>> {
+        let ret = self
+            .call(
+                "blockchain.transaction.get",
+                json!([txid.to_string(), false]),
+            )
+            .await;
+        let data = match ret {
+            Ok(Value::String(v)) => hex::decode(v)?,
+            Ok(v) => {
+                return Err(IndexerError::ElectrumError(format!(
+                    "unexpected tx result:{}",
+                    v
+                )))
+            }
+            Err(IndexerError::ElectrumError(e)) if is_not_found(&e) => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        Ok(Some(deserialize(&data)?))
+    }
+
+    // electrum has no mempool entries,the txs go out without fee
+    async fn get_mempool_entry(&self, _: &Txid) -> IndexerResult<Option<MempoolEntry>> {
+        Ok(None)
+    }
+
+    async fn get_mempool_txs(&self) -> IndexerResult<Option<Vec<MempoolListing>>> {
+        Ok(None)
+    }
+
+    async fn get_block(&self, _: &BlockHash) -> IndexerResult<Option<Block>> {
+        unsupported("electrum get_block")
+    }
+
+    async fn get_block_hash(&self, height: u32) -> IndexerResult<Option<BlockHash>> {
+        let ret = self.call("blockchain.block.header", json!([height])).await;
+        let data = match ret {
+            Ok(Value::String(v)) => hex::decode(v)?,
+            Ok(v) => {
+                return Err(IndexerError::ElectrumError(format!(
+                    "unexpected header result:{}",
+                    v
+                )))
+            }
+            Err(IndexerError::ElectrumError(e)) if is_not_found(&e) => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let header: Header = deserialize(&data)?;
+        Ok(Some(header.block_hash()))
+    }
+
+    async fn get_block_height(&self, _: &BlockHash) -> IndexerResult<Option<u32>> {
+        unsupported("electrum get_block_height")
+    }
+
+    async fn get_tip_height(&self) -> IndexerResult<u32> {
+        let ret = self.call("blockchain.headers.subscribe", json!([])).await?;
+        ret.get("height")
+            .and_then(Value::as_u64)
+            .map(|v| v as u32)
+            .ok_or_else(|| IndexerError::ElectrumError(format!("unexpected tip:{}", ret)))
+    }
+
+    // btc/kB,negative when the server has no estimate
+    async fn estimate_fee(&self, target_blocks: u16) -> IndexerResult<Option<f64>> {
+        let ret = self
+            .call("blockchain.estimatefee", json!([target_blocks]))
+            .await?;
+        Ok(ret.as_f64().filter(|v| *v > 0.0).map(|v| v * 100_000.0))
+    }
+}
+
+// the key electrum indexes scripts by:the reversed sha256 of the script,in hex
+pub fn script_hash(script: &Script) -> String {
+    let mut hash = sha256::Hash::hash(script.as_bytes()).to_byte_array();
+    hash.reverse();
+    hex::encode(hash)
+}
+
+fn is_not_found(message: &str) -> bool {
+    let message = message.to_lowercase();
+    message.contains("not found") || message.contains("no such") || message.contains("out of range")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoincore_rpc::bitcoin::absolute::LockTime;
+    use bitcoincore_rpc::bitcoin::consensus::serialize;
+    use bitcoincore_rpc::bitcoin::ScriptBuf;
+    use tokio::net::TcpListener;
+
+    #[test]
+    pub fn test_script_hash() {
+        let script =
+            ScriptBuf::from_hex("76a91462e907b15cbf27d5425399ebf6f0fb50ebb88f1888ac").unwrap();
+        assert_eq!(
+            script_hash(&script),
+            "8b01df4e368ea28f8dc0423bcf7a4923e3a12d307c875e47a0cfbf90b5c39161"
+        );
+    }
+
+    #[tokio::test]
+    pub async fn test_electrum_get_transaction() {
+        let tx = Transaction {
+            version: 2,
+            lock_time: LockTime::ZERO,
+            input: vec![],
+            output: vec![],
+        };
+        let raw = hex::encode(serialize(&tx));
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut stream = BufReader::new(stream);
+            for i in 0..2 {
+                let mut line = String::new();
+                stream.read_line(&mut line).await.unwrap();
+                let request: Value = serde_json::from_str(&line).unwrap();
+                let id = request["id"].clone();
+                let response = if i == 0 {
+                    // a notification first,then the answer
+                    format!(
+                        "{}\n{}\n",
+                        json!({"jsonrpc": "2.0", "method": "blockchain.headers.subscribe", "params": []}),
+                        json!({"jsonrpc": "2.0", "id": id, "result": raw})
+                    )
+                } else {
+                    format!(
+                        "{}\n",
+                        json!({"jsonrpc": "2.0", "id": id, "error": {"code": 2, "message": "daemon error: No such mempool or blockchain transaction"}})
+                    )
+                };
+                stream
+                    .get_mut()
+                    .write_all(response.as_bytes())
+                    .await
+                    .unwrap();
+            }
+        });
+
+        let client = ElectrumClient::new(&format!("tcp://{}", addr)).unwrap();
+        let found = client.get_transaction(&tx.txid()).await.unwrap();
+        assert_eq!(found, Some(tx.clone()));
+        assert_eq!(client.get_transaction(&tx.txid()).await.unwrap(), None);
+        assert!(ElectrumClient::new("ssl://localhost:50002").is_err());
+    }
+}
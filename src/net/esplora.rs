@@ -0,0 +1,175 @@
+use crate::error::{IndexerError, IndexerResult};
+use crate::net::http::{get, split_url, HttpResponse};
+use crate::net::ChainDataSource;
+use crate::types::transaction::{MempoolEntry, MempoolListing};
+use async_trait::async_trait;
+use bitcoincore_rpc::bitcoin::consensus::deserialize;
+use bitcoincore_rpc::bitcoin::{Block, BlockHash, Transaction, Txid};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::str::FromStr;
+
+// the rest api of esplora (blockstream.info,mempool.space),over plain http
+pub struct EsploraClient {
+    host: String,
+    prefix: String,
+}
+
+impl EsploraClient {
+    // e.g. http://localhost:3000/api
+    pub fn new(url: &str) -> IndexerResult<Self> {
+        let (host, prefix) = split_url(url)?;
+        Ok(Self { host, prefix })
+    }
+
+    // none on 404
+    async fn get(&self, path: &str) -> IndexerResult<Option<Vec<u8>>> {
+        let HttpResponse { status, body } = get(&self.host, &(self.prefix.clone() + path)).await?;
+        match status {
+            200 => Ok(Some(body)),
+            404 => Ok(None),
+            // esplora answers unknown hashes and heights with a 400 sometimes
+            400 if String::from_utf8_lossy(&body).contains("not found") => Ok(None),
+            _ => Err(IndexerError::EsploraError(format!(
+                "{} returned {}:{}",
+                path,
+                status,
+                String::from_utf8_lossy(&body)
+            ))),
+        }
+    }
+
+    async fn get_text(&self, path: &str) -> IndexerResult<Option<String>> {
+        let ret = self.get(path).await?;
+        Ok(ret.map(|v| String::from_utf8_lossy(&v).trim().to_string()))
+    }
+}
+
+fn invalid(what: &str, v: impl std::fmt::Display) -> IndexerError {
+    IndexerError::EsploraError(format!("invalid {}:{}", what, v))
+}
+
+#[async_trait]
+impl ChainDataSource for EsploraClient {
+    async fn get_transaction(&self, txid: &Txid) -> IndexerResult<Option<Transaction>> {
+        let ret = self.get(&format!("/tx/{}/raw", txid)).await?;
+        Ok(ret.map(|v| deserialize(&v)).transpose()?)
+    }
+
+    // esplora has no mempool entry time,the txs go out without fee
+    async fn get_mempool_entry(&self, _: &Txid) -> IndexerResult<Option<MempoolEntry>> {
+        Ok(None)
+    }
+
+    // the mempool is listed without entry times and parents
+    async fn get_mempool_txs(&self) -> IndexerResult<Option<Vec<MempoolListing>>> {
+        Ok(None)
+    }
+
+    async fn get_block(&self, hash: &BlockHash) -> IndexerResult<Option<Block>> {
+        let ret = self.get(&format!("/block/{}/raw", hash)).await?;
+        Ok(ret.map(|v| deserialize(&v)).transpose()?)
+    }
+
+    async fn get_block_hash(&self, height: u32) -> IndexerResult<Option<BlockHash>> {
+        let ret = self.get_text(&format!("/block-height/{}", height)).await?;
+        ret.map(|v| BlockHash::from_str(&v).map_err(|_| invalid("block hash", &v)))
+            .transpose()
+    }
+
+    async fn get_block_height(&self, hash: &BlockHash) -> IndexerResult<Option<u32>> {
+        let Some(data) = self.get(&format!("/block/{}", hash)).await? else {
+            return Ok(None);
+        };
+        let info: Value = serde_json::from_slice(&data)?;
+        let height = info
+            .get("height")
+            .and_then(Value::as_u64)
+            .ok_or_else(|| invalid("block", &info))?;
+        Ok(Some(height as u32))
+    }
+
+    async fn get_tip_height(&self) -> IndexerResult<u32> {
+        let ret = self
+            .get_text("/blocks/tip/height")
+            .await?
+            .unwrap_or_default();
+        ret.parse().map_err(|_| invalid("tip height", &ret))
+    }
+
+    async fn estimate_fee(&self, target_blocks: u16) -> IndexerResult<Option<f64>> {
+        let Some(data) = self.get("/fee-estimates").await? else {
+            return Ok(None);
+        };
+        let estimates: HashMap<String, f64> = serde_json::from_slice(&data)?;
+        Ok(pick_estimate(&estimates, target_blocks))
+    }
+}
+
+// the estimates cover a few targets only,the nearest one at or below the target is taken
+fn pick_estimate(estimates: &HashMap<String, f64>, target_blocks: u16) -> Option<f64> {
+    estimates
+        .iter()
+        .filter_map(|(k, v)| Some((k.parse::<u16>().ok()?, *v)))
+        .filter(|(k, _)| *k <= target_blocks.max(1))
+        .max_by_key(|(k, _)| *k)
+        .map(|(_, v)| v)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoincore_rpc::bitcoin::absolute::LockTime;
+    use bitcoincore_rpc::bitcoin::consensus::serialize;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    #[test]
+    pub fn test_pick_estimate() {
+        let estimates: HashMap<String, f64> = [("1", 20.0), ("3", 10.0), ("6", 5.0)]
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v))
+            .collect();
+        assert_eq!(pick_estimate(&estimates, 1), Some(20.0));
+        assert_eq!(pick_estimate(&estimates, 5), Some(10.0));
+        assert_eq!(pick_estimate(&estimates, 144), Some(5.0));
+        assert_eq!(pick_estimate(&estimates, 0), Some(20.0));
+        assert_eq!(pick_estimate(&HashMap::new(), 6), None);
+    }
+
+    #[tokio::test]
+    pub async fn test_esplora_get_transaction() {
+        let tx = Transaction {
+            version: 2,
+            lock_time: LockTime::ZERO,
+            input: vec![],
+            output: vec![],
+        };
+        let raw = serialize(&tx);
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let txid = tx.txid();
+        tokio::spawn(async move {
+            for _ in 0..2 {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut buf = vec![0u8; 1024];
+                let n = stream.read(&mut buf).await.unwrap();
+                let request = String::from_utf8_lossy(&buf[..n]).to_string();
+                let mut response = if request.starts_with(&format!("GET /api/tx/{}/raw", txid)) {
+                    format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n", raw.len()).into_bytes()
+                } else {
+                    b"HTTP/1.1 404 Not Found\r\n\r\nTransaction not found".to_vec()
+                };
+                if response.starts_with(b"HTTP/1.1 200") {
+                    response.extend_from_slice(&raw);
+                }
+                stream.write_all(&response).await.unwrap();
+            }
+        });
+
+        let client = EsploraClient::new(&format!("http://{}/api", addr)).unwrap();
+        assert_eq!(client.get_transaction(&txid).await.unwrap(), Some(tx));
+        let block = BlockHash::from_str(&"00".repeat(32)).unwrap();
+        assert_eq!(client.get_block(&block).await.unwrap(), None);
+    }
+}
@@ -0,0 +1,224 @@
+use crate::error::{IndexerError, IndexerResult};
+use log::warn;
+use std::future::Future;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+
+const MAX_REQUEST_SIZE: usize = 8 * 1024;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub(crate) struct HttpResponse {
+    pub status: u16,
+    pub body: Vec<u8>,
+}
+
+// host:port and the path prefix of a plain http base url
+pub(crate) fn split_url(url: &str) -> IndexerResult<(String, String)> {
+    let rest = url.strip_prefix("http://").ok_or_else(|| {
+        IndexerError::InvalidConfiguration(format!("only http urls are supported:{}", url))
+    })?;
+    let (host, path) = match rest.find('/') {
+        Some(i) => (&rest[..i], &rest[i..]),
+        None => (rest, ""),
+    };
+    let host = if host.contains(':') {
+        host.to_string()
+    } else {
+        format!("{}:80", host)
+    };
+    Ok((host, path.trim_end_matches('/').to_string()))
+}
+
+// one request per connection,the response is read until the server closes it
+pub(crate) async fn get(host: &str, path: &str) -> IndexerResult<HttpResponse> {
+    let mut stream = TcpStream::connect(host).await?;
+    let request = format!(
+        "GET {} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\nAccept-Encoding: identity\r\n\r\n",
+        path, host
+    );
+    stream.write_all(request.as_bytes()).await?;
+    let mut data = vec![];
+    stream.read_to_end(&mut data).await?;
+    parse_response(&data)
+}
+
+fn parse_response(data: &[u8]) -> IndexerResult<HttpResponse> {
+    let invalid = |v: &str| IndexerError::CodecError(format!("invalid http response:{}", v));
+    let end = data
+        .windows(4)
+        .position(|v| v == b"\r\n\r\n")
+        .ok_or_else(|| invalid("no header end"))?;
+    let head = String::from_utf8_lossy(&data[..end]).to_string();
+    let mut lines = head.split("\r\n");
+    let status = lines
+        .next()
+        .and_then(|v| v.split(' ').nth(1))
+        .and_then(|v| v.parse().ok())
+        .ok_or_else(|| invalid("status line"))?;
+    let chunked = lines.any(|v| {
+        let v = v.to_lowercase();
+        v.starts_with("transfer-encoding:") && v.contains("chunked")
+    });
+    let body = &data[end + 4..];
+    let body = if chunked {
+        decode_chunked(body).ok_or_else(|| invalid("chunked body"))?
+    } else {
+        body.to_vec()
+    };
+    Ok(HttpResponse { status, body })
+}
+
+// answers the GET requests with the response of handler(path) until the exit signal,
+// one request per connection. for scrapes and probes,not for the internet
+pub(crate) fn serve<F, Fut>(
+    listener: TcpListener,
+    mut exit: watch::Receiver<()>,
+    handler: F,
+) -> JoinHandle<()>
+where
+    F: Fn(String) -> Fut + Clone + Send + Sync + 'static,
+    Fut: Future<Output = HttpResponse> + Send,
+{
+    tokio::task::spawn(async move {
+        loop {
+            let accepted = tokio::select! {
+                _ = exit.changed() => return,
+                v = listener.accept() => v,
+            };
+            let stream = match accepted {
+                Ok((stream, _)) => stream,
+                Err(e) => {
+                    warn!("http accept failed:{}", e);
+                    continue;
+                }
+            };
+            let handler = handler.clone();
+            tokio::task::spawn(async move {
+                match tokio::time::timeout(REQUEST_TIMEOUT, respond(stream, handler)).await {
+                    Ok(Err(e)) => warn!("http request failed:{}", e),
+                    Err(_) => warn!("http request timed out"),
+                    Ok(Ok(())) => {}
+                }
+            });
+        }
+    })
+}
+
+async fn respond<F, Fut>(mut stream: TcpStream, handler: F) -> IndexerResult<()>
+where
+    F: Fn(String) -> Fut,
+    Fut: Future<Output = HttpResponse>,
+{
+    let mut data = vec![];
+    let mut buf = [0u8; 1024];
+    while !data.windows(4).any(|v| v == b"\r\n\r\n") {
+        if data.len() > MAX_REQUEST_SIZE {
+            return Err(IndexerError::CodecError(
+                "http request too large".to_string(),
+            ));
+        }
+        let n = stream.read(&mut buf).await?;
+        if n == 0 {
+            return Ok(());
+        }
+        data.extend_from_slice(&buf[..n]);
+    }
+    let head = String::from_utf8_lossy(&data).to_string();
+    let mut parts = head.lines().next().unwrap_or_default().split(' ');
+    let response = match (parts.next(), parts.next()) {
+        (Some("GET"), Some(path)) => {
+            let path = path.split('?').next().unwrap_or_default();
+            handler(path.to_string()).await
+        }
+        _ => HttpResponse {
+            status: 405,
+            body: b"method not allowed".to_vec(),
+        },
+    };
+    let reason = match response.status {
+        200 => "OK",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        503 => "Service Unavailable",
+        _ => "",
+    };
+    let head = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: text/plain; version=0.0.4; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        response.status,
+        reason,
+        response.body.len()
+    );
+    stream.write_all(head.as_bytes()).await?;
+    stream.write_all(&response.body).await?;
+    stream.shutdown().await?;
+    Ok(())
+}
+
+// size(hex)\r\n data\r\n ... 0\r\n\r\n
+fn decode_chunked(mut data: &[u8]) -> Option<Vec<u8>> {
+    let mut ret = vec![];
+    loop {
+        let line_end = data.windows(2).position(|v| v == b"\r\n")?;
+        let size = String::from_utf8_lossy(&data[..line_end]);
+        let size = usize::from_str_radix(size.split(';').next()?.trim(), 16).ok()?;
+        data = &data[line_end + 2..];
+        if size == 0 {
+            return Some(ret);
+        }
+        ret.extend_from_slice(data.get(..size)?);
+        data = data.get(size + 2..)?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_parse_response() {
+        let ret = parse_response(b"HTTP/1.1 200 OK\r\nContent-Length: 3\r\n\r\nabc").unwrap();
+        assert_eq!((ret.status, ret.body), (200, b"abc".to_vec()));
+
+        let ret = parse_response(
+            b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n3\r\nabc\r\n2\r\nde\r\n0\r\n\r\n",
+        )
+        .unwrap();
+        assert_eq!(ret.body, b"abcde".to_vec());
+
+        let ret = parse_response(b"HTTP/1.1 404 Not Found\r\n\r\nTransaction not found").unwrap();
+        assert_eq!(ret.status, 404);
+
+        assert_eq!(
+            split_url("http://localhost:3000/api/").unwrap(),
+            ("localhost:3000".to_string(), "/api".to_string())
+        );
+        assert!(split_url("https://blockstream.info/api").is_err());
+    }
+
+    #[tokio::test]
+    pub async fn test_serve() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let host = listener.local_addr().unwrap().to_string();
+        let (exit_tx, exit_rx) = watch::channel(());
+        let handle = serve(listener, exit_rx, |path: String| async move {
+            match path.as_str() {
+                "/ping" => HttpResponse {
+                    status: 200,
+                    body: b"pong".to_vec(),
+                },
+                _ => HttpResponse {
+                    status: 404,
+                    body: vec![],
+                },
+            }
+        });
+        let ret = get(&host, "/ping?verbose=1").await.unwrap();
+        assert_eq!((ret.status, ret.body), (200, b"pong".to_vec()));
+        assert_eq!(get(&host, "/other").await.unwrap().status, 404);
+        drop(exit_tx);
+        handle.await.unwrap();
+    }
+}
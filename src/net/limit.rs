@@ -0,0 +1,202 @@
+use crate::configuration::base::RateLimitConfiguration;
+use crate::error::IndexerResult;
+use crate::net::ChainDataSource;
+use crate::types::transaction::{MempoolEntry, MempoolListing};
+use async_trait::async_trait;
+use bitcoincore_rpc::bitcoin::{Block, BlockHash, Transaction, Txid};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MethodClass {
+    // tx lookups,e.g. restores and prevouts
+    Tx,
+    // blocks,block hashes and heights
+    Block,
+    Fee,
+}
+
+// up to rate requests per second,bursts of one second. a rate of 0 is unlimited
+pub struct TokenBucket {
+    rate: AtomicU32,
+    // tokens and when they were counted
+    state: Mutex<(f64, Instant)>,
+}
+
+impl TokenBucket {
+    pub fn new(per_second: u32) -> Self {
+        Self {
+            rate: AtomicU32::new(per_second),
+            state: Mutex::new((per_second as f64, Instant::now())),
+        }
+    }
+
+    // the tokens above the new burst are dropped on the next acquire
+    pub fn set_rate(&self, per_second: u32) {
+        self.rate.store(per_second, Ordering::Relaxed);
+    }
+
+    pub fn is_unlimited(&self) -> bool {
+        self.rate.load(Ordering::Relaxed) == 0
+    }
+
+    pub async fn acquire(&self) {
+        loop {
+            let rate = self.rate.load(Ordering::Relaxed) as f64;
+            if rate == 0.0 {
+                return;
+            }
+            let wait = {
+                let mut state = self.state.lock().await;
+                let now = Instant::now();
+                let tokens = (state.0 + (now - state.1).as_secs_f64() * rate).min(rate);
+                *state = (tokens, now);
+                if tokens >= 1.0 {
+                    state.0 -= 1.0;
+                    return;
+                }
+                Duration::from_secs_f64((1.0 - tokens) / rate)
+            };
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+// the overall bucket and the one of the method class
+pub struct RateLimiter {
+    all: TokenBucket,
+    txs: TokenBucket,
+    blocks: TokenBucket,
+    fees: TokenBucket,
+}
+
+impl RateLimiter {
+    // none when nothing is limited
+    pub fn new(cfg: &RateLimitConfiguration) -> Option<Self> {
+        let ret = Self::from_configuration(cfg);
+        [&ret.all, &ret.txs, &ret.blocks, &ret.fees]
+            .iter()
+            .any(|v| !v.is_unlimited())
+            .then_some(ret)
+    }
+
+    // also when nothing is limited yet,so a reload can set limits later
+    pub fn from_configuration(cfg: &RateLimitConfiguration) -> Self {
+        Self {
+            all: TokenBucket::new(cfg.per_second),
+            txs: TokenBucket::new(cfg.txs_per_second),
+            blocks: TokenBucket::new(cfg.blocks_per_second),
+            fees: TokenBucket::new(cfg.fees_per_second),
+        }
+    }
+
+    pub fn update(&self, cfg: &RateLimitConfiguration) {
+        self.all.set_rate(cfg.per_second);
+        self.txs.set_rate(cfg.txs_per_second);
+        self.blocks.set_rate(cfg.blocks_per_second);
+        self.fees.set_rate(cfg.fees_per_second);
+    }
+
+    // waits until count requests of the class may go out
+    pub async fn acquire(&self, class: MethodClass, count: usize) {
+        let class = match class {
+            MethodClass::Tx => &self.txs,
+            MethodClass::Block => &self.blocks,
+            MethodClass::Fee => &self.fees,
+        };
+        for _ in 0..count {
+            class.acquire().await;
+            self.all.acquire().await;
+        }
+    }
+}
+
+pub struct RateLimitedSource {
+    inner: Arc<dyn ChainDataSource>,
+    limiter: Arc<RateLimiter>,
+}
+
+impl RateLimitedSource {
+    pub fn new(inner: Arc<dyn ChainDataSource>, limiter: Arc<RateLimiter>) -> Self {
+        Self { inner, limiter }
+    }
+}
+
+#[async_trait]
+impl ChainDataSource for RateLimitedSource {
+    async fn get_transaction(&self, txid: &Txid) -> IndexerResult<Option<Transaction>> {
+        self.limiter.acquire(MethodClass::Tx, 1).await;
+        self.inner.get_transaction(txid).await
+    }
+
+    async fn get_mempool_entry(&self, txid: &Txid) -> IndexerResult<Option<MempoolEntry>> {
+        self.limiter.acquire(MethodClass::Tx, 1).await;
+        self.inner.get_mempool_entry(txid).await
+    }
+
+    async fn get_mempool_txs(&self) -> IndexerResult<Option<Vec<MempoolListing>>> {
+        self.limiter.acquire(MethodClass::Tx, 1).await;
+        self.inner.get_mempool_txs().await
+    }
+
+    async fn get_block(&self, hash: &BlockHash) -> IndexerResult<Option<Block>> {
+        self.limiter.acquire(MethodClass::Block, 1).await;
+        self.inner.get_block(hash).await
+    }
+
+    async fn get_block_hash(&self, height: u32) -> IndexerResult<Option<BlockHash>> {
+        self.limiter.acquire(MethodClass::Block, 1).await;
+        self.inner.get_block_hash(height).await
+    }
+
+    async fn get_block_height(&self, hash: &BlockHash) -> IndexerResult<Option<u32>> {
+        self.limiter.acquire(MethodClass::Block, 1).await;
+        self.inner.get_block_height(hash).await
+    }
+
+    async fn get_tip_height(&self) -> IndexerResult<u32> {
+        self.limiter.acquire(MethodClass::Block, 1).await;
+        self.inner.get_tip_height().await
+    }
+
+    async fn estimate_fee(&self, target_blocks: u16) -> IndexerResult<Option<f64>> {
+        self.limiter.acquire(MethodClass::Fee, 1).await;
+        self.inner.estimate_fee(target_blocks).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    pub async fn test_rate_limiter() {
+        assert!(RateLimiter::new(&RateLimitConfiguration::default()).is_none());
+        let limiter = RateLimiter::new(&RateLimitConfiguration {
+            txs_per_second: 20,
+            per_second: 1000,
+            ..Default::default()
+        })
+        .unwrap();
+
+        // the burst of one second goes out at once
+        let start = Instant::now();
+        limiter.acquire(MethodClass::Tx, 20).await;
+        limiter.acquire(MethodClass::Block, 50).await;
+        assert!(start.elapsed() < Duration::from_millis(40));
+
+        // the tx bucket is empty now,the others are not
+        let start = Instant::now();
+        limiter.acquire(MethodClass::Tx, 2).await;
+        let elapsed = start.elapsed();
+        assert!(elapsed >= Duration::from_millis(90) && elapsed < Duration::from_millis(300));
+
+        // lifted by a reload
+        limiter.update(&RateLimitConfiguration::default());
+        let start = Instant::now();
+        limiter.acquire(MethodClass::Tx, 100).await;
+        assert!(start.elapsed() < Duration::from_millis(40));
+    }
+}
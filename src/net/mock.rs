@@ -0,0 +1,311 @@
+use bitcoincore_rpc::bitcoin::block::{Header, Version};
+use bitcoincore_rpc::bitcoin::blockdata::script::Builder;
+use bitcoincore_rpc::bitcoin::consensus::encode::{deserialize, serialize_hex};
+use bitcoincore_rpc::bitcoin::hash_types::TxMerkleNode;
+use bitcoincore_rpc::bitcoin::hashes::Hash;
+use bitcoincore_rpc::bitcoin::{
+    absolute, Block, BlockHash, CompactTarget, OutPoint, ScriptBuf, Sequence, Transaction, TxIn,
+    TxOut, Txid, Witness,
+};
+use serde_json::{json, Value};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+
+// a json-rpc node answering every request,or every entry of a batch,with answer(request).
+// returns its url
+pub(crate) async fn serve_rpc<F>(answer: F) -> String
+where
+    F: Fn(&Value) -> Value + Send + Sync + 'static,
+{
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let answer = Arc::new(answer);
+    tokio::spawn(async move {
+        loop {
+            let (stream, _) = listener.accept().await.unwrap();
+            tokio::spawn(serve(stream, answer.clone()));
+        }
+    });
+    url
+}
+
+async fn serve<F>(stream: TcpStream, answer: Arc<F>)
+where
+    F: Fn(&Value) -> Value,
+{
+    let mut stream = BufReader::new(stream);
+    loop {
+        let mut len = 0;
+        loop {
+            let mut line = String::new();
+            if stream.read_line(&mut line).await.unwrap_or(0) == 0 {
+                return;
+            }
+            if line == "\r\n" {
+                break;
+            }
+            if let Some(v) = line.to_lowercase().strip_prefix("content-length:") {
+                len = v.trim().parse().unwrap();
+            }
+        }
+        let mut body = vec![0u8; len];
+        stream.read_exact(&mut body).await.unwrap();
+        let request: Value = serde_json::from_slice(&body).unwrap();
+        let body = match request.as_array() {
+            Some(batch) => Value::Array(batch.iter().map(|v| answer(v)).collect()),
+            None => answer(&request),
+        }
+        .to_string();
+        let response = format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+            body.len(),
+            body
+        );
+        if stream
+            .get_mut()
+            .write_all(response.as_bytes())
+            .await
+            .is_err()
+        {
+            return;
+        }
+    }
+}
+
+// a node without txindex: its mempool and best chain are shared with the test,
+// which mines and disconnects blocks while the sdk talks to it
+#[derive(Clone)]
+pub(crate) struct MockNode {
+    state: Arc<Mutex<MockChain>>,
+}
+
+#[derive(Default)]
+struct MockChain {
+    mempool: Vec<Transaction>,
+    blocks: Vec<Block>,
+    // disconnected blocks by height,still known to the node
+    stale: Vec<(u32, Block)>,
+}
+
+impl MockNode {
+    // a chain with the genesis block only
+    pub fn new() -> Self {
+        let genesis = mock_block(BlockHash::all_zeros(), 0, vec![]);
+        Self {
+            state: Arc::new(Mutex::new(MockChain {
+                mempool: vec![],
+                blocks: vec![genesis],
+                stale: vec![],
+            })),
+        }
+    }
+
+    pub async fn serve(&self) -> String {
+        let state = self.state.clone();
+        serve_rpc(move |request| {
+            let ret = answer(&state.lock().unwrap(), request);
+            match ret {
+                Some(v) => json!({"result": v, "error": null, "id": request["id"]}),
+                None => json!({"result": null, "error": {"code": -5, "message": "not found"}, "id": request["id"]}),
+            }
+        })
+        .await
+    }
+
+    pub fn add_to_mempool(&self, tx: Transaction) {
+        self.state.lock().unwrap().mempool.push(tx);
+    }
+
+    pub fn remove_from_mempool(&self, txid: &Txid) {
+        self.state
+            .lock()
+            .unwrap()
+            .mempool
+            .retain(|v| &v.txid() != txid);
+    }
+
+    // a block with a coinbase and txs on top of the tip,the txs leave the mempool
+    pub fn mine(&self, txs: Vec<Transaction>) -> (u32, Block) {
+        let mut state = self.state.lock().unwrap();
+        let height = state.blocks.len() as u32;
+        let prev = state.blocks.last().unwrap().block_hash();
+        state
+            .mempool
+            .retain(|v| !txs.iter().any(|tx| tx.txid() == v.txid()));
+        let block = mock_block(prev, height, txs);
+        state.blocks.push(block.clone());
+        (height, block)
+    }
+
+    // the tip leaves the best chain
+    pub fn disconnect_tip(&self) -> (u32, Block) {
+        let mut state = self.state.lock().unwrap();
+        let block = state.blocks.pop().unwrap();
+        let height = state.blocks.len() as u32;
+        state.stale.push((height, block.clone()));
+        (height, block)
+    }
+}
+
+// a tx spending output vout of prev,distinct per value
+pub(crate) fn mock_tx(prev: OutPoint, value: u64) -> Transaction {
+    Transaction {
+        version: 2,
+        lock_time: absolute::LockTime::ZERO,
+        input: vec![TxIn {
+            previous_output: prev,
+            script_sig: ScriptBuf::new(),
+            sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+            witness: Witness::new(),
+        }],
+        output: vec![TxOut {
+            value,
+            script_pubkey: ScriptBuf::new(),
+        }],
+    }
+}
+
+fn mock_block(prev: BlockHash, height: u32, txs: Vec<Transaction>) -> Block {
+    let coinbase = Transaction {
+        version: 2,
+        lock_time: absolute::LockTime::ZERO,
+        input: vec![TxIn {
+            previous_output: OutPoint::null(),
+            script_sig: Builder::new().push_int(height as i64).into_script(),
+            sequence: Sequence::MAX,
+            witness: Witness::new(),
+        }],
+        output: vec![TxOut {
+            value: 50,
+            script_pubkey: ScriptBuf::new(),
+        }],
+    };
+    let mut txdata = vec![coinbase];
+    txdata.extend(txs);
+    let time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    let mut block = Block {
+        header: Header {
+            version: Version::TWO,
+            prev_blockhash: prev,
+            merkle_root: TxMerkleNode::all_zeros(),
+            time: time as u32,
+            bits: CompactTarget::from_consensus(0x207fffff),
+            nonce: height,
+        },
+        txdata,
+    };
+    block.header.merkle_root = block.compute_merkle_root().unwrap();
+    block
+}
+
+fn answer(state: &MockChain, request: &Value) -> Option<Value> {
+    let params = &request["params"];
+    // on the best chain or disconnected
+    let block_of = |v: &Value| {
+        let hash: BlockHash = v.as_str()?.parse().ok()?;
+        if let Some(height) = state.blocks.iter().position(|b| b.block_hash() == hash) {
+            return Some((height, &state.blocks[height]));
+        }
+        let (height, block) = state.stale.iter().find(|(_, b)| b.block_hash() == hash)?;
+        Some((*height as usize, block))
+    };
+    let tip = state.blocks.len() - 1;
+    match request["method"].as_str()? {
+        "getblockcount" => Some(json!(tip)),
+        "getblockhash" => {
+            let block = state.blocks.get(params[0].as_u64()? as usize)?;
+            Some(json!(block.block_hash().to_string()))
+        }
+        "getblock" => Some(json!(serialize_hex(block_of(&params[0])?.1))),
+        "getblockheader" => {
+            let (height, block) = block_of(&params[0])?;
+            let best = state.blocks.get(height) == Some(block);
+            let next = state
+                .blocks
+                .get(height + 1)
+                .filter(|_| best)
+                .map(|v| v.block_hash().to_string());
+            // -1 for a block off the best chain
+            let confirmations = if best { (tip - height + 1) as i64 } else { -1 };
+            Some(json!({
+                "hash": block.block_hash().to_string(),
+                "confirmations": confirmations,
+                "height": height,
+                "version": block.header.version.to_consensus(),
+                "merkleroot": block.header.merkle_root.to_string(),
+                "time": block.header.time,
+                "mediantime": block.header.time,
+                "nonce": block.header.nonce,
+                "bits": format!("{:08x}", block.header.bits.to_consensus()),
+                "difficulty": 1.0,
+                "chainwork": "00",
+                "nTx": block.txdata.len(),
+                "previousblockhash": (height > 0).then(|| block.header.prev_blockhash.to_string()),
+                "nextblockhash": next,
+            }))
+        }
+        "getrawtransaction" => {
+            let txid: Txid = params[0].as_str()?.parse().ok()?;
+            // without txindex only the mempool or the given block
+            let tx = match params.get(2) {
+                Some(hash) => block_of(hash)?.1.txdata.iter().find(|v| v.txid() == txid),
+                None => state.mempool.iter().find(|v| v.txid() == txid),
+            };
+            Some(json!(serialize_hex(tx?)))
+        }
+        "getrawmempool" => Some(Value::Object(
+            state
+                .mempool
+                .iter()
+                .map(|v| (v.txid().to_string(), mempool_entry(state, v)))
+                .collect(),
+        )),
+        "getmempoolentry" => {
+            let txid: Txid = params[0].as_str()?.parse().ok()?;
+            let tx = state.mempool.iter().find(|v| v.txid() == txid)?;
+            Some(mempool_entry(state, tx))
+        }
+        "sendrawtransaction" => {
+            let tx: Transaction = deserialize(&hex::decode(params[0].as_str()?).ok()?).ok()?;
+            Some(json!(tx.txid().to_string()))
+        }
+        _ => None,
+    }
+}
+
+// parents in the mempool are listed as depends
+fn mempool_entry(state: &MockChain, tx: &Transaction) -> Value {
+    let depends: Vec<String> = tx
+        .input
+        .iter()
+        .filter(|i| {
+            state
+                .mempool
+                .iter()
+                .any(|v| v.txid() == i.previous_output.txid)
+        })
+        .map(|i| i.previous_output.txid.to_string())
+        .collect();
+    let fee = 0.00001;
+    json!({
+        "vsize": tx.vsize(),
+        "weight": tx.weight().to_wu(),
+        "time": SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs(),
+        "height": state.blocks.len() - 1,
+        "descendantcount": 1,
+        "descendantsize": tx.vsize(),
+        "ancestorcount": 1,
+        "ancestorsize": tx.vsize(),
+        "wtxid": tx.wtxid().to_string(),
+        "fees": {"base": fee, "modified": fee, "ancestor": fee, "descendant": fee},
+        "depends": depends,
+        "spentby": [],
+        "bip125-replaceable": true,
+        "unbroadcast": false,
+    })
+}
@@ -0,0 +1,266 @@
+use crate::configuration::base::{RetryConfiguration, RpcConfiguration};
+use crate::error::{IndexerError, IndexerResult};
+use crate::net::electrum::ElectrumClient;
+use crate::net::esplora::EsploraClient;
+use crate::processor::retry::{is_not_found, with_retry};
+use crate::types::transaction::{MempoolEntry, MempoolListing};
+use async_trait::async_trait;
+use bitcoincore_rpc::bitcoin::{Block, BlockHash, Network, Transaction, Txid};
+use bitcoincore_rpc::RpcApi;
+use log::warn;
+use std::sync::Arc;
+
+pub mod cookie;
+pub mod electrum;
+pub mod esplora;
+pub(crate) mod http;
+pub mod limit;
+#[cfg(test)]
+pub(crate) mod mock;
+pub mod pool;
+pub mod quorum;
+
+// where the processor and the clients fetch txs,blocks and fee estimates from.
+// lookups of unknown items return none instead of an error
+#[async_trait]
+pub trait ChainDataSource: Send + Sync {
+    async fn get_transaction(&self, txid: &Txid) -> IndexerResult<Option<Transaction>>;
+
+    // none for txs outside the mempool,or when the source has no mempool view
+    async fn get_mempool_entry(&self, txid: &Txid) -> IndexerResult<Option<MempoolEntry>>;
+
+    // every tx of the mempool,none when the source has no mempool view
+    async fn get_mempool_txs(&self) -> IndexerResult<Option<Vec<MempoolListing>>>;
+
+    async fn get_block(&self, hash: &BlockHash) -> IndexerResult<Option<Block>>;
+
+    async fn get_block_hash(&self, height: u32) -> IndexerResult<Option<BlockHash>>;
+
+    // height of a block of the best chain
+    async fn get_block_height(&self, hash: &BlockHash) -> IndexerResult<Option<u32>>;
+
+    async fn get_tip_height(&self) -> IndexerResult<u32>;
+
+    // sat/vB to be mined within target blocks,none when the source has no estimate
+    async fn estimate_fee(&self, target_blocks: u16) -> IndexerResult<Option<f64>>;
+}
+
+// the source the configuration asks for:esplora or rpc for the chain,electrum for the txs
+pub fn source_from_configuration(
+    cfg: &RpcConfiguration,
+    retry: &RetryConfiguration,
+    btc_client: Arc<bitcoincore_rpc::Client>,
+) -> IndexerResult<Arc<dyn ChainDataSource>> {
+    let chain: Arc<dyn ChainDataSource> = match &cfg.esplora_url {
+        Some(url) => Arc::new(EsploraClient::new(url)?),
+        None => Arc::new(RpcSource::new(btc_client, retry.clone())),
+    };
+    Ok(match &cfg.electrum_url {
+        Some(url) => Arc::new(SplitSource {
+            txs: Arc::new(ElectrumClient::new(url)?),
+            chain,
+        }),
+        None => chain,
+    })
+}
+
+// refuses a node of another chain,an unreachable node is left to the retries of the components
+pub fn check_network(client: &bitcoincore_rpc::Client, network: Network) -> IndexerResult<()> {
+    // only the chain is read,the other fields differ across node versions
+    let info: serde_json::Value = match client.call("getblockchaininfo", &[]) {
+        Ok(v) => v,
+        Err(e) => {
+            warn!("network of the node unknown,getblockchaininfo failed:{}", e);
+            return Ok(());
+        }
+    };
+    let chain = info["chain"].as_str().unwrap_or_default();
+    match Network::from_core_arg(chain) {
+        Ok(v) if v == network => Ok(()),
+        _ => Err(IndexerError::WrongNetwork(network, chain.to_string())),
+    }
+}
+
+pub struct RpcSource {
+    client: Arc<bitcoincore_rpc::Client>,
+    retry: RetryConfiguration,
+}
+
+impl RpcSource {
+    pub fn new(client: Arc<bitcoincore_rpc::Client>, retry: RetryConfiguration) -> Self {
+        Self { client, retry }
+    }
+}
+
+fn found<T>(ret: IndexerResult<T>) -> IndexerResult<Option<T>> {
+    match ret {
+        Ok(v) => Ok(Some(v)),
+        Err(e) if is_not_found(&e) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+#[async_trait]
+impl ChainDataSource for RpcSource {
+    async fn get_transaction(&self, txid: &Txid) -> IndexerResult<Option<Transaction>> {
+        found(
+            with_retry(&self.retry, "getrawtransaction", || {
+                self.client.get_raw_transaction(txid, None)
+            })
+            .await,
+        )
+    }
+
+    async fn get_mempool_entry(&self, txid: &Txid) -> IndexerResult<Option<MempoolEntry>> {
+        let entry = found(
+            with_retry(&self.retry, "getmempoolentry", || {
+                self.client.get_mempool_entry(txid)
+            })
+            .await,
+        )?;
+        Ok(entry.map(|v| MempoolEntry {
+            vsize: v.vsize,
+            fee: v.fees.base.to_sat(),
+            time: v.time,
+        }))
+    }
+
+    async fn get_mempool_txs(&self) -> IndexerResult<Option<Vec<MempoolListing>>> {
+        let txs = with_retry(&self.retry, "getrawmempool", || {
+            self.client.get_raw_mempool_verbose()
+        })
+        .await?;
+        let txs = txs
+            .into_iter()
+            .map(|(txid, v)| MempoolListing {
+                txid,
+                time: v.time,
+                depends: v.depends,
+            })
+            .collect();
+        Ok(Some(txs))
+    }
+
+    async fn get_block(&self, hash: &BlockHash) -> IndexerResult<Option<Block>> {
+        found(with_retry(&self.retry, "getblock", || self.client.get_block(hash)).await)
+    }
+
+    async fn get_block_hash(&self, height: u32) -> IndexerResult<Option<BlockHash>> {
+        if height as u64 > self.get_tip_height().await? as u64 {
+            return Ok(None);
+        }
+        found(
+            with_retry(&self.retry, "getblockhash", || {
+                self.client.get_block_hash(height as u64)
+            })
+            .await,
+        )
+    }
+
+    async fn get_block_height(&self, hash: &BlockHash) -> IndexerResult<Option<u32>> {
+        let info = found(
+            with_retry(&self.retry, "getblockheader", || {
+                self.client.get_block_header_info(hash)
+            })
+            .await,
+        )?;
+        Ok(info.map(|v| v.height as u32))
+    }
+
+    async fn get_tip_height(&self) -> IndexerResult<u32> {
+        let ret = with_retry(&self.retry, "getblockcount", || {
+            self.client.get_block_count()
+        })
+        .await?;
+        Ok(ret as u32)
+    }
+
+    async fn estimate_fee(&self, target_blocks: u16) -> IndexerResult<Option<f64>> {
+        let ret = with_retry(&self.retry, "estimatesmartfee", || {
+            self.client.estimate_smart_fee(target_blocks, None)
+        })
+        .await?;
+        Ok(ret.fee_rate.map(|v| v.to_sat() as f64 / 1000.0))
+    }
+}
+
+// txs and fee estimates from one source,blocks and heights from the other
+pub struct SplitSource {
+    pub txs: Arc<dyn ChainDataSource>,
+    pub chain: Arc<dyn ChainDataSource>,
+}
+
+#[async_trait]
+impl ChainDataSource for SplitSource {
+    async fn get_transaction(&self, txid: &Txid) -> IndexerResult<Option<Transaction>> {
+        self.txs.get_transaction(txid).await
+    }
+
+    // the chain source may be a node with a mempool when the txs one has none
+    async fn get_mempool_entry(&self, txid: &Txid) -> IndexerResult<Option<MempoolEntry>> {
+        match self.txs.get_mempool_entry(txid).await? {
+            Some(v) => Ok(Some(v)),
+            None => self.chain.get_mempool_entry(txid).await,
+        }
+    }
+
+    async fn get_mempool_txs(&self) -> IndexerResult<Option<Vec<MempoolListing>>> {
+        match self.txs.get_mempool_txs().await? {
+            Some(v) => Ok(Some(v)),
+            None => self.chain.get_mempool_txs().await,
+        }
+    }
+
+    async fn get_block(&self, hash: &BlockHash) -> IndexerResult<Option<Block>> {
+        self.chain.get_block(hash).await
+    }
+
+    async fn get_block_hash(&self, height: u32) -> IndexerResult<Option<BlockHash>> {
+        self.chain.get_block_hash(height).await
+    }
+
+    async fn get_block_height(&self, hash: &BlockHash) -> IndexerResult<Option<u32>> {
+        self.chain.get_block_height(hash).await
+    }
+
+    async fn get_tip_height(&self) -> IndexerResult<u32> {
+        self.chain.get_tip_height().await
+    }
+
+    async fn estimate_fee(&self, target_blocks: u16) -> IndexerResult<Option<f64>> {
+        self.txs.estimate_fee(target_blocks).await
+    }
+}
+
+pub(crate) fn unsupported<T>(what: &str) -> IndexerResult<T> {
+    Err(IndexerError::Unsupported(what.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::net::mock::serve_rpc;
+    use bitcoincore_rpc::Auth;
+    use serde_json::json;
+
+    #[tokio::test(flavor = "multi_thread")]
+    pub async fn test_check_network() {
+        let url = serve_rpc(|request| {
+            json!({"result": {"chain": "signet", "blocks": 1}, "error": null, "id": request["id"]})
+        })
+        .await;
+        let client = bitcoincore_rpc::Client::new(&url, Auth::None).unwrap();
+        tokio::task::spawn_blocking(move || {
+            check_network(&client, Network::Signet).unwrap();
+            assert!(matches!(
+                check_network(&client, Network::Bitcoin),
+                Err(IndexerError::WrongNetwork(Network::Bitcoin, _))
+            ));
+            // left to the retries
+            let down = bitcoincore_rpc::Client::new("http://127.0.0.1:1", Auth::None).unwrap();
+            check_network(&down, Network::Bitcoin).unwrap();
+        })
+        .await
+        .unwrap();
+    }
+}
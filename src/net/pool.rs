@@ -0,0 +1,180 @@
+use crate::net::limit::{MethodClass, RateLimiter};
+use crate::types::transaction::MempoolEntry;
+use bitcoincore_rpc::bitcoin::consensus::deserialize;
+use bitcoincore_rpc::bitcoin::{Transaction, Txid};
+use bitcoincore_rpc::json::GetMempoolEntryResult;
+use bitcoincore_rpc::jsonrpc;
+use log::warn;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+
+// every bitcoincore_rpc client sends one call at a time,the pool spreads calls over several
+// connections with at most max_concurrent of them in flight
+pub struct RpcPool {
+    clients: Vec<Arc<bitcoincore_rpc::Client>>,
+    next: AtomicUsize,
+    permits: Arc<Semaphore>,
+    limiter: Option<Arc<RateLimiter>>,
+}
+
+impl RpcPool {
+    pub fn new(clients: Vec<bitcoincore_rpc::Client>, max_concurrent: usize) -> Self {
+        assert!(!clients.is_empty(), "rpc pool without clients");
+        Self {
+            clients: clients.into_iter().map(Arc::new).collect(),
+            next: AtomicUsize::new(0),
+            permits: Arc::new(Semaphore::new(max_concurrent.max(1))),
+            limiter: None,
+        }
+    }
+
+    // a batch takes a token per call
+    pub fn with_limiter(mut self, limiter: Option<Arc<RateLimiter>>) -> Self {
+        self.limiter = limiter;
+        self
+    }
+
+    fn next_client(&self) -> Arc<bitcoincore_rpc::Client> {
+        let i = self.next.fetch_add(1, Ordering::Relaxed);
+        self.clients[i % self.clients.len()].clone()
+    }
+
+    // one json-rpc batch per batch_size txs asks for the txs and their mempool entries,
+    // a permit covers the whole batch.
+    // the txs the node returned,failed lookups are left out for the caller to retry
+    pub async fn get_mempool_transactions(
+        &self,
+        txids: Vec<Txid>,
+        batch_size: usize,
+    ) -> HashMap<Txid, (Transaction, Option<MempoolEntry>)> {
+        let mut calls = JoinSet::new();
+        for batch in txids.chunks(batch_size.max(1)) {
+            if let Some(limiter) = &self.limiter {
+                limiter.acquire(MethodClass::Tx, batch.len() * 2).await;
+            }
+            let permit = self.permits.clone().acquire_owned().await.unwrap();
+            let client = self.next_client();
+            let batch = batch.to_vec();
+            calls.spawn_blocking(move || {
+                let ret = get_mempool_transaction_batch(&client, &batch);
+                drop(permit);
+                (batch.len(), ret)
+            });
+        }
+        let mut ret = HashMap::new();
+        while let Some(call) = calls.join_next().await {
+            match call {
+                Ok((_, Ok(txs))) => ret.extend(txs),
+                Ok((len, Err(e))) => warn!("batch of {} mempool txs failed:{:?}", len, e),
+                Err(e) => warn!("pooled rpc call failed:{:?}", e),
+            }
+        }
+        ret
+    }
+}
+
+// the txs of the batch bitcoind knows,with the entry when they are still in its mempool
+fn get_mempool_transaction_batch(
+    client: &bitcoincore_rpc::Client,
+    txids: &[Txid],
+) -> Result<Vec<(Txid, (Transaction, Option<MempoolEntry>))>, bitcoincore_rpc::Error> {
+    let client = client.get_jsonrpc_client();
+    let params: Vec<_> = txids.iter().map(|v| [jsonrpc::arg(v)]).collect();
+    let requests: Vec<_> = params
+        .iter()
+        .flat_map(|v| {
+            [
+                client.build_request("getrawtransaction", v),
+                client.build_request("getmempoolentry", v),
+            ]
+        })
+        .collect();
+    let responses = client.send_batch(&requests)?;
+    let mut ret = Vec::with_capacity(txids.len());
+    for (txid, responses) in txids.iter().zip(responses.chunks(2)) {
+        // unknown txs come back as an error entry of the batch
+        let Some(Ok(raw)) = responses[0].as_ref().map(|v| v.result::<String>()) else {
+            continue;
+        };
+        let raw = hex::decode(raw).map_err(|_| bitcoincore_rpc::Error::UnexpectedStructure)?;
+        let entry = responses[1]
+            .as_ref()
+            .and_then(|v| v.result::<GetMempoolEntryResult>().ok())
+            .map(|v| MempoolEntry {
+                vsize: v.vsize,
+                fee: v.fees.base.to_sat(),
+                time: v.time,
+            });
+        ret.push((*txid, (deserialize(&raw)?, entry)));
+    }
+    Ok(ret)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::net::mock::serve_rpc;
+    use bitcoincore_rpc::bitcoin::absolute::LockTime;
+    use bitcoincore_rpc::bitcoin::consensus::serialize;
+    use bitcoincore_rpc::Auth;
+    use serde_json::json;
+
+    #[tokio::test(flavor = "multi_thread")]
+    pub async fn test_rpc_pool() {
+        let tx = Transaction {
+            version: 2,
+            lock_time: LockTime::ZERO,
+            input: vec![],
+            output: vec![],
+        };
+        let raw = hex::encode(serialize(&tx));
+        // the txids of odd bytes are unknown,the ones of bytes divisible by 4 are in the mempool
+        let unknown = |v: &str| u8::from_str_radix(&v[..2], 16).unwrap() % 2 == 1;
+        let in_mempool = |v: &str| u8::from_str_radix(&v[..2], 16).unwrap() % 4 == 0;
+        let url = serve_rpc(move |request| {
+            let txid = request["params"][0].as_str().unwrap();
+            let not_found = json!({"result": null, "error": {"code": -5, "message": "unknown"}, "id": request["id"]});
+            match request["method"].as_str() {
+                _ if unknown(txid) => not_found,
+                Some("getmempoolentry") if !in_mempool(txid) => not_found,
+                Some("getmempoolentry") => json!({"result": {
+                    "vsize": 100, "weight": 400, "time": 7, "height": 1,
+                    "descendantcount": 1, "descendantsize": 100,
+                    "ancestorcount": 1, "ancestorsize": 100, "wtxid": txid,
+                    "fees": {"base": 0.00001, "modified": 0.00001, "ancestor": 0.00001, "descendant": 0.00001},
+                    "depends": [], "spentby": [], "bip125-replaceable": false,
+                }, "error": null, "id": request["id"]}),
+                _ => json!({"result": raw, "error": null, "id": request["id"]}),
+            }
+        })
+        .await;
+
+        let clients = (0..3)
+            .map(|_| bitcoincore_rpc::Client::new(&url, Auth::None).unwrap())
+            .collect();
+        let pool = RpcPool::new(clients, 2);
+        let txids = (0..12u8)
+            .map(|v| {
+                use bitcoincore_rpc::bitcoin::hashes::Hash;
+                Txid::from_byte_array([v; 32])
+            })
+            .collect();
+        let ret = pool.get_mempool_transactions(txids, 5).await;
+        assert_eq!(ret.len(), 6);
+        assert!(ret.values().all(|(v, _)| v == &tx));
+        let entry = MempoolEntry {
+            vsize: 100,
+            fee: 1000,
+            time: 7,
+        };
+        for (txid, (_, v)) in &ret {
+            let expected = (txid[0] % 4 == 0).then(|| entry.clone());
+            assert_eq!(v, &expected);
+        }
+        // batches of 5,5 and 2
+        assert_eq!(pool.next.load(Ordering::Relaxed), 3);
+    }
+}
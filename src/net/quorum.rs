@@ -0,0 +1,121 @@
+use crate::configuration::base::QuorumConfiguration;
+use crate::error::{IndexerError, IndexerResult};
+use crate::factory::common::create_client_for_endpoint;
+use bitcoincore_rpc::bitcoin::BlockHash;
+use bitcoincore_rpc::RpcApi;
+use log::{info, warn};
+use std::time::Duration;
+
+// asks the other nodes which block they have at a height,the node announcing it counts as one vote
+pub struct QuorumVerifier {
+    nodes: Vec<bitcoincore_rpc::Client>,
+    required: usize,
+    attempts: u32,
+    retry_delay: Duration,
+}
+
+impl QuorumVerifier {
+    pub fn new(cfg: &QuorumConfiguration, timeout: Duration) -> Self {
+        let nodes = cfg
+            .nodes
+            .iter()
+            .map(|v| create_client_for_endpoint(v, timeout))
+            .collect();
+        Self::with_clients(nodes, cfg)
+    }
+
+    pub fn with_clients(nodes: Vec<bitcoincore_rpc::Client>, cfg: &QuorumConfiguration) -> Self {
+        Self {
+            nodes,
+            required: cfg.required,
+            attempts: cfg.attempts.max(1),
+            retry_delay: Duration::from_millis(cfg.retry_delay_ms),
+        }
+    }
+
+    fn agreeing(&self, height: u32, hash: &BlockHash) -> usize {
+        let votes = self
+            .nodes
+            .iter()
+            .filter(|node| match node.get_block_hash(height as u64) {
+                Ok(v) => v == *hash,
+                Err(e) => {
+                    warn!("quorum node failed to answer height {}:{:?}", height, e);
+                    false
+                }
+            })
+            .count();
+        votes + 1
+    }
+
+    pub async fn verify(&self, height: u32, hash: &BlockHash) -> IndexerResult<()> {
+        let mut agreeing = 0;
+        for attempt in 0..self.attempts {
+            if attempt > 0 {
+                tokio::time::sleep(self.retry_delay).await;
+            }
+            agreeing = self.agreeing(height, hash);
+            if agreeing >= self.required {
+                return Ok(());
+            }
+            info!(
+                "block {} at height {} agreed by {} of {} required nodes,attempt:{}",
+                hash,
+                height,
+                agreeing,
+                self.required,
+                attempt + 1
+            );
+        }
+        Err(IndexerError::QuorumNotReached(
+            hash.to_string(),
+            height,
+            agreeing,
+            self.required,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::net::mock::serve_rpc;
+    use bitcoincore_rpc::bitcoin::hashes::Hash;
+    use bitcoincore_rpc::Auth;
+    use serde_json::json;
+
+    #[tokio::test(flavor = "multi_thread")]
+    pub async fn test_quorum() {
+        let hash = BlockHash::from_byte_array([1u8; 32]);
+        let other = BlockHash::from_byte_array([2u8; 32]);
+        let mut nodes = vec![];
+        for v in [hash, hash, other] {
+            let url = serve_rpc(
+                move |request| json!({"result": v.to_string(), "error": null, "id": request["id"]}),
+            )
+            .await;
+            nodes.push(url);
+        }
+        let clients = |urls: &[String]| {
+            urls.iter()
+                .map(|v| bitcoincore_rpc::Client::new(v, Auth::None).unwrap())
+                .collect()
+        };
+        let cfg = QuorumConfiguration {
+            required: 3,
+            attempts: 2,
+            retry_delay_ms: 1,
+            ..Default::default()
+        };
+        let verifier = QuorumVerifier::with_clients(clients(&nodes), &cfg);
+        verifier.verify(7, &hash).await.unwrap();
+        assert!(matches!(
+            verifier.verify(7, &other).await,
+            Err(IndexerError::QuorumNotReached(_, 7, 2, 3))
+        ));
+
+        let cfg = QuorumConfiguration { required: 4, ..cfg };
+        let verifier = QuorumVerifier::with_clients(clients(&nodes), &cfg);
+        assert!(verifier.verify(7, &hash).await.is_err());
+    }
+}
@@ -1,29 +1,50 @@
-use crate::client::event::ClientEvent;
-use crate::configuration::base::IndexerConfiguration;
+use crate::client::event::{BlockContext, ClientEvent};
+use crate::client::filter::{subject_tx, EventFilter, Subscription};
+use crate::configuration::base::{IndexerConfiguration, ProcessorConfiguration};
 use crate::dispatcher::event::DispatchEvent;
-use crate::error::IndexerResult;
-use crate::event::{AddressType, BalanceType, IndexerEvent, TxIdType};
-use crate::processor::node::TxNode;
+use crate::error::{IndexerError, IndexerResult};
+use crate::event::{AddressType, BalanceSender, IndexerEvent, TokenType, TxIdType};
+use crate::health::{ComponentHealth, HealthCheck, HealthStatus};
+use crate::logging::{set_log_field, with_log_fields};
+use crate::net::limit::RateLimiter;
+use crate::net::pool::RpcPool;
+use crate::net::quorum::QuorumVerifier;
+use crate::net::{ChainDataSource, RpcSource};
+use crate::processor::consumer::Consumer;
+use crate::processor::header::{ChainHeader, HeaderChain, HeaderUpdate};
+use crate::processor::lookup::{find_block, find_transaction, MinedTxIndex};
+use crate::processor::node::{order_by_dependencies, TxNode};
+use crate::processor::reorg::{orphaned_txs, BlockTxIndex};
+use crate::processor::simulate::simulate_delta;
+use crate::processor::state::{ChainState, MinedBlock};
+use crate::storage::block_cache::BlockCache;
 use crate::storage::prefix::DeltaStatus;
+use crate::storage::snapshot::remote::RemoteSnapshotStore;
+use crate::storage::snapshot::Snapshot;
 use crate::storage::StorageProcessor;
 use crate::types::delta::TransactionDelta;
+use crate::types::request::{BlockId, ReorgReport};
+use crate::types::response::StatusResponse;
+use crate::types::transaction::{MempoolEntry, MempoolTransaction, RichTransaction, Utxo};
 use crate::{Component, HookComponent, IndexProcessor};
 use async_channel::{Receiver, Sender};
+use bitcoincore_rpc::bitcoin::block::Header;
 use bitcoincore_rpc::bitcoin::consensus::{deserialize, serialize};
-use bitcoincore_rpc::bitcoin::{Transaction, Txid};
+use bitcoincore_rpc::bitcoin::{Block, BlockHash, OutPoint, Transaction, Txid};
 use bitcoincore_rpc::RpcApi;
 use chrono::Local;
 use log::{error, info, warn};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use wg::AsyncWaitGroup;
 
 #[derive(Clone)]
 pub struct IndexerProcessorImpl<T: StorageProcessor> {
-    config: IndexerConfiguration,
-    tx: async_channel::Sender<ClientEvent>,
+    config: ProcessorConfiguration,
+    // indexed by the consumer id of the clients
+    consumers: Vec<Consumer>,
     storage: T,
     btc_client: Arc<bitcoincore_rpc::Client>,
 
@@ -40,6 +61,41 @@ pub struct IndexerProcessorImpl<T: StorageProcessor> {
     current_chain_latest_height: Option<(u32, i64)>,
 
     analyses: HashMap<TxIdType, TxNode>,
+
+    // latest connected blocks,rawblock and sequence both announce a block
+    connected_blocks: VecDeque<BlockHash>,
+    // inputs of the dispatched mempool txs
+    spent_outpoints: HashMap<OutPoint, TxIdType>,
+    tx_outpoints: HashMap<TxIdType, Vec<OutPoint>>,
+
+    headers: HeaderChain,
+    block_txs: BlockTxIndex,
+    // mined blocks waiting for the configured confirmations
+    pending_confirmations: VecDeque<MinedBlock>,
+    // mined txs whose delta has not been pushed yet -> height of the block
+    early_confirmed: HashMap<TxIdType, (u32, BlockContext)>,
+    // coinbase txs which can not be spent yet,height and hash of their block
+    immature_coinbases: VecDeque<(u32, BlockHash, Transaction)>,
+    // client events are held back while a block or restore pass is processed
+    batching: bool,
+    block_cache: BlockCache,
+    // set by the zmq component while its socket is subscribed
+    zmq_connected: Arc<AtomicBool>,
+    // between a pause and a resume of the clients
+    paused: bool,
+    // txs and blocks are fetched through it,rpc unless configured otherwise
+    source: Arc<dyn ChainDataSource>,
+    // restores fetch the mempool txs through it ahead of handling them
+    rpc_pool: Option<Arc<RpcPool>>,
+    prefetched: HashMap<TxIdType, Transaction>,
+    prefetched_entries: HashMap<TxIdType, MempoolEntry>,
+    // restored txs missing from the mempool,searched in the blocks of the current restore pass
+    mined_txs: MinedTxIndex,
+    quorum: Option<Arc<QuorumVerifier>>,
+    // shared with the source and the rpc pool,a reload updates its rates
+    limiter: Option<Arc<RateLimiter>>,
+    // checkpoints are uploaded to it,the mounted bucket of the configuration unless replaced
+    snapshot_store: Option<RemoteSnapshotStore>,
 }
 
 unsafe impl<T: StorageProcessor> Send for IndexerProcessorImpl<T> {}
@@ -47,11 +103,16 @@ unsafe impl<T: StorageProcessor> Send for IndexerProcessorImpl<T> {}
 unsafe impl<T: StorageProcessor> Sync for IndexerProcessorImpl<T> {}
 
 const MAX_UPDATE_CHAIN_HEIGHT_INTERVAL: i64 = 60 * 3;
+// confirmations before a coinbase output can be spent
+const COINBASE_MATURITY: u32 = 100;
+// restores stop re-dispatching a tx the client never executed after this many announcements
+const MAX_RE_DISPATCH: u32 = 16;
+
 impl<T: StorageProcessor> IndexerProcessorImpl<T> {
     pub fn new(
-        config: IndexerConfiguration,
+        config: ProcessorConfiguration,
         wg: AsyncWaitGroup,
-        tx: Sender<ClientEvent>,
+        consumers: Vec<Consumer>,
         storage: T,
         client: Arc<bitcoincore_rpc::Client>,
         client_tx: Sender<ClientEvent>,
@@ -59,9 +120,14 @@ impl<T: StorageProcessor> IndexerProcessorImpl<T> {
         grap_tx: Sender<DispatchEvent>,
         grap_rx: Receiver<DispatchEvent>,
     ) -> Self {
+        let headers = HeaderChain::new(config.storage.save_block_cache_count as usize);
+        let block_txs = BlockTxIndex::new(config.storage.save_block_cache_count as usize);
+        let block_cache = BlockCache::new(config.storage.save_block_cache_count as usize);
+        let source = Arc::new(RpcSource::new(client.clone(), config.rpc.retry.clone()));
+        let snapshot_store = RemoteSnapshotStore::from_configuration(&config.storage.snapshot);
         Self {
             config,
-            tx,
+            consumers,
             storage,
             btc_client: client,
             client_tx,
@@ -73,8 +139,95 @@ impl<T: StorageProcessor> IndexerProcessorImpl<T> {
             current_chain_latest_height: None,
             grap_rx,
             analyses: Default::default(),
+            connected_blocks: Default::default(),
+            spent_outpoints: Default::default(),
+            tx_outpoints: Default::default(),
+            headers,
+            block_txs,
+            pending_confirmations: Default::default(),
+            early_confirmed: Default::default(),
+            immature_coinbases: Default::default(),
+            batching: false,
+            block_cache,
+            zmq_connected: Default::default(),
+            paused: false,
+            source,
+            rpc_pool: None,
+            prefetched: Default::default(),
+            prefetched_entries: Default::default(),
+            mined_txs: Default::default(),
+            quorum: None,
+            limiter: None,
+            snapshot_store,
         }
     }
+
+    pub fn with_zmq_state(mut self, connected: Arc<AtomicBool>) -> Self {
+        self.zmq_connected = connected;
+        self
+    }
+
+    pub fn with_source(mut self, source: Arc<dyn ChainDataSource>) -> Self {
+        self.source = source;
+        self
+    }
+
+    pub fn with_rpc_pool(mut self, pool: Option<Arc<RpcPool>>) -> Self {
+        self.rpc_pool = pool;
+        self
+    }
+
+    pub fn with_quorum(mut self, quorum: Option<Arc<QuorumVerifier>>) -> Self {
+        self.quorum = quorum;
+        self
+    }
+
+    pub fn with_limiter(mut self, limiter: Arc<RateLimiter>) -> Self {
+        self.limiter = Some(limiter);
+        self
+    }
+
+    pub fn with_snapshot_store(mut self, store: Option<RemoteSnapshotStore>) -> Self {
+        self.snapshot_store = store;
+        self
+    }
+
+    // the mempool and the chain state are kept,only the tunables change.
+    // the restart fields are the ones of the processor sections
+    async fn do_handle_reload(&mut self, cfg: &IndexerConfiguration) -> IndexerResult<Vec<String>> {
+        cfg.validate()?;
+        let cfg = &cfg.clone().resolve_secrets()?;
+        let section = cfg.processor();
+        let restart = self.config.restart_required(&section);
+        let deeper = section.confirmations > self.config.confirmations;
+        self.config.apply_tunables(&section);
+        log::set_max_level(cfg.log_configuration.log_level);
+        if let Some(limiter) = &self.limiter {
+            limiter.update(&cfg.rpc.rate_limit);
+        }
+        // blocks which reached the lower depth already
+        if !deeper {
+            if let Some((tip, _, _)) = self.pending_confirmations.back() {
+                self.settle_confirmations(*tip).await?;
+                self.save_chain_state().await?;
+            }
+        }
+        info!("configuration reloaded,restart required for:{:?}", restart);
+        Ok(restart)
+    }
+
+    // blocks the other nodes disagree on fail into the dead letters
+    async fn verify_quorum(&self, height: u32, hash: &BlockHash) -> IndexerResult<()> {
+        match &self.quorum {
+            Some(quorum) => quorum.verify(height, hash).await,
+            None => Ok(()),
+        }
+    }
+
+    // the clients serve blocks from the same cache
+    pub fn block_cache(&self) -> BlockCache {
+        self.block_cache.clone()
+    }
 }
 
 #[async_trait::async_trait]
@@ -85,8 +238,11 @@ impl<T: StorageProcessor> HookComponent<DispatchEvent> for IndexerProcessorImpl<
         rx: Receiver<DispatchEvent>,
     ) -> IndexerResult<()> {
         self.wg.wait().await;
+        self.load_chain_state().await?;
         self.wait_catchup(rx.clone()).await?;
         self.restore_from_mempool(sender).await?;
+        // the events held when the last run stopped while paused
+        self.drain_held_events().await?;
 
         Ok(())
     }
@@ -96,9 +252,25 @@ impl<T: StorageProcessor> HookComponent<DispatchEvent> for IndexerProcessorImpl<
 impl<T: StorageProcessor> Component<DispatchEvent> for IndexerProcessorImpl<T> {
     async fn handle_event(&mut self, event: &DispatchEvent) -> IndexerResult<()> {
         let event = event.get_indexer_event().unwrap();
-        if let Err(e) = self.do_handle_event(event).await {
-            error!("handle_event error:{:?}", e)
+        match event {
+            IndexerEvent::ReplayDeadLetters => return self.do_replay_dead_letters().await,
+            IndexerEvent::Pause => {
+                info!("processor paused");
+                self.paused = true;
+                return Ok(());
+            }
+            IndexerEvent::Resume => {
+                self.paused = false;
+                return self.drain_held_events().await;
+            }
+            _ => {}
         }
+        // queries are still answered
+        if self.paused && !event.is_query() {
+            self.storage.hold_event(&event.to_bytes()).await?;
+            return Ok(());
+        }
+        self.handle_or_dead_letter(event).await;
         Ok(())
     }
 
@@ -107,6 +279,28 @@ impl<T: StorageProcessor> Component<DispatchEvent> for IndexerProcessorImpl<T> {
     }
 }
 
+// from the last known heights,no rpc call
+#[async_trait::async_trait]
+impl<T: StorageProcessor> HealthCheck for IndexerProcessorImpl<T> {
+    async fn health(&self) -> ComponentHealth {
+        let status = match (
+            self.current_chain_latest_height,
+            self.current_indexer_height,
+        ) {
+            _ if !self.flag.load(Ordering::Relaxed) => {
+                HealthStatus::Lagging("restoring the mempool".to_string())
+            }
+            _ if self.paused => HealthStatus::Lagging("paused".to_string()),
+            (Some((chain, _)), Some(indexed)) if indexed < chain => HealthStatus::Lagging(format!(
+                "indexed height {} behind chain height {}",
+                indexed, chain
+            )),
+            _ => HealthStatus::Connected,
+        };
+        ComponentHealth::new("processor", status)
+    }
+}
+
 impl<T: StorageProcessor> IndexerProcessorImpl<T> {
     async fn restore_from_mempool(&mut self, sender: Sender<DispatchEvent>) -> IndexerResult<()> {
         self.do_handle_sync_mempool(sender).await?;
@@ -117,28 +311,56 @@ impl<T: StorageProcessor> IndexerProcessorImpl<T> {
         let all_unconsumed = self.storage.get_all_un_consumed_txs().await?;
         info!("all unconsumed txs:{:?}", all_unconsumed);
         let txs = {
-            // sort by timestamp to execute tx in order
-            let txs = self.btc_client.get_raw_mempool_verbose()?;
+            // parents first,then by timestamp to execute tx in order
+            let txs = match self.source.get_mempool_txs().await? {
+                Some(txs) => txs,
+                None => {
+                    warn!("the chain data source has no mempool view,only the stored txs are restored");
+                    vec![]
+                }
+            };
+            let mempool: HashSet<Txid> = txs.iter().map(|v| v.txid).collect();
             let mut append = vec![];
             for (k, ts) in &all_unconsumed {
                 let tx_id: Txid = k.clone().into();
-                if !txs.contains_key(&tx_id) {
-                    append.push((k.clone(), *ts));
+                if !mempool.contains(&tx_id) {
+                    append.push((k.clone(), *ts, vec![]));
                 }
             }
-            let mut sorted_pairs: Vec<_> = txs
+            let missing = append.iter().map(|(k, _, _)| k.clone().into()).collect();
+            self.mined_txs = MinedTxIndex::new(missing);
+            let mut pairs: Vec<_> = txs
                 .into_iter()
-                .map(|(tx_id, info)| {
-                    let tx_id: TxIdType = tx_id.into();
-                    (tx_id, info.time as i64)
+                .map(|v| {
+                    let tx_id: TxIdType = v.txid.into();
+                    let depends = v.depends.into_iter().map(|v| v.into()).collect();
+                    (tx_id, v.time as i64, depends)
                 })
                 .collect();
-            sorted_pairs.extend_from_slice(append.as_slice());
-            sorted_pairs.sort_by(|a, b| a.1.cmp(&b.1));
-            sorted_pairs
+            pairs.extend(append);
+            order_by_dependencies(pairs)
         };
 
-        for (tx_id, _) in txs {
+        if let Some(pool) = self.rpc_pool.clone() {
+            let txids = txs.iter().map(|v| v.clone().into()).collect();
+            let fetched = pool
+                .get_mempool_transactions(txids, self.config.rpc.rpc_batch_size)
+                .await;
+            info!("prefetched {} of {} restored txs", fetched.len(), txs.len());
+            self.prefetched.clear();
+            self.prefetched_entries.clear();
+            for (txid, (tx, entry)) in fetched {
+                let tx_id: TxIdType = txid.into();
+                if let Some(entry) = entry {
+                    self.prefetched_entries.insert(tx_id.clone(), entry);
+                }
+                self.prefetched.insert(tx_id, tx);
+            }
+        }
+
+        // the restored txs are handled later,the flush event closes the batch after them
+        self.begin_batch();
+        for tx_id in txs {
             info!("get tx from mempool or db:{:?}", &tx_id);
             tx.send(DispatchEvent::IndexerEvent(
                 IndexerEvent::TxFromRestoreByTxId(tx_id),
@@ -146,6 +368,11 @@ impl<T: StorageProcessor> IndexerProcessorImpl<T> {
             .await
             .unwrap();
         }
+        if self.config.client.batch_delivery {
+            tx.send(DispatchEvent::IndexerEvent(IndexerEvent::FlushBatch))
+                .await
+                .unwrap();
+        }
         self.flag.store(true, Ordering::Relaxed);
 
         Ok(())
@@ -155,9 +382,10 @@ impl<T: StorageProcessor> IndexerProcessorImpl<T> {
         let grap_tx = self.client_tx.clone();
         let grap_rx = rx.clone();
         loop {
-            let latest_block = self.btc_client.get_block_count();
+            let latest_block = self.source.get_tip_height().await;
             if let Err(e) = latest_block {
                 error!("get latest block error:{}", e);
+                tokio::time::sleep(Duration::from_secs(2)).await;
                 continue;
             }
             let net_latest_block = latest_block.unwrap();
@@ -181,7 +409,7 @@ impl<T: StorageProcessor> IndexerProcessorImpl<T> {
                     "indexer latest height:{},chain latest height:{}",
                     h, net_latest_block
                 );
-                if *h as u64 >= net_latest_block {
+                if *h >= net_latest_block {
                     info!("indexer catch up,waitsync done!");
                     self.current_indexer_height = Some(*h);
                     break;
@@ -191,18 +419,135 @@ impl<T: StorageProcessor> IndexerProcessorImpl<T> {
         }
         Ok(())
     }
+    async fn handle_or_dead_letter(&mut self, event: &IndexerEvent) {
+        #[cfg(feature = "metrics")]
+        crate::metrics::METRICS.events_dispatched.inc(event.name());
+        let handled = with_log_fields(
+            vec![("event", event.name().into())],
+            self.do_handle_event(event),
+        );
+        let Err(e) = handled.await else {
+            return;
+        };
+        error!("handle_event error:{:?}", e);
+        // queries can not be persisted,their caller gets the error anyway
+        if event.is_query() {
+            return;
+        }
+        if let Err(err) = self
+            .storage
+            .add_dead_letter(&event.to_bytes(), &e.to_string())
+            .await
+        {
+            error!("store dead letter error:{:?},event:{:?}", err, event);
+        }
+    }
+
+    async fn drain_held_events(&mut self) -> IndexerResult<()> {
+        let events = self.storage.get_held_events().await?;
+        info!("handle held events:{}", events.len());
+        // removed once handled or dead lettered,a crash in between handles it again
+        for (index, data) in events {
+            match IndexerEvent::from_bytes(&data) {
+                Ok(event) => self.handle_or_dead_letter(&event).await,
+                Err(e) => {
+                    error!("undecodable held event:{},err:{:?}", index, e);
+                    self.storage.add_dead_letter(&data, &e.to_string()).await?;
+                }
+            }
+            self.storage.remove_held_event(index).await?;
+        }
+        Ok(())
+    }
+
+    async fn do_replay_dead_letters(&mut self) -> IndexerResult<()> {
+        let letters = self.storage.get_dead_letters().await?;
+        info!("replay dead letters:{}", letters.len());
+        for letter in letters {
+            // an undecodable letter stays where it is
+            let event = match IndexerEvent::from_bytes(&letter.event) {
+                Ok(v) => v,
+                Err(e) => {
+                    error!("undecodable dead letter:{},err:{:?}", letter.index, e);
+                    continue;
+                }
+            };
+            // failing again stores it with a new index,the old one goes once it is handled or
+            // stored again so a crash in between replays it
+            self.handle_or_dead_letter(&event).await;
+            self.storage.remove_dead_letter(letter.index).await?;
+        }
+        Ok(())
+    }
+
     async fn do_handle_event(&mut self, event: &IndexerEvent) -> IndexerResult<()> {
         info!("do_handle_event,event:{:?}", event);
         match event {
             IndexerEvent::NewTxComing(data, _) => {
                 self.do_handle_new_tx_coming(data, false).await?;
             }
-            IndexerEvent::GetBalance(address, tx) => {
-                self.do_handle_get_balance(address, tx).await?;
+            IndexerEvent::GetBalance(address, token, tx) => {
+                self.do_handle_get_balance(address, token, tx).await?;
+            }
+            IndexerEvent::GetBalances(keys, tx) => {
+                let ret = self.storage.get_balances(keys).await;
+                if tx.send(ret).is_err() {
+                    warn!("get_balances receiver dropped,keys:{}", keys.len());
+                }
+            }
+            IndexerEvent::BroadcastTransaction(data, tx) => {
+                let ret = self.do_handle_broadcast(data).await;
+                if tx.send(ret).is_err() {
+                    warn!("broadcast receiver dropped,tx_id:{}", data.txid());
+                }
+            }
+            IndexerEvent::SimulateDelta(delta, tx) => {
+                let ret = simulate_delta(&mut self.storage, delta).await;
+                if tx.send(ret).is_err() {
+                    warn!("simulate_delta receiver dropped,tx_id:{:?}", delta.tx_id);
+                }
+            }
+            IndexerEvent::GetStatus(tx) => {
+                let status = self.status().await;
+                if tx.send(status).is_err() {
+                    warn!("get_status receiver dropped");
+                }
+            }
+            IndexerEvent::GetHealth(tx) => {
+                let health = self.health().await;
+                if tx.send(health).is_err() {
+                    warn!("get_health receiver dropped");
+                }
+            }
+            IndexerEvent::Reload(cfg, tx) => {
+                let ret = self.do_handle_reload(cfg).await;
+                if tx.send(ret).is_err() {
+                    warn!("reload receiver dropped");
+                }
+            }
+            IndexerEvent::GetBlock(id, tx) => {
+                let ret = find_block(&self.block_cache, self.source.as_ref(), id).await;
+                if tx.send(ret).is_err() {
+                    warn!("get_block receiver dropped,block:{:?}", id);
+                }
+            }
+            IndexerEvent::GetTransaction(tx_id, tx) => {
+                let ret = find_transaction(&mut self.storage, self.source.as_ref(), tx_id).await;
+                if tx.send(ret).is_err() {
+                    warn!("get_transaction receiver dropped,tx_id:{:?}", tx_id);
+                }
             }
             IndexerEvent::UpdateDelta(data) => {
                 self.do_handle_update_delta(data).await?;
             }
+            IndexerEvent::UpdateDeltas(data) => {
+                self.storage.add_transaction_deltas(data).await?;
+                for delta in data {
+                    if let Some((_, block)) = self.early_confirmed.remove(&delta.tx_id) {
+                        self.confirm_and_notify(&delta.tx_id, block).await?;
+                    }
+                }
+            }
             IndexerEvent::TxConfirmed(tx_id) => {
                 self.do_handle_tx_confirmed(tx_id, DeltaStatus::Confirmed)
                     .await?;
@@ -216,8 +561,71 @@ impl<T: StorageProcessor> IndexerProcessorImpl<T> {
             IndexerEvent::ReportHeight(h) => {
                 self.do_handle_block_catch_up(h).await?;
             }
-            IndexerEvent::ReportReorg(v) => {
-                self.do_handle_report_reorg(*v).await?;
+            IndexerEvent::ReportReorg(report) => {
+                self.do_handle_reorg_report(report).await?;
+            }
+            IndexerEvent::RawBlockComing(block, _) => {
+                self.do_handle_raw_block(block).await?;
+            }
+            IndexerEvent::BlockConnected(hash) => {
+                self.do_handle_block_connected(hash).await?;
+            }
+            IndexerEvent::HeaderConnected(header, height) => {
+                self.do_handle_header_connected(header, *height).await?;
+            }
+            IndexerEvent::BlockDisconnected(hash) => {
+                self.do_handle_block_disconnected(hash).await?;
+            }
+            IndexerEvent::SequenceGap(_, _) => {
+                self.do_handle_sequence_gap().await?;
+            }
+            IndexerEvent::IngestDegraded(reason) => {
+                warn!("ingest degraded:{}", reason);
+                self.notify(ClientEvent::IngestDegraded(reason.clone()))
+                    .await;
+            }
+            IndexerEvent::IngestRestored => {
+                info!("ingest restored");
+                self.notify(ClientEvent::IngestRestored).await;
+            }
+            IndexerEvent::ComponentFailed(component, reason) => {
+                self.notify(ClientEvent::ComponentFailed(
+                    component.clone(),
+                    reason.clone(),
+                ))
+                .await;
+            }
+            IndexerEvent::TopicMessage(topic, body) => {
+                self.notify(ClientEvent::TopicMessage(topic.clone(), body.clone()))
+                    .await;
+            }
+            // handled before,replaying re-enters this function
+            IndexerEvent::ReplayDeadLetters | IndexerEvent::Pause | IndexerEvent::Resume => {}
+            IndexerEvent::ReplayJournal(consumer, seq) => {
+                self.do_replay_journal(*consumer, *seq).await?;
+            }
+            IndexerEvent::FlushBatch => {
+                self.flush_batch().await;
+            }
+            IndexerEvent::Subscribe(consumer, filter) => {
+                info!("subscribe,consumer:{},filter:{:?}", consumer, filter);
+                self.consumer(*consumer)?.subscription = Some(Subscription::new(filter.clone()));
+            }
+            IndexerEvent::CommitCursor(consumer, seq) => {
+                self.consumer(*consumer)?;
+                self.storage.set_cursor(*consumer, *seq).await?;
+            }
+            IndexerEvent::Ack(consumer, tx_id) => {
+                self.consumer(*consumer)?.in_flight.ack(tx_id);
+            }
+            IndexerEvent::Nack(consumer, tx_id) => {
+                let consumer = self.consumer(*consumer)?;
+                if let Some(event) = consumer.in_flight.nack(tx_id, Instant::now()) {
+                    consumer.send(event).await;
+                }
+            }
+            IndexerEvent::RedeliverExpired => {
+                self.do_redeliver_expired().await;
             }
         }
         Ok(())
@@ -231,16 +639,28 @@ impl<T: StorageProcessor> IndexerProcessorImpl<T> {
     ) -> IndexerResult<()> {
         let data = self.parse_zmq_data(&data);
         if let Some((tx_id, tx)) = data {
-            let seen = self.storage.seen_and_store_txs(&tx).await?;
+            set_log_field("txid", tx_id.0.clone());
+            let seen = self.storage.seen_and_store_txs(&tx, from_restore).await?;
             if seen.is_seen() {
                 if from_restore {
                     if seen.is_executed() {
                         info!("tx_id:{:?} is seen and  has been executed,skip", tx_id);
                         return Ok(());
+                    } else if seen.re_announced() > MAX_RE_DISPATCH {
+                        // the client keeps ignoring it,dont flood it on every restore
+                        warn!(
+                            "tx_id:{:?} first seen at {} has been re-announced {} times without being executed,skip",
+                            tx_id,
+                            seen.first_seen(),
+                            seen.re_announced()
+                        );
+                        return Ok(());
                     } else {
                         info!(
-                            "tx_id:{:?} from restore  is seen but  has not been executed,start to dispatch",
-                            tx_id
+                            "tx_id:{:?} from restore  is seen but  has not been executed,start to dispatch,re-announced:{},first seen from restore:{}",
+                            tx_id,
+                            seen.re_announced(),
+                            seen.is_from_restore()
                         );
                     }
                 } else {
@@ -249,9 +669,13 @@ impl<T: StorageProcessor> IndexerProcessorImpl<T> {
                 }
             } else {
                 info!("tx_id:{:?} has not been executed,start to dispatch", tx_id);
+                self.detect_replacement(&tx_id, &tx).await?;
                 self.analyse_transaction(&tx);
+                if self.config.track_utxos {
+                    self.track_utxos(&tx_id, &tx).await?;
+                }
             }
-            let latest_chain_height = self.get_latest_chain_height()?;
+            let latest_chain_height = self.get_latest_chain_height().await?;
             let latest_indexer_height = self.get_current_indexer_height();
 
             if latest_chain_height > latest_indexer_height {
@@ -266,11 +690,339 @@ impl<T: StorageProcessor> IndexerProcessorImpl<T> {
             // self.storage
             //     .save_height_tx(latest_indexer_height, tx_id.clone())
             //     .await?;
-            self.tx.send(ClientEvent::Transaction(tx)).await.unwrap();
+            let tx = self.with_mempool_entry(tx).await;
+            self.dispatch_transaction(tx).await;
+        }
+        Ok(())
+    }
+
+    // dispatched here,the zmq announcement of the tx is skipped as seen
+    async fn do_handle_broadcast(&mut self, tx: &Transaction) -> IndexerResult<TxIdType> {
+        let tx_id: TxIdType = self.btc_client.send_raw_transaction(tx)?.into();
+        info!("broadcast tx:{:?}", tx_id);
+        let seen = self.storage.seen_and_store_txs(tx, false).await?;
+        if !seen.is_seen() {
+            self.detect_replacement(&tx_id, tx).await?;
+            self.analyse_transaction(tx);
+            if self.config.track_utxos {
+                self.track_utxos(&tx_id, tx).await?;
+            }
+            let tx = self.with_mempool_entry(tx.clone()).await;
+            self.dispatch_transaction(tx).await;
+        }
+        Ok(tx_id)
+    }
+
+    async fn track_utxos(&mut self, tx_id: &TxIdType, tx: &Transaction) -> IndexerResult<()> {
+        let created = tx
+            .output
+            .iter()
+            .enumerate()
+            .map(|(vout, out)| Utxo {
+                tx_id: tx_id.clone(),
+                vout: vout as u32,
+                address: AddressType::from_bytes(out.script_pubkey.as_bytes()),
+                value: out.value,
+            })
+            .collect();
+        let spent = self.resolve_prevouts(tx).await?;
+        self.storage.apply_utxos(tx_id, created, spent).await
+    }
+
+    // outputs spent by tx,from the local utxo index or rpc
+    async fn resolve_prevouts(&mut self, tx: &Transaction) -> IndexerResult<Vec<Utxo>> {
+        let mut ret = vec![];
+        for input in &tx.input {
+            if input.previous_output.is_null() {
+                continue;
+            }
+            let prev_tx_id: TxIdType = input.previous_output.txid.into();
+            let vout = input.previous_output.vout;
+            if let Some(utxo) = self.storage.get_utxo(&prev_tx_id, vout).await? {
+                ret.push(utxo);
+                continue;
+            }
+            // outputs created before tracking started
+            let prev = self
+                .source
+                .get_transaction(&input.previous_output.txid)
+                .await?;
+            let Some(prev) = prev else {
+                warn!("prevout tx {} not found", input.previous_output.txid);
+                continue;
+            };
+            if let Some(out) = prev.output.get(vout as usize) {
+                ret.push(Utxo {
+                    tx_id: prev_tx_id,
+                    vout,
+                    address: AddressType::from_bytes(out.script_pubkey.as_bytes()),
+                    value: out.value,
+                });
+            }
+        }
+        Ok(ret)
+    }
+
+    // every event for the client goes through here to be journaled
+    async fn notify(&mut self, event: ClientEvent) {
+        if self.config.client.event_journal {
+            match self.storage.append_journal(&event.to_bytes()).await {
+                Ok(seq) => self.retain_journal(seq).await,
+                Err(e) => error!("journal event error:{:?},event:{:?}", e, event),
+            }
+        }
+        let (spent, tokens) = self.filter_inputs(&event).await;
+        let tracked = match &event {
+            ClientEvent::Transaction(v) => Some(v.tx.txid().into()),
+            ClientEvent::RichTransaction(v) => Some(v.tx.tx.txid().into()),
+            _ => None,
+        }
+        .filter(|_: &TxIdType| self.config.client.ack_timeout_secs > 0);
+        // nothing is left to handle for these
+        let settled = match &event {
+            ClientEvent::TxDroped(v)
+            | ClientEvent::TxReplaced { old: v, .. }
+            | ClientEvent::TxConflicted(v, _) => Some(v),
+            _ => None,
+        };
+        for i in 0..self.consumers.len() {
+            let consumer = &mut self.consumers[i];
+            if let Some(tx_id) = settled {
+                consumer.in_flight.ack(tx_id);
+            }
+            if let Some(sub) = &mut consumer.subscription {
+                if !sub.accept(&event, &spent, &tokens) {
+                    continue;
+                }
+            }
+            if let Some(tx_id) = &tracked {
+                consumer
+                    .in_flight
+                    .track(tx_id.clone(), event.clone(), Instant::now());
+            }
+            if self.batching {
+                consumer.batch.push(event.clone());
+            } else {
+                consumer.send(event.clone()).await;
+            }
+        }
+    }
+
+    async fn do_redeliver_expired(&mut self) {
+        let timeout = Duration::from_secs(self.config.client.ack_timeout_secs);
+        let now = Instant::now();
+        for (i, consumer) in self.consumers.iter_mut().enumerate() {
+            let expired = consumer.in_flight.expired(now, timeout);
+            if !expired.is_empty() {
+                warn!(
+                    "redeliver {} unacknowledged txs to consumer:{}",
+                    expired.len(),
+                    i
+                );
+            }
+            for event in expired {
+                consumer.send(event).await;
+            }
+        }
+    }
+
+    fn consumer(&mut self, consumer: u32) -> IndexerResult<&mut Consumer> {
+        self.consumers
+            .get_mut(consumer as usize)
+            .ok_or(IndexerError::UnknownConsumer(consumer))
+    }
+
+    // the spent scripts and the delta tokens the filters need,looked up once for all consumers.
+    // lookups are best effort,a failed one is treated as no match
+    async fn filter_inputs(&mut self, event: &ClientEvent) -> (Vec<AddressType>, Vec<TokenType>) {
+        let filters: Vec<EventFilter> = self
+            .consumers
+            .iter()
+            .filter_map(|v| v.subscription.as_ref().map(|v| v.filter.clone()))
+            .collect();
+        let mut spent = vec![];
+        if let ClientEvent::Transaction(tx) = event {
+            if filters.iter().any(|v| v.needs_prevouts(event)) {
+                let tx = tx.tx.clone();
+                spent = self
+                    .resolve_prevouts(&tx)
+                    .await
+                    .map(|v| v.into_iter().map(|v| v.address).collect())
+                    .unwrap_or_default();
+            }
+        }
+        let mut tokens = vec![];
+        if let Some(tx_id) =
+            subject_tx(event).filter(|_| filters.iter().any(|v| !v.tokens.is_empty()))
+        {
+            if let Ok(Some(delta)) = self.storage.get_transaction_delta(tx_id).await {
+                tokens = delta
+                    .data
+                    .deltas
+                    .values()
+                    .flatten()
+                    .map(|(token, _)| token.clone())
+                    .collect();
+            }
+        }
+        (spent, tokens)
+    }
+
+    fn begin_batch(&mut self) {
+        if self.config.client.batch_delivery {
+            self.batching = true;
+        }
+    }
+
+    async fn flush_batch(&mut self) {
+        self.batching = false;
+        for consumer in self.consumers.iter_mut() {
+            consumer.flush_batch().await;
         }
+    }
+
+    async fn retain_journal(&mut self, seq: u64) {
+        let retention = self.config.client.journal_retention;
+        if retention == 0 || seq < retention {
+            return;
+        }
+        if let Err(e) = self.storage.truncate_journal(seq + 1 - retention).await {
+            error!("truncate journal error:{:?},seq:{}", e, seq);
+        }
+    }
+
+    // only the consumer asking for it gets the replayed events
+    async fn do_replay_journal(&mut self, consumer: u32, from_seq: u64) -> IndexerResult<()> {
+        self.consumer(consumer)?;
+        const PAGE: usize = 1000;
+        let mut seq = from_seq;
+        loop {
+            let records = self.storage.get_journal(seq, PAGE).await?;
+            for (_, data) in &records {
+                let event = ClientEvent::from_bytes(data)?;
+                let (spent, tokens) = self.filter_inputs(&event).await;
+                let target = &mut self.consumers[consumer as usize];
+                if let Some(sub) = &mut target.subscription {
+                    if !sub.accept(&event, &spent, &tokens) {
+                        continue;
+                    }
+                }
+                if target.tx.send(event).await.is_err() {
+                    warn!("consumer:{} is gone,stop replaying the journal", consumer);
+                    return Ok(());
+                }
+            }
+            match records.last() {
+                Some((last, _)) if records.len() == PAGE => seq = last + 1,
+                _ => break,
+            }
+        }
+        info!(
+            "replayed journal to consumer:{} from seq:{} to seq:{}",
+            consumer, from_seq, seq
+        );
         Ok(())
     }
 
+    async fn dispatch_transaction(&mut self, tx: MempoolTransaction) {
+        if !self.config.enrich_prevouts {
+            self.notify(ClientEvent::Transaction(tx)).await;
+            return;
+        }
+        match self.resolve_prevouts(&tx.tx).await {
+            Ok(prevouts) => {
+                self.notify(ClientEvent::RichTransaction(RichTransaction {
+                    tx,
+                    prevouts,
+                }))
+                .await
+            }
+            Err(e) => {
+                // the tx is still delivered,just without prevouts
+                warn!("resolve prevouts of {} failed:{:?}", tx.tx.txid(), e);
+                self.notify(ClientEvent::Transaction(tx)).await;
+            }
+        }
+    }
+
+    // a tx spending the inputs of an earlier dispatched one replaces it (rbf)
+    async fn detect_replacement(
+        &mut self,
+        tx_id: &TxIdType,
+        tx: &Transaction,
+    ) -> IndexerResult<()> {
+        let mut replaced = vec![];
+        for input in &tx.input {
+            if let Some(old) = self.spent_outpoints.get(&input.previous_output) {
+                if old != tx_id && !replaced.contains(old) {
+                    replaced.push(old.clone());
+                }
+            }
+        }
+        for old in replaced {
+            warn!("tx_id:{:?} is replaced by tx_id:{:?}", old, tx_id);
+            self.do_handle_tx_confirmed(&old, DeltaStatus::InActive)
+                .await?;
+            self.notify(ClientEvent::TxReplaced {
+                old,
+                new: tx_id.clone(),
+            })
+            .await;
+        }
+        let outpoints: Vec<OutPoint> = tx.input.iter().map(|v| v.previous_output).collect();
+        for outpoint in &outpoints {
+            self.spent_outpoints.insert(*outpoint, tx_id.clone());
+        }
+        self.tx_outpoints.insert(tx_id.clone(), outpoints);
+        Ok(())
+    }
+    // a mined tx spending an input of a pending tx,unlike an rbf the loser never made it
+    async fn detect_conflicts(&mut self, tx_id: &TxIdType, tx: &Transaction) -> IndexerResult<()> {
+        let mut conflicted = vec![];
+        for input in &tx.input {
+            if let Some(pending) = self.spent_outpoints.get(&input.previous_output) {
+                if pending != tx_id && !conflicted.contains(pending) {
+                    conflicted.push(pending.clone());
+                }
+            }
+        }
+        for pending in conflicted {
+            warn!(
+                "tx_id:{:?} conflicts with the mined tx_id:{:?}",
+                pending, tx_id
+            );
+            self.do_handle_tx_confirmed(&pending, DeltaStatus::InActive)
+                .await?;
+            self.notify(ClientEvent::TxConflicted(pending, tx_id.clone()))
+                .await;
+        }
+        Ok(())
+    }
+    fn forget_outpoints(&mut self, tx_id: &TxIdType) {
+        if let Some(outpoints) = self.tx_outpoints.remove(tx_id) {
+            for outpoint in outpoints {
+                if self.spent_outpoints.get(&outpoint) == Some(tx_id) {
+                    self.spent_outpoints.remove(&outpoint);
+                }
+            }
+        }
+    }
+    // prefetched with the restored txs,asked from the source otherwise.
+    // e.g. already mined or evicted txs have none
+    async fn with_mempool_entry(&mut self, tx: Transaction) -> MempoolTransaction {
+        let txid = tx.txid();
+        let entry = match self.prefetched_entries.remove(&txid.into()) {
+            Some(v) => Some(v),
+            None => match self.source.get_mempool_entry(&txid).await {
+                Ok(v) => v,
+                Err(e) => {
+                    warn!("get mempool entry of {} failed:{:?}", txid, e);
+                    None
+                }
+            },
+        };
+        MempoolTransaction::with_entry(tx, entry)
+    }
     fn analyse_transaction(&mut self, tx: &Transaction) {
         let tx_id: TxIdType = tx.txid().into();
         let node = self.analyses.get(&tx_id);
@@ -312,7 +1064,7 @@ impl<T: StorageProcessor> IndexerProcessorImpl<T> {
         }
         ret
     }
-    fn get_latest_chain_height(&mut self) -> IndexerResult<u32> {
+    async fn get_latest_chain_height(&mut self) -> IndexerResult<u32> {
         let dt = Local::now();
         let now = dt.timestamp();
         if let Some((h, ts)) = self.current_chain_latest_height {
@@ -321,10 +1073,36 @@ impl<T: StorageProcessor> IndexerProcessorImpl<T> {
                 return Ok(h);
             }
         }
-        let height = self.btc_client.get_block_count()? as u32;
+        let height = self.source.get_tip_height().await?;
         self.current_chain_latest_height = Some((height, now));
         Ok(height)
     }
+    async fn status(&mut self) -> StatusResponse {
+        let chain_height = match self.source.get_tip_height().await {
+            Ok(h) => {
+                self.current_chain_latest_height = Some((h, Local::now().timestamp()));
+                Some(h)
+            }
+            Err(e) => {
+                warn!("get block count failed:{:?}", e);
+                None
+            }
+        };
+        StatusResponse {
+            chain_height,
+            indexed_height: self.current_indexer_height,
+            restoring: !self.flag.load(Ordering::Relaxed),
+            paused: self.paused,
+            pending_events: self.grap_rx.len(),
+            undelivered_events: self
+                .consumers
+                .iter()
+                .map(|v| v.tx.len() + v.priority_tx.len())
+                .sum(),
+            zmq_connected: self.zmq_connected.load(Ordering::Relaxed),
+            configuration: serde_json::to_value(self.config.redacted()).unwrap_or_default(),
+        }
+    }
     fn get_current_indexer_height(&mut self) -> u32 {
         self.current_indexer_height.unwrap()
     }
@@ -333,46 +1111,562 @@ impl<T: StorageProcessor> IndexerProcessorImpl<T> {
         Some((tx.txid().into(), tx))
     }
 
+    // stored balances already include the deltas of executed but unconfirmed txs
     pub(crate) async fn do_handle_get_balance(
-        &self,
-        _: &AddressType,
-        _: &crossbeam::channel::Sender<BalanceType>,
+        &mut self,
+        address: &AddressType,
+        token: &TokenType,
+        tx: &BalanceSender,
     ) -> IndexerResult<()> {
-        todo!()
+        let balance = self.storage.get_balance(address, token).await?;
+        if let Err(e) = tx.send(balance) {
+            warn!(
+                "get_balance receiver dropped,address:{:?},err:{:?}",
+                address, e
+            );
+        }
+        Ok(())
     }
 
     async fn do_handle_update_delta(&mut self, data: &TransactionDelta) -> IndexerResult<()> {
-        self.storage.add_transaction_delta(data).await?;
+        self.storage.begin_tx().await?;
+        if let Err(e) = self.storage.add_transaction_delta(data).await {
+            self.storage.rollback().await?;
+            return Err(e);
+        }
+        self.storage.commit().await?;
+        if let Some((_, block)) = self.early_confirmed.remove(&data.tx_id) {
+            self.confirm_and_notify(&data.tx_id, block).await?;
+        }
         Ok(())
     }
     async fn do_handle_tx_confirmed(
         &mut self,
         tx_id: &TxIdType,
-        _: DeltaStatus,
+        status: DeltaStatus,
     ) -> IndexerResult<()> {
+        set_log_field("txid", tx_id.0.clone());
         info!("do_handle_tx_confirmed,tx_id:{:?}", tx_id);
+        if self.config.track_utxos {
+            let revert = matches!(status, DeltaStatus::InActive);
+            self.storage.settle_utxos(tx_id, revert).await?;
+        }
+        self.storage.remove_transaction_delta(tx_id, status).await?;
         self.storage.remove_tx_traces(vec![tx_id.clone()]).await?;
         self.analyses.remove(tx_id);
+        self.forget_outpoints(tx_id);
         Ok(())
     }
     async fn do_handle_restore_tx_by_tx_id(&mut self, tx_id: &TxIdType) -> IndexerResult<()> {
         let txid: Txid = tx_id.clone().into();
+        set_log_field("txid", tx_id.0.clone());
         info!("do_handle_force_tx_by_tx_id,txid:{:?}", txid);
-        let transaction = self.btc_client.get_raw_transaction(&txid, None)?;
+        let transaction = match self.prefetched.remove(tx_id) {
+            Some(tx) => Some(tx),
+            None => self.source.get_transaction(&txid).await?,
+        };
+        let Some(transaction) = transaction else {
+            return self.do_handle_missing_restore_tx(tx_id).await;
+        };
         let data = serialize(&transaction);
         self.do_handle_new_tx_coming(&data, true).await?;
 
         Ok(())
     }
+    // evicted from the mempool or mined. without txindex the blocks are searched: the recent ones
+    // first,then the ones mined since the tx was first seen,a restart forgot the recent ones
+    async fn do_handle_missing_restore_tx(&mut self, tx_id: &TxIdType) -> IndexerResult<()> {
+        let txid: Txid = tx_id.clone().into();
+        for hash in self.connected_blocks.clone().iter().rev() {
+            let block = find_block(
+                &self.block_cache,
+                self.source.as_ref(),
+                &BlockId::Hash(*hash),
+            )
+            .await?;
+            let mined = block.and_then(|v| v.txdata.into_iter().find(|v| v.txid() == txid));
+            if let Some(transaction) = mined {
+                // confirmed through the pending confirmations of its block
+                info!("restore tx:{:?} was mined in block:{}", tx_id, hash);
+                let data = serialize(&transaction);
+                return self.do_handle_new_tx_coming(&data, true).await;
+            }
+        }
+        let first_seen = self.storage.seen_tx(tx_id.clone()).await?.first_seen();
+        let source = self.source.clone();
+        let mined = self
+            .mined_txs
+            .find(source.as_ref(), &txid, first_seen)
+            .await?;
+        if let Some((block, transaction)) = mined {
+            info!(
+                "restore tx:{:?} was mined in block:{} at height:{}",
+                tx_id, block.hash, block.height
+            );
+            let data = serialize(&transaction);
+            self.do_handle_new_tx_coming(&data, true).await?;
+            return self.queue_confirmation(tx_id, block).await;
+        }
+        warn!(
+            "restore tx:{:?} is neither in the mempool nor in the blocks since it was seen,mark it inactive",
+            tx_id
+        );
+        self.do_handle_tx_removed(tx_id).await
+    }
+    // a tx mined in a block which is not connected again,e.g. while the sdk was down
+    async fn queue_confirmation(
+        &mut self,
+        tx_id: &TxIdType,
+        block: BlockContext,
+    ) -> IndexerResult<()> {
+        let tip = self.source.get_tip_height().await?;
+        let depth = self.config.confirmations.max(1);
+        if block.height.saturating_add(depth - 1) <= tip {
+            if self.storage.is_tx_executed(tx_id).await? {
+                return self.confirm_and_notify(tx_id, block).await;
+            }
+            // confirmed as soon as the client pushes its delta
+            self.early_confirmed.insert(tx_id.clone(), (tip, block));
+            return self.save_chain_state().await;
+        }
+        let entry = (tx_id.clone(), block.position);
+        match self
+            .pending_confirmations
+            .iter_mut()
+            .find(|(_, h, _)| h == &block.hash)
+        {
+            Some((_, _, txs)) => txs.push(entry),
+            None => {
+                let at = self
+                    .pending_confirmations
+                    .iter()
+                    .position(|(h, _, _)| *h > block.height)
+                    .unwrap_or(self.pending_confirmations.len());
+                self.pending_confirmations
+                    .insert(at, (block.height, block.hash, vec![entry]));
+            }
+        }
+        self.save_chain_state().await
+    }
+    async fn do_handle_block_connected(&mut self, hash: &BlockHash) -> IndexerResult<()> {
+        if self.connected_blocks.contains(hash) {
+            return Ok(());
+        }
+        let (Some(block), Some(height)) = (
+            self.source.get_block(hash).await?,
+            self.source.get_block_height(hash).await?,
+        ) else {
+            warn!("connected block {} not found", hash);
+            return Ok(());
+        };
+        self.do_handle_block(height, &block).await
+    }
+    // the block holds no watched script,its txs are skipped but the chain and the confirmations
+    // still advance
+    async fn do_handle_header_connected(
+        &mut self,
+        header: &Header,
+        height: u32,
+    ) -> IndexerResult<()> {
+        set_log_field("height", height);
+        let hash = header.block_hash();
+        if self.headers.contains(&hash) {
+            return Ok(());
+        }
+        if self
+            .headers
+            .tip()
+            .is_some_and(|v| v.hash != header.prev_blockhash)
+        {
+            // a reorg needs the whole block
+            return self.do_handle_block_connected(&hash).await;
+        }
+        self.verify_quorum(height, &hash).await?;
+        let header = ChainHeader {
+            height,
+            hash,
+            prev_hash: header.prev_blockhash,
+        };
+        // the tip was checked above,an ancestor lookup means the chain moved under us
+        self.headers.connect(header, |prev| {
+            Err(IndexerError::UnknownHeader(prev.to_string()))
+        })?;
+        self.begin_batch();
+        self.settle_confirmations(height).await?;
+        self.settle_coinbases(height).await?;
+        self.flush_batch().await;
+        self.save_chain_state().await
+    }
+    async fn do_handle_raw_block(&mut self, block: &Block) -> IndexerResult<()> {
+        let hash = block.block_hash();
+        if self.connected_blocks.contains(&hash) {
+            return Ok(());
+        }
+        let height = match block.bip34_block_height() {
+            Ok(h) => h as u32,
+            Err(_) => self
+                .source
+                .get_block_height(&hash)
+                .await?
+                .ok_or_else(|| IndexerError::UnknownHeader(hash.to_string()))?,
+        };
+        self.do_handle_block(height, block).await
+    }
+    async fn do_handle_block(&mut self, height: u32, block: &Block) -> IndexerResult<()> {
+        set_log_field("height", height);
+        let hash = block.block_hash();
+        info!(
+            "block connected,height:{},hash:{},txs:{}",
+            height,
+            hash,
+            block.txdata.len()
+        );
+        self.verify_quorum(height, &hash).await?;
+        let reorg = self.track_header(height, block).await?;
+        if let Some((fork_height, disconnected)) = &reorg {
+            self.recover_reorg(*fork_height, disconnected, height, block)
+                .await?;
+        }
+        self.connect_block(height, block).await?;
+        if let Some((fork_height, _)) = reorg {
+            self.notify(ClientEvent::Reorg {
+                from_height: fork_height,
+                to_height: height,
+            })
+            .await;
+            // the indexer already executed the replaced heights
+            if self
+                .current_indexer_height
+                .is_some_and(|h| h >= fork_height)
+            {
+                self.do_handle_report_reorg(fork_height).await?;
+            }
+        }
+        Ok(())
+    }
+    // dispatches the unseen txs of a block on the best chain and queues their confirmations
+    async fn connect_block(&mut self, height: u32, block: &Block) -> IndexerResult<()> {
+        let hash = block.block_hash();
+        self.connected_blocks.push_back(hash);
+        while self.connected_blocks.len()
+            > self.config.storage.save_block_cache_count.max(1) as usize
+        {
+            self.connected_blocks.pop_front();
+        }
+        let mut txs = vec![];
+        self.begin_batch();
+        for tx in &block.txdata {
+            let tx_id: TxIdType = tx.txid().into();
+            self.detect_conflicts(&tx_id, tx).await?;
+            // txs which never reached our mempool view
+            let seen = self.storage.seen_and_store_txs(tx, false).await?;
+            if tx.is_coin_base() {
+                // credits are held back until the coinbase can be spent
+                if !seen.is_seen() {
+                    self.immature_coinbases
+                        .push_back((height, hash, tx.clone()));
+                }
+                txs.push(tx_id.clone());
+                continue;
+            }
+            if !seen.is_seen() {
+                info!("tx_id:{:?} first seen in block {},dispatch", tx_id, hash);
+                self.analyse_transaction(tx);
+                if self.config.track_utxos {
+                    self.track_utxos(&tx_id, tx).await?;
+                }
+                self.dispatch_transaction(MempoolTransaction::new(tx.clone()))
+                    .await;
+            }
+            txs.push(tx_id);
+        }
+        // the coinbase is always the first tx,it is confirmed once matured
+        let confirmable = txs
+            .iter()
+            .enumerate()
+            .skip(1)
+            .map(|(i, v)| (v.clone(), i as u32))
+            .collect();
+        self.pending_confirmations
+            .push_back((height, hash, confirmable));
+        self.block_txs.insert(hash, txs.clone());
+        self.block_cache.insert(height, block.clone());
+        self.notify(ClientEvent::BlockConnected(height, hash, txs))
+            .await;
+        self.settle_confirmations(height).await?;
+        self.settle_coinbases(height).await?;
+        self.flush_batch().await;
+        let keep = self.config.storage.save_block_cache_count;
+        self.early_confirmed
+            .retain(|_, (h, _)| h.saturating_add(keep) >= height);
+        self.save_chain_state().await
+    }
+    // matured coinbases are dispatched and confirmed once the client pushes their delta
+    async fn settle_coinbases(&mut self, height: u32) -> IndexerResult<()> {
+        while let Some((h, _, _)) = self.immature_coinbases.front() {
+            if h.saturating_add(COINBASE_MATURITY - 1) > height {
+                break;
+            }
+            let (h, hash, tx) = self.immature_coinbases.pop_front().unwrap();
+            let tx_id: TxIdType = tx.txid().into();
+            info!("coinbase tx_id:{:?} matured at height:{}", tx_id, height);
+            self.analyse_transaction(&tx);
+            if self.config.track_utxos {
+                self.track_utxos(&tx_id, &tx).await?;
+            }
+            self.dispatch_transaction(MempoolTransaction::new(tx)).await;
+            self.notify(ClientEvent::CoinbaseMatured(tx_id.clone()))
+                .await;
+            let block = BlockContext {
+                height: h,
+                hash,
+                position: 0,
+            };
+            self.early_confirmed.insert(tx_id, (height, block));
+        }
+        Ok(())
+    }
+    async fn save_chain_state(&mut self) -> IndexerResult<()> {
+        let state = ChainState {
+            immature_coinbases: self.immature_coinbases.clone(),
+            pending_confirmations: self.pending_confirmations.clone(),
+            early_confirmed: self.early_confirmed.clone().into_iter().collect(),
+        };
+        self.storage.set_chain_state(&state.encode()).await
+    }
+    // the state the last run saved,its blocks are not connected again
+    async fn load_chain_state(&mut self) -> IndexerResult<()> {
+        let Some(data) = self.storage.get_chain_state().await? else {
+            return Ok(());
+        };
+        let state = ChainState::decode(&data)?;
+        info!(
+            "loaded chain state,immature coinbases:{},pending blocks:{},early confirmed:{}",
+            state.immature_coinbases.len(),
+            state.pending_confirmations.len(),
+            state.early_confirmed.len()
+        );
+        self.immature_coinbases = state.immature_coinbases;
+        self.pending_confirmations = state.pending_confirmations;
+        self.early_confirmed = state.early_confirmed.into_iter().collect();
+        Ok(())
+    }
+    // txs of the blocks which reached the configured depth at tip height
+    async fn settle_confirmations(&mut self, height: u32) -> IndexerResult<()> {
+        let depth = self.config.confirmations.max(1);
+        while let Some((h, _, _)) = self.pending_confirmations.front() {
+            if h.saturating_add(depth - 1) > height {
+                break;
+            }
+            let (h, hash, txs) = self.pending_confirmations.pop_front().unwrap();
+            for (tx_id, position) in txs {
+                let block = BlockContext {
+                    height: h,
+                    hash,
+                    position,
+                };
+                if self.storage.is_tx_executed(&tx_id).await? {
+                    self.confirm_and_notify(&tx_id, block).await?;
+                } else {
+                    // confirmed as soon as the client pushes its delta
+                    self.early_confirmed.insert(tx_id, (h, block));
+                }
+            }
+        }
+        Ok(())
+    }
+    async fn confirm_and_notify(
+        &mut self,
+        tx_id: &TxIdType,
+        block: BlockContext,
+    ) -> IndexerResult<()> {
+        self.do_handle_tx_confirmed(tx_id, DeltaStatus::Confirmed)
+            .await?;
+        self.notify(ClientEvent::TxConfirmed(tx_id.clone(), block))
+            .await;
+        Ok(())
+    }
+    // returns the fork height and the disconnected headers when the block forked the chain
+    async fn track_header(
+        &mut self,
+        height: u32,
+        block: &Block,
+    ) -> IndexerResult<Option<(u32, Vec<ChainHeader>)>> {
+        let header = ChainHeader {
+            height,
+            hash: block.block_hash(),
+            prev_hash: block.header.prev_blockhash,
+        };
+        // the source is async,the ancestors connect asks for are fetched and the connect retried
+        let mut fetched: HashMap<BlockHash, ChainHeader> = HashMap::new();
+        let update = loop {
+            let mut missing = None;
+            let ret = self
+                .headers
+                .connect(header.clone(), |hash| match fetched.get(hash) {
+                    Some(v) => Ok(v.clone()),
+                    None => {
+                        missing = Some(*hash);
+                        Err(IndexerError::UnknownHeader(hash.to_string()))
+                    }
+                });
+            let Some(hash) = missing.filter(|_| ret.is_err()) else {
+                break ret?;
+            };
+            let (Some(prev), Some(height)) = (
+                self.source.get_block(&hash).await?,
+                self.source.get_block_height(&hash).await?,
+            ) else {
+                return Err(IndexerError::UnknownHeader(hash.to_string()));
+            };
+            let prev_hash = prev.header.prev_blockhash;
+            fetched.insert(
+                hash,
+                ChainHeader {
+                    height,
+                    hash,
+                    prev_hash,
+                },
+            );
+        };
+        if let HeaderUpdate::Reorg(fork_height, disconnected) = update {
+            warn!(
+                "new tip:{} does not extend the previous one,reorg from height:{}",
+                block.block_hash(),
+                fork_height
+            );
+            return Ok(Some((fork_height, disconnected)));
+        }
+        Ok(None)
+    }
+    // rolls back to the common ancestor: txs only the disconnected blocks mined become inactive,
+    // and the blocks of the new branch below the tip are connected again
+    async fn recover_reorg(
+        &mut self,
+        fork_height: u32,
+        disconnected: &[ChainHeader],
+        tip_height: u32,
+        tip: &Block,
+    ) -> IndexerResult<()> {
+        let mut disconnected_txs = vec![];
+        for v in disconnected {
+            self.connected_blocks.retain(|h| h != &v.hash);
+            self.pending_confirmations.retain(|(_, h, _)| h != &v.hash);
+            self.immature_coinbases.retain(|(_, h, _)| h != &v.hash);
+            self.block_cache.remove(&v.hash);
+            if let Some(txs) = self.block_txs.remove(&v.hash) {
+                disconnected_txs.push(txs);
+            }
+            self.notify(ClientEvent::BlockDisconnected(v.height, v.hash))
+                .await;
+        }
+
+        let mut branch = vec![];
+        for v in self.headers.headers_from(fork_height) {
+            if v.height >= tip_height {
+                continue;
+            }
+            let block = self
+                .source
+                .get_block(&v.hash)
+                .await?
+                .ok_or_else(|| IndexerError::UnknownHeader(v.hash.to_string()))?;
+            branch.push((v.height, block));
+        }
+        let mut branch_txs: Vec<Vec<TxIdType>> = branch
+            .iter()
+            .map(|(_, b)| b.txdata.iter().map(|v| v.txid().into()).collect())
+            .collect();
+        branch_txs.push(tip.txdata.iter().map(|v| v.txid().into()).collect());
+
+        // confirmed deltas are beyond the configured confirmations and kept
+        for tx_id in orphaned_txs(&disconnected_txs, &branch_txs) {
+            info!("tx_id:{:?} was only mined by a disconnected block", tx_id);
+            self.early_confirmed.remove(&tx_id);
+            self.do_handle_tx_confirmed(&tx_id, DeltaStatus::InActive)
+                .await?;
+        }
+        for (height, block) in branch {
+            self.connect_block(height, &block).await?;
+        }
+        info!(
+            "reorg recovered,fork height:{},new tip height:{}",
+            fork_height, tip_height
+        );
+        Ok(())
+    }
+    async fn do_handle_block_disconnected(&mut self, hash: &BlockHash) -> IndexerResult<()> {
+        self.connected_blocks.retain(|v| v != hash);
+        self.pending_confirmations.retain(|(_, h, _)| h != hash);
+        self.immature_coinbases.retain(|(_, h, _)| h != hash);
+        self.headers.disconnect(hash);
+        self.block_cache.remove(hash);
+        let height = self
+            .source
+            .get_block_height(hash)
+            .await?
+            .ok_or_else(|| IndexerError::UnknownHeader(hash.to_string()))?;
+        self.save_chain_state().await?;
+        info!("block disconnected,height:{},hash:{}", height, hash);
+        self.notify(ClientEvent::BlockDisconnected(height, *hash))
+            .await;
+        if self.current_indexer_height.is_some_and(|h| h >= height) {
+            self.do_handle_report_reorg(height).await?;
+        }
+        Ok(())
+    }
+    // executed txs are skipped by the restore,so mostly the lost ones are dispatched again
+    async fn do_handle_sequence_gap(&mut self) -> IndexerResult<()> {
+        self.restore_from_mempool(self.grap_tx.clone()).await?;
+        self.notify(ClientEvent::Resynced).await;
+        Ok(())
+    }
     async fn do_handle_tx_removed(&mut self, tx_id: &TxIdType) -> IndexerResult<()> {
         self.do_handle_tx_confirmed(tx_id, DeltaStatus::InActive)
             .await?;
-        self.tx
-            .send(ClientEvent::TxDroped(tx_id.clone()))
-            .await
-            .unwrap();
+        self.notify(ClientEvent::TxDroped(tx_id.clone())).await;
         Ok(())
     }
+    // the executor reverted its own state,the deltas of the affected txs and the checkpoints of
+    // the dead branch are dropped here
+    async fn do_handle_reorg_report(&mut self, report: &ReorgReport) -> IndexerResult<()> {
+        let fork_height = report.fork_height();
+        info!(
+            "reorg reported,fork height:{},old tip:{},new tip:{},affected txs:{}",
+            fork_height,
+            report.old_tip.hash,
+            report.new_tip.hash,
+            report.affected_txs.len()
+        );
+        for v in &report.disconnected_blocks {
+            self.connected_blocks.retain(|h| h != &v.hash);
+            self.pending_confirmations.retain(|(_, h, _)| h != &v.hash);
+            self.immature_coinbases.retain(|(_, h, _)| h != &v.hash);
+            self.headers.disconnect(&v.hash);
+            self.block_cache.remove(&v.hash);
+            self.block_txs.remove(&v.hash);
+        }
+        for tx_id in &report.affected_txs {
+            self.early_confirmed.remove(tx_id);
+            self.do_handle_tx_confirmed(tx_id, DeltaStatus::InActive)
+                .await?;
+        }
+        self.save_chain_state().await?;
+        if let Some(dir) = &self.config.storage.snapshot.snapshot_dir {
+            let removed = Snapshot::remove_from(dir, fork_height)?;
+            info!(
+                "removed {} checkpoints from height:{}",
+                removed, fork_height
+            );
+        }
+        self.storage
+            .update_indexed_height(fork_height.saturating_sub(1))
+            .await?;
+        if self.current_indexer_height.is_none() {
+            return Ok(());
+        }
+        self.do_handle_report_reorg(fork_height).await
+    }
     async fn do_handle_report_reorg(&mut self, org: u32) -> IndexerResult<()> {
         let current_height = self.current_indexer_height.unwrap();
         for i in org..current_height + 1 {
@@ -401,11 +1695,14 @@ impl<T: StorageProcessor> IndexerProcessorImpl<T> {
     async fn clean(&mut self, h: u32) -> IndexerResult<()> {
         self.flag.store(false, Ordering::Relaxed);
         self.analyses.clear();
+        self.spent_outpoints.clear();
+        self.tx_outpoints.clear();
         self.storage.remove_height_traces(h).await?;
         Ok(())
     }
     async fn do_handle_block_catch_up(&mut self, h: &u32) -> IndexerResult<()> {
         self.current_indexer_height = Some(*h);
+        self.storage.update_indexed_height(*h).await?;
         if self.last_indexer_height.is_none() {
             self.last_indexer_height = Some(*h);
         }
@@ -413,9 +1710,14 @@ impl<T: StorageProcessor> IndexerProcessorImpl<T> {
             error!("remove_height_traces error:{:?}", e);
             e
         })?;
-        // if h % self.config.save_block_cache_count == 0 {
+        // a checkpoint is an optimization,indexing goes on without it
+        if let Err(e) = self.try_checkpoint(*h).await {
+            error!("checkpoint at height:{} error:{:?}", h, e);
+        }
+        self.try_notify_checkpoint(*h).await?;
+        // if h % self.config.storage.save_block_cache_count == 0 {
         //     // try to flush
-        //     for i in h - self.config.save_block_cache_count..h + 1 {
+        //     for i in h - self.config.storage.save_block_cache_count..h + 1 {
         //         self.storage.remove_height_traces(*i).await.map_err(|e| {
         //             error!("remove_height_traces error:{:?}", e);
         //             e
@@ -424,7 +1726,824 @@ impl<T: StorageProcessor> IndexerProcessorImpl<T> {
         // }
         Ok(())
     }
+    async fn try_notify_checkpoint(&mut self, h: u32) -> IndexerResult<()> {
+        let interval = self.config.client.checkpoint_interval;
+        if interval == 0 || !h.is_multiple_of(interval) {
+            return Ok(());
+        }
+        let state_root = self.storage.get_state_root().await?;
+        info!("checkpoint event,height:{},state root:{}", h, state_root);
+        self.notify(ClientEvent::Checkpoint {
+            height: h,
+            state_root,
+        })
+        .await;
+        Ok(())
+    }
+    async fn try_checkpoint(&mut self, h: u32) -> IndexerResult<()> {
+        let cfg = &self.config.storage.snapshot;
+        if cfg.snapshot_dir.is_none()
+            || cfg.block_interval == 0
+            || !h.is_multiple_of(cfg.block_interval)
+        {
+            return Ok(());
+        }
+        let dir = cfg.snapshot_dir.clone().unwrap();
+        let snapshot = self.storage.take_snapshot(h).await?;
+        let path = snapshot.save(dir.as_str())?;
+        info!("checkpoint at height:{} saved to {}", h, path);
+        if cfg.keep > 0 {
+            Snapshot::retain_latest(dir.as_str(), cfg.keep as usize)?;
+        }
+        if let Some(remote) = self.snapshot_store.clone() {
+            // uploads and their retries run aside,a failed one must not stop indexing
+            tokio::spawn(async move {
+                if let Err(e) = remote.upload(path.as_str()).await {
+                    error!("upload checkpoint at height:{} error:{:?}", h, e);
+                }
+            });
+        }
+        Ok(())
+    }
 }
 
 #[async_trait::async_trait]
 impl<T: StorageProcessor> IndexProcessor<DispatchEvent> for IndexerProcessorImpl<T> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::BalanceType;
+    use crate::net::mock::{mock_tx, serve_rpc, MockNode};
+    use crate::storage::db::memory::MemoryDB;
+    use crate::storage::kv::KVStorageProcessor;
+    use crate::storage::snapshot::remote::ObjectStore;
+    use bitcoincore_rpc::bitcoin::absolute::LockTime;
+    use bitcoincore_rpc::bitcoin::hashes::Hash;
+    use bitcoincore_rpc::Auth;
+    use serde_json::json;
+
+    #[tokio::test(flavor = "multi_thread")]
+    pub async fn test_broadcast_dispatches_once() {
+        let tx = Transaction {
+            version: 2,
+            lock_time: LockTime::ZERO,
+            input: vec![],
+            output: vec![],
+        };
+        let tx_id = tx.txid();
+        let url = serve_rpc(move |request| match request["method"].as_str() {
+            Some("sendrawtransaction") => {
+                json!({"result": tx_id.to_string(), "error": null, "id": request["id"]})
+            }
+            _ => json!({"result": null, "error": {"code": -5, "message": "not in mempool"}, "id": request["id"]}),
+        })
+        .await;
+        let client = Arc::new(bitcoincore_rpc::Client::new(&url, Auth::None).unwrap());
+        let (tx_sender, rx) = async_channel::unbounded();
+        let (priority_tx, _priority_rx) = async_channel::unbounded();
+        let (client_tx, _client_rx) = async_channel::unbounded();
+        let (grap_tx, grap_rx) = async_channel::unbounded();
+        let mut processor = IndexerProcessorImpl::new(
+            ProcessorConfiguration::default(),
+            AsyncWaitGroup::new(),
+            vec![Consumer::new(tx_sender, priority_tx)],
+            KVStorageProcessor::new(MemoryDB::default()),
+            client,
+            client_tx,
+            Arc::new(AtomicBool::new(false)),
+            grap_tx,
+            grap_rx,
+        );
+
+        let ret = processor.do_handle_broadcast(&tx).await.unwrap();
+        assert_eq!(ret, tx_id.into());
+        match rx.try_recv().unwrap() {
+            ClientEvent::Transaction(v) => assert_eq!(v.tx, tx),
+            v => panic!("unexpected event:{:?}", v),
+        }
+        // the zmq announcement of the broadcast tx is not dispatched again
+        processor
+            .do_handle_new_tx_coming(&serialize(&tx), false)
+            .await
+            .unwrap();
+        assert!(rx.try_recv().is_err());
+    }
+
+    type TestProcessor = IndexerProcessorImpl<KVStorageProcessor<MemoryDB>>;
+
+    // a processor on the node,caught up with its chain. both lanes of the consumer go to the
+    // returned receiver,the dispatch events to the other one
+    async fn processor_on(
+        node: &MockNode,
+        storage: KVStorageProcessor<MemoryDB>,
+        config: ProcessorConfiguration,
+    ) -> (
+        TestProcessor,
+        Receiver<ClientEvent>,
+        Receiver<DispatchEvent>,
+    ) {
+        let url = node.serve().await;
+        let client = Arc::new(bitcoincore_rpc::Client::new(&url, Auth::None).unwrap());
+        let (tx_sender, rx) = async_channel::unbounded();
+        let (client_tx, _client_rx) = async_channel::unbounded();
+        let (grap_tx, grap_rx) = async_channel::unbounded();
+        let mut processor = IndexerProcessorImpl::new(
+            config,
+            AsyncWaitGroup::new(),
+            vec![Consumer::new(tx_sender.clone(), tx_sender)],
+            storage,
+            client,
+            client_tx,
+            Arc::new(AtomicBool::new(false)),
+            grap_tx,
+            grap_rx.clone(),
+        );
+        let tip = processor.source.get_tip_height().await.unwrap();
+        processor.current_indexer_height = Some(tip);
+        (processor, rx, grap_rx)
+    }
+
+    // handles the events the processor sent itself,e.g. the txs of a restore
+    async fn handle_pending(processor: &mut TestProcessor, grap_rx: &Receiver<DispatchEvent>) {
+        while let Ok(event) = grap_rx.try_recv() {
+            processor.handle_event(&event).await.unwrap();
+        }
+    }
+
+    fn drain(rx: &Receiver<ClientEvent>) -> Vec<ClientEvent> {
+        let mut ret = vec![];
+        while let Ok(v) = rx.try_recv() {
+            ret.push(v);
+        }
+        ret
+    }
+
+    fn empty_delta(tx_id: &TxIdType) -> DispatchEvent {
+        DispatchEvent::IndexerEvent(IndexerEvent::UpdateDelta(TransactionDelta {
+            tx_id: tx_id.clone(),
+            deltas: Default::default(),
+        }))
+    }
+
+    // a delta crediting an address of its own to the tx,unlike an empty one it is stored
+    fn credit(tx_id: &TxIdType) -> DispatchEvent {
+        let mut deltas = HashMap::new();
+        deltas.insert(
+            credited_address(tx_id),
+            vec![(TokenType::from_bytes(&[0u8; 20]), BalanceType::from(1))],
+        );
+        DispatchEvent::IndexerEvent(IndexerEvent::UpdateDelta(TransactionDelta {
+            tx_id: tx_id.clone(),
+            deltas,
+        }))
+    }
+
+    fn credited_address(tx_id: &TxIdType) -> AddressType {
+        AddressType::from_bytes(&tx_id.0.as_bytes()[..20])
+    }
+
+    // 1 while the credit of the tx is active
+    async fn credited(processor: &mut TestProcessor, tx_id: &TxIdType) -> BalanceType {
+        processor
+            .storage
+            .get_balance(&credited_address(tx_id), &TokenType::from_bytes(&[0u8; 20]))
+            .await
+            .unwrap()
+    }
+
+    // the rawtx announcement of the zmq component
+    fn new_tx(tx: &Transaction) -> DispatchEvent {
+        DispatchEvent::IndexerEvent(IndexerEvent::NewTxComing(serialize(tx), 0))
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    pub async fn test_restore_mined_after_restart() {
+        let node = MockNode::new();
+        let (_, funding) = node.mine(vec![]);
+        let storage = KVStorageProcessor::new(MemoryDB::default());
+        // seen by the last run,mined while the sdk was down
+        let tx = mock_tx(OutPoint::new(funding.txdata[0].txid(), 0), 10);
+        let tx_id: TxIdType = tx.txid().into();
+        storage
+            .clone()
+            .seen_and_store_txs(&tx, false)
+            .await
+            .unwrap();
+        let (height, block) = node.mine(vec![tx.clone()]);
+        let evicted = mock_tx(OutPoint::new(funding.txdata[0].txid(), 1), 20);
+        let evicted_id: TxIdType = evicted.txid().into();
+        storage
+            .clone()
+            .seen_and_store_txs(&evicted, false)
+            .await
+            .unwrap();
+
+        let (mut processor, rx, grap_rx) =
+            processor_on(&node, storage, ProcessorConfiguration::default()).await;
+        processor
+            .do_handle_sync_mempool(processor.grap_tx.clone())
+            .await
+            .unwrap();
+        handle_pending(&mut processor, &grap_rx).await;
+        let events = drain(&rx);
+        assert!(events
+            .iter()
+            .any(|v| matches!(v, ClientEvent::Transaction(v) if v.tx == tx)));
+        assert!(!events
+            .iter()
+            .any(|v| matches!(v, ClientEvent::TxDroped(v) if v == &tx_id)));
+        // only the tx found in no block is dropped
+        assert!(events
+            .iter()
+            .any(|v| matches!(v, ClientEvent::TxDroped(v) if v == &evicted_id)));
+
+        processor.handle_event(&empty_delta(&tx_id)).await.unwrap();
+        let block = BlockContext {
+            height,
+            hash: block.block_hash(),
+            position: 1,
+        };
+        match drain(&rx).as_slice() {
+            [ClientEvent::TxConfirmed(v, b)] => {
+                assert_eq!(v, &tx_id);
+                assert_eq!(b, &block);
+            }
+            v => panic!("unexpected events:{:?}", v),
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    pub async fn test_immature_coinbase_after_restart() {
+        let node = MockNode::new();
+        let storage = KVStorageProcessor::new(MemoryDB::default());
+        let (_, block) = node.mine(vec![]);
+        let coinbase_id: TxIdType = block.txdata[0].txid().into();
+        let (mut processor, rx, _) =
+            processor_on(&node, storage.clone(), ProcessorConfiguration::default()).await;
+        processor
+            .do_handle_block_connected(&block.block_hash())
+            .await
+            .unwrap();
+        assert!(!drain(&rx)
+            .iter()
+            .any(|v| matches!(v, ClientEvent::CoinbaseMatured(_))));
+        drop(processor);
+
+        // the coinbase block is not connected again,it matures from the saved state
+        let mut tip = None;
+        for _ in 0..COINBASE_MATURITY - 1 {
+            tip = Some(node.mine(vec![]));
+        }
+        let (height, tip) = tip.unwrap();
+        let (mut processor, rx, _) =
+            processor_on(&node, storage, ProcessorConfiguration::default()).await;
+        processor.load_chain_state().await.unwrap();
+        processor
+            .do_handle_header_connected(&tip.header, height)
+            .await
+            .unwrap();
+        let events = drain(&rx);
+        assert!(events
+            .iter()
+            .any(|v| matches!(v, ClientEvent::CoinbaseMatured(v) if v == &coinbase_id)));
+        assert!(events
+            .iter()
+            .any(|v| matches!(v, ClientEvent::Transaction(v) if v.tx == block.txdata[0])));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    pub async fn test_confirmations_after_restart() {
+        let node = MockNode::new();
+        let storage = KVStorageProcessor::new(MemoryDB::default());
+        let config = ProcessorConfiguration {
+            confirmations: 2,
+            ..Default::default()
+        };
+        let (_, funding) = node.mine(vec![]);
+        let executed = mock_tx(OutPoint::new(funding.txdata[0].txid(), 0), 10);
+        let unexecuted = mock_tx(OutPoint::new(funding.txdata[0].txid(), 1), 20);
+        let (executed_id, unexecuted_id): (TxIdType, TxIdType) =
+            (executed.txid().into(), unexecuted.txid().into());
+        let (h2, b2) = node.mine(vec![unexecuted.clone()]);
+        let (h3, b3) = node.mine(vec![executed.clone()]);
+        let (mut processor, rx, _) = processor_on(&node, storage.clone(), config.clone()).await;
+        for hash in [b2.block_hash(), b3.block_hash()] {
+            processor.do_handle_block_connected(&hash).await.unwrap();
+        }
+        // the first block reached its depth before the delta,the second one did not
+        processor
+            .handle_event(&empty_delta(&executed_id))
+            .await
+            .unwrap();
+        assert!(!drain(&rx)
+            .iter()
+            .any(|v| matches!(v, ClientEvent::TxConfirmed(_, _))));
+        drop(processor);
+
+        let (h4, b4) = node.mine(vec![]);
+        let (mut processor, rx, _) = processor_on(&node, storage, config).await;
+        processor.load_chain_state().await.unwrap();
+        processor
+            .handle_event(&empty_delta(&unexecuted_id))
+            .await
+            .unwrap();
+        processor
+            .do_handle_header_connected(&b4.header, h4)
+            .await
+            .unwrap();
+        let confirmed: Vec<_> = drain(&rx)
+            .into_iter()
+            .filter_map(|v| match v {
+                ClientEvent::TxConfirmed(tx_id, block) => Some((tx_id, block)),
+                _ => None,
+            })
+            .collect();
+        let expected = vec![
+            (
+                unexecuted_id,
+                BlockContext {
+                    height: h2,
+                    hash: b2.block_hash(),
+                    position: 1,
+                },
+            ),
+            (
+                executed_id,
+                BlockContext {
+                    height: h3,
+                    hash: b3.block_hash(),
+                    position: 1,
+                },
+            ),
+        ];
+        assert_eq!(confirmed, expected);
+    }
+
+    // holds every put until a permit is added,records the keys
+    struct HeldStore {
+        permits: tokio::sync::Semaphore,
+        keys: std::sync::Mutex<Vec<String>>,
+    }
+
+    #[async_trait::async_trait]
+    impl ObjectStore for HeldStore {
+        async fn put(&self, key: &str, _data: Vec<u8>) -> IndexerResult<()> {
+            self.permits.acquire().await.unwrap().forget();
+            self.keys.lock().unwrap().push(key.to_string());
+            Ok(())
+        }
+
+        async fn get(&self, _key: &str) -> IndexerResult<Option<Vec<u8>>> {
+            Ok(None)
+        }
+
+        async fn list(&self, _prefix: &str) -> IndexerResult<Vec<String>> {
+            Ok(vec![])
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    pub async fn test_checkpoint_uploads_aside() {
+        let dir = std::env::temp_dir().join("test_checkpoint_uploads_aside");
+        let _ = std::fs::remove_dir_all(&dir);
+        let mut config = ProcessorConfiguration::default();
+        config.storage.snapshot.snapshot_dir = Some(dir.to_str().unwrap().to_string());
+        config.storage.snapshot.block_interval = 1;
+        let store = Arc::new(HeldStore {
+            permits: tokio::sync::Semaphore::new(0),
+            keys: Default::default(),
+        });
+        let node = MockNode::new();
+        let storage = KVStorageProcessor::new(MemoryDB::default());
+        let (processor, _, _) = processor_on(&node, storage, config).await;
+        let mut processor =
+            processor.with_snapshot_store(Some(RemoteSnapshotStore::new(store.clone(), 0)));
+
+        // the held upload does not hold the checkpoint
+        tokio::time::timeout(Duration::from_secs(5), processor.try_checkpoint(1))
+            .await
+            .expect("checkpoint waited for the upload")
+            .unwrap();
+        assert!(store.keys.lock().unwrap().is_empty());
+        store.permits.add_permits(2);
+        for _ in 0..100 {
+            if store.keys.lock().unwrap().len() == 2 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(
+            *store.keys.lock().unwrap(),
+            vec![
+                "snapshot-0000000001.json".to_string(),
+                "snapshot-0000000001.json.sha256".to_string()
+            ]
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    pub async fn test_restore_prefetches_mempool_entries() {
+        let node = MockNode::new();
+        let (_, funding) = node.mine(vec![]);
+        let tx = mock_tx(OutPoint::new(funding.txdata[0].txid(), 0), 10);
+        node.add_to_mempool(tx.clone());
+        let storage = KVStorageProcessor::new(MemoryDB::default());
+        let (processor, rx, grap_rx) =
+            processor_on(&node, storage, ProcessorConfiguration::default()).await;
+        let url = node.serve().await;
+        let clients = vec![bitcoincore_rpc::Client::new(&url, Auth::None).unwrap()];
+        let mut processor = processor.with_rpc_pool(Some(Arc::new(RpcPool::new(clients, 1))));
+        processor
+            .do_handle_sync_mempool(processor.grap_tx.clone())
+            .await
+            .unwrap();
+        // a lookup per tx would find no entry now
+        node.remove_from_mempool(&tx.txid());
+        handle_pending(&mut processor, &grap_rx).await;
+        match drain(&rx).as_slice() {
+            [ClientEvent::Transaction(v)] => {
+                assert_eq!(v.tx, tx);
+                assert_eq!(v.fee, Some(1000));
+            }
+            v => panic!("unexpected events:{:?}", v),
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    pub async fn test_replacement_from_zmq() {
+        let node = MockNode::new();
+        let (_, funding) = node.mine(vec![]);
+        let old = mock_tx(OutPoint::new(funding.txdata[0].txid(), 0), 10);
+        let new = mock_tx(OutPoint::new(funding.txdata[0].txid(), 0), 5);
+        let (old_id, new_id): (TxIdType, TxIdType) = (old.txid().into(), new.txid().into());
+        let storage = KVStorageProcessor::new(MemoryDB::default());
+        let (mut processor, rx, _) =
+            processor_on(&node, storage, ProcessorConfiguration::default()).await;
+        node.add_to_mempool(old.clone());
+        processor.handle_event(&new_tx(&old)).await.unwrap();
+        processor.handle_event(&credit(&old_id)).await.unwrap();
+        assert_eq!(
+            credited(&mut processor, &old_id).await,
+            BalanceType::from(1)
+        );
+
+        // the replacement spends the same input
+        node.remove_from_mempool(&old.txid());
+        node.add_to_mempool(new.clone());
+        processor.handle_event(&new_tx(&new)).await.unwrap();
+        match drain(&rx).as_slice() {
+            [ClientEvent::Transaction(a), ClientEvent::TxReplaced { old: o, new: n }, ClientEvent::Transaction(b)] =>
+            {
+                assert_eq!(a.tx, old);
+                assert_eq!((o, n), (&old_id, &new_id));
+                assert_eq!(b.tx, new);
+            }
+            v => panic!("unexpected events:{:?}", v),
+        }
+        // the credit of the replaced tx is reverted
+        assert_eq!(
+            credited(&mut processor, &old_id).await,
+            BalanceType::from(0)
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    pub async fn test_sequence_removal_and_disconnect() {
+        let node = MockNode::new();
+        let (_, funding) = node.mine(vec![]);
+        let dropped = mock_tx(OutPoint::new(funding.txdata[0].txid(), 0), 10);
+        let mined = mock_tx(OutPoint::new(funding.txdata[0].txid(), 1), 20);
+        let (dropped_id, mined_id): (TxIdType, TxIdType) =
+            (dropped.txid().into(), mined.txid().into());
+        let config = ProcessorConfiguration {
+            confirmations: 2,
+            ..Default::default()
+        };
+        let storage = KVStorageProcessor::new(MemoryDB::default());
+        let (mut processor, rx, _) = processor_on(&node, storage, config).await;
+        node.add_to_mempool(dropped.clone());
+        processor.handle_event(&new_tx(&dropped)).await.unwrap();
+        processor.handle_event(&credit(&dropped_id)).await.unwrap();
+
+        // R of the sequence topic
+        node.remove_from_mempool(&dropped.txid());
+        processor
+            .handle_event(&DispatchEvent::IndexerEvent(IndexerEvent::TxRemoved(
+                dropped_id.clone(),
+            )))
+            .await
+            .unwrap();
+        assert!(drain(&rx)
+            .iter()
+            .any(|v| matches!(v, ClientEvent::TxDroped(v) if v == &dropped_id)));
+        assert_eq!(
+            credited(&mut processor, &dropped_id).await,
+            BalanceType::from(0)
+        );
+
+        // C then D
+        let (height, block) = node.mine(vec![mined.clone()]);
+        processor
+            .handle_event(&DispatchEvent::IndexerEvent(IndexerEvent::BlockConnected(
+                block.block_hash(),
+            )))
+            .await
+            .unwrap();
+        processor.handle_event(&credit(&mined_id)).await.unwrap();
+        node.disconnect_tip();
+        drain(&rx);
+        processor
+            .handle_event(&DispatchEvent::IndexerEvent(
+                IndexerEvent::BlockDisconnected(block.block_hash()),
+            ))
+            .await
+            .unwrap();
+        match drain(&rx).as_slice() {
+            [ClientEvent::BlockDisconnected(h, hash)] => {
+                assert_eq!((*h, *hash), (height, block.block_hash()));
+            }
+            v => panic!("unexpected events:{:?}", v),
+        }
+
+        // the new branch reaches the depth,the tx of the disconnected block is not confirmed
+        for _ in 0..2 {
+            let (_, block) = node.mine(vec![]);
+            processor
+                .handle_event(&DispatchEvent::IndexerEvent(IndexerEvent::BlockConnected(
+                    block.block_hash(),
+                )))
+                .await
+                .unwrap();
+        }
+        assert!(!drain(&rx)
+            .iter()
+            .any(|v| matches!(v, ClientEvent::TxConfirmed(_, _))));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    pub async fn test_sequence_gap_resyncs() {
+        let node = MockNode::new();
+        let (_, funding) = node.mine(vec![]);
+        let executed = mock_tx(OutPoint::new(funding.txdata[0].txid(), 0), 10);
+        let lost = mock_tx(OutPoint::new(funding.txdata[0].txid(), 1), 20);
+        let storage = KVStorageProcessor::new(MemoryDB::default());
+        let (mut processor, rx, grap_rx) =
+            processor_on(&node, storage, ProcessorConfiguration::default()).await;
+        node.add_to_mempool(executed.clone());
+        processor.handle_event(&new_tx(&executed)).await.unwrap();
+        processor
+            .handle_event(&credit(&executed.txid().into()))
+            .await
+            .unwrap();
+        drain(&rx);
+
+        // the announcement of lost never arrived
+        node.add_to_mempool(lost.clone());
+        processor
+            .handle_event(&DispatchEvent::IndexerEvent(IndexerEvent::SequenceGap(
+                3, 5,
+            )))
+            .await
+            .unwrap();
+        handle_pending(&mut processor, &grap_rx).await;
+        let events = drain(&rx);
+        assert!(events.iter().any(|v| matches!(v, ClientEvent::Resynced)));
+        let dispatched: Vec<_> = events
+            .iter()
+            .filter_map(|v| match v {
+                ClientEvent::Transaction(v) => Some(&v.tx),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(dispatched, vec![&lost]);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    pub async fn test_conflict_from_block() {
+        let node = MockNode::new();
+        let (_, funding) = node.mine(vec![]);
+        let pending = mock_tx(OutPoint::new(funding.txdata[0].txid(), 0), 10);
+        let winner = mock_tx(OutPoint::new(funding.txdata[0].txid(), 0), 5);
+        let (pending_id, winner_id): (TxIdType, TxIdType) =
+            (pending.txid().into(), winner.txid().into());
+        let storage = KVStorageProcessor::new(MemoryDB::default());
+        let (mut processor, rx, _) =
+            processor_on(&node, storage, ProcessorConfiguration::default()).await;
+        node.add_to_mempool(pending.clone());
+        processor.handle_event(&new_tx(&pending)).await.unwrap();
+        processor.handle_event(&credit(&pending_id)).await.unwrap();
+        drain(&rx);
+
+        // the other spend was mined without ever reaching our mempool view
+        node.remove_from_mempool(&pending.txid());
+        let (_, block) = node.mine(vec![winner.clone()]);
+        processor
+            .handle_event(&DispatchEvent::IndexerEvent(IndexerEvent::BlockConnected(
+                block.block_hash(),
+            )))
+            .await
+            .unwrap();
+        let events = drain(&rx);
+        assert!(events.iter().any(
+            |v| matches!(v, ClientEvent::TxConflicted(a, b) if (a, b) == (&pending_id, &winner_id))
+        ));
+        assert!(events
+            .iter()
+            .any(|v| matches!(v, ClientEvent::Transaction(v) if v.tx == winner)));
+        assert_eq!(
+            credited(&mut processor, &pending_id).await,
+            BalanceType::from(0)
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    pub async fn test_reorg_rolls_back_to_fork() {
+        let node = MockNode::new();
+        let storage = KVStorageProcessor::new(MemoryDB::default());
+        // nothing is confirmed before the reorg
+        let config = ProcessorConfiguration {
+            confirmations: 4,
+            ..Default::default()
+        };
+        let (mut processor, rx, _) = processor_on(&node, storage, config).await;
+        let connect = |block: &Block| {
+            DispatchEvent::IndexerEvent(IndexerEvent::BlockConnected(block.block_hash()))
+        };
+        let (_, funding) = node.mine(vec![]);
+        processor.handle_event(&connect(&funding)).await.unwrap();
+        let orphaned = mock_tx(OutPoint::new(funding.txdata[0].txid(), 0), 10);
+        let kept = mock_tx(OutPoint::new(funding.txdata[0].txid(), 1), 20);
+        let (fork_height, stale) = node.mine(vec![orphaned.clone(), kept.clone()]);
+        processor.handle_event(&connect(&stale)).await.unwrap();
+        let (orphaned_id, kept_id): (TxIdType, TxIdType) =
+            (orphaned.txid().into(), kept.txid().into());
+        processor.handle_event(&credit(&orphaned_id)).await.unwrap();
+        processor.handle_event(&credit(&kept_id)).await.unwrap();
+
+        // the zmq notification of the disconnect was missed,only the new tip arrives
+        node.disconnect_tip();
+        node.mine(vec![kept.clone()]);
+        let (tip_height, tip) = node.mine(vec![]);
+        drain(&rx);
+        processor.handle_event(&connect(&tip)).await.unwrap();
+        let events = drain(&rx);
+        assert!(events.iter().any(|v| matches!(
+            v,
+            ClientEvent::BlockDisconnected(h, hash) if (*h, *hash) == (fork_height, stale.block_hash())
+        )));
+        let reorgs: Vec<_> = events
+            .iter()
+            .filter_map(|v| match v {
+                ClientEvent::Reorg {
+                    from_height,
+                    to_height,
+                } => Some((*from_height, *to_height)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(reorgs, vec![(fork_height, tip_height)]);
+        // kept is mined again by the new branch,not dispatched a second time
+        assert!(!events
+            .iter()
+            .any(|v| matches!(v, ClientEvent::Transaction(_))));
+        assert_eq!(
+            credited(&mut processor, &orphaned_id).await,
+            BalanceType::from(0)
+        );
+        assert_eq!(
+            credited(&mut processor, &kept_id).await,
+            BalanceType::from(1)
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    pub async fn test_pause_holds_events() {
+        let node = MockNode::new();
+        let (_, funding) = node.mine(vec![]);
+        let first = mock_tx(OutPoint::new(funding.txdata[0].txid(), 0), 10);
+        let second = mock_tx(OutPoint::new(funding.txdata[0].txid(), 1), 20);
+        node.add_to_mempool(first.clone());
+        node.add_to_mempool(second.clone());
+        let storage = KVStorageProcessor::new(MemoryDB::default());
+        let (mut processor, rx, _) =
+            processor_on(&node, storage.clone(), ProcessorConfiguration::default()).await;
+        let pause = DispatchEvent::IndexerEvent(IndexerEvent::Pause);
+        processor.handle_event(&pause).await.unwrap();
+        processor.handle_event(&new_tx(&first)).await.unwrap();
+        assert!(drain(&rx).is_empty());
+        assert!(processor.status().await.paused);
+        processor
+            .handle_event(&DispatchEvent::IndexerEvent(IndexerEvent::Resume))
+            .await
+            .unwrap();
+        match drain(&rx).as_slice() {
+            [ClientEvent::Transaction(v)] => assert_eq!(v.tx, first),
+            v => panic!("unexpected events:{:?}", v),
+        }
+
+        // held events survive a restart while paused
+        processor.handle_event(&pause).await.unwrap();
+        processor.handle_event(&new_tx(&second)).await.unwrap();
+        drop(processor);
+        let (mut processor, rx, _) =
+            processor_on(&node, storage, ProcessorConfiguration::default()).await;
+        processor.drain_held_events().await.unwrap();
+        match drain(&rx).as_slice() {
+            [ClientEvent::Transaction(v)] => assert_eq!(v.tx, second),
+            v => panic!("unexpected events:{:?}", v),
+        }
+        assert!(processor
+            .storage
+            .get_held_events()
+            .await
+            .unwrap()
+            .is_empty());
+
+        // a corrupt held event is dead lettered,not a panic
+        processor.storage.hold_event(&[0xff]).await.unwrap();
+        processor.drain_held_events().await.unwrap();
+        assert!(processor
+            .storage
+            .get_held_events()
+            .await
+            .unwrap()
+            .is_empty());
+        let letters = processor.storage.get_dead_letters().await.unwrap();
+        assert_eq!(letters.len(), 1);
+        assert_eq!(letters[0].event, vec![0xff]);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    pub async fn test_replay_dead_letters() {
+        let node = MockNode::new();
+        let storage = KVStorageProcessor::new(MemoryDB::default());
+        let (mut processor, _, _) =
+            processor_on(&node, storage, ProcessorConfiguration::default()).await;
+        let failing = IndexerEvent::BlockDisconnected(BlockHash::all_zeros());
+        processor
+            .storage
+            .add_dead_letter(&failing.to_bytes(), "failed")
+            .await
+            .unwrap();
+        processor
+            .storage
+            .add_dead_letter(&IndexerEvent::FlushBatch.to_bytes(), "failed")
+            .await
+            .unwrap();
+        processor
+            .handle_event(&DispatchEvent::IndexerEvent(
+                IndexerEvent::ReplayDeadLetters,
+            ))
+            .await
+            .unwrap();
+        // the handled one is gone,the failing one is stored again before its old index goes
+        let letters = processor.storage.get_dead_letters().await.unwrap();
+        assert_eq!(letters.len(), 1);
+        assert_eq!(letters[0].index, 2);
+        assert_eq!(letters[0].event, failing.to_bytes());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    pub async fn test_confirmed_balance() {
+        let node = MockNode::new();
+        let (_, funding) = node.mine(vec![]);
+        let tx = mock_tx(OutPoint::new(funding.txdata[0].txid(), 0), 10);
+        let tx_id: TxIdType = tx.txid().into();
+        let config = ProcessorConfiguration {
+            confirmations: 1,
+            ..Default::default()
+        };
+        let storage = KVStorageProcessor::new(MemoryDB::default());
+        let (mut processor, rx, _) = processor_on(&node, storage, config).await;
+        let (_, block) = node.mine(vec![tx.clone()]);
+        processor
+            .handle_event(&DispatchEvent::IndexerEvent(IndexerEvent::BlockConnected(
+                block.block_hash(),
+            )))
+            .await
+            .unwrap();
+        processor.handle_event(&credit(&tx_id)).await.unwrap();
+        assert!(drain(&rx)
+            .iter()
+            .any(|v| matches!(v, ClientEvent::TxConfirmed(v, _) if v == &tx_id)));
+
+        // the settled state outlives the traces of the tx
+        let detail = processor
+            .storage
+            .get_balance_detail(
+                &credited_address(&tx_id),
+                &TokenType::from_bytes(&[0u8; 20]),
+            )
+            .await
+            .unwrap();
+        assert_eq!(detail.confirmed, BalanceType::from(1));
+        let confirmed = processor
+            .storage
+            .list_deltas(DeltaStatus::Confirmed, &Default::default())
+            .await
+            .unwrap();
+        assert_eq!(confirmed.len(), 1);
+    }
+}
@@ -0,0 +1,72 @@
+use crate::client::event::ClientEvent;
+use crate::client::filter::Subscription;
+use crate::processor::inflight::InFlight;
+use async_channel::Sender;
+
+// one client of the processor,filters and batches independently of the others
+#[derive(Clone)]
+pub struct Consumer {
+    pub tx: Sender<ClientEvent>,
+    pub priority_tx: Sender<ClientEvent>,
+    pub subscription: Option<Subscription>,
+    pub batch: Vec<ClientEvent>,
+    pub in_flight: InFlight,
+}
+
+impl Consumer {
+    pub fn new(tx: Sender<ClientEvent>, priority_tx: Sender<ClientEvent>) -> Self {
+        Self {
+            tx,
+            priority_tx,
+            subscription: None,
+            batch: vec![],
+            in_flight: Default::default(),
+        }
+    }
+
+    pub async fn send(&self, event: ClientEvent) {
+        let tx = if event.is_priority() {
+            &self.priority_tx
+        } else {
+            &self.tx
+        };
+        tx.send(event).await.unwrap();
+    }
+
+    pub async fn flush_batch(&mut self) {
+        if !self.batch.is_empty() {
+            let batch = std::mem::take(&mut self.batch);
+            self.tx.send(ClientEvent::Batch(batch)).await.unwrap();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::TxIdType;
+
+    #[test]
+    pub fn test_consumer_batch() {
+        let (tx, rx) = async_channel::unbounded();
+        let (priority_tx, priority_rx) = async_channel::unbounded();
+        let mut consumer = Consumer::new(tx, priority_tx);
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        rt.block_on(async {
+            consumer
+                .send(ClientEvent::TxDroped(TxIdType::from_bytes(&[1u8; 32])))
+                .await;
+            consumer.send(ClientEvent::GetHeight).await;
+            consumer.flush_batch().await;
+            consumer.batch.push(ClientEvent::Resynced);
+            consumer.batch.push(ClientEvent::GetHeight);
+            consumer.flush_batch().await;
+        });
+        assert_eq!(priority_rx.len(), 1);
+        assert!(matches!(rx.try_recv(), Ok(ClientEvent::GetHeight)));
+        assert!(matches!(rx.try_recv(), Ok(ClientEvent::Batch(v)) if v.len() == 2));
+        assert!(rx.is_empty());
+    }
+}
@@ -0,0 +1,176 @@
+use crate::error::IndexerResult;
+use bitcoincore_rpc::bitcoin::BlockHash;
+use std::collections::VecDeque;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChainHeader {
+    pub height: u32,
+    pub hash: BlockHash,
+    pub prev_hash: BlockHash,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum HeaderUpdate {
+    // the header is the new tip,missing ancestors included
+    Extended,
+    Known,
+    // the headers from the fork height were replaced,disconnected ones are ordered tip first
+    Reorg(u32, Vec<ChainHeader>),
+}
+
+// the latest headers of the best chain,oldest first
+#[derive(Clone)]
+pub struct HeaderChain {
+    headers: VecDeque<ChainHeader>,
+    capacity: usize,
+}
+
+impl HeaderChain {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            headers: Default::default(),
+            capacity: capacity.max(1),
+        }
+    }
+
+    pub fn tip(&self) -> Option<&ChainHeader> {
+        self.headers.back()
+    }
+
+    pub fn contains(&self, hash: &BlockHash) -> bool {
+        self.headers.iter().any(|v| &v.hash == hash)
+    }
+
+    // fetch returns the header of an ancestor which is not tracked yet
+    pub fn connect<F>(&mut self, header: ChainHeader, mut fetch: F) -> IndexerResult<HeaderUpdate>
+    where
+        F: FnMut(&BlockHash) -> IndexerResult<ChainHeader>,
+    {
+        if self.contains(&header.hash) {
+            return Ok(HeaderUpdate::Known);
+        }
+        if self.headers.is_empty() {
+            self.push(header);
+            return Ok(HeaderUpdate::Extended);
+        }
+        let oldest = self.headers.front().unwrap().height;
+        let mut branch = vec![header];
+        loop {
+            let current = branch.last().unwrap();
+            let fork = self
+                .headers
+                .iter()
+                .position(|v| v.hash == current.prev_hash);
+            if let Some(pos) = fork {
+                let disconnected: Vec<ChainHeader> = self.headers.drain(pos + 1..).rev().collect();
+                for v in branch.into_iter().rev() {
+                    self.push(v);
+                }
+                if disconnected.is_empty() {
+                    return Ok(HeaderUpdate::Extended);
+                }
+                let fork_height = disconnected.last().unwrap().height;
+                return Ok(HeaderUpdate::Reorg(fork_height, disconnected));
+            }
+            if current.height <= oldest || branch.len() > self.capacity + 1 {
+                // forked below the tracked window,start over from the new branch
+                let disconnected: Vec<ChainHeader> = self.headers.drain(..).rev().collect();
+                let fork_height = current.height.min(oldest);
+                for v in branch.into_iter().rev() {
+                    self.push(v);
+                }
+                return Ok(HeaderUpdate::Reorg(fork_height, disconnected));
+            }
+            let prev = fetch(&current.prev_hash)?;
+            branch.push(prev);
+        }
+    }
+
+    // tracked headers from height up to the tip,oldest first
+    pub fn headers_from(&self, height: u32) -> Vec<ChainHeader> {
+        self.headers
+            .iter()
+            .filter(|v| v.height >= height)
+            .cloned()
+            .collect()
+    }
+
+    // drops the tip if it is hash
+    pub fn disconnect(&mut self, hash: &BlockHash) {
+        if self.tip().is_some_and(|v| &v.hash == hash) {
+            self.headers.pop_back();
+        }
+    }
+
+    fn push(&mut self, header: ChainHeader) {
+        self.headers.push_back(header);
+        while self.headers.len() > self.capacity {
+            self.headers.pop_front();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoincore_rpc::bitcoin::hashes::Hash;
+    use std::collections::HashMap;
+
+    fn hash(v: u8) -> BlockHash {
+        BlockHash::from_byte_array([v; 32])
+    }
+
+    fn header(height: u32, v: u8, prev: u8) -> ChainHeader {
+        ChainHeader {
+            height,
+            hash: hash(v),
+            prev_hash: hash(prev),
+        }
+    }
+
+    #[test]
+    pub fn test_header_chain() {
+        let mut known = HashMap::new();
+        for v in [header(3, 3, 2), header(3, 13, 2), header(4, 14, 13)] {
+            known.insert(v.hash, v);
+        }
+        let mut fetch = |h: &BlockHash| Ok(known.get(h).cloned().unwrap());
+
+        let mut chain = HeaderChain::new(10);
+        assert_eq!(
+            chain.connect(header(1, 1, 0), &mut fetch).unwrap(),
+            HeaderUpdate::Extended
+        );
+        assert_eq!(
+            chain.connect(header(2, 2, 1), &mut fetch).unwrap(),
+            HeaderUpdate::Extended
+        );
+        // a missed block is fetched
+        assert_eq!(
+            chain.connect(header(4, 4, 3), &mut fetch).unwrap(),
+            HeaderUpdate::Extended
+        );
+        assert_eq!(chain.tip().unwrap().height, 4);
+        assert_eq!(
+            chain.connect(header(4, 4, 3), &mut fetch).unwrap(),
+            HeaderUpdate::Known
+        );
+
+        // a competing branch replaces 3 and 4
+        assert_eq!(
+            chain.connect(header(5, 15, 14), &mut fetch).unwrap(),
+            HeaderUpdate::Reorg(3, vec![header(4, 4, 3), header(3, 3, 2)])
+        );
+        assert_eq!(chain.tip().unwrap().hash, hash(15));
+        assert!(!chain.contains(&hash(3)));
+        assert!(chain.contains(&hash(13)));
+        let branch: Vec<BlockHash> = chain.headers_from(3).iter().map(|v| v.hash).collect();
+        assert_eq!(branch, vec![hash(13), hash(14), hash(15)]);
+
+        chain.disconnect(&hash(15));
+        assert_eq!(
+            chain.connect(header(5, 25, 14), &mut fetch).unwrap(),
+            HeaderUpdate::Extended
+        );
+    }
+}
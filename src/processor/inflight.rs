@@ -0,0 +1,121 @@
+use crate::client::event::ClientEvent;
+use crate::dispatcher::event::DispatchEvent;
+use crate::event::{IndexerEvent, TxIdType};
+use async_channel::Sender;
+use log::info;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+
+// delivered tx events a consumer has not acknowledged yet
+#[derive(Clone, Default)]
+pub struct InFlight {
+    entries: HashMap<TxIdType, (Instant, ClientEvent)>,
+}
+
+impl InFlight {
+    pub fn track(&mut self, tx_id: TxIdType, event: ClientEvent, now: Instant) {
+        self.entries.insert(tx_id, (now, event));
+    }
+
+    pub fn ack(&mut self, tx_id: &TxIdType) -> bool {
+        self.entries.remove(tx_id).is_some()
+    }
+
+    // the event to deliver again,it stays in flight from now on
+    pub fn nack(&mut self, tx_id: &TxIdType, now: Instant) -> Option<ClientEvent> {
+        self.entries.get_mut(tx_id).map(|(at, event)| {
+            *at = now;
+            event.clone()
+        })
+    }
+
+    // events delivered at least timeout ago,they stay in flight from now on
+    pub fn expired(&mut self, now: Instant, timeout: Duration) -> Vec<ClientEvent> {
+        let mut ret: Vec<(Instant, ClientEvent)> = self
+            .entries
+            .values_mut()
+            .filter(|(at, _)| now.duration_since(*at) >= timeout)
+            .map(|(at, event)| {
+                let delivered = *at;
+                *at = now;
+                (delivered, event.clone())
+            })
+            .collect();
+        // in the order they were delivered first
+        ret.sort_by_key(|(at, _)| *at);
+        ret.into_iter().map(|(_, event)| event).collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+// asks the processor to redeliver the timed out events
+pub fn start_redelivery(
+    tx: Sender<DispatchEvent>,
+    timeout_secs: u64,
+    mut exit: watch::Receiver<()>,
+) -> JoinHandle<()> {
+    tokio::task::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(timeout_secs.max(1)));
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {
+                    if tx.send(DispatchEvent::IndexerEvent(IndexerEvent::RedeliverExpired)).await.is_err() {
+                        return;
+                    }
+                }
+                _ = exit.changed() => {
+                    info!("redelivery exit");
+                    return;
+                }
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_in_flight() {
+        let id = |v: u8| TxIdType::from_bytes(&[v; 32]);
+        let timeout = Duration::from_secs(10);
+        let start = Instant::now();
+        let mut in_flight = InFlight::default();
+        in_flight.track(id(1), ClientEvent::TxDroped(id(1)), start);
+        in_flight.track(
+            id(2),
+            ClientEvent::TxDroped(id(2)),
+            start + Duration::from_secs(5),
+        );
+        in_flight.track(
+            id(3),
+            ClientEvent::TxDroped(id(3)),
+            start + Duration::from_secs(1),
+        );
+
+        assert!(in_flight.ack(&id(3)));
+        assert!(!in_flight.ack(&id(3)));
+        assert!(in_flight.nack(&id(3), start).is_none());
+
+        let now = start + Duration::from_secs(12);
+        let expired = in_flight.expired(now, timeout);
+        assert_eq!(expired.len(), 1);
+        assert!(matches!(&expired[0], ClientEvent::TxDroped(v) if v == &id(1)));
+        // redelivered ones wait for another timeout
+        assert!(in_flight.expired(now, timeout).is_empty());
+        assert_eq!(in_flight.expired(now + timeout, timeout).len(), 2);
+
+        assert!(in_flight.nack(&id(2), now).is_some());
+        assert_eq!(in_flight.len(), 2);
+    }
+}
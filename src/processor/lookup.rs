@@ -0,0 +1,102 @@
+use crate::client::event::BlockContext;
+use crate::error::IndexerResult;
+use crate::event::TxIdType;
+use crate::net::ChainDataSource;
+use crate::storage::block_cache::BlockCache;
+use crate::storage::StorageProcessor;
+use crate::types::request::BlockId;
+use bitcoincore_rpc::bitcoin::{Block, Transaction, Txid};
+use std::collections::{HashMap, HashSet};
+
+// the stored tx first,txs the sdk never saw come from the chain data source
+pub(crate) async fn find_transaction<T: StorageProcessor>(
+    storage: &mut T,
+    source: &dyn ChainDataSource,
+    tx_id: &TxIdType,
+) -> IndexerResult<Option<Transaction>> {
+    if let Some(tx) = storage.get_raw_tx(tx_id).await? {
+        return Ok(Some(tx));
+    }
+    source.get_transaction(&tx_id.clone().into()).await
+}
+
+// cached blocks first,older ones or blocks off the best chain come from the chain data source
+pub(crate) async fn find_block(
+    cache: &BlockCache,
+    source: &dyn ChainDataSource,
+    id: &BlockId,
+) -> IndexerResult<Option<Block>> {
+    if let Some((_, block)) = cache.get(id) {
+        return Ok(Some(block));
+    }
+    let hash = match id {
+        BlockId::Hash(hash) => *hash,
+        BlockId::Height(height) => match source.get_block_hash(*height).await? {
+            Some(hash) => hash,
+            None => return Ok(None),
+        },
+    };
+    source.get_block(&hash).await
+}
+
+// a block time may lag behind the time a node first saw one of its txs by this much
+const MAX_BLOCK_TIME_DRIFT: i64 = 2 * 60 * 60;
+
+// the restored txs which left the mempool,searched in the blocks mined since they were first
+// seen. a restore after a restart has no recent blocks in memory and the node may lack txindex,
+// so the best chain is walked down from the tip once per restore pass
+#[derive(Clone, Default)]
+pub(crate) struct MinedTxIndex {
+    wanted: HashSet<Txid>,
+    found: HashMap<Txid, (BlockContext, Transaction)>,
+    // height of the lowest block searched,none before the first search
+    lowest: Option<u32>,
+}
+
+impl MinedTxIndex {
+    pub fn new(wanted: HashSet<Txid>) -> Self {
+        Self {
+            wanted,
+            ..Default::default()
+        }
+    }
+
+    // the block and the tx,none once the blocks are older than first_seen
+    pub async fn find(
+        &mut self,
+        source: &dyn ChainDataSource,
+        txid: &Txid,
+        first_seen: i64,
+    ) -> IndexerResult<Option<(BlockContext, Transaction)>> {
+        if self.lowest.is_none() {
+            self.lowest = Some(source.get_tip_height().await? + 1);
+        }
+        while !self.found.contains_key(txid) {
+            let Some(height) = self.lowest.unwrap().checked_sub(1) else {
+                break;
+            };
+            let Some(hash) = source.get_block_hash(height).await? else {
+                break;
+            };
+            let Some(block) = source.get_block(&hash).await? else {
+                break;
+            };
+            if (block.header.time as i64) + MAX_BLOCK_TIME_DRIFT < first_seen {
+                break;
+            }
+            for (position, tx) in block.txdata.into_iter().enumerate() {
+                let id = tx.txid();
+                if self.wanted.contains(&id) {
+                    let block = BlockContext {
+                        height,
+                        hash,
+                        position: position as u32,
+                    };
+                    self.found.insert(id, (block, tx));
+                }
+            }
+            self.lowest = Some(height);
+        }
+        Ok(self.found.remove(txid))
+    }
+}
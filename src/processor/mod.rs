@@ -1,2 +1,10 @@
 pub mod common;
+pub(crate) mod consumer;
+mod header;
+pub(crate) mod inflight;
+pub(crate) mod lookup;
 mod node;
+mod reorg;
+pub(crate) mod retry;
+pub(crate) mod simulate;
+mod state;
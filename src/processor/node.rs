@@ -1,6 +1,6 @@
 use crate::event::TxIdType;
 use serde::{Deserialize, Serialize};
-use std::collections::HashSet;
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::hash::{Hash, Hasher};
 
 #[derive(Clone, Eq, PartialEq, Serialize, Deserialize)]
@@ -27,3 +27,58 @@ impl Hash for TxNode {
 }
 #[test]
 pub fn test_asd() {}
+// orders txs so that a parent is always before the txs spending its outputs,
+// txs without dependencies between them keep the timestamp order
+pub fn order_by_dependencies(txs: Vec<(TxIdType, i64, Vec<TxIdType>)>) -> Vec<TxIdType> {
+    let indexes: HashMap<TxIdType, usize> = txs
+        .iter()
+        .enumerate()
+        .map(|(i, (tx_id, _, _))| (tx_id.clone(), i))
+        .collect();
+    let mut in_degrees = vec![0usize; txs.len()];
+    let mut children: Vec<Vec<usize>> = vec![vec![]; txs.len()];
+    for (i, (_, _, depends)) in txs.iter().enumerate() {
+        // parents out of the set are confirmed or already dispatched
+        for parent in depends.iter().filter_map(|v| indexes.get(v)) {
+            in_degrees[i] += 1;
+            children[*parent].push(i);
+        }
+    }
+
+    let mut ready: BTreeSet<(i64, usize)> = txs
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| in_degrees[*i] == 0)
+        .map(|(i, (_, ts, _))| (*ts, i))
+        .collect();
+    let mut ret = Vec::with_capacity(txs.len());
+    while let Some((ts, i)) = ready.pop_first() {
+        ret.push(i);
+        for child in &children[i] {
+            in_degrees[*child] -= 1;
+            if in_degrees[*child] == 0 {
+                ready.insert((txs[*child].1.max(ts), *child));
+            }
+        }
+    }
+    ret.into_iter().map(|i| txs[i].0.clone()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_order_by_dependencies() {
+        let id = |v: u8| TxIdType::from_bytes(&[v; 32]);
+        // the child is seen before its parent
+        let txs = vec![
+            (id(1), 10, vec![id(2)]),
+            (id(2), 20, vec![id(9)]),
+            (id(3), 15, vec![]),
+            (id(4), 5, vec![id(1), id(3)]),
+        ];
+        let ordered = order_by_dependencies(txs);
+        assert_eq!(ordered, vec![id(3), id(2), id(1), id(4)]);
+    }
+}
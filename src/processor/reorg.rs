@@ -0,0 +1,73 @@
+use crate::event::TxIdType;
+use bitcoincore_rpc::bitcoin::BlockHash;
+use std::collections::{HashMap, HashSet, VecDeque};
+
+// tx ids of the latest connected blocks,a reorg looks up what the disconnected blocks mined
+#[derive(Clone)]
+pub struct BlockTxIndex {
+    blocks: HashMap<BlockHash, Vec<TxIdType>>,
+    order: VecDeque<BlockHash>,
+    capacity: usize,
+}
+
+impl BlockTxIndex {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            blocks: Default::default(),
+            order: Default::default(),
+            capacity: capacity.max(1),
+        }
+    }
+
+    pub fn insert(&mut self, hash: BlockHash, txs: Vec<TxIdType>) {
+        if self.blocks.insert(hash, txs).is_none() {
+            self.order.push_back(hash);
+        }
+        while self.order.len() > self.capacity {
+            let oldest = self.order.pop_front().unwrap();
+            self.blocks.remove(&oldest);
+        }
+    }
+
+    pub fn remove(&mut self, hash: &BlockHash) -> Option<Vec<TxIdType>> {
+        self.order.retain(|v| v != hash);
+        self.blocks.remove(hash)
+    }
+}
+
+// txs of the disconnected blocks the new branch did not mine again,in block order
+pub fn orphaned_txs(disconnected: &[Vec<TxIdType>], branch: &[Vec<TxIdType>]) -> Vec<TxIdType> {
+    let mined: HashSet<&TxIdType> = branch.iter().flatten().collect();
+    disconnected
+        .iter()
+        .flatten()
+        .filter(|v| !mined.contains(v))
+        .cloned()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoincore_rpc::bitcoin::hashes::Hash;
+
+    #[test]
+    pub fn test_orphaned_txs() {
+        let id = |v: u8| TxIdType::from_bytes(&[v; 32]);
+        let hash = |v: u8| BlockHash::from_byte_array([v; 32]);
+
+        let mut index = BlockTxIndex::new(2);
+        index.insert(hash(1), vec![id(1)]);
+        index.insert(hash(2), vec![id(2), id(3)]);
+        index.insert(hash(3), vec![id(4), id(5)]);
+        assert!(index.remove(&hash(1)).is_none());
+
+        let disconnected: Vec<Vec<TxIdType>> = [hash(3), hash(2)]
+            .iter()
+            .filter_map(|v| index.remove(v))
+            .collect();
+        let branch = vec![vec![id(9), id(3)], vec![id(5)]];
+        assert_eq!(orphaned_txs(&disconnected, &branch), vec![id(4), id(2)]);
+        assert!(index.remove(&hash(2)).is_none());
+    }
+}
@@ -0,0 +1,167 @@
+use crate::configuration::base::RetryConfiguration;
+use crate::error::{IndexerError, IndexerResult};
+use bitcoincore_rpc::jsonrpc;
+use log::warn;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// runs a bitcoind rpc call,retrying transport failures with jittered exponential backoff
+pub(crate) async fn with_retry<T, F>(
+    cfg: &RetryConfiguration,
+    name: &str,
+    mut call: F,
+) -> IndexerResult<T>
+where
+    F: FnMut() -> Result<T, bitcoincore_rpc::Error>,
+{
+    let max_retries = cfg.max_retries(name);
+    let mut attempt = 0;
+    loop {
+        #[cfg(feature = "metrics")]
+        let ret = crate::metrics::METRICS.rpc_latency.time(name, &mut call);
+        #[cfg(not(feature = "metrics"))]
+        let ret = call();
+        match ret {
+            Ok(v) => return Ok(v),
+            Err(e) if attempt < max_retries && is_transient(&e) => {
+                let delay = backoff(cfg, attempt);
+                warn!(
+                    "rpc {} failed,attempt:{},retry in {:?},err:{}",
+                    name,
+                    attempt + 1,
+                    delay,
+                    e
+                );
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+// errors returned by bitcoind itself (e.g. unknown tx) wont change on retry
+fn is_transient(e: &bitcoincore_rpc::Error) -> bool {
+    match e {
+        bitcoincore_rpc::Error::JsonRpc(jsonrpc::Error::Rpc(_)) => false,
+        bitcoincore_rpc::Error::JsonRpc(_) => true,
+        bitcoincore_rpc::Error::Io(_) => true,
+        _ => false,
+    }
+}
+
+// RPC_INVALID_ADDRESS_OR_KEY,e.g. a tx neither in the mempool nor in the given block
+const RPC_NOT_FOUND: i32 = -5;
+
+pub(crate) fn is_not_found(e: &IndexerError) -> bool {
+    matches!(
+        e,
+        IndexerError::BitCoinClientError(bitcoincore_rpc::Error::JsonRpc(jsonrpc::Error::Rpc(v)))
+            if v.code == RPC_NOT_FOUND
+    )
+}
+
+// a random delay in [half,full] of the capped exponential delay
+fn backoff(cfg: &RetryConfiguration, attempt: u32) -> Duration {
+    let full = cfg
+        .base_delay_ms
+        .saturating_mul(1 << attempt.min(32))
+        .min(cfg.max_delay_ms);
+    let half = full / 2;
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |v| v.subsec_nanos() as u64);
+    Duration::from_millis(half + nanos % (full - half + 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    #[test]
+    pub fn test_backoff() {
+        let cfg = RetryConfiguration {
+            max_retries: 5,
+            base_delay_ms: 100,
+            max_delay_ms: 1000,
+            ..Default::default()
+        };
+        for attempt in 0..10 {
+            let full = (100u64 << attempt).min(1000);
+            let delay = backoff(&cfg, attempt).as_millis() as u64;
+            assert!(delay >= full / 2 && delay <= full);
+        }
+    }
+
+    #[test]
+    pub fn test_is_not_found() {
+        let rpc_error = |code| {
+            IndexerError::BitCoinClientError(bitcoincore_rpc::Error::JsonRpc(jsonrpc::Error::Rpc(
+                jsonrpc::error::RpcError {
+                    code,
+                    message: "".to_string(),
+                    data: None,
+                },
+            )))
+        };
+        assert!(is_not_found(&rpc_error(-5)));
+        assert!(!is_not_found(&rpc_error(-8)));
+        assert!(!is_not_found(&IndexerError::InvalidZmqMessage(
+            "".to_string()
+        )));
+    }
+
+    #[tokio::test]
+    pub async fn test_with_retry() {
+        let cfg = RetryConfiguration {
+            max_retries: 2,
+            base_delay_ms: 1,
+            max_delay_ms: 1,
+            method_retries: [("getblock".to_string(), 0)].into(),
+        };
+        let calls = Cell::new(0);
+        let ret: IndexerResult<u32> = with_retry(&cfg, "test", || {
+            calls.set(calls.get() + 1);
+            Err(bitcoincore_rpc::Error::Io(
+                std::io::ErrorKind::TimedOut.into(),
+            ))
+        })
+        .await;
+        assert!(ret.is_err());
+        assert_eq!(calls.get(), 3);
+
+        calls.set(0);
+        let ret: IndexerResult<u32> = with_retry(&cfg, "test", || {
+            calls.set(calls.get() + 1);
+            Err(bitcoincore_rpc::Error::ReturnedError("unknown".to_string()))
+        })
+        .await;
+        assert!(ret.is_err());
+        assert_eq!(calls.get(), 1);
+
+        calls.set(0);
+        let ret = with_retry(&cfg, "test", || {
+            calls.set(calls.get() + 1);
+            if calls.get() < 2 {
+                return Err(bitcoincore_rpc::Error::Io(
+                    std::io::ErrorKind::ConnectionReset.into(),
+                ));
+            }
+            Ok(7)
+        })
+        .await;
+        assert_eq!(ret.unwrap(), 7);
+        assert_eq!(calls.get(), 2);
+
+        calls.set(0);
+        let ret: IndexerResult<u32> = with_retry(&cfg, "getblock", || {
+            calls.set(calls.get() + 1);
+            Err(bitcoincore_rpc::Error::Io(
+                std::io::ErrorKind::TimedOut.into(),
+            ))
+        })
+        .await;
+        assert!(ret.is_err());
+        assert_eq!(calls.get(), 1);
+    }
+}
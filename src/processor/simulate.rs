@@ -0,0 +1,91 @@
+use crate::error::IndexerResult;
+use crate::event::{AddressType, BalanceType, TokenType};
+use crate::storage::StorageProcessor;
+use crate::types::delta::TransactionDelta;
+use crate::types::response::SimulationResult;
+use bigdecimal::Zero;
+use std::collections::HashMap;
+
+// applies the delta to the stored balances in memory,nothing is written
+pub(crate) async fn simulate_delta<T: StorageProcessor>(
+    storage: &mut T,
+    delta: &TransactionDelta,
+) -> IndexerResult<SimulationResult> {
+    let mut changes: HashMap<(AddressType, TokenType), BalanceType> = HashMap::new();
+    for (address, deltas) in &delta.deltas {
+        for (token, bal) in deltas {
+            let change = changes.entry((address.clone(), token.clone())).or_default();
+            change.0 = change.0.clone() + bal.0.clone();
+        }
+    }
+    let mut changes: Vec<_> = changes.into_iter().collect();
+    changes.sort_by(|(a, _), (b, _)| (&a.0 .0, &a.1 .0).cmp(&(&b.0 .0, &b.1 .0)));
+    let keys: Vec<_> = changes.iter().map(|(k, _)| k.clone()).collect();
+    let current = storage.get_balances(&keys).await?;
+
+    let mut ret = SimulationResult::default();
+    for (((address, token), change), mut balance) in changes.into_iter().zip(current) {
+        balance.0 = balance.0.clone() + change.0;
+        if balance.0 < bigdecimal::BigDecimal::zero() {
+            ret.violations
+                .push((address.clone(), token.clone(), balance.clone()));
+        }
+        ret.balances.push((address, token, balance));
+    }
+    Ok(ret)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::TxIdType;
+    use crate::storage::db::memory::MemoryDB;
+    use crate::storage::kv::KVStorageProcessor;
+
+    #[tokio::test]
+    pub async fn test_simulate_delta() {
+        let mut storage = KVStorageProcessor::new(MemoryDB::default());
+        let alice = AddressType::from_bytes(&[1u8; 20]);
+        let bob = AddressType::from_bytes(&[2u8; 20]);
+        let token = TokenType::from_bytes(&[0u8; 20]);
+        let mut deltas = HashMap::default();
+        deltas.insert(alice.clone(), vec![(token.clone(), BalanceType::from(5))]);
+        storage
+            .add_transaction_delta(&TransactionDelta {
+                tx_id: TxIdType::from_bytes(&[0u8; 32]),
+                deltas,
+            })
+            .await
+            .unwrap();
+
+        let mut deltas = HashMap::default();
+        deltas.insert(
+            alice.clone(),
+            vec![
+                (token.clone(), BalanceType::from(-4)),
+                (token.clone(), BalanceType::from(-3)),
+            ],
+        );
+        deltas.insert(bob.clone(), vec![(token.clone(), BalanceType::from(7))]);
+        let delta = TransactionDelta {
+            tx_id: TxIdType::from_bytes(&[1u8; 32]),
+            deltas,
+        };
+        let ret = simulate_delta(&mut storage, &delta).await.unwrap();
+        assert_eq!(
+            ret.balances,
+            vec![
+                (alice.clone(), token.clone(), BalanceType::from(-2)),
+                (bob.clone(), token.clone(), BalanceType::from(7)),
+            ]
+        );
+        assert_eq!(
+            ret.violations,
+            vec![(alice.clone(), token.clone(), BalanceType::from(-2))]
+        );
+        assert!(!ret.is_valid());
+        // nothing was written
+        let bal = storage.get_balance(&alice, &token).await.unwrap();
+        assert_eq!(bal, BalanceType::from(5));
+    }
+}
@@ -0,0 +1,31 @@
+use crate::client::event::BlockContext;
+use crate::event::TxIdType;
+use bitcoincore_rpc::bitcoin::{BlockHash, Transaction};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+// height,hash and the txs of a block with their position in it
+pub(crate) type MinedBlock = (u32, BlockHash, Vec<(TxIdType, u32)>);
+
+// what the processor waits on across blocks,the blocks are not connected again after a restart.
+// saved per block: a delta pushed since leaves an early confirmation behind,which is never
+// looked up again and dropped with its height
+#[derive(Default, Serialize, Deserialize)]
+pub(crate) struct ChainState {
+    pub immature_coinbases: VecDeque<(u32, BlockHash, Transaction)>,
+    #[serde(default)]
+    pub pending_confirmations: VecDeque<MinedBlock>,
+    // a map in the processor,json keys must be strings
+    #[serde(default)]
+    pub early_confirmed: Vec<(TxIdType, (u32, BlockContext))>,
+}
+
+impl ChainState {
+    pub fn encode(&self) -> Vec<u8> {
+        serde_json::to_vec(self).unwrap()
+    }
+
+    pub fn decode(data: &[u8]) -> serde_json::Result<Self> {
+        serde_json::from_slice(data)
+    }
+}
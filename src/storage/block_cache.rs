@@ -0,0 +1,91 @@
+use crate::types::request::BlockId;
+use bitcoincore_rpc::bitcoin::{Block, BlockHash};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+// the latest save_block_cache_count connected blocks,shared by the processor and its clients
+#[derive(Clone, Default)]
+pub struct BlockCache {
+    inner: Arc<Mutex<VecDeque<(u32, Block)>>>,
+    capacity: usize,
+}
+
+impl BlockCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            inner: Default::default(),
+            capacity,
+        }
+    }
+
+    // a block at a cached height replaces the old one
+    pub fn insert(&self, height: u32, block: Block) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut blocks = self.inner.lock().unwrap();
+        blocks.retain(|(h, _)| *h != height);
+        blocks.push_back((height, block));
+        while blocks.len() > self.capacity {
+            blocks.pop_front();
+        }
+    }
+
+    pub fn remove(&self, hash: &BlockHash) {
+        self.inner
+            .lock()
+            .unwrap()
+            .retain(|(_, b)| b.block_hash() != *hash);
+    }
+
+    pub fn get(&self, id: &BlockId) -> Option<(u32, Block)> {
+        self.inner
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|(h, b)| match id {
+                BlockId::Height(height) => h == height,
+                BlockId::Hash(hash) => b.block_hash() == *hash,
+            })
+            .cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoincore_rpc::bitcoin::block::{Header, Version};
+    use bitcoincore_rpc::bitcoin::hash_types::TxMerkleNode;
+    use bitcoincore_rpc::bitcoin::hashes::Hash;
+    use bitcoincore_rpc::bitcoin::CompactTarget;
+
+    #[test]
+    pub fn test_block_cache() {
+        let block = |nonce: u32| Block {
+            header: Header {
+                version: Version::ONE,
+                prev_blockhash: BlockHash::all_zeros(),
+                merkle_root: TxMerkleNode::all_zeros(),
+                time: 0,
+                bits: CompactTarget::from_consensus(0),
+                nonce,
+            },
+            txdata: vec![],
+        };
+        let cache = BlockCache::new(2);
+        for i in 0..3 {
+            cache.insert(i, block(i));
+        }
+        assert!(cache.get(&BlockId::Height(0)).is_none());
+        assert_eq!(cache.get(&BlockId::Height(1)).unwrap().1, block(1));
+
+        // a reorged block replaces the one at its height
+        cache.insert(2, block(9));
+        let hash = block(9).block_hash();
+        assert_eq!(cache.get(&hash.into()).unwrap().0, 2);
+        assert!(cache.get(&block(2).block_hash().into()).is_none());
+        cache.remove(&hash);
+        assert!(cache.get(&BlockId::Height(2)).is_none());
+        assert!(BlockCache::new(0).get(&BlockId::Height(1)).is_none());
+    }
+}
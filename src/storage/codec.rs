@@ -0,0 +1,146 @@
+use crate::error::{IndexerError, IndexerResult};
+use crate::event::{AddressType, BalanceType, TokenType, TxIdType};
+use crate::storage::kv::TransactionDeltaWrapper;
+use crate::types::delta::TransactionDelta;
+use bigdecimal::BigDecimal;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::str::FromStr;
+
+// value stored for each seen tx
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct SeenRecord {
+    // first seen
+    pub timestamp: i64,
+    pub status: u8,
+    pub re_announced: u32,
+    pub from_restore: bool,
+}
+
+// encoding of the records persisted by the kv storage processor
+pub trait Codec: Clone + Send + Sync {
+    fn encode_delta(&self, delta: &TransactionDeltaWrapper) -> IndexerResult<Vec<u8>>;
+    fn decode_delta(&self, data: &[u8]) -> IndexerResult<TransactionDeltaWrapper>;
+
+    fn encode_seen(&self, seen: &SeenRecord) -> IndexerResult<Vec<u8>>;
+    fn decode_seen(&self, data: &[u8]) -> IndexerResult<SeenRecord>;
+}
+
+// json deltas and timestamp(le)|status|re_announced(le)|from_restore seen records,
+// the original on disk format; legacy timestamp|status records are still readable
+#[derive(Clone, Debug, Default)]
+pub struct DefaultCodec;
+
+impl Codec for DefaultCodec {
+    fn encode_delta(&self, delta: &TransactionDeltaWrapper) -> IndexerResult<Vec<u8>> {
+        Ok(serde_json::to_vec(delta)?)
+    }
+
+    fn decode_delta(&self, data: &[u8]) -> IndexerResult<TransactionDeltaWrapper> {
+        Ok(serde_json::from_slice(data)?)
+    }
+
+    fn encode_seen(&self, seen: &SeenRecord) -> IndexerResult<Vec<u8>> {
+        let mut ret = seen.timestamp.to_le_bytes().to_vec();
+        ret.push(seen.status);
+        ret.extend_from_slice(&seen.re_announced.to_le_bytes());
+        ret.push(seen.from_restore as u8);
+        Ok(ret)
+    }
+
+    fn decode_seen(&self, data: &[u8]) -> IndexerResult<SeenRecord> {
+        if data.len() != 9 && data.len() != 14 {
+            return Err(IndexerError::CodecError(format!(
+                "invalid seen record length:{}",
+                data.len()
+            )));
+        }
+        let mut ret = SeenRecord {
+            timestamp: i64::from_le_bytes(data[..8].try_into().unwrap()),
+            status: data[8],
+            re_announced: 0,
+            from_restore: false,
+        };
+        if data.len() == 14 {
+            ret.re_announced = u32::from_le_bytes(data[9..13].try_into().unwrap());
+            ret.from_restore = data[13] != 0;
+        }
+        Ok(ret)
+    }
+}
+
+// compact binary records,balances are kept as decimal strings
+// because bigdecimal's serde impl needs a self describing format
+#[derive(Clone, Debug, Default)]
+pub struct BincodeCodec;
+
+// address -> [(token,balance)]
+type BinaryBalances = Vec<(Vec<u8>, Vec<(Vec<u8>, String)>)>;
+
+#[derive(Serialize, Deserialize)]
+struct BinaryDelta {
+    tx_id: TxIdType,
+    deltas: BinaryBalances,
+    status: u8,
+    executed_height: u32,
+    settled_height: u32,
+    settled_at: i64,
+}
+
+impl Codec for BincodeCodec {
+    fn encode_delta(&self, delta: &TransactionDeltaWrapper) -> IndexerResult<Vec<u8>> {
+        let data = BinaryDelta {
+            tx_id: delta.data.tx_id.clone(),
+            deltas: delta
+                .data
+                .deltas
+                .iter()
+                .map(|(address, changes)| {
+                    let changes = changes
+                        .iter()
+                        .map(|(token, bal)| (token.to_bytes(), bal.0.to_string()))
+                        .collect();
+                    (address.to_bytes(), changes)
+                })
+                .collect(),
+            status: delta.status,
+            executed_height: delta.executed_height,
+            settled_height: delta.settled_height,
+            settled_at: delta.settled_at,
+        };
+        bincode::serialize(&data).map_err(|e| IndexerError::CodecError(e.to_string()))
+    }
+
+    fn decode_delta(&self, data: &[u8]) -> IndexerResult<TransactionDeltaWrapper> {
+        let data: BinaryDelta =
+            bincode::deserialize(data).map_err(|e| IndexerError::CodecError(e.to_string()))?;
+        let mut deltas = HashMap::new();
+        for (address, changes) in data.deltas {
+            let mut balances = vec![];
+            for (token, bal) in changes {
+                let bal = BigDecimal::from_str(bal.as_str())
+                    .map_err(|e| IndexerError::CodecError(e.to_string()))?;
+                balances.push((TokenType::from_bytes(&token), BalanceType(bal)));
+            }
+            deltas.insert(AddressType::from_bytes(&address), balances);
+        }
+        Ok(TransactionDeltaWrapper {
+            data: TransactionDelta {
+                tx_id: data.tx_id,
+                deltas,
+            },
+            status: data.status,
+            executed_height: data.executed_height,
+            settled_height: data.settled_height,
+            settled_at: data.settled_at,
+        })
+    }
+
+    fn encode_seen(&self, seen: &SeenRecord) -> IndexerResult<Vec<u8>> {
+        bincode::serialize(seen).map_err(|e| IndexerError::CodecError(e.to_string()))
+    }
+
+    fn decode_seen(&self, data: &[u8]) -> IndexerResult<SeenRecord> {
+        bincode::deserialize(data).map_err(|e| IndexerError::CodecError(e.to_string()))
+    }
+}
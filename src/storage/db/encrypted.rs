@@ -0,0 +1,222 @@
+use crate::configuration::base::StorageConfiguration;
+use crate::error::{IndexerError, IndexerResult};
+use crate::event::TxIdType;
+use crate::storage::db::batch::Batch;
+use crate::storage::db::DB;
+use crate::storage::prefix::KeyPrefix;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+
+const NONCE_LEN: usize = 12;
+
+// encrypts every value with ChaCha20-Poly1305,keys are left as is so prefix iteration keeps working.
+// the storage key is the associated data,so a value moved under another key fails to decrypt.
+// stored value layout: nonce(12) | ciphertext. only the tx traces are stored as is,the backend
+// writes them below this layer
+#[derive(Clone)]
+pub struct EncryptedDB<T: DB + Clone> {
+    internal: T,
+    cipher: ChaCha20Poly1305,
+}
+
+impl<T: DB + Clone> EncryptedDB<T> {
+    pub fn new(internal: T, key: &[u8; 32]) -> Self {
+        let cipher = ChaCha20Poly1305::new(Key::from_slice(key));
+        Self { internal, cipher }
+    }
+
+    // key is the hex encoded 32 bytes key
+    pub fn from_hex_key(internal: T, key: &str) -> IndexerResult<Self> {
+        let key = hex::decode(key)?;
+        let key: [u8; 32] = key.as_slice().try_into().map_err(|_| {
+            IndexerError::EncryptionError(format!("invalid key length:{}", key.len()))
+        })?;
+        Ok(Self::new(internal, &key))
+    }
+
+    pub fn from_configuration(internal: T, cfg: &StorageConfiguration) -> IndexerResult<Self> {
+        let key = cfg.encryption_key.as_ref().ok_or_else(|| {
+            IndexerError::EncryptionError("encryption_key is not configured".to_string())
+        })?;
+        Self::from_hex_key(internal, key.as_str())
+    }
+
+    fn is_trace(key: &[u8]) -> bool {
+        key.starts_with(KeyPrefix::TxKeyTrace.get_prefix())
+    }
+
+    fn encrypt(cipher: &ChaCha20Poly1305, key: &[u8], value: &[u8]) -> IndexerResult<Vec<u8>> {
+        if Self::is_trace(key) {
+            return Ok(value.to_vec());
+        }
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let data = cipher
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: value,
+                    aad: key,
+                },
+            )
+            .map_err(|e| IndexerError::EncryptionError(e.to_string()))?;
+        let mut ret = nonce.to_vec();
+        ret.extend_from_slice(data.as_slice());
+        Ok(ret)
+    }
+
+    fn encrypt_batch(cipher: &ChaCha20Poly1305, batch: &Batch) -> IndexerResult<Batch> {
+        let mut ret = Batch::new();
+        for (k, v) in batch.iter() {
+            match v {
+                None => ret.delete(k),
+                Some(v) => ret.put(k, Self::encrypt(cipher, k, v)?.as_slice()),
+            }
+        }
+        Ok(ret)
+    }
+
+    fn decrypt(cipher: &ChaCha20Poly1305, key: &[u8], value: &[u8]) -> IndexerResult<Vec<u8>> {
+        if Self::is_trace(key) {
+            return Ok(value.to_vec());
+        }
+        if value.len() < NONCE_LEN {
+            return Err(IndexerError::EncryptionError(
+                "value is too short".to_string(),
+            ));
+        }
+        let (nonce, data) = value.split_at(NONCE_LEN);
+        cipher
+            .decrypt(
+                Nonce::from_slice(nonce),
+                Payload {
+                    msg: data,
+                    aad: key,
+                },
+            )
+            .map_err(|e| IndexerError::EncryptionError(e.to_string()))
+    }
+}
+
+impl<T: DB + Clone> DB for EncryptedDB<T> {
+    fn set(&mut self, tx_id: Option<TxIdType>, key: &[u8], value: &[u8]) -> IndexerResult<()> {
+        let value = Self::encrypt(&self.cipher, key, value)?;
+        self.internal.set(tx_id, key, value.as_slice())
+    }
+
+    fn get(&mut self, key: &[u8]) -> IndexerResult<Option<Vec<u8>>> {
+        match self.internal.get(key)? {
+            None => Ok(None),
+            Some(v) => Ok(Some(Self::decrypt(&self.cipher, key, v.as_slice())?)),
+        }
+    }
+
+    fn multi_get(&mut self, keys: &[Vec<u8>]) -> IndexerResult<Vec<Option<Vec<u8>>>> {
+        self.internal
+            .multi_get(keys)?
+            .into_iter()
+            .zip(keys)
+            .map(|(v, k)| {
+                v.map(|v| Self::decrypt(&self.cipher, k, v.as_slice()))
+                    .transpose()
+            })
+            .collect()
+    }
+
+    fn delete(&mut self, key: &[u8]) -> IndexerResult<()> {
+        self.internal.delete(key)
+    }
+
+    fn delete_prefix(&mut self, prefix: &[u8]) -> IndexerResult<()> {
+        self.internal.delete_prefix(prefix)
+    }
+
+    fn write_batch(
+        &mut self,
+        tx_id: Option<TxIdType>,
+        batch: Batch,
+        sync: bool,
+    ) -> IndexerResult<()> {
+        let batch = Self::encrypt_batch(&self.cipher, &batch)?;
+        self.internal.write_batch(tx_id, batch, sync)
+    }
+
+    fn write_batches(
+        &mut self,
+        batches: Vec<(Option<TxIdType>, Batch)>,
+        sync: bool,
+    ) -> IndexerResult<()> {
+        let mut encrypted = vec![];
+        for (tx_id, batch) in batches {
+            encrypted.push((tx_id, Self::encrypt_batch(&self.cipher, &batch)?));
+        }
+        self.internal.write_batches(encrypted, sync)
+    }
+
+    fn iter_all_mut<KF, VF, K, V>(
+        &mut self,
+        prefix: &[u8],
+        mut kf: KF,
+        mut vf: VF,
+    ) -> IndexerResult<Vec<(K, V)>>
+    where
+        KF: FnMut(Vec<u8>) -> K,
+        VF: FnMut(Vec<u8>) -> Option<V>,
+    {
+        // the values are decrypted with their raw key
+        let mut ret = vec![];
+        for (k, v) in self.internal.iter_all_mut(prefix, |k| k, Some)? {
+            let v = Self::decrypt(&self.cipher, k.as_slice(), v.as_slice())?;
+            if let Some(v) = vf(v) {
+                ret.push((kf(k), v));
+            }
+        }
+        Ok(ret)
+    }
+
+    fn remove_tx_traces(&mut self, tx_id: Vec<TxIdType>) -> IndexerResult<()> {
+        self.internal.remove_tx_traces(tx_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::db::memory::MemoryDB;
+
+    #[test]
+    pub fn test_encrypted_db() {
+        let inner = MemoryDB::default();
+        let mut db = EncryptedDB::new(inner.clone(), &[7u8; 32]);
+        db.set(None, b"key", b"value").unwrap();
+
+        let mut raw = inner.clone();
+        let stored = raw.get(b"key").unwrap().unwrap();
+        assert_ne!(stored, b"value".to_vec());
+        assert_eq!(db.get(b"key").unwrap(), Some(b"value".to_vec()));
+
+        let mut other = EncryptedDB::new(inner.clone(), &[8u8; 32]);
+        assert!(other.get(b"key").is_err());
+
+        // a value swapped under another key does not decrypt
+        raw.set(None, b"other", stored.as_slice()).unwrap();
+        assert!(db.get(b"other").is_err());
+        assert!(db.iter_all_mut(b"other", |k| k, Some).is_err());
+        assert_eq!(
+            db.multi_get(&[b"key".to_vec()]).unwrap(),
+            vec![Some(b"value".to_vec())]
+        );
+
+        // empty values are sealed too,blanking a value is caught
+        db.set(None, b"empty", b"").unwrap();
+        assert_ne!(raw.get(b"empty").unwrap(), Some(vec![]));
+        assert_eq!(db.get(b"empty").unwrap(), Some(vec![]));
+        raw.set(None, b"key", b"").unwrap();
+        assert!(db.get(b"key").is_err());
+
+        // the traces the backend writes stay readable
+        let tx_id = TxIdType::from_bytes(&[0u8; 32]);
+        db.set(Some(tx_id.clone()), b"traced", b"value").unwrap();
+        let prefix = KeyPrefix::interator_tx_key_prefix(&tx_id);
+        assert_eq!(db.iter_all_mut(&prefix, |k| k, Some).unwrap().len(), 1);
+    }
+}
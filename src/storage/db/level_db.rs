@@ -1,5 +1,6 @@
 use crate::error::IndexerResult;
 use crate::event::TxIdType;
+use crate::storage::db::batch::Batch;
 use crate::storage::db::DB;
 use crate::storage::prefix::KeyPrefix;
 use rusty_leveldb::{LdbIterator, WriteBatch};
@@ -55,6 +56,11 @@ impl DB for LevelDB {
         Ok(db.get(key))
     }
 
+    fn multi_get(&mut self, keys: &[Vec<u8>]) -> IndexerResult<Vec<Option<Vec<u8>>>> {
+        let mut db = self.db.borrow_mut();
+        Ok(keys.iter().map(|k| db.get(k)).collect())
+    }
+
     fn delete(&mut self, key: &[u8]) -> IndexerResult<()> {
         let mut db = self.db.borrow_mut();
         db.delete(key)?;
@@ -62,27 +68,48 @@ impl DB for LevelDB {
         Ok(())
     }
 
+    fn delete_prefix(&mut self, prefix: &[u8]) -> IndexerResult<()> {
+        let mut batch = WriteBatch::new();
+        self.iter_all_mut(
+            prefix,
+            |k| {
+                batch.delete(&k);
+            },
+            |_| None::<Vec<u8>>,
+        )?;
+        let mut db = self.db.borrow_mut();
+        db.write(batch, true)?;
+        db.flush()?;
+        Ok(())
+    }
+
     fn write_batch(
         &mut self,
         tx_id: Option<TxIdType>,
-        batch: WriteBatch,
+        batch: Batch,
+        sync: bool,
+    ) -> IndexerResult<()> {
+        self.write_batches(vec![(tx_id, batch)], sync)
+    }
+
+    fn write_batches(
+        &mut self,
+        batches: Vec<(Option<TxIdType>, Batch)>,
         sync: bool,
     ) -> IndexerResult<()> {
         let mut db = self.db.borrow_mut();
-        // better way?
-        let mut entry = vec![];
-        if let Some(tx_id) = tx_id {
-            batch.iter().for_each(|(k, v)| {
-                if v.is_some() {
-                    entry.push((KeyPrefix::build_tx_key_trace(&tx_id, k), &[]));
+        let mut new_batch = WriteBatch::new();
+        for (tx_id, batch) in &batches {
+            for (k, v) in batch.iter() {
+                match v {
+                    None => new_batch.delete(k),
+                    Some(v) => {
+                        new_batch.put(k, v);
+                        if let Some(tx_id) = tx_id {
+                            new_batch.put(&KeyPrefix::build_tx_key_trace(tx_id, k), &[]);
+                        }
+                    }
                 }
-                // new_batch.put(k, v) foreach once?
-            });
-        }
-        let mut new_batch = WriteBatch::from(batch);
-        if !entry.is_empty() {
-            for (k, v) in entry {
-                new_batch.put(&k, v);
             }
         }
 
@@ -91,7 +118,6 @@ impl DB for LevelDB {
         Ok(())
     }
 
-    // FIXME:BAD CODE
     fn iter_all_mut<KF, VF, K, V>(
         &mut self,
         prefix: &[u8],
@@ -106,67 +132,61 @@ impl DB for LevelDB {
         let mut iter = db.new_iter()?;
         iter.seek(prefix);
 
+        // the seek lands on the first key after the prefix when none has it
         let mut ret = vec![];
-        let v = current_key_val(&iter);
-        if let Some((k, v)) = v {
-            let value = vf(v);
-            let key = kf(k);
-            if value.is_some() {
-                ret.push((key, value.unwrap()))
-            }
-        }
-        loop {
-            if !iter.valid() {
-                return Ok(ret);
-            }
-            let next = iter.next();
-            if next.is_none() {
-                return Ok(ret);
-            }
-            let (k, v) = next.unwrap();
+        let mut current = current_key_val(&iter);
+        while let Some((k, v)) = current {
             if !k.starts_with(prefix) {
-                return Ok(ret);
+                break;
             }
             let key = kf(k);
-            let value = vf(v);
-            if value.is_some() {
-                ret.push((key, value.unwrap()))
+            if let Some(value) = vf(v) {
+                ret.push((key, value));
             }
+            current = iter.next();
         }
+        Ok(ret)
     }
 
     fn remove_tx_traces(&mut self, tx_id: Vec<TxIdType>) -> IndexerResult<()> {
         if tx_id.is_empty() {
             return Ok(());
         }
-        let mut batch = WriteBatch::new();
         for tx_id in tx_id {
             let prefix = KeyPrefix::interator_tx_key_prefix(&tx_id);
-            self.iter_all_mut(
-                &prefix,
-                |k| {
-                    batch.delete(&k);
-                },
-                |_| None::<Vec<u8>>,
-            )?;
+            self.delete_prefix(&prefix)?;
         }
-        let mut db = self.db.borrow_mut();
-        db.write(batch, true)?;
-        db.flush()?;
         Ok(())
     }
 }
 
+fn current_key_val<It: LdbIterator + ?Sized>(it: &It) -> Option<(Vec<u8>, Vec<u8>)> {
+    let (mut k, mut v) = (vec![], vec![]);
+    if it.current(&mut k, &mut v) {
+        Some((k, v))
+    } else {
+        None
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use std::collections::HashMap;
+    use std::path::PathBuf;
+
+    // a fresh db in the temp dir,the test removes the dir when done
+    fn temp_db(name: &str) -> (LevelDB, PathBuf) {
+        let dir = std::env::temp_dir().join(name);
+        let _ = std::fs::remove_dir_all(&dir);
+        (LevelDB::new(dir.to_str().unwrap()).unwrap(), dir)
+    }
 
     #[test]
     pub fn test_rm_tx_traces() {
-        let mut db = LevelDB::new("./test_rm_tx_traces").unwrap();
+        let (mut db, dir) = temp_db("test_rm_tx_traces");
 
-        let mut batch = WriteBatch::new();
+        let mut batch = Batch::new();
         let couple = vec![
             (b"abc".to_vec(), b"def".to_vec()),
             (b"123".to_vec(), b"456".to_vec()),
@@ -182,7 +202,7 @@ mod tests {
         db.write_batch(Some(tx_id.clone()), batch, true).unwrap();
 
         let prefix = KeyPrefix::interator_tx_key_prefix(&tx_id);
-        let couples = db.iter_all_mut(&prefix, |k| k, |v| Some(v)).unwrap();
+        let couples = db.iter_all_mut(&prefix, |k| k, Some).unwrap();
         assert_eq!(couples.len(), couple.len());
         let mut data: HashMap<Vec<u8>, Vec<u8>> = HashMap::default();
         for (k, _) in &couples {
@@ -193,14 +213,60 @@ mod tests {
         for (k, v) in couple {
             assert_eq!(data.get(&k), Some(&v));
         }
+        drop(db);
+        std::fs::remove_dir_all(&dir).unwrap();
     }
-}
 
-fn current_key_val<It: LdbIterator + ?Sized>(it: &It) -> Option<(Vec<u8>, Vec<u8>)> {
-    let (mut k, mut v) = (vec![], vec![]);
-    if it.current(&mut k, &mut v) {
-        Some((k, v))
-    } else {
-        None
+    #[test]
+    pub fn test_write_batches() {
+        let (mut db, dir) = temp_db("test_write_batches");
+        let a = TxIdType::from_bytes(&[1u8; 32]);
+        let b = TxIdType::from_bytes(&[2u8; 32]);
+        let batches = [
+            (Some(a.clone()), b"a1"),
+            (Some(b.clone()), b"b1"),
+            (None, b"c1"),
+        ]
+        .into_iter()
+        .map(|(tx_id, k)| {
+            let mut batch = Batch::new();
+            batch.put(k, b"v");
+            (tx_id, batch)
+        })
+        .collect();
+        db.write_batches(batches, true).unwrap();
+
+        for (tx_id, key) in [(a, b"a1"), (b, b"b1")] {
+            let prefix = KeyPrefix::interator_tx_key_prefix(&tx_id);
+            let traces = db.iter_all_mut(&prefix, |k| k, Some).unwrap();
+            assert_eq!(traces.len(), 1);
+            assert_eq!(KeyPrefix::split_tx_key_trace(&traces[0].0).1, key.to_vec());
+        }
+        assert_eq!(db.get(b"c1").unwrap(), Some(b"v".to_vec()));
+        drop(db);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    pub fn test_delete_prefix() {
+        let (mut db, dir) = temp_db("test_delete_prefix");
+        db.set(None, b"pa1", b"1").unwrap();
+        db.set(None, b"pa2", b"2").unwrap();
+        db.set(None, b"pb1", b"3").unwrap();
+
+        db.delete_prefix(b"pa").unwrap();
+        assert_eq!(db.get(b"pa1").unwrap(), None);
+        assert_eq!(db.get(b"pa2").unwrap(), None);
+        assert_eq!(db.get(b"pb1").unwrap(), Some(b"3".to_vec()));
+
+        // no key has the prefix,the seek lands on pb1
+        db.delete_prefix(b"pab").unwrap();
+        assert_eq!(db.get(b"pb1").unwrap(), Some(b"3".to_vec()));
+        let found = db.iter_all_mut(b"pab", |k| k, Some).unwrap();
+        assert!(found.is_empty());
+        let found = db.iter_all_mut(b"pz", |k| k, Some).unwrap();
+        assert!(found.is_empty());
+        drop(db);
+        std::fs::remove_dir_all(&dir).unwrap();
     }
 }
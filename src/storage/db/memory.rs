@@ -1,7 +1,8 @@
 use crate::error::IndexerResult;
 use crate::event::TxIdType;
+use crate::storage::db::batch::Batch;
 use crate::storage::db::DB;
-use rusty_leveldb::WriteBatch;
+use crate::storage::prefix::KeyPrefix;
 use std::cell::RefCell;
 use std::collections::HashMap;
 use std::rc::Rc;
@@ -9,8 +10,6 @@ use std::rc::Rc;
 #[derive(Default, Clone)]
 pub struct MemoryDB {
     datas: Rc<RefCell<HashMap<Vec<u8>, Vec<u8>>>>,
-
-    tx_traces: Rc<RefCell<HashMap<TxIdType, Vec<Vec<u8>>>>>,
 }
 
 unsafe impl Send for MemoryDB {}
@@ -18,9 +17,9 @@ unsafe impl Sync for MemoryDB {}
 impl DB for MemoryDB {
     fn set(&mut self, tx_id: Option<TxIdType>, key: &[u8], value: &[u8]) -> IndexerResult<()> {
         let mut data = self.datas.borrow_mut();
-        let mut traces = self.tx_traces.borrow_mut();
+        // the same trace keys as leveldb
         if let Some(tx_id) = tx_id {
-            traces.entry(tx_id).or_insert(vec![]).push(key.to_vec());
+            data.insert(KeyPrefix::build_tx_key_trace(&tx_id, key), vec![]);
         }
         data.insert(key.to_vec(), value.to_vec());
         Ok(())
@@ -31,32 +30,50 @@ impl DB for MemoryDB {
         Ok(data.get(key).cloned())
     }
 
+    fn multi_get(&mut self, keys: &[Vec<u8>]) -> IndexerResult<Vec<Option<Vec<u8>>>> {
+        let data = self.datas.borrow_mut();
+        Ok(keys.iter().map(|k| data.get(k).cloned()).collect())
+    }
+
     fn delete(&mut self, key: &[u8]) -> IndexerResult<()> {
         let mut data = self.datas.borrow_mut();
         data.remove(key);
         Ok(())
     }
 
+    fn delete_prefix(&mut self, prefix: &[u8]) -> IndexerResult<()> {
+        let mut data = self.datas.borrow_mut();
+        data.retain(|k, _| !k.starts_with(prefix));
+        Ok(())
+    }
+
     fn write_batch(
         &mut self,
         tx_id: Option<TxIdType>,
-        batch: WriteBatch,
+        batch: Batch,
+        sync: bool,
+    ) -> IndexerResult<()> {
+        self.write_batches(vec![(tx_id, batch)], sync)
+    }
+
+    fn write_batches(
+        &mut self,
+        batches: Vec<(Option<TxIdType>, Batch)>,
         _: bool,
     ) -> IndexerResult<()> {
         let mut data = self.datas.borrow_mut();
-        let mut entry = vec![];
-        batch.iter().for_each(|(k, v)| {
-            if v.is_none() {
-                data.remove(k);
-            } else {
-                entry.push(k.to_vec());
-                let v = v.unwrap().to_vec();
-                data.insert(k.to_vec(), v);
-            }
-        });
-        let mut tx_traces = self.tx_traces.borrow_mut();
-        if let Some(tx_id) = tx_id {
-            tx_traces.entry(tx_id).or_insert(vec![]).append(&mut entry);
+        for (tx_id, batch) in batches {
+            batch.iter().for_each(|(k, v)| match v {
+                None => {
+                    data.remove(k);
+                }
+                Some(v) => {
+                    data.insert(k.to_vec(), v.to_vec());
+                    if let Some(tx_id) = &tx_id {
+                        data.insert(KeyPrefix::build_tx_key_trace(tx_id, k), vec![]);
+                    }
+                }
+            });
         }
         Ok(())
     }
@@ -84,19 +101,11 @@ impl DB for MemoryDB {
         Ok(ret)
     }
 
+    // only the traces,the keys they point to stay
     fn remove_tx_traces(&mut self, tx_ids: Vec<TxIdType>) -> IndexerResult<()> {
         for tx_id in tx_ids {
-            let traces = self.tx_traces.borrow_mut().remove(&tx_id);
-            if let None = traces {
-                return Ok(());
-            }
-            let mut data = self.datas.borrow_mut();
-            let traces = traces.unwrap();
-            for key in traces {
-                data.remove(&key);
-            }
+            self.delete_prefix(&KeyPrefix::interator_tx_key_prefix(&tx_id))?;
         }
-
         Ok(())
     }
 }
@@ -1,3 +1,5 @@
+pub mod batch;
+pub mod encrypted;
 pub mod level_db;
 pub mod memory;
 pub mod prefix;
@@ -5,18 +7,32 @@ pub mod thread_safe;
 
 use crate::error::IndexerResult;
 use crate::event::TxIdType;
-use rusty_leveldb::WriteBatch;
+use crate::storage::db::batch::Batch;
 
 pub trait DB {
     fn set(&mut self, tx_id: Option<TxIdType>, key: &[u8], value: &[u8]) -> IndexerResult<()>;
     fn get(&mut self, key: &[u8]) -> IndexerResult<Option<Vec<u8>>>;
 
+    // the values in the order of the keys
+    fn multi_get(&mut self, keys: &[Vec<u8>]) -> IndexerResult<Vec<Option<Vec<u8>>>> {
+        keys.iter().map(|k| self.get(k)).collect()
+    }
+
     fn delete(&mut self, key: &[u8]) -> IndexerResult<()>;
 
+    fn delete_prefix(&mut self, prefix: &[u8]) -> IndexerResult<()>;
+
     fn write_batch(
         &mut self,
         tx_id: Option<TxIdType>,
-        batch: WriteBatch,
+        batch: Batch,
+        sync: bool,
+    ) -> IndexerResult<()>;
+
+    // several batches in one atomic write,the keys of each are traced to its tx
+    fn write_batches(
+        &mut self,
+        batches: Vec<(Option<TxIdType>, Batch)>,
         sync: bool,
     ) -> IndexerResult<()>;
 
@@ -1,7 +1,7 @@
 use crate::error::IndexerResult;
 use crate::event::TxIdType;
+use crate::storage::db::batch::Batch;
 use crate::storage::db::DB;
-use rusty_leveldb::WriteBatch;
 use std::sync::{Arc, Mutex, RwLock};
 
 #[derive(Clone)]
@@ -20,7 +20,7 @@ impl<T: DB + Clone> ThreadSafeDB<T> {
 impl<T: DB + Clone> DB for ThreadSafeDB<T> {
     fn set(&mut self, tx_id: Option<TxIdType>, key: &[u8], value: &[u8]) -> IndexerResult<()> {
         let mut lock = self.lock.lock().unwrap();
-        lock.set(tx_id, key, value)
+        write_latency("set", || lock.set(tx_id, key, value))
     }
 
     fn get(&mut self, key: &[u8]) -> IndexerResult<Option<Vec<u8>>> {
@@ -28,19 +28,38 @@ impl<T: DB + Clone> DB for ThreadSafeDB<T> {
         lock.get(key)
     }
 
+    fn multi_get(&mut self, keys: &[Vec<u8>]) -> IndexerResult<Vec<Option<Vec<u8>>>> {
+        let mut lock = self.lock.lock().unwrap();
+        lock.multi_get(keys)
+    }
+
     fn delete(&mut self, key: &[u8]) -> IndexerResult<()> {
         let mut lock = self.lock.lock().unwrap();
-        lock.delete(key)
+        write_latency("delete", || lock.delete(key))
+    }
+
+    fn delete_prefix(&mut self, prefix: &[u8]) -> IndexerResult<()> {
+        let mut lock = self.lock.lock().unwrap();
+        write_latency("delete_prefix", || lock.delete_prefix(prefix))
     }
 
     fn write_batch(
         &mut self,
         tx_id: Option<TxIdType>,
-        batch: WriteBatch,
+        batch: Batch,
+        sync: bool,
+    ) -> IndexerResult<()> {
+        let mut lock = self.lock.lock().unwrap();
+        write_latency("write_batch", || lock.write_batch(tx_id, batch, sync))
+    }
+
+    fn write_batches(
+        &mut self,
+        batches: Vec<(Option<TxIdType>, Batch)>,
         sync: bool,
     ) -> IndexerResult<()> {
         let mut lock = self.lock.lock().unwrap();
-        lock.write_batch(tx_id, batch, sync)
+        write_latency("write_batch", || lock.write_batches(batches, sync))
     }
 
     fn iter_all_mut<KF, VF, K, V>(
@@ -62,3 +81,14 @@ impl<T: DB + Clone> DB for ThreadSafeDB<T> {
         lock.remove_tx_traces(tx_id)
     }
 }
+
+// the lock is taken before,so contention doesnt count
+fn write_latency<T>(op: &str, f: impl FnOnce() -> T) -> T {
+    #[cfg(feature = "metrics")]
+    return crate::metrics::METRICS.storage_write_latency.time(op, f);
+    #[cfg(not(feature = "metrics"))]
+    {
+        let _ = op;
+        f()
+    }
+}
@@ -0,0 +1,85 @@
+use crate::error::IndexerResult;
+use crate::event::TxIdType;
+use crate::storage::kv::TransactionDeltaWrapper;
+use crate::types::delta::TransactionDelta;
+use serde::{Deserialize, Serialize};
+use std::fs;
+
+// 2: the audit log and the raw entries
+// 3: the address history and the confirmed totals as raw entries
+pub const EXPORT_FORMAT_VERSION: u32 = 3;
+
+// backend independent dump of the storage processor,balances are rebuilt from the executed deltas
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct StorageExport {
+    pub version: u32,
+    pub state: u32,
+    pub deltas: Vec<ExportedDelta>,
+    pub seen_txs: Vec<ExportedSeenTx>,
+    #[serde(default)]
+    pub audit_log: Vec<ExportedDelta>,
+    // hex encoded key/value pairs of the records stored independent of the codec
+    #[serde(default)]
+    pub entries: Vec<(String, String)>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ExportedDelta {
+    pub index: u32,
+    pub status: u8,
+    pub data: TransactionDelta,
+    #[serde(default)]
+    pub executed_height: u32,
+    #[serde(default)]
+    pub settled_height: u32,
+    #[serde(default)]
+    pub settled_at: i64,
+}
+
+impl ExportedDelta {
+    pub fn new(index: u32, wrapper: TransactionDeltaWrapper) -> Self {
+        Self {
+            index,
+            status: wrapper.status,
+            data: wrapper.data,
+            executed_height: wrapper.executed_height,
+            settled_height: wrapper.settled_height,
+            settled_at: wrapper.settled_at,
+        }
+    }
+
+    pub fn to_wrapper(&self) -> TransactionDeltaWrapper {
+        TransactionDeltaWrapper {
+            data: self.data.clone(),
+            status: self.status,
+            executed_height: self.executed_height,
+            settled_height: self.settled_height,
+            settled_at: self.settled_at,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ExportedSeenTx {
+    pub tx_id: TxIdType,
+    pub timestamp: i64,
+    pub status: u8,
+    #[serde(default)]
+    pub re_announced: u32,
+    #[serde(default)]
+    pub from_restore: bool,
+}
+
+impl StorageExport {
+    pub fn save(&self, path: &str) -> IndexerResult<()> {
+        let data = serde_json::to_vec(self)?;
+        fs::write(path, data)?;
+        Ok(())
+    }
+
+    pub fn load(path: &str) -> IndexerResult<Self> {
+        let data = fs::read(path)?;
+        let ret: StorageExport = serde_json::from_slice(data.as_slice())?;
+        Ok(ret)
+    }
+}
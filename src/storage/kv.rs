@@ -1,50 +1,163 @@
 #[warn(dead_code)]
-use crate::error::IndexerResult;
+use crate::error::{IndexerError, IndexerResult};
 use crate::event::{AddressType, BalanceType, TokenType, TxIdType};
+use crate::storage::codec::{Codec, DefaultCodec, SeenRecord};
+use crate::storage::db::batch::Batch;
 use crate::storage::db::DB;
-use crate::storage::prefix::SEEN_DATA_STATUS_INDEX;
+use crate::storage::export::{ExportedDelta, ExportedSeenTx, StorageExport, EXPORT_FORMAT_VERSION};
+use crate::storage::migration::Migrator;
 use crate::storage::prefix::{DeltaStatus, KeyPrefix, SeenStatus};
-use crate::storage::{SeenStatusResponse, StorageProcessor};
+use crate::storage::prune::PruneRetention;
+use crate::storage::snapshot::Snapshot;
+use crate::storage::{SeenStatusResponse, StorageProcessor, StorageStats};
+use crate::types::dead_letter::DeadLetter;
 use crate::types::delta::TransactionDelta;
-use crate::types::response::AllBalanceResponse;
+use crate::types::request::Pagination;
+use crate::types::response::{AllBalanceResponse, BalanceDetailResponse, PendingTxResponse};
+use crate::types::token::TokenMeta;
+use crate::types::transaction::Utxo;
+use bitcoincore_rpc::bitcoin::consensus::{deserialize, serialize};
+use bitcoincore_rpc::bitcoin::hashes::{sha256, Hash, HashEngine};
 use bitcoincore_rpc::bitcoin::Transaction;
 use chrono::Local;
 use log::{error, info};
-use rusty_leveldb::WriteBatch;
 use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
 
 const MAX_DELAY: i64 = 60 * 60 * 24 * 5; // five days
 
+// export and import: rebuilt from the deltas and the seen txs of the export
+const REBUILT_EXPORT_PREFIXES: [KeyPrefix; 5] = [
+    KeyPrefix::State,
+    KeyPrefix::TransactionDelta,
+    KeyPrefix::TransactionIndexMap,
+    KeyPrefix::AddressTokenBalance,
+    KeyPrefix::SeenTx,
+];
+// copied as is,the audit log is exported with the deltas.
+// history and confirmed totals outlive the deltas pruned or collected,they can't be rebuilt
+const RAW_EXPORT_PREFIXES: [KeyPrefix; 16] = [
+    KeyPrefix::AddressHistory,
+    KeyPrefix::ConfirmedBalance,
+    KeyPrefix::HeightTxSet,
+    KeyPrefix::IndexedHeight,
+    KeyPrefix::Utxo,
+    KeyPrefix::UtxoOwner,
+    KeyPrefix::UtxoChanges,
+    KeyPrefix::TokenMeta,
+    KeyPrefix::DeadLetter,
+    KeyPrefix::EventJournal,
+    KeyPrefix::JournalHead,
+    KeyPrefix::JournalTail,
+    KeyPrefix::ConsumerCursor,
+    KeyPrefix::RawTx,
+    KeyPrefix::HeldEvent,
+    KeyPrefix::ChainState,
+];
+// not exported and cleared on import: the traces belong to the backend the keys were written to,
+// the schema version stays the one of the importing db
+const EXCLUDED_EXPORT_PREFIXES: [KeyPrefix; 1] = [KeyPrefix::TxKeyTrace];
+
 #[derive(Clone)]
-pub struct KVStorageProcessor<T: DB + Send + Sync + Clone> {
+pub struct KVStorageProcessor<T: DB + Send + Sync + Clone, C: Codec = DefaultCodec> {
     db: T,
+    codec: C,
+    // writes buffered by begin_tx,flushed by commit
+    pending: Option<PendingWrites>,
+    // shared between clones,for stats
+    write_ops: Arc<AtomicU64>,
+    // none until the first stats call scans the db,bulk deletes reset them
+    counters: Arc<Mutex<Option<StorageCounters>>>,
+    created_at: i64,
 }
-impl<T: DB + Send + Sync + Clone + Default> Default for KVStorageProcessor<T> {
+impl<T: DB + Send + Sync + Clone + Default, C: Codec + Default> Default
+    for KVStorageProcessor<T, C>
+{
     fn default() -> Self {
-        Self { db: T::default() }
+        Self {
+            db: T::default(),
+            codec: C::default(),
+            pending: None,
+            write_ops: Default::default(),
+            counters: Default::default(),
+            created_at: Local::now().timestamp(),
+        }
+    }
+}
+
+// the counted part of the stats,the tx key traces of the backend are left out
+#[derive(Clone, Copy, Debug, Default)]
+struct StorageCounters {
+    seen_txs: u64,
+    unconsumed_deltas: u64,
+    total_keys: u64,
+    total_bytes: u64,
+}
+
+impl StorageCounters {
+    fn apply<C: Codec>(
+        &mut self,
+        codec: &C,
+        key: &[u8],
+        value: &[u8],
+        add: bool,
+    ) -> IndexerResult<()> {
+        let step = |n: &mut u64, by: u64| {
+            *n = if add { *n + by } else { n.saturating_sub(by) };
+        };
+        step(&mut self.total_keys, 1);
+        step(&mut self.total_bytes, (key.len() + value.len()) as u64);
+        if key.starts_with(KeyPrefix::SeenTx.get_prefix()) {
+            step(&mut self.seen_txs, 1);
+        } else if key.starts_with(KeyPrefix::TransactionDelta.get_prefix()) {
+            let wrapper = codec.decode_delta(value)?;
+            if wrapper.status == DeltaStatus::Executed.to_u8() {
+                step(&mut self.unconsumed_deltas, 1);
+            }
+        }
+        Ok(())
     }
 }
 
-unsafe impl<T: DB + Send + Sync + Clone> Send for KVStorageProcessor<T> {}
-unsafe impl<T: DB + Send + Sync + Clone> Sync for KVStorageProcessor<T> {}
+#[derive(Clone, Default)]
+struct PendingWrites {
+    ops: Vec<(Option<TxIdType>, Batch)>,
+    // read your own writes inside a transaction
+    overlay: HashMap<Vec<u8>, Option<Vec<u8>>>,
+}
+
+unsafe impl<T: DB + Send + Sync + Clone, C: Codec> Send for KVStorageProcessor<T, C> {}
+unsafe impl<T: DB + Send + Sync + Clone, C: Codec> Sync for KVStorageProcessor<T, C> {}
 
 #[async_trait::async_trait]
-impl<T: DB + Send + Sync + Clone> StorageProcessor for KVStorageProcessor<T> {
+impl<T: DB + Send + Sync + Clone, C: Codec> StorageProcessor for KVStorageProcessor<T, C> {
     async fn get_balance(
         &mut self,
         address: &AddressType,
         token_type: &TokenType,
     ) -> IndexerResult<BalanceType> {
         let key = KeyPrefix::build_address_token_key(address, token_type);
-        let value = self
-            .db
-            .get(key.as_slice())?
-            .map_or(BalanceType::default(), |v| {
-                let bal: BalanceType = serde_json::from_slice(v.as_slice()).unwrap();
-                bal
+        self.read_balance(key.as_slice())
+    }
+
+    async fn get_balances(
+        &mut self,
+        keys: &[(AddressType, TokenType)],
+    ) -> IndexerResult<Vec<BalanceType>> {
+        let keys: Vec<_> = keys
+            .iter()
+            .map(|(address, token)| KeyPrefix::build_address_token_key(address, token))
+            .collect();
+        let mut ret = vec![];
+        for v in self.read_many(&keys)? {
+            ret.push(match v {
+                Some(v) => serde_json::from_slice(v.as_slice())?,
+                None => BalanceType::default(),
             });
-        Ok(value)
+        }
+        Ok(ret)
     }
 
     async fn add_transaction_delta(&mut self, transaction: &TransactionDelta) -> IndexerResult<()> {
@@ -53,29 +166,63 @@ impl<T: DB + Send + Sync + Clone> StorageProcessor for KVStorageProcessor<T> {
             &transaction.tx_id, transaction
         );
         if transaction.deltas.is_empty() {
-            let mut batch = WriteBatch::new();
+            let mut batch = Batch::new();
             self.wrap_seen_txs(&mut batch, &transaction.tx_id, SeenStatus::Executed)?;
-            self.db
-                .write_batch(Some(transaction.tx_id.clone()), batch, true)?;
+            self.write(Some(transaction.tx_id.clone()), batch)?;
             return Ok(());
         }
-        let mut batch = WriteBatch::new();
+        let mut batch = Batch::new();
+        // totals every tx adds to,never traced to one of them
+        let mut shared = Batch::new();
         let next_state = self.acquire_next_state()?;
         info!(
             "tx_id:{:?},add transaction delta, next state: {:?},delta:{:?}",
             transaction.tx_id, next_state, transaction
         );
-        self.wrap_transaction_delta(&mut batch, DeltaStatus::Executed, next_state, transaction);
-        self.wrap_update_state(&mut batch, next_state);
+        let wrapper = TransactionDeltaWrapper {
+            data: transaction.clone(),
+            status: DeltaStatus::Executed.to_u8(),
+            executed_height: self.get_indexed_height()?,
+            settled_height: 0,
+            settled_at: 0,
+        };
+        self.wrap_transaction_delta(&mut batch, next_state, &wrapper)?;
+        self.wrap_update_state(&mut shared, next_state);
+        self.wrap_address_history(&mut batch, next_state, transaction, true);
         // build user utxo
-        self.wrap_address_utxo(&mut batch, transaction, true)?;
+        self.wrap_address_utxo(&mut shared, transaction, true)?;
         self.wrap_seen_txs(&mut batch, &transaction.tx_id, SeenStatus::Executed)?;
 
-        self.db
-            .write_batch(Some(transaction.tx_id.clone()), batch, true)?;
+        self.write_all(vec![
+            (Some(transaction.tx_id.clone()), batch),
+            (None, shared),
+        ])?;
         Ok(())
     }
 
+    async fn add_transaction_deltas(
+        &mut self,
+        transactions: &[TransactionDelta],
+    ) -> IndexerResult<()> {
+        if self.pending.is_some() {
+            // joins the caller's transaction
+            for transaction in transactions {
+                self.add_transaction_delta(transaction).await?;
+            }
+            return Ok(());
+        }
+        self.pending = Some(PendingWrites::default());
+        for transaction in transactions {
+            if let Err(e) = self.add_transaction_delta(transaction).await {
+                self.pending = None;
+                return Err(e);
+            }
+        }
+        let pending = self.pending.take().unwrap();
+        // each tx keeps its key traces for a rollback
+        self.flush(pending)
+    }
+
     async fn remove_transaction_delta(
         &mut self,
         tx_id: &TxIdType,
@@ -86,78 +233,99 @@ impl<T: DB + Send + Sync + Clone> StorageProcessor for KVStorageProcessor<T> {
             info!("tx_id,delta:{:?} not found", tx_id);
             return Ok(());
         }
-        let (delta, index) = delta.unwrap();
-        if delta.status != DeltaStatus::Default.to_u8() {
+        let (mut delta, index) = delta.unwrap();
+        if delta.status != DeltaStatus::Executed.to_u8() {
             info!("tx_id,delta:{:?} is inactive,already consumed", tx_id);
             return Ok(());
         }
 
-        let mut batch = WriteBatch::new();
-        self.wrap_transaction_delta(&mut batch, status, index, &delta.data);
+        delta.status = status.to_u8();
+        delta.settled_height = self.get_indexed_height()?;
+        delta.settled_at = Local::now().timestamp();
+        let mut batch = Batch::new();
+        if let DeltaStatus::InActive = status {
+            // dropped txs never touched the address
+            self.wrap_address_history(&mut batch, index, &delta.data, false);
+        }
+        self.wrap_transaction_delta(&mut batch, index, &delta)?;
         self.wrap_address_utxo(&mut batch, &delta.data, false)?;
+        if let DeltaStatus::Confirmed = status {
+            self.wrap_confirmed_balance(&mut batch, &delta.data)?;
+        }
         self.rm_seen_tx(&mut batch, tx_id);
 
-        self.db.write_batch(Some(tx_id.clone()), batch, true)?;
+        // settled,the traces of the tx are dropped next and nothing rolls this back
+        self.write(None, batch)?;
         Ok(())
     }
 
-    async fn seen_and_store_txs(&mut self, tx: &Transaction) -> IndexerResult<SeenStatusResponse> {
+    async fn seen_and_store_txs(
+        &mut self,
+        tx: &Transaction,
+        from_restore: bool,
+    ) -> IndexerResult<SeenStatusResponse> {
         let tx_id: TxIdType = tx.txid().into();
-        let seen_status = self.seen_tx(tx_id.clone()).await?;
-        if seen_status.is_seen() {
-            return Ok(seen_status);
-        }
         let key = KeyPrefix::build_seen_tx_key(&tx_id);
+        if let Some(data) = self.read(key.as_slice())? {
+            let mut record = self.codec.decode_seen(data.as_slice())?;
+            record.re_announced += 1;
+            let data = self.codec.encode_seen(&record)?;
+            self.put(Some(tx_id.clone()), key.as_slice(), data.as_slice())?;
+            return Ok(SeenStatusResponse::new(
+                true,
+                SeenStatus::from_u8(record.status),
+                &record,
+            ));
+        }
         let dt = Local::now();
         let ts = dt.timestamp();
-        let mut data = ts.to_le_bytes().to_vec();
-        data.extend_from_slice(SeenStatus::UnExecuted.to_u8().to_le_bytes().as_slice());
+        let record = SeenRecord {
+            timestamp: ts,
+            status: SeenStatus::UnExecuted.to_u8(),
+            re_announced: 0,
+            from_restore,
+        };
+        let data = self.codec.encode_seen(&record)?;
         info!("tx_id:{:?} is not seen,store it", tx_id);
-        self.db
-            .set(Some(tx_id.clone()), key.as_slice(), data.as_slice())?;
-        return Ok(SeenStatusResponse {
-            seen: false,
-            status: SeenStatus::UnExecuted,
-        });
+        self.put(Some(tx_id.clone()), key.as_slice(), data.as_slice())?;
+        self.put(
+            Some(tx_id.clone()),
+            KeyPrefix::build_raw_tx_key(&tx_id).as_slice(),
+            serialize(tx).as_slice(),
+        )?;
+        return Ok(SeenStatusResponse::new(
+            false,
+            SeenStatus::UnExecuted,
+            &record,
+        ));
     }
 
     async fn seen_tx(&mut self, tx_id: TxIdType) -> IndexerResult<SeenStatusResponse> {
         let key = KeyPrefix::build_seen_tx_key(&tx_id);
-        let ret = self.db.get(key.as_slice())?;
+        let ret = self.read(key.as_slice())?;
         if ret.is_none() {
-            return Ok(SeenStatusResponse {
-                seen: false,
-                status: SeenStatus::UnExecuted,
-            });
+            return Ok(SeenStatusResponse::new(
+                false,
+                SeenStatus::UnExecuted,
+                &SeenRecord::default(),
+            ));
         }
-        let ret = ret.unwrap();
-        let last = ret[ret.len() - 1];
-        Ok(SeenStatusResponse {
-            seen: true,
-            status: SeenStatus::from_u8(last),
-        })
+        let record = self.codec.decode_seen(ret.unwrap().as_slice())?;
+        Ok(SeenStatusResponse::new(
+            true,
+            SeenStatus::from_u8(record.status),
+            &record,
+        ))
     }
 
     async fn get_all_un_consumed_txs(&mut self) -> IndexerResult<HashMap<TxIdType, i64>> {
         let now = Local::now().timestamp();
-        let iter = self.db.iter_all_mut(
-            KeyPrefix::SeenTx.get_prefix(),
-            |k| k,
-            |v| {
-                let last = v[v.len() - 1];
-                if last == SeenStatus::Executed.to_u8() {
-                    return None;
-                }
-                let ts = i64::from_le_bytes(v[..8].try_into().unwrap());
-                if now - ts > MAX_DELAY {
-                    return None;
-                }
-                Some(ts)
-            },
-        )?;
-        let txs: HashMap<TxIdType, i64> = iter
+        let txs: HashMap<TxIdType, i64> = self
+            .all_seen_txs()?
             .into_iter()
-            .map(|(k, ts)| (KeyPrefix::get_tx_id_from_seen_key(k.as_slice()), ts))
+            .filter(|(_, v)| v.status != SeenStatus::Executed.to_u8())
+            .filter(|(_, v)| now - v.timestamp <= MAX_DELAY)
+            .map(|(tx_id, v)| (tx_id, v.timestamp))
             .collect();
         Ok(txs)
     }
@@ -179,12 +347,14 @@ impl<T: DB + Send + Sync + Clone> StorageProcessor for KVStorageProcessor<T> {
                 let token_type = TokenType::from_bytes(&k[l..]);
                 token_type
             },
-            |v| {
-                let balance: BalanceType = serde_json::from_slice(v.as_slice()).unwrap();
-                Some(balance)
-            },
+            Some,
         )?;
-        let ret: Vec<AllBalanceResponse> = ret
+        let mut balances = vec![];
+        for (token_type, v) in ret {
+            let balance: BalanceType = serde_json::from_slice(v.as_slice())?;
+            balances.push((token_type, balance));
+        }
+        let ret: Vec<AllBalanceResponse> = balances
             .into_iter()
             .map(|(token_type, balance)| AllBalanceResponse {
                 balance,
@@ -200,12 +370,12 @@ impl<T: DB + Send + Sync + Clone> StorageProcessor for KVStorageProcessor<T> {
         key: &[u8],
         value: Vec<u8>,
     ) -> IndexerResult<()> {
-        self.db.set(Some(tx_id.clone()), key, value.as_slice())?;
+        self.put(Some(tx_id.clone()), key, value.as_slice())?;
         Ok(())
     }
 
     async fn simple_get(&mut self, key: &[u8]) -> IndexerResult<Option<Vec<u8>>> {
-        let ret = self.db.get(key)?;
+        let ret = self.read(key)?;
         Ok(ret)
     }
 
@@ -213,7 +383,7 @@ impl<T: DB + Send + Sync + Clone> StorageProcessor for KVStorageProcessor<T> {
         let (key, mut data) = self.get_height_txs(height)?;
         data.insert(tx_id.clone());
         let data = serde_json::to_vec(&data).unwrap();
-        self.db.set(Some(tx_id), key.as_slice(), data.as_slice())?;
+        self.put(Some(tx_id), key.as_slice(), data.as_slice())?;
         Ok(())
     }
 
@@ -221,182 +391,1135 @@ impl<T: DB + Send + Sync + Clone> StorageProcessor for KVStorageProcessor<T> {
         let (key, txs) = self.get_height_txs(height)?;
         let txs = txs.into_iter().map(|v| v).collect();
         self.db.remove_tx_traces(txs)?;
+        self.count_writes(std::iter::once((key.as_slice(), None)))?;
         self.db.delete(&key)?;
 
         Ok(())
     }
 
+    // traces are not counted
     async fn remove_tx_traces(&mut self, tx_id: Vec<TxIdType>) -> IndexerResult<()> {
-        self.db.remove_tx_traces(tx_id)
+        self.db.remove_tx_traces(tx_id)?;
+        Ok(())
     }
-}
 
-#[derive(Clone, Serialize, Deserialize)]
-pub struct TransactionDeltaWrapper {
-    pub data: TransactionDelta,
-    pub status: u8,
-}
-impl<T: DB + Send + Sync + Clone> KVStorageProcessor<T> {
-    pub fn new(db: T) -> Self {
-        Self { db }
+    async fn take_snapshot(&mut self, height: u32) -> IndexerResult<Snapshot> {
+        let entries = self.db.iter_all_mut(&[], |k| k, Some)?;
+        info!(
+            "take snapshot at height:{},entries:{}",
+            height,
+            entries.len()
+        );
+        Ok(Snapshot::new(height, entries))
     }
 
-    fn get_height_txs(&mut self, height: u32) -> IndexerResult<(Vec<u8>, HashSet<TxIdType>)> {
-        let key = KeyPrefix::build_height_txs_key(height);
-        let ret = self.db.get(key.as_slice())?;
-        let data = if ret.is_none() {
-            let data = HashSet::new();
-            data
-        } else {
-            let data = ret.unwrap();
-            let data: HashSet<TxIdType> = serde_json::from_slice(data.as_slice()).unwrap();
-            data
+    async fn restore_snapshot(&mut self, snapshot: &Snapshot) -> IndexerResult<()> {
+        info!(
+            "restore snapshot of height:{},entries:{}",
+            snapshot.height,
+            snapshot.len()
+        );
+        self.db.delete_prefix(&[])?;
+        self.reset_counters();
+        let mut batch = Batch::new();
+        for (k, v) in snapshot.entries()? {
+            batch.put(k.as_slice(), v.as_slice());
+        }
+        self.db.write_batch(None, batch, true)?;
+        Ok(())
+    }
+
+    async fn update_indexed_height(&mut self, height: u32) -> IndexerResult<()> {
+        let key = KeyPrefix::build_indexed_height_key();
+        self.put(None, key.as_slice(), height.to_le_bytes().as_slice())
+    }
+
+    async fn stats(&mut self) -> IndexerResult<StorageStats> {
+        let counters = self.counters()?;
+        let mut ret = StorageStats {
+            seen_txs: counters.seen_txs,
+            unconsumed_deltas: counters.unconsumed_deltas,
+            total_keys: counters.total_keys,
+            total_bytes: counters.total_bytes,
+            ..Default::default()
         };
-        Ok((key, data))
+        ret.write_ops = self.write_ops.load(Ordering::Relaxed);
+        let elapsed = (Local::now().timestamp() - self.created_at).max(1);
+        ret.write_ops_per_sec = ret.write_ops as f64 / elapsed as f64;
+        Ok(ret)
     }
-    fn get_transaction_delta_by_tx_id(
+
+    async fn prune_deltas(&mut self, retention: &PruneRetention) -> IndexerResult<u32> {
+        let height = self.get_indexed_height()?;
+        let now = Local::now().timestamp();
+        let deltas = self.all_deltas()?;
+        let mut batch = Batch::new();
+        let mut count = 0;
+        for (index, wrapper) in deltas {
+            if wrapper.status != DeltaStatus::Confirmed.to_u8()
+                && wrapper.status != DeltaStatus::InActive.to_u8()
+            {
+                continue;
+            }
+            let expired = match retention {
+                PruneRetention::Height(n) => wrapper.settled_height.saturating_add(*n) < height,
+                PruneRetention::Age(n) => wrapper.settled_at.saturating_add(*n) < now,
+            };
+            if !expired {
+                continue;
+            }
+            batch.delete(KeyPrefix::build_transaction_data_key(index).as_slice());
+            batch
+                .delete(KeyPrefix::build_transaction_index_map_key(&wrapper.data.tx_id).as_slice());
+            count += 1;
+        }
+        if count > 0 {
+            self.write(None, batch)?;
+        }
+        Ok(count)
+    }
+
+    async fn gc_inactive_deltas(&mut self) -> IndexerResult<u32> {
+        let deltas = self.all_deltas()?;
+        let mut batch = Batch::new();
+        let mut count = 0;
+        for (index, wrapper) in deltas {
+            if wrapper.status != DeltaStatus::InActive.to_u8() {
+                continue;
+            }
+            batch.put(
+                KeyPrefix::build_audit_log_key(index).as_slice(),
+                self.codec.encode_delta(&wrapper)?.as_slice(),
+            );
+            batch.delete(KeyPrefix::build_transaction_data_key(index).as_slice());
+            batch
+                .delete(KeyPrefix::build_transaction_index_map_key(&wrapper.data.tx_id).as_slice());
+            count += 1;
+        }
+        if count > 0 {
+            self.write(None, batch)?;
+        }
+        Ok(count)
+    }
+
+    async fn get_audit_log(
         &mut self,
-        tx_id: &TxIdType,
-    ) -> IndexerResult<Option<(TransactionDeltaWrapper, u32)>> {
-        let key = KeyPrefix::build_transaction_index_map_key(tx_id);
-        let index = self.db.get(key.as_slice())?;
-        if index.is_none() {
-            info!("tx_id:{:?} not found", tx_id);
-            return Ok(None);
+        pagination: &Pagination,
+    ) -> IndexerResult<Vec<TransactionDeltaWrapper>> {
+        let mut records = self
+            .db
+            .iter_all_mut(KeyPrefix::AuditLog.get_prefix(), |k| k, Some)?;
+        records.sort_by(|a, b| a.0.cmp(&b.0));
+        let mut ret = vec![];
+        for (_, v) in records
+            .into_iter()
+            .skip(pagination.offset)
+            .take(pagination.limit)
+        {
+            ret.push(self.codec.decode_delta(v.as_slice())?);
         }
-        let index = index.unwrap();
-        let index = u32::from_le_bytes(index.as_slice().try_into().unwrap());
-        let delta = self
-            .get_transaction_delta_by_index(index)?
-            .expect("impossible");
-        Ok(Some((delta, index)))
+        Ok(ret)
     }
-    fn get_transaction_delta_by_index(
+
+    async fn get_address_history(
         &mut self,
-        index: u32,
-    ) -> IndexerResult<Option<TransactionDeltaWrapper>> {
-        let key = KeyPrefix::TransactionDelta.build_prefix(&index.to_le_bytes());
-        let value = self.db.get(key.as_slice())?;
-        if value.is_none() {
-            return Ok(None);
+        address: &AddressType,
+        pagination: &Pagination,
+    ) -> IndexerResult<Vec<TxIdType>> {
+        let prefix = KeyPrefix::build_address_history_prefix(address);
+        let mut ret = self.db.iter_all_mut(
+            prefix.as_slice(),
+            |k| k,
+            |v| Some(TxIdType::from_bytes(v.as_slice())),
+        )?;
+        ret.sort_by(|a, b| a.0.cmp(&b.0));
+        Ok(ret
+            .into_iter()
+            .skip(pagination.offset)
+            .take(pagination.limit)
+            .map(|(_, v)| v)
+            .collect())
+    }
+
+    async fn apply_utxos(
+        &mut self,
+        tx_id: &TxIdType,
+        created: Vec<Utxo>,
+        spent: Vec<Utxo>,
+    ) -> IndexerResult<()> {
+        let mut batch = Batch::new();
+        for utxo in &created {
+            self.wrap_utxo(&mut batch, utxo, true);
         }
-        let value = value.unwrap();
-        let wrapper: TransactionDeltaWrapper = serde_json::from_slice(value.as_slice()).unwrap();
-        Ok(Some(wrapper))
+        for utxo in &spent {
+            self.wrap_utxo(&mut batch, utxo, false);
+        }
+        let changes = UtxoChanges { created, spent };
+        let key = KeyPrefix::build_utxo_changes_key(tx_id);
+        batch.put(key.as_slice(), serde_json::to_vec(&changes)?.as_slice());
+        self.write(None, batch)
     }
-    fn rm_seen_tx(&self, batch: &mut WriteBatch, tx_id: &TxIdType) {
-        let key = KeyPrefix::build_seen_tx_key(tx_id);
+
+    async fn settle_utxos(&mut self, tx_id: &TxIdType, revert: bool) -> IndexerResult<()> {
+        let key = KeyPrefix::build_utxo_changes_key(tx_id);
+        let changes = self.read(key.as_slice())?;
+        if changes.is_none() {
+            return Ok(());
+        }
+        let changes: UtxoChanges = serde_json::from_slice(changes.unwrap().as_slice())?;
+        let mut batch = Batch::new();
+        if revert {
+            for utxo in &changes.created {
+                self.wrap_utxo(&mut batch, utxo, false);
+            }
+            for utxo in &changes.spent {
+                self.wrap_utxo(&mut batch, utxo, true);
+            }
+        }
         batch.delete(key.as_slice());
+        self.write(None, batch)
     }
-    fn wrap_transaction_delta(
-        &self,
-        batch: &mut WriteBatch,
-        status: DeltaStatus,
-        index: u32,
-        data: &TransactionDelta,
-    ) {
-        let wrapper = TransactionDeltaWrapper {
-            data: data.clone(),
-            status: status.to_u8(),
-        };
-        let value = serde_json::to_vec(&wrapper).unwrap();
-        let key = KeyPrefix::build_transaction_data_key(index);
-        batch.put(key.as_slice(), value.as_slice());
 
-        let key = KeyPrefix::build_transaction_index_map_key(&data.tx_id);
-        let value = index.to_le_bytes().to_vec();
-        batch.put(key.as_slice(), value.as_slice());
+    async fn get_utxo(&mut self, tx_id: &TxIdType, vout: u32) -> IndexerResult<Option<Utxo>> {
+        let key = KeyPrefix::build_utxo_owner_key(tx_id, vout);
+        let address = self.read(key.as_slice())?;
+        if address.is_none() {
+            return Ok(None);
+        }
+        let address = AddressType::from_bytes(address.unwrap().as_slice());
+        let key = KeyPrefix::build_utxo_key(&address, tx_id, vout);
+        let value = self.read(key.as_slice())?;
+        Ok(value.map(|v| Utxo {
+            tx_id: tx_id.clone(),
+            vout,
+            address,
+            value: u64::from_le_bytes(v.as_slice().try_into().unwrap()),
+        }))
     }
-    fn wrap_update_state(&self, batch: &mut WriteBatch, index: u32) {
-        let binding = KeyPrefix::build_state_key();
-        let key = binding.as_slice();
-        batch.put(key, index.to_le_bytes().as_slice());
+
+    async fn get_utxos(&mut self, address: &AddressType) -> IndexerResult<Vec<Utxo>> {
+        let prefix = KeyPrefix::build_utxo_prefix(address);
+        let l = prefix.len();
+        let ret = self.db.iter_all_mut(
+            prefix.as_slice(),
+            |k| {
+                let tx_id = TxIdType::from_bytes(&k[l..l + 32]);
+                let vout = u32::from_be_bytes(k[l + 32..].try_into().unwrap());
+                (tx_id, vout)
+            },
+            |v| Some(u64::from_le_bytes(v.as_slice().try_into().unwrap())),
+        )?;
+        Ok(ret
+            .into_iter()
+            .map(|((tx_id, vout), value)| Utxo {
+                tx_id,
+                vout,
+                address: address.clone(),
+                value,
+            })
+            .collect())
     }
-    // address|token -> balance
-    pub(crate) fn wrap_address_utxo(
+
+    async fn get_balance_at_height(
         &mut self,
-        batch: &mut WriteBatch,
-        data: &TransactionDelta,
-        add: bool,
-    ) -> IndexerResult<()> {
-        for (address, delta) in &data.deltas {
-            for (token_type, bal) in delta {
-                let key = KeyPrefix::build_address_token_key(address, token_type);
-                let mut value = self.db.get(key.as_slice())?.unwrap_or(vec![]);
-                let mut balance = BalanceType::default();
-                if !value.is_empty() {
-                    balance = serde_json::from_slice(value.as_slice()).unwrap();
-                }
-                if add {
-                    balance.0 = balance.0.clone() + bal.0.clone();
-                } else {
-                    balance.0 = balance.0.clone() - bal.0.clone();
-                    // todo: if balance=0 ,remove key
+        address: &AddressType,
+        token_type: &TokenType,
+        height: u32,
+    ) -> IndexerResult<BalanceType> {
+        let mut ret = BalanceType::default();
+        for (_, wrapper) in self.all_deltas()? {
+            if wrapper.executed_height > height {
+                continue;
+            }
+            // settled deltas were already reverted at settled_height
+            if wrapper.status != DeltaStatus::Executed.to_u8() && wrapper.settled_height <= height {
+                continue;
+            }
+            if let Some(changes) = wrapper.data.deltas.get(address) {
+                for (token, bal) in changes {
+                    if token == token_type {
+                        ret.0 = ret.0.clone() + bal.0.clone();
+                    }
                 }
-                value = serde_json::to_vec(&balance).unwrap();
-                batch.put(key.as_slice(), value.as_slice());
             }
         }
-        Ok(())
+        Ok(ret)
     }
-    pub(crate) fn wrap_seen_txs(
+
+    async fn get_balance_detail(
+        &mut self,
+        address: &AddressType,
+        token_type: &TokenType,
+    ) -> IndexerResult<BalanceDetailResponse> {
+        let pending = self.get_balance(address, token_type).await?;
+        let key = KeyPrefix::build_confirmed_balance_key(address, token_type);
+        let confirmed = self.read_balance(key.as_slice())?;
+        Ok(BalanceDetailResponse { confirmed, pending })
+    }
+
+    async fn get_transaction_delta(
         &mut self,
-        write_batch: &mut WriteBatch,
         tx_id: &TxIdType,
-        status: SeenStatus,
+    ) -> IndexerResult<Option<TransactionDeltaWrapper>> {
+        Ok(self.get_transaction_delta_by_tx_id(tx_id)?.map(|(v, _)| v))
+    }
+
+    async fn list_deltas(
+        &mut self,
+        status: DeltaStatus,
+        pagination: &Pagination,
+    ) -> IndexerResult<Vec<TransactionDeltaWrapper>> {
+        let mut deltas = self.all_deltas()?;
+        deltas.sort_by_key(|(index, _)| *index);
+        Ok(deltas
+            .into_iter()
+            .filter(|(_, v)| v.status == status.to_u8())
+            .skip(pagination.offset)
+            .take(pagination.limit)
+            .map(|(_, v)| v)
+            .collect())
+    }
+
+    async fn register_token(
+        &mut self,
+        token_type: &TokenType,
+        meta: &TokenMeta,
     ) -> IndexerResult<()> {
-        let key = KeyPrefix::build_seen_tx_key(tx_id);
-        let ret = self.db.get(key.as_slice())?;
-        if ret.is_none() {
-            error!(
-                "wrap_seen_txs tx_id:{:?} not found,this should not happen",
-                tx_id
-            );
-            return Ok(());
+        let key = KeyPrefix::build_token_meta_key(token_type);
+        self.put(None, key.as_slice(), serde_json::to_vec(meta)?.as_slice())
+    }
+
+    async fn get_token_meta(&mut self, token_type: &TokenType) -> IndexerResult<Option<TokenMeta>> {
+        let key = KeyPrefix::build_token_meta_key(token_type);
+        match self.read(key.as_slice())? {
+            Some(v) => Ok(Some(serde_json::from_slice(v.as_slice())?)),
+            None => Ok(None),
         }
-        let mut data = ret.unwrap();
-        data[SEEN_DATA_STATUS_INDEX] = status.to_u8();
-        write_batch.put(key.as_slice(), data.as_slice());
-        Ok(())
     }
-    // pub(crate) fn rm_seen_record(&mut self, batch: &mut WriteBatch, tx_id: &TxIdType) {
-    //     let key = KeyPrefix::build_seen_tx_key(tx_id);
-    //     batch.delete(key.as_slice());
-    // }
-    pub fn update_state(&mut self, id: u32) -> IndexerResult<()> {
-        let key = KeyPrefix::State.get_prefix();
-        self.db.set(None, key, id.to_le_bytes().as_slice())?;
-        Ok(())
+
+    async fn add_dead_letter(&mut self, event: &[u8], error: &str) -> IndexerResult<()> {
+        let index = self
+            .get_dead_letters()
+            .await?
+            .last()
+            .map_or(0, |v| v.index + 1);
+        let letter = DeadLetter {
+            index,
+            event: event.to_vec(),
+            error: error.to_string(),
+            timestamp: Local::now().timestamp(),
+        };
+        let key = KeyPrefix::build_dead_letter_key(index);
+        self.put(
+            None,
+            key.as_slice(),
+            serde_json::to_vec(&letter)?.as_slice(),
+        )
     }
-    pub fn acquire_next_state(&mut self) -> IndexerResult<u32> {
-        let ret = self.acquire_latest_state()?;
-        Ok(ret + 1)
+
+    async fn get_dead_letters(&mut self) -> IndexerResult<Vec<DeadLetter>> {
+        let mut records = self.db.iter_all_mut(
+            KeyPrefix::DeadLetter.get_prefix(),
+            |k| k,
+            |v| serde_json::from_slice::<DeadLetter>(v.as_slice()).ok(),
+        )?;
+        records.sort_by(|a, b| a.0.cmp(&b.0));
+        Ok(records.into_iter().map(|(_, v)| v).collect())
     }
-    pub fn acquire_latest_state(&mut self) -> IndexerResult<u32> {
-        let key = KeyPrefix::State.get_prefix();
-        let ret = self
-            .db
-            .get(key)?
-            .map_or(0, |v| u32::from_le_bytes(v.as_slice().try_into().unwrap()));
-        Ok(ret)
+
+    async fn remove_dead_letter(&mut self, index: u64) -> IndexerResult<()> {
+        let mut batch = Batch::new();
+        batch.delete(KeyPrefix::build_dead_letter_key(index).as_slice());
+        self.write(None, batch)
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::storage::db::memory::MemoryDB;
-    use std::collections::HashMap;
+    async fn hold_event(&mut self, event: &[u8]) -> IndexerResult<u64> {
+        let index = self
+            .get_held_events()
+            .await?
+            .last()
+            .map_or(0, |(index, _)| index + 1);
+        let mut batch = Batch::new();
+        batch.put(KeyPrefix::build_held_event_key(index).as_slice(), event);
+        self.write(None, batch)?;
+        Ok(index)
+    }
 
-    #[tokio::test]
-    pub async fn test_get_balance() {
-        let db = MemoryDB::default();
-        let mut storage = KVStorageProcessor::new(db);
+    async fn get_held_events(&mut self) -> IndexerResult<Vec<(u64, Vec<u8>)>> {
+        let prefix_len = KeyPrefix::HeldEvent.get_prefix().len();
+        let mut records = self.db.iter_all_mut(
+            KeyPrefix::HeldEvent.get_prefix(),
+            |k| u64::from_be_bytes(k[prefix_len..].try_into().unwrap()),
+            Some,
+        )?;
+        records.sort_by_key(|(index, _)| *index);
+        Ok(records)
+    }
 
-        let tx_id = [0u8; 32];
-        let mut delta = HashMap::default();
+    async fn remove_held_event(&mut self, index: u64) -> IndexerResult<()> {
+        let mut batch = Batch::new();
+        batch.delete(KeyPrefix::build_held_event_key(index).as_slice());
+        self.write(None, batch)
+    }
 
-        let address = AddressType::from_bytes(&[0u8; 20]);
+    async fn set_chain_state(&mut self, state: &[u8]) -> IndexerResult<()> {
+        let mut batch = Batch::new();
+        batch.put(KeyPrefix::build_chain_state_key().as_slice(), state);
+        self.write(None, batch)
+    }
+
+    async fn get_chain_state(&mut self) -> IndexerResult<Option<Vec<u8>>> {
+        self.read(KeyPrefix::build_chain_state_key().as_slice())
+    }
+
+    async fn append_journal(&mut self, event: &[u8]) -> IndexerResult<u64> {
+        let head_key = KeyPrefix::build_journal_head_key();
+        let seq = self.read_journal_seq(head_key.as_slice())?;
+        let mut batch = Batch::new();
+        batch.put(KeyPrefix::build_event_journal_key(seq).as_slice(), event);
+        batch.put(head_key.as_slice(), &(seq + 1).to_le_bytes());
+        self.write(None, batch)?;
+        Ok(seq)
+    }
+
+    async fn get_journal(
+        &mut self,
+        from_seq: u64,
+        limit: usize,
+    ) -> IndexerResult<Vec<(u64, Vec<u8>)>> {
+        // the seqs are dense between the tail and the head,so the page is read by key
+        let head = self.read_journal_seq(KeyPrefix::build_journal_head_key().as_slice())?;
+        let tail = self.read_journal_seq(KeyPrefix::build_journal_tail_key().as_slice())?;
+        let from = from_seq.max(tail);
+        let to = head.min(from.saturating_add(limit as u64));
+        let seqs: Vec<u64> = (from..to).collect();
+        let keys: Vec<_> = seqs
+            .iter()
+            .map(|seq| KeyPrefix::build_event_journal_key(*seq))
+            .collect();
+        let values = self.read_many(&keys)?;
+        Ok(seqs
+            .into_iter()
+            .zip(values)
+            .filter_map(|(seq, v)| v.map(|v| (seq, v)))
+            .collect())
+    }
+
+    async fn truncate_journal(&mut self, before_seq: u64) -> IndexerResult<()> {
+        let head = self.read_journal_seq(KeyPrefix::build_journal_head_key().as_slice())?;
+        let tail_key = KeyPrefix::build_journal_tail_key();
+        let tail = self.read_journal_seq(tail_key.as_slice())?;
+        let before = before_seq.min(head);
+        if before <= tail {
+            return Ok(());
+        }
+        let mut batch = Batch::new();
+        for seq in tail..before {
+            batch.delete(KeyPrefix::build_event_journal_key(seq).as_slice());
+        }
+        batch.put(tail_key.as_slice(), &before.to_le_bytes());
+        self.write(None, batch)
+    }
+
+    async fn get_raw_tx(&mut self, tx_id: &TxIdType) -> IndexerResult<Option<Transaction>> {
+        let key = KeyPrefix::build_raw_tx_key(tx_id);
+        match self.read(key.as_slice())? {
+            Some(v) => Ok(Some(deserialize(v.as_slice())?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn set_cursor(&mut self, consumer: u32, seq: u64) -> IndexerResult<()> {
+        let mut batch = Batch::new();
+        batch.put(
+            KeyPrefix::build_consumer_cursor_key(consumer).as_slice(),
+            &seq.to_le_bytes(),
+        );
+        self.write(None, batch)
+    }
+
+    async fn get_cursor(&mut self, consumer: u32) -> IndexerResult<Option<u64>> {
+        let key = KeyPrefix::build_consumer_cursor_key(consumer);
+        Ok(self
+            .read(key.as_slice())?
+            .map(|v| u64::from_le_bytes(v.as_slice().try_into().unwrap())))
+    }
+
+    async fn get_pending_txs(&mut self) -> IndexerResult<Vec<PendingTxResponse>> {
+        let mut ret = vec![];
+        for (tx_id, record) in self.all_seen_txs()? {
+            let delta_status = self
+                .get_transaction_delta_by_tx_id(&tx_id)?
+                .map(|(v, _)| DeltaStatus::from_u8(v.status));
+            ret.push(PendingTxResponse {
+                tx_id,
+                first_seen: record.timestamp,
+                executed: record.status == SeenStatus::Executed.to_u8(),
+                delta_status,
+                re_announced: record.re_announced,
+                from_restore: record.from_restore,
+            });
+        }
+        ret.sort_by_key(|v| v.first_seen);
+        Ok(ret)
+    }
+
+    async fn get_state_root(&mut self) -> IndexerResult<String> {
+        let mut deltas = self.all_deltas()?;
+        deltas.sort_by_key(|(index, _)| *index);
+        let mut engine = sha256::Hash::engine();
+        for (index, wrapper) in deltas {
+            engine.input(&index.to_be_bytes());
+            engine.input(&[wrapper.status]);
+            engine.input(&wrapper.data.tx_id.to_bytes());
+            // the delta map has no stable order
+            let mut addresses: Vec<_> = wrapper.data.deltas.iter().collect();
+            addresses.sort_by(|a, b| a.0 .0.cmp(&b.0 .0));
+            for (address, balances) in addresses {
+                engine.input(&address.0);
+                for (token, balance) in balances {
+                    engine.input(&token.to_bytes());
+                    engine.input(balance.0.to_string().as_bytes());
+                }
+            }
+        }
+        Ok(sha256::Hash::from_engine(engine).to_string())
+    }
+
+    async fn begin_tx(&mut self) -> IndexerResult<()> {
+        if self.pending.is_some() {
+            return Err(IndexerError::TransactionAlreadyStarted);
+        }
+        self.pending = Some(PendingWrites::default());
+        Ok(())
+    }
+
+    async fn commit(&mut self) -> IndexerResult<()> {
+        let pending = self
+            .pending
+            .take()
+            .ok_or(IndexerError::NoActiveTransaction)?;
+        self.flush(pending)
+    }
+
+    async fn rollback(&mut self) -> IndexerResult<()> {
+        self.pending
+            .take()
+            .ok_or(IndexerError::NoActiveTransaction)?;
+        Ok(())
+    }
+
+    async fn export(&mut self, path: &str) -> IndexerResult<()> {
+        let state = self.acquire_latest_state()?;
+        let deltas = self.all_deltas()?;
+        let seen_txs = self.all_seen_txs()?;
+        let mut audit_log = vec![];
+        for (k, v) in self
+            .db
+            .iter_all_mut(KeyPrefix::AuditLog.get_prefix(), |k| k, Some)?
+        {
+            let index = u32::from_be_bytes(KeyPrefix::AuditLog.get_suffix(&k).try_into().unwrap());
+            audit_log.push(ExportedDelta::new(index, self.codec.decode_delta(&v)?));
+        }
+        let mut entries = vec![];
+        for prefix in RAW_EXPORT_PREFIXES {
+            for (k, v) in self.db.iter_all_mut(prefix.get_prefix(), |k| k, Some)? {
+                entries.push((hex::encode(k), hex::encode(v)));
+            }
+        }
+        let mut data = StorageExport {
+            version: EXPORT_FORMAT_VERSION,
+            state,
+            deltas: deltas
+                .into_iter()
+                .map(|(index, wrapper)| ExportedDelta::new(index, wrapper))
+                .collect(),
+            seen_txs: seen_txs
+                .into_iter()
+                .map(|(tx_id, v)| ExportedSeenTx {
+                    tx_id,
+                    timestamp: v.timestamp,
+                    status: v.status,
+                    re_announced: v.re_announced,
+                    from_restore: v.from_restore,
+                })
+                .collect(),
+            audit_log,
+            entries,
+        };
+        data.deltas.sort_by_key(|v| v.index);
+        data.audit_log.sort_by_key(|v| v.index);
+        data.entries.sort();
+        info!(
+            "export storage to {},deltas:{},seen txs:{},entries:{}",
+            path,
+            data.deltas.len(),
+            data.seen_txs.len(),
+            data.entries.len()
+        );
+        data.save(path)
+    }
+
+    async fn import(&mut self, path: &str) -> IndexerResult<()> {
+        let data = StorageExport::load(path)?;
+        if data.version == 0 || data.version > EXPORT_FORMAT_VERSION {
+            return Err(IndexerError::UnsupportedExportVersion(data.version));
+        }
+        // the old keys are dropped in the same write the imported ones land with
+        let mut batch = Batch::new();
+        for prefix in REBUILT_EXPORT_PREFIXES
+            .iter()
+            .chain(RAW_EXPORT_PREFIXES.iter())
+            .chain(EXCLUDED_EXPORT_PREFIXES.iter())
+        {
+            for (k, _) in self.db.iter_all_mut(prefix.get_prefix(), |k| k, Some)? {
+                batch.delete(k.as_slice());
+            }
+        }
+
+        // before 3 the history and the confirmed totals were rebuilt from the deltas
+        let legacy = data.version < 3;
+        let mut balances: HashMap<Vec<u8>, BalanceType> = HashMap::new();
+        for delta in &data.deltas {
+            self.wrap_transaction_delta(&mut batch, delta.index, &delta.to_wrapper())?;
+            if legacy && delta.status != DeltaStatus::InActive.to_u8() {
+                self.wrap_address_history(&mut batch, delta.index, &delta.data, true);
+            }
+            // pending balances come from executed deltas,confirmed ones from confirmed deltas
+            let build_key = if delta.status == DeltaStatus::Executed.to_u8() {
+                KeyPrefix::build_address_token_key
+            } else if legacy && delta.status == DeltaStatus::Confirmed.to_u8() {
+                KeyPrefix::build_confirmed_balance_key
+            } else {
+                continue;
+            };
+            for (address, changes) in &delta.data.deltas {
+                for (token_type, bal) in changes {
+                    let key = build_key(address, token_type);
+                    let balance = balances.entry(key).or_default();
+                    balance.0 = balance.0.clone() + bal.0.clone();
+                }
+            }
+        }
+        for (key, balance) in balances {
+            let value = serde_json::to_vec(&balance)?;
+            batch.put(key.as_slice(), value.as_slice());
+        }
+        for seen in &data.seen_txs {
+            let key = KeyPrefix::build_seen_tx_key(&seen.tx_id);
+            let value = self.codec.encode_seen(&SeenRecord {
+                timestamp: seen.timestamp,
+                status: seen.status,
+                re_announced: seen.re_announced,
+                from_restore: seen.from_restore,
+            })?;
+            batch.put(key.as_slice(), value.as_slice());
+        }
+        for delta in &data.audit_log {
+            let value = self.codec.encode_delta(&delta.to_wrapper())?;
+            batch.put(
+                KeyPrefix::build_audit_log_key(delta.index).as_slice(),
+                value.as_slice(),
+            );
+        }
+        for (k, v) in &data.entries {
+            batch.put(hex::decode(k)?.as_slice(), hex::decode(v)?.as_slice());
+        }
+        self.wrap_update_state(&mut batch, data.state);
+        self.db.write_batch(None, batch, true)?;
+        self.reset_counters();
+        info!(
+            "import storage from {},deltas:{},seen txs:{},entries:{}",
+            path,
+            data.deltas.len(),
+            data.seen_txs.len(),
+            data.entries.len()
+        );
+        Ok(())
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct UtxoChanges {
+    created: Vec<Utxo>,
+    spent: Vec<Utxo>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TransactionDeltaWrapper {
+    pub data: TransactionDelta,
+    pub status: u8,
+    // indexed height when the delta was executed
+    #[serde(default)]
+    pub executed_height: u32,
+    // indexed height and timestamp when the delta got confirmed or inactive
+    #[serde(default)]
+    pub settled_height: u32,
+    #[serde(default)]
+    pub settled_at: i64,
+}
+impl<T: DB + Send + Sync + Clone> KVStorageProcessor<T> {
+    pub fn new(db: T) -> Self {
+        Self::with_codec(db, DefaultCodec)
+    }
+}
+impl<T: DB + Send + Sync + Clone, C: Codec> KVStorageProcessor<T, C> {
+    pub fn with_codec(db: T, codec: C) -> Self {
+        Self {
+            db,
+            codec,
+            pending: None,
+            write_ops: Default::default(),
+            counters: Default::default(),
+            created_at: Local::now().timestamp(),
+        }
+    }
+
+    fn all_deltas(&mut self) -> IndexerResult<Vec<(u32, TransactionDeltaWrapper)>> {
+        let prefix_len = KeyPrefix::TransactionDelta.get_prefix().len();
+        let deltas = self.db.iter_all_mut(
+            KeyPrefix::TransactionDelta.get_prefix(),
+            |k| u32::from_le_bytes(k[prefix_len..].try_into().unwrap()),
+            Some,
+        )?;
+        let mut ret = vec![];
+        for (index, v) in deltas {
+            ret.push((index, self.codec.decode_delta(v.as_slice())?));
+        }
+        Ok(ret)
+    }
+
+    fn all_seen_txs(&mut self) -> IndexerResult<Vec<(TxIdType, SeenRecord)>> {
+        let seen_txs = self.db.iter_all_mut(
+            KeyPrefix::SeenTx.get_prefix(),
+            |k| KeyPrefix::get_tx_id_from_seen_key(k.as_slice()),
+            Some,
+        )?;
+        let mut ret = vec![];
+        for (tx_id, v) in seen_txs {
+            ret.push((tx_id, self.codec.decode_seen(v.as_slice())?));
+        }
+        Ok(ret)
+    }
+
+    fn read_journal_seq(&mut self, key: &[u8]) -> IndexerResult<u64> {
+        match self.read(key)? {
+            Some(v) => Ok(u64::from_le_bytes(v.as_slice().try_into().unwrap())),
+            None => Ok(0),
+        }
+    }
+
+    fn read(&mut self, key: &[u8]) -> IndexerResult<Option<Vec<u8>>> {
+        if let Some(pending) = &self.pending {
+            if let Some(v) = pending.overlay.get(key) {
+                return Ok(v.clone());
+            }
+        }
+        self.db.get(key)
+    }
+
+    fn read_many(&mut self, keys: &[Vec<u8>]) -> IndexerResult<Vec<Option<Vec<u8>>>> {
+        let Some(pending) = &self.pending else {
+            return self.db.multi_get(keys);
+        };
+        let misses: Vec<_> = keys
+            .iter()
+            .filter(|k| !pending.overlay.contains_key(k.as_slice()))
+            .cloned()
+            .collect();
+        let mut fetched = self.db.multi_get(&misses)?.into_iter();
+        let pending = self.pending.as_ref().unwrap();
+        Ok(keys
+            .iter()
+            .map(|k| match pending.overlay.get(k.as_slice()) {
+                Some(v) => v.clone(),
+                None => fetched.next().unwrap(),
+            })
+            .collect())
+    }
+
+    fn write(&mut self, tx_id: Option<TxIdType>, batch: Batch) -> IndexerResult<()> {
+        match &mut self.pending {
+            None => {
+                self.write_ops.fetch_add(1, Ordering::Relaxed);
+                self.count_writes(batch.iter())?;
+                self.db.write_batch(tx_id, batch, true)
+            }
+            Some(pending) => {
+                batch.iter().for_each(|(k, v)| {
+                    pending.overlay.insert(k.to_vec(), v.map(|v| v.to_vec()));
+                });
+                pending.ops.push((tx_id, batch));
+                Ok(())
+            }
+        }
+    }
+
+    // the batches land in one write
+    fn write_all(&mut self, batches: Vec<(Option<TxIdType>, Batch)>) -> IndexerResult<()> {
+        if self.pending.is_some() {
+            for (tx_id, batch) in batches {
+                self.write(tx_id, batch)?;
+            }
+            return Ok(());
+        }
+        self.write_ops.fetch_add(1, Ordering::Relaxed);
+        self.count_writes(batches.iter().flat_map(|(_, batch)| batch.iter()))?;
+        self.db.write_batches(batches, true)
+    }
+
+    // every op of the transaction and its key traces in one write,a crash leaves all or nothing
+    fn flush(&mut self, pending: PendingWrites) -> IndexerResult<()> {
+        self.write_ops.fetch_add(1, Ordering::Relaxed);
+        self.count_writes(pending.ops.iter().flat_map(|(_, batch)| batch.iter()))?;
+        self.db.write_batches(group_by_tx(pending.ops), true)
+    }
+
+    fn put(&mut self, tx_id: Option<TxIdType>, key: &[u8], value: &[u8]) -> IndexerResult<()> {
+        if self.pending.is_none() {
+            self.write_ops.fetch_add(1, Ordering::Relaxed);
+            self.count_writes([(key, Some(value))].into_iter())?;
+            return self.db.set(tx_id, key, value);
+        }
+        let mut batch = Batch::new();
+        batch.put(key, value);
+        self.write(tx_id, batch)
+    }
+
+    fn counters(&mut self) -> IndexerResult<StorageCounters> {
+        let mut counters = self.counters.lock().unwrap();
+        if let Some(ret) = *counters {
+            return Ok(ret);
+        }
+        let mut ret = StorageCounters::default();
+        let trace = KeyPrefix::TxKeyTrace.get_prefix();
+        for (k, v) in self.db.iter_all_mut(&[], |k| k, Some)? {
+            if !k.starts_with(trace) {
+                ret.apply(&self.codec, &k, &v, true)?;
+            }
+        }
+        *counters = Some(ret);
+        Ok(ret)
+    }
+
+    // keeps the counters in step with a write that is about to land,nothing is read before the first stats call
+    fn count_writes<'a>(
+        &mut self,
+        ops: impl Iterator<Item = (&'a [u8], Option<&'a [u8]>)>,
+    ) -> IndexerResult<()> {
+        let mut counters = self.counters.lock().unwrap();
+        let Some(mut ret) = *counters else {
+            return Ok(());
+        };
+        let mut latest: HashMap<&[u8], Option<&[u8]>> = HashMap::new();
+        for (k, v) in ops {
+            latest.insert(k, v);
+        }
+        let keys: Vec<_> = latest.keys().map(|k| k.to_vec()).collect();
+        for (k, old) in keys.iter().zip(self.db.multi_get(&keys)?) {
+            if let Some(old) = old {
+                ret.apply(&self.codec, k, &old, false)?;
+            }
+            if let Some(new) = latest[k.as_slice()] {
+                ret.apply(&self.codec, k, new, true)?;
+            }
+        }
+        *counters = Some(ret);
+        Ok(())
+    }
+
+    fn reset_counters(&mut self) {
+        *self.counters.lock().unwrap() = None;
+    }
+
+    pub fn migrate(&mut self) -> IndexerResult<u32>
+    where
+        C: 'static,
+    {
+        self.reset_counters();
+        Migrator::with_codec(self.codec.clone()).run(&mut self.db)
+    }
+
+    fn get_height_txs(&mut self, height: u32) -> IndexerResult<(Vec<u8>, HashSet<TxIdType>)> {
+        let key = KeyPrefix::build_height_txs_key(height);
+        let ret = self.read(key.as_slice())?;
+        let data = if ret.is_none() {
+            let data = HashSet::new();
+            data
+        } else {
+            let data = ret.unwrap();
+            let data: HashSet<TxIdType> = serde_json::from_slice(data.as_slice())?;
+            data
+        };
+        Ok((key, data))
+    }
+    fn get_transaction_delta_by_tx_id(
+        &mut self,
+        tx_id: &TxIdType,
+    ) -> IndexerResult<Option<(TransactionDeltaWrapper, u32)>> {
+        let key = KeyPrefix::build_transaction_index_map_key(tx_id);
+        let index = self.read(key.as_slice())?;
+        if index.is_none() {
+            info!("tx_id:{:?} not found", tx_id);
+            return Ok(None);
+        }
+        let index = index.unwrap();
+        let index = u32::from_le_bytes(index.as_slice().try_into().unwrap());
+        let delta = self
+            .get_transaction_delta_by_index(index)?
+            .expect("impossible");
+        Ok(Some((delta, index)))
+    }
+    fn get_transaction_delta_by_index(
+        &mut self,
+        index: u32,
+    ) -> IndexerResult<Option<TransactionDeltaWrapper>> {
+        let key = KeyPrefix::TransactionDelta.build_prefix(&index.to_le_bytes());
+        let value = self.read(key.as_slice())?;
+        if value.is_none() {
+            return Ok(None);
+        }
+        let wrapper = self.codec.decode_delta(value.unwrap().as_slice())?;
+        Ok(Some(wrapper))
+    }
+    fn rm_seen_tx(&self, batch: &mut Batch, tx_id: &TxIdType) {
+        let key = KeyPrefix::build_seen_tx_key(tx_id);
+        batch.delete(key.as_slice());
+    }
+    fn wrap_transaction_delta(
+        &self,
+        batch: &mut Batch,
+        index: u32,
+        wrapper: &TransactionDeltaWrapper,
+    ) -> IndexerResult<()> {
+        let value = self.codec.encode_delta(wrapper)?;
+        let key = KeyPrefix::build_transaction_data_key(index);
+        batch.put(key.as_slice(), value.as_slice());
+
+        let key = KeyPrefix::build_transaction_index_map_key(&wrapper.data.tx_id);
+        let value = index.to_le_bytes().to_vec();
+        batch.put(key.as_slice(), value.as_slice());
+        Ok(())
+    }
+    fn wrap_address_history(
+        &self,
+        batch: &mut Batch,
+        index: u32,
+        data: &TransactionDelta,
+        add: bool,
+    ) {
+        for address in data.deltas.keys() {
+            let key = KeyPrefix::build_address_history_key(address, index);
+            if add {
+                batch.put(key.as_slice(), data.tx_id.to_bytes().as_slice());
+            } else {
+                batch.delete(key.as_slice());
+            }
+        }
+    }
+    fn wrap_utxo(&self, batch: &mut Batch, utxo: &Utxo, add: bool) {
+        let key = KeyPrefix::build_utxo_key(&utxo.address, &utxo.tx_id, utxo.vout);
+        let owner = KeyPrefix::build_utxo_owner_key(&utxo.tx_id, utxo.vout);
+        if add {
+            batch.put(key.as_slice(), utxo.value.to_le_bytes().as_slice());
+            batch.put(owner.as_slice(), utxo.address.to_bytes().as_slice());
+        } else {
+            batch.delete(key.as_slice());
+            batch.delete(owner.as_slice());
+        }
+    }
+    fn wrap_update_state(&self, batch: &mut Batch, index: u32) {
+        let binding = KeyPrefix::build_state_key();
+        let key = binding.as_slice();
+        batch.put(key, index.to_le_bytes().as_slice());
+    }
+    // address|token -> balance
+    pub(crate) fn wrap_address_utxo(
+        &mut self,
+        batch: &mut Batch,
+        data: &TransactionDelta,
+        add: bool,
+    ) -> IndexerResult<()> {
+        for (address, delta) in &data.deltas {
+            for (token_type, bal) in delta {
+                let key = KeyPrefix::build_address_token_key(address, token_type);
+                let mut value = self.read(key.as_slice())?.unwrap_or(vec![]);
+                let mut balance = BalanceType::default();
+                if !value.is_empty() {
+                    balance = serde_json::from_slice(value.as_slice())?;
+                }
+                if add {
+                    balance.0 = balance.0.clone() + bal.0.clone();
+                } else {
+                    balance.0 = balance.0.clone() - bal.0.clone();
+                    // todo: if balance=0 ,remove key
+                }
+                value = serde_json::to_vec(&balance).unwrap();
+                batch.put(key.as_slice(), value.as_slice());
+            }
+        }
+        Ok(())
+    }
+    fn read_balance(&mut self, key: &[u8]) -> IndexerResult<BalanceType> {
+        let value = self.read(key)?;
+        if value.is_none() {
+            return Ok(BalanceType::default());
+        }
+        Ok(serde_json::from_slice(value.unwrap().as_slice())?)
+    }
+    // address|token -> confirmed balance
+    fn wrap_confirmed_balance(
+        &mut self,
+        batch: &mut Batch,
+        data: &TransactionDelta,
+    ) -> IndexerResult<()> {
+        for (address, delta) in &data.deltas {
+            for (token_type, bal) in delta {
+                let key = KeyPrefix::build_confirmed_balance_key(address, token_type);
+                let mut balance = self.read_balance(key.as_slice())?;
+                balance.0 = balance.0.clone() + bal.0.clone();
+                batch.put(key.as_slice(), serde_json::to_vec(&balance)?.as_slice());
+            }
+        }
+        Ok(())
+    }
+    pub(crate) fn wrap_seen_txs(
+        &mut self,
+        write_batch: &mut Batch,
+        tx_id: &TxIdType,
+        status: SeenStatus,
+    ) -> IndexerResult<()> {
+        let key = KeyPrefix::build_seen_tx_key(tx_id);
+        let ret = self.read(key.as_slice())?;
+        if ret.is_none() {
+            error!(
+                "wrap_seen_txs tx_id:{:?} not found,this should not happen",
+                tx_id
+            );
+            return Ok(());
+        }
+        let mut record = self.codec.decode_seen(ret.unwrap().as_slice())?;
+        record.status = status.to_u8();
+        let data = self.codec.encode_seen(&record)?;
+        write_batch.put(key.as_slice(), data.as_slice());
+        Ok(())
+    }
+    // pub(crate) fn rm_seen_record(&mut self, batch: &mut Batch, tx_id: &TxIdType) {
+    //     let key = KeyPrefix::build_seen_tx_key(tx_id);
+    //     batch.delete(key.as_slice());
+    // }
+    pub fn update_state(&mut self, id: u32) -> IndexerResult<()> {
+        let key = KeyPrefix::State.get_prefix();
+        self.put(None, key, id.to_le_bytes().as_slice())?;
+        Ok(())
+    }
+    pub fn acquire_next_state(&mut self) -> IndexerResult<u32> {
+        let ret = self.acquire_latest_state()?;
+        Ok(ret + 1)
+    }
+    pub fn get_indexed_height(&mut self) -> IndexerResult<u32> {
+        let key = KeyPrefix::build_indexed_height_key();
+        let ret = self
+            .read(key.as_slice())?
+            .map_or(0, |v| u32::from_le_bytes(v.as_slice().try_into().unwrap()));
+        Ok(ret)
+    }
+    pub fn acquire_latest_state(&mut self) -> IndexerResult<u32> {
+        let key = KeyPrefix::State.get_prefix();
+        let ret = self
+            .read(key)?
+            .map_or(0, |v| u32::from_le_bytes(v.as_slice().try_into().unwrap()));
+        Ok(ret)
+    }
+}
+
+// consecutive writes of the same tx are merged into one batch
+fn group_by_tx(ops: Vec<(Option<TxIdType>, Batch)>) -> Vec<(Option<TxIdType>, Batch)> {
+    let mut groups: Vec<(Option<TxIdType>, Batch)> = vec![];
+    for (tx_id, ops) in ops {
+        let merge = groups.last().is_some_and(|(last, _)| *last == tx_id);
+        if !merge {
+            groups.push((tx_id, Batch::new()));
+        }
+        groups.last_mut().unwrap().1.append(ops);
+    }
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::IndexerEvent;
+    use crate::storage::codec::BincodeCodec;
+    use crate::storage::db::memory::MemoryDB;
+    use crate::storage::db::thread_safe::ThreadSafeDB;
+    use bitcoincore_rpc::bitcoin::absolute::LockTime;
+    use std::collections::HashMap;
+
+    // the keys traced to the tx
+    fn traces<T: DB + Send + Sync + Clone, C: Codec>(
+        storage: &mut KVStorageProcessor<T, C>,
+        tx_id: &TxIdType,
+    ) -> usize {
+        let prefix = KeyPrefix::interator_tx_key_prefix(tx_id);
+        storage.db.iter_all_mut(&prefix, |k| k, Some).unwrap().len()
+    }
+
+    #[tokio::test]
+    pub async fn test_get_balance() {
+        let db = MemoryDB::default();
+        let mut storage = KVStorageProcessor::new(db);
+
+        let tx_id = [0u8; 32];
+        let mut delta = HashMap::default();
+
+        let address = AddressType::from_bytes(&[0u8; 20]);
+        {
+            let mut deltas = vec![];
+            deltas.push((TokenType::from_bytes(&[0u8; 20]), BalanceType::from(1)));
+            deltas.push((TokenType::from_bytes(&[1u8; 20]), BalanceType::from(2)));
+            delta.insert(address.clone(), deltas);
+        }
+        let delta = TransactionDelta {
+            tx_id: TxIdType::from_bytes(&tx_id),
+            deltas: delta,
+        };
+        storage.add_transaction_delta(&delta).await.unwrap();
+
+        let bal = storage
+            .get_balance(&address, &TokenType::from_bytes(&[0u8; 20]))
+            .await
+            .unwrap();
+        println!("{:?}", bal);
+        assert_eq!(bal, BalanceType::from(1i32));
+
+        let other = AddressType::from_bytes(&[1u8; 20]);
+        let bals = storage
+            .get_balances(&[
+                (address.clone(), TokenType::from_bytes(&[1u8; 20])),
+                (other, TokenType::from_bytes(&[0u8; 20])),
+                (address.clone(), TokenType::from_bytes(&[0u8; 20])),
+            ])
+            .await
+            .unwrap();
+        assert_eq!(
+            bals,
+            vec![
+                BalanceType::from(2),
+                BalanceType::default(),
+                BalanceType::from(1)
+            ]
+        );
+    }
+    #[tokio::test]
+    pub async fn test_revert() {
+        let db = MemoryDB::default();
+        let mut storage = KVStorageProcessor::new(db);
+
+        let tx_id = [0u8; 32];
+        let mut delta = HashMap::default();
+
+        let address = AddressType::from_bytes(&[0u8; 20]);
         {
             let mut deltas = vec![];
             deltas.push((TokenType::from_bytes(&[0u8; 20]), BalanceType::from(1)));
@@ -404,76 +1527,958 @@ mod tests {
             delta.insert(address.clone(), deltas);
         }
         let delta = TransactionDelta {
-            tx_id: TxIdType::from_bytes(&tx_id),
+            tx_id: TxIdType::from_bytes(&tx_id),
+            deltas: delta,
+        };
+        storage.add_transaction_delta(&delta).await.unwrap();
+
+        let bal = storage
+            .get_balance(&address, &TokenType::from_bytes(&[0u8; 20]))
+            .await
+            .unwrap();
+        println!("{:?}", bal);
+        assert_eq!(bal, BalanceType::from(1i32));
+
+        let tx_id = [1u8; 32];
+        let mut delta = HashMap::default();
+        {
+            let mut deltas = vec![];
+            deltas.push((TokenType::from_bytes(&[0u8; 20]), BalanceType::from(10)));
+            deltas.push((TokenType::from_bytes(&[1u8; 20]), BalanceType::from(20)));
+            delta.insert(address.clone(), deltas);
+        }
+        let delta = TransactionDelta {
+            tx_id: TxIdType::from_bytes(&tx_id),
+            deltas: delta,
+        };
+        storage.add_transaction_delta(&delta).await.unwrap();
+
+        let bal = storage
+            .get_balance(&address, &TokenType::from_bytes(&[0u8; 20]))
+            .await
+            .unwrap();
+        println!("{:?}", bal);
+        assert_eq!(bal, BalanceType::from(11i32));
+
+        storage
+            .remove_transaction_delta(&TxIdType::from_bytes(&tx_id), DeltaStatus::Confirmed)
+            .await
+            .unwrap();
+        let bal = storage
+            .get_balance(&address, &TokenType::from_bytes(&[0u8; 20]))
+            .await
+            .unwrap();
+        println!("{:?}", bal);
+        assert_eq!(bal, BalanceType::from(1i32));
+    }
+
+    #[tokio::test]
+    pub async fn test_snapshot_restore() {
+        let mut storage = KVStorageProcessor::new(MemoryDB::default());
+        let address = AddressType::from_bytes(&[0u8; 20]);
+        let token = TokenType::from_bytes(&[0u8; 20]);
+        let mut delta = HashMap::default();
+        delta.insert(address.clone(), vec![(token.clone(), BalanceType::from(5))]);
+        let delta = TransactionDelta {
+            tx_id: TxIdType::from_bytes(&[0u8; 32]),
+            deltas: delta,
+        };
+        storage.add_transaction_delta(&delta).await.unwrap();
+
+        let dir = "./test_snapshot_restore";
+        let snapshot = storage.take_snapshot(10).await.unwrap();
+        snapshot.save(dir).unwrap();
+
+        let snapshot = Snapshot::latest(dir).unwrap().unwrap();
+        assert_eq!(snapshot.height, 10);
+        let mut restored = KVStorageProcessor::new(MemoryDB::default());
+        restored.restore_snapshot(&snapshot).await.unwrap();
+        let bal = restored.get_balance(&address, &token).await.unwrap();
+        assert_eq!(bal, BalanceType::from(5));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    pub async fn test_export_import() {
+        let mut storage = KVStorageProcessor::new(MemoryDB::default());
+        let address = AddressType::from_bytes(&[0u8; 20]);
+        let token = TokenType::from_bytes(&[0u8; 20]);
+        for (i, amount) in [(0u8, 3), (1u8, 4)] {
+            storage.update_indexed_height(10 + i as u32).await.unwrap();
+            storage
+                .save_height_tx(10 + i as u32, TxIdType::from_bytes(&[i; 32]))
+                .await
+                .unwrap();
+            let mut delta = HashMap::default();
+            delta.insert(
+                address.clone(),
+                vec![(token.clone(), BalanceType::from(amount))],
+            );
+            let delta = TransactionDelta {
+                tx_id: TxIdType::from_bytes(&[i; 32]),
+                deltas: delta,
+            };
+            storage.add_transaction_delta(&delta).await.unwrap();
+        }
+        storage.update_indexed_height(12).await.unwrap();
+        storage
+            .remove_transaction_delta(&TxIdType::from_bytes(&[1u8; 32]), DeltaStatus::InActive)
+            .await
+            .unwrap();
+
+        let path = "./test_export_import.json";
+        storage.export(path).await.unwrap();
+
+        let mut imported = KVStorageProcessor::new(MemoryDB::default());
+        imported.update_indexed_height(99).await.unwrap();
+        imported
+            .save_height_tx(99, TxIdType::from_bytes(&[9u8; 32]))
+            .await
+            .unwrap();
+        imported.import(path).await.unwrap();
+        let bal = imported.get_balance(&address, &token).await.unwrap();
+        assert_eq!(bal, BalanceType::from(3));
+        assert_eq!(imported.acquire_latest_state().unwrap(), 2);
+        assert_eq!(imported.get_indexed_height().unwrap(), 12);
+        for (height, expect) in [(9, 0), (10, 3), (11, 7), (12, 3)] {
+            let bal = imported
+                .get_balance_at_height(&address, &token, height)
+                .await
+                .unwrap();
+            assert_eq!(bal, BalanceType::from(expect));
+        }
+        assert_eq!(imported.get_height_txs(10).unwrap().1.len(), 1);
+        assert!(imported.get_height_txs(99).unwrap().1.is_empty());
+        // every key made it across
+        let (from, to) = (
+            storage.stats().await.unwrap(),
+            imported.stats().await.unwrap(),
+        );
+        assert_eq!(
+            (from.total_keys, from.total_bytes),
+            (to.total_keys, to.total_bytes)
+        );
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    pub async fn test_commit_rollback() {
+        let mut storage = KVStorageProcessor::new(MemoryDB::default());
+        let address = AddressType::from_bytes(&[0u8; 20]);
+        let token = TokenType::from_bytes(&[0u8; 20]);
+        let build = |i: u8| {
+            let mut delta = HashMap::default();
+            delta.insert(address.clone(), vec![(token.clone(), BalanceType::from(2))]);
+            TransactionDelta {
+                tx_id: TxIdType::from_bytes(&[i; 32]),
+                deltas: delta,
+            }
+        };
+
+        storage.begin_tx().await.unwrap();
+        storage.add_transaction_delta(&build(0)).await.unwrap();
+        storage.add_transaction_delta(&build(1)).await.unwrap();
+        let bal = storage.get_balance(&address, &token).await.unwrap();
+        assert_eq!(bal, BalanceType::from(4));
+        let bals = storage
+            .get_balances(&[(address.clone(), token.clone())])
+            .await
+            .unwrap();
+        assert_eq!(bals, vec![BalanceType::from(4)]);
+        storage.rollback().await.unwrap();
+        let bal = storage.get_balance(&address, &token).await.unwrap();
+        assert_eq!(bal, BalanceType::default());
+
+        storage.begin_tx().await.unwrap();
+        assert!(storage.begin_tx().await.is_err());
+        storage.add_transaction_delta(&build(0)).await.unwrap();
+        storage.commit().await.unwrap();
+        let bal = storage.get_balance(&address, &token).await.unwrap();
+        assert_eq!(bal, BalanceType::from(2));
+        assert!(storage.commit().await.is_err());
+
+        // several txs land with a single write and keep their own traces
+        let write_ops = storage.stats().await.unwrap().write_ops;
+        storage.begin_tx().await.unwrap();
+        storage.add_transaction_delta(&build(1)).await.unwrap();
+        storage.add_transaction_delta(&build(2)).await.unwrap();
+        storage.commit().await.unwrap();
+        assert_eq!(storage.stats().await.unwrap().write_ops, write_ops + 1);
+        let reverted = TxIdType::from_bytes(&[1u8; 32]);
+        storage
+            .remove_tx_traces(vec![reverted.clone()])
+            .await
+            .unwrap();
+        assert_eq!(traces(&mut storage, &reverted), 0);
+        assert!(traces(&mut storage, &TxIdType::from_bytes(&[2u8; 32])) > 0);
+    }
+
+    #[tokio::test]
+    pub async fn test_prune_deltas() {
+        let mut storage = KVStorageProcessor::new(MemoryDB::default());
+        let address = AddressType::from_bytes(&[0u8; 20]);
+        let token = TokenType::from_bytes(&[0u8; 20]);
+        for i in 0..3u8 {
+            let mut delta = HashMap::default();
+            delta.insert(address.clone(), vec![(token.clone(), BalanceType::from(1))]);
+            let delta = TransactionDelta {
+                tx_id: TxIdType::from_bytes(&[i; 32]),
+                deltas: delta,
+            };
+            storage.add_transaction_delta(&delta).await.unwrap();
+        }
+        storage.update_indexed_height(10).await.unwrap();
+        storage
+            .remove_transaction_delta(&TxIdType::from_bytes(&[0u8; 32]), DeltaStatus::Confirmed)
+            .await
+            .unwrap();
+        storage.update_indexed_height(20).await.unwrap();
+        storage
+            .remove_transaction_delta(&TxIdType::from_bytes(&[1u8; 32]), DeltaStatus::InActive)
+            .await
+            .unwrap();
+
+        storage.update_indexed_height(25).await.unwrap();
+        let pruned = storage
+            .prune_deltas(&PruneRetention::Height(10))
+            .await
+            .unwrap();
+        assert_eq!(pruned, 1);
+        assert!(storage
+            .get_transaction_delta_by_tx_id(&TxIdType::from_bytes(&[0u8; 32]))
+            .unwrap()
+            .is_none());
+        assert!(storage
+            .get_transaction_delta_by_tx_id(&TxIdType::from_bytes(&[1u8; 32]))
+            .unwrap()
+            .is_some());
+
+        // executed deltas are never pruned
+        storage.update_indexed_height(100).await.unwrap();
+        let pruned = storage
+            .prune_deltas(&PruneRetention::Height(10))
+            .await
+            .unwrap();
+        assert_eq!(pruned, 1);
+        assert!(storage
+            .get_transaction_delta_by_tx_id(&TxIdType::from_bytes(&[2u8; 32]))
+            .unwrap()
+            .is_some());
+        let bal = storage.get_balance(&address, &token).await.unwrap();
+        assert_eq!(bal, BalanceType::from(1));
+
+        // the pruned deltas are gone,the history and the confirmed total come along as is
+        let path = std::env::temp_dir().join("test_prune_deltas_export.json");
+        let path = path.to_str().unwrap();
+        storage.export(path).await.unwrap();
+        let mut imported = KVStorageProcessor::new(MemoryDB::default());
+        imported.import(path).await.unwrap();
+        let detail = imported.get_balance_detail(&address, &token).await.unwrap();
+        assert_eq!(detail.confirmed, BalanceType::from(1));
+        assert_eq!(detail.pending, BalanceType::from(1));
+        let history = imported
+            .get_address_history(&address, &Pagination::default())
+            .await
+            .unwrap();
+        assert_eq!(
+            history,
+            storage
+                .get_address_history(&address, &Pagination::default())
+                .await
+                .unwrap()
+        );
+        assert!(history.contains(&TxIdType::from_bytes(&[0u8; 32])));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    pub async fn test_stats() {
+        let db = MemoryDB::default();
+        let mut storage = KVStorageProcessor::new(db.clone());
+        let address = AddressType::from_bytes(&[0u8; 20]);
+        let token = TokenType::from_bytes(&[0u8; 20]);
+        for i in 0..2u8 {
+            let mut delta = HashMap::default();
+            delta.insert(address.clone(), vec![(token.clone(), BalanceType::from(1))]);
+            let delta = TransactionDelta {
+                tx_id: TxIdType::from_bytes(&[i; 32]),
+                deltas: delta,
+            };
+            storage.add_transaction_delta(&delta).await.unwrap();
+        }
+        storage
+            .remove_transaction_delta(&TxIdType::from_bytes(&[0u8; 32]), DeltaStatus::Confirmed)
+            .await
+            .unwrap();
+
+        let stats = storage.stats().await.unwrap();
+        assert_eq!(stats.unconsumed_deltas, 1);
+        assert_eq!(stats.write_ops, 3);
+        assert!(stats.total_keys > 0);
+        assert!(stats.total_bytes > 0);
+
+        // kept in step on write,a fresh scan agrees
+        storage
+            .remove_transaction_delta(&TxIdType::from_bytes(&[1u8; 32]), DeltaStatus::Confirmed)
+            .await
+            .unwrap();
+        let tx = Transaction {
+            version: 1,
+            lock_time: LockTime::ZERO,
+            input: vec![],
+            output: vec![],
+        };
+        storage.seen_and_store_txs(&tx, false).await.unwrap();
+        let stats = storage.stats().await.unwrap();
+        assert_eq!(stats.unconsumed_deltas, 0);
+        assert_eq!(stats.seen_txs, 1);
+        let scanned = KVStorageProcessor::new(db.clone()).stats().await.unwrap();
+        assert_eq!(
+            (stats.seen_txs, stats.total_keys, stats.total_bytes),
+            (scanned.seen_txs, scanned.total_keys, scanned.total_bytes)
+        );
+
+        // dropping traces keeps the counters,no rescan
+        storage
+            .save_height_tx(5, TxIdType::from_bytes(&[1u8; 32]))
+            .await
+            .unwrap();
+        storage
+            .remove_tx_traces(vec![TxIdType::from_bytes(&[0u8; 32])])
+            .await
+            .unwrap();
+        storage.remove_height_traces(5).await.unwrap();
+        assert!(storage.counters.lock().unwrap().is_some());
+        let stats = storage.stats().await.unwrap();
+        let scanned = KVStorageProcessor::new(db.clone()).stats().await.unwrap();
+        assert_eq!(
+            (stats.total_keys, stats.total_bytes),
+            (scanned.total_keys, scanned.total_bytes)
+        );
+
+        // a corrupt balance is an error,not a panic
+        let key = KeyPrefix::build_address_token_key(&address, &token);
+        db.clone().set(None, key.as_slice(), b"{").unwrap();
+        assert!(storage.get_balance(&address, &token).await.is_err());
+        assert!(storage.get_all_balance(&address).await.is_err());
+    }
+
+    #[tokio::test]
+    pub async fn test_bincode_codec() {
+        let db = MemoryDB::default();
+        let mut storage = KVStorageProcessor::with_codec(db.clone(), BincodeCodec);
+        let address = AddressType::from_bytes(&[0u8; 20]);
+        let token = TokenType::from_bytes(&[0u8; 20]);
+        let tx_id = TxIdType::from_bytes(&[0u8; 32]);
+        let mut delta = HashMap::default();
+        delta.insert(address.clone(), vec![(token.clone(), BalanceType::from(5))]);
+        let delta = TransactionDelta {
+            tx_id: tx_id.clone(),
             deltas: delta,
         };
         storage.add_transaction_delta(&delta).await.unwrap();
 
-        let bal = storage
-            .get_balance(&address, &TokenType::from_bytes(&[0u8; 20]))
+        let (wrapper, _) = storage
+            .get_transaction_delta_by_tx_id(&tx_id)
+            .unwrap()
+            .unwrap();
+        assert_eq!(wrapper.status, DeltaStatus::Executed.to_u8());
+        // records are not json anymore
+        let mut json = KVStorageProcessor::new(db);
+        assert!(json.get_transaction_delta_by_tx_id(&tx_id).is_err());
+
+        storage
+            .remove_transaction_delta(&tx_id, DeltaStatus::Confirmed)
             .await
             .unwrap();
-        println!("{:?}", bal);
-        assert_eq!(bal, BalanceType::from(1i32))
+        let bal = storage.get_balance(&address, &token).await.unwrap();
+        assert_eq!(bal, BalanceType::default());
     }
+
     #[tokio::test]
-    pub async fn test_revert() {
-        let db = MemoryDB::default();
-        let mut storage = KVStorageProcessor::new(db);
+    pub async fn test_get_all_un_consumed_txs() {
+        // same wiring as the default factory
+        let mut storage = KVStorageProcessor::new(ThreadSafeDB::new(MemoryDB::default()));
+        let tx = Transaction {
+            version: 1,
+            lock_time: LockTime::ZERO,
+            input: vec![],
+            output: vec![],
+        };
+        let tx_id: TxIdType = tx.txid().into();
+        assert!(storage.get_all_un_consumed_txs().await.unwrap().is_empty());
 
-        let tx_id = [0u8; 32];
-        let mut delta = HashMap::default();
+        storage.seen_and_store_txs(&tx, false).await.unwrap();
+        let txs = storage.get_all_un_consumed_txs().await.unwrap();
+        assert_eq!(txs.len(), 1);
+        assert!(txs.contains_key(&tx_id));
+
+        storage
+            .add_transaction_delta(&TransactionDelta {
+                tx_id,
+                deltas: HashMap::default(),
+            })
+            .await
+            .unwrap();
+        assert!(storage.get_all_un_consumed_txs().await.unwrap().is_empty());
+    }
 
+    #[tokio::test]
+    pub async fn test_address_history() {
+        let mut storage = KVStorageProcessor::new(MemoryDB::default());
         let address = AddressType::from_bytes(&[0u8; 20]);
-        {
-            let mut deltas = vec![];
-            deltas.push((TokenType::from_bytes(&[0u8; 20]), BalanceType::from(1)));
-            deltas.push((TokenType::from_bytes(&[1u8; 20]), BalanceType::from(2)));
-            delta.insert(address.clone(), deltas);
+        let other = AddressType::from_bytes(&[1u8; 20]);
+        let token = TokenType::from_bytes(&[0u8; 20]);
+        for i in 0..4u8 {
+            let mut delta = HashMap::default();
+            delta.insert(address.clone(), vec![(token.clone(), BalanceType::from(1))]);
+            if i == 0 {
+                delta.insert(other.clone(), vec![(token.clone(), BalanceType::from(1))]);
+            }
+            let delta = TransactionDelta {
+                tx_id: TxIdType::from_bytes(&[i; 32]),
+                deltas: delta,
+            };
+            storage.add_transaction_delta(&delta).await.unwrap();
         }
-        let delta = TransactionDelta {
-            tx_id: TxIdType::from_bytes(&tx_id),
-            deltas: delta,
+        storage
+            .remove_transaction_delta(&TxIdType::from_bytes(&[1u8; 32]), DeltaStatus::InActive)
+            .await
+            .unwrap();
+
+        let history = storage
+            .get_address_history(&address, &Pagination::default())
+            .await
+            .unwrap();
+        assert_eq!(
+            history,
+            vec![
+                TxIdType::from_bytes(&[0u8; 32]),
+                TxIdType::from_bytes(&[2u8; 32]),
+                TxIdType::from_bytes(&[3u8; 32]),
+            ]
+        );
+        let page = storage
+            .get_address_history(
+                &address,
+                &Pagination {
+                    offset: 1,
+                    limit: 1,
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(page, vec![TxIdType::from_bytes(&[2u8; 32])]);
+        let history = storage
+            .get_address_history(&other, &Pagination::default())
+            .await
+            .unwrap();
+        assert_eq!(history, vec![TxIdType::from_bytes(&[0u8; 32])]);
+    }
+
+    #[tokio::test]
+    pub async fn test_utxos() {
+        let mut storage = KVStorageProcessor::new(MemoryDB::default());
+        let alice = AddressType::from_bytes(&[0u8; 22]);
+        let bob = AddressType::from_bytes(&[1u8; 22]);
+        let funding = TxIdType::from_bytes(&[0u8; 32]);
+        let spending = TxIdType::from_bytes(&[1u8; 32]);
+        let utxo = |tx_id: &TxIdType, vout: u32, address: &AddressType, value: u64| Utxo {
+            tx_id: tx_id.clone(),
+            vout,
+            address: address.clone(),
+            value,
         };
-        storage.add_transaction_delta(&delta).await.unwrap();
 
-        let bal = storage
-            .get_balance(&address, &TokenType::from_bytes(&[0u8; 20]))
+        storage
+            .apply_utxos(&funding, vec![utxo(&funding, 0, &alice, 100)], vec![])
             .await
             .unwrap();
-        println!("{:?}", bal);
-        assert_eq!(bal, BalanceType::from(1i32));
+        storage.settle_utxos(&funding, false).await.unwrap();
+        let spent = storage.get_utxo(&funding, 0).await.unwrap().unwrap();
+        storage
+            .apply_utxos(
+                &spending,
+                vec![utxo(&spending, 0, &bob, 60), utxo(&spending, 1, &alice, 30)],
+                vec![spent],
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            storage.get_utxos(&alice).await.unwrap(),
+            vec![utxo(&spending, 1, &alice, 30)]
+        );
+        assert_eq!(storage.get_utxos(&bob).await.unwrap().len(), 1);
 
-        let tx_id = [1u8; 32];
-        let mut delta = HashMap::default();
-        {
-            let mut deltas = vec![];
-            deltas.push((TokenType::from_bytes(&[0u8; 20]), BalanceType::from(10)));
-            deltas.push((TokenType::from_bytes(&[1u8; 20]), BalanceType::from(20)));
-            delta.insert(address.clone(), deltas);
+        // the spending tx is dropped
+        storage.settle_utxos(&spending, true).await.unwrap();
+        assert_eq!(
+            storage.get_utxos(&alice).await.unwrap(),
+            vec![utxo(&funding, 0, &alice, 100)]
+        );
+        assert!(storage.get_utxos(&bob).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    pub async fn test_balance_at_height() {
+        let mut storage = KVStorageProcessor::new(MemoryDB::default());
+        let address = AddressType::from_bytes(&[0u8; 20]);
+        let token = TokenType::from_bytes(&[0u8; 20]);
+        let build = |i: u8, v: i32| {
+            let mut delta = HashMap::default();
+            delta.insert(address.clone(), vec![(token.clone(), BalanceType::from(v))]);
+            TransactionDelta {
+                tx_id: TxIdType::from_bytes(&[i; 32]),
+                deltas: delta,
+            }
+        };
+        storage.update_indexed_height(10).await.unwrap();
+        storage.add_transaction_delta(&build(0, 5)).await.unwrap();
+        storage.update_indexed_height(12).await.unwrap();
+        storage.add_transaction_delta(&build(1, 3)).await.unwrap();
+        storage.update_indexed_height(15).await.unwrap();
+        storage
+            .remove_transaction_delta(&TxIdType::from_bytes(&[0u8; 32]), DeltaStatus::Confirmed)
+            .await
+            .unwrap();
+
+        for (h, expect) in [(9, 0), (10, 5), (14, 8), (15, 3)] {
+            let bal = storage
+                .get_balance_at_height(&address, &token, h)
+                .await
+                .unwrap();
+            assert_eq!(bal, BalanceType::from(expect));
         }
-        let delta = TransactionDelta {
-            tx_id: TxIdType::from_bytes(&tx_id),
-            deltas: delta,
+    }
+
+    #[tokio::test]
+    pub async fn test_add_transaction_deltas() {
+        let mut storage = KVStorageProcessor::new(MemoryDB::default());
+        let address = AddressType::from_bytes(&[0u8; 20]);
+        let token = TokenType::from_bytes(&[0u8; 20]);
+        let deltas: Vec<TransactionDelta> = (0..10u8)
+            .map(|i| {
+                let mut delta = HashMap::default();
+                delta.insert(address.clone(), vec![(token.clone(), BalanceType::from(2))]);
+                TransactionDelta {
+                    tx_id: TxIdType::from_bytes(&[i; 32]),
+                    deltas: delta,
+                }
+            })
+            .collect();
+        storage.add_transaction_deltas(&deltas).await.unwrap();
+
+        let bal = storage.get_balance(&address, &token).await.unwrap();
+        assert_eq!(bal, BalanceType::from(20));
+        assert_eq!(storage.acquire_latest_state().unwrap(), 10);
+
+        // every tx of the set keeps its own traces
+        let reverted = TxIdType::from_bytes(&[3u8; 32]);
+        storage
+            .remove_tx_traces(vec![reverted.clone()])
+            .await
+            .unwrap();
+        assert_eq!(traces(&mut storage, &reverted), 0);
+        assert!(storage
+            .get_transaction_delta_by_tx_id(&reverted)
+            .unwrap()
+            .is_some());
+        for i in (0..10u8).filter(|i| *i != 3) {
+            let tx_id = TxIdType::from_bytes(&[i; 32]);
+            assert!(traces(&mut storage, &tx_id) > 0);
+        }
+    }
+
+    #[tokio::test]
+    pub async fn test_balance_detail() {
+        let mut storage = KVStorageProcessor::new(MemoryDB::default());
+        let address = AddressType::from_bytes(&[0u8; 20]);
+        let token = TokenType::from_bytes(&[0u8; 20]);
+        for (i, v) in [(0u8, 5), (1, 3), (2, 7)] {
+            let mut delta = HashMap::default();
+            delta.insert(address.clone(), vec![(token.clone(), BalanceType::from(v))]);
+            let delta = TransactionDelta {
+                tx_id: TxIdType::from_bytes(&[i; 32]),
+                deltas: delta,
+            };
+            storage.add_transaction_delta(&delta).await.unwrap();
+        }
+        storage
+            .remove_transaction_delta(&TxIdType::from_bytes(&[0u8; 32]), DeltaStatus::Confirmed)
+            .await
+            .unwrap();
+        storage
+            .remove_transaction_delta(&TxIdType::from_bytes(&[1u8; 32]), DeltaStatus::InActive)
+            .await
+            .unwrap();
+
+        let detail = storage.get_balance_detail(&address, &token).await.unwrap();
+        assert_eq!(detail.confirmed, BalanceType::from(5));
+        assert_eq!(detail.pending, BalanceType::from(7));
+
+        // the totals are shared by every tx,no trace points at them
+        let shared = [
+            KeyPrefix::build_address_token_key(&address, &token),
+            KeyPrefix::build_confirmed_balance_key(&address, &token),
+            KeyPrefix::build_state_key(),
+        ];
+        for i in 0..3u8 {
+            let prefix = KeyPrefix::interator_tx_key_prefix(&TxIdType::from_bytes(&[i; 32]));
+            let traced = storage
+                .db
+                .iter_all_mut(&prefix, |k| KeyPrefix::split_tx_key_trace(&k).1, Some)
+                .unwrap();
+            assert!(traced.iter().all(|(k, _)| !shared.contains(k)));
+        }
+
+        // the confirm path drops the traces of every settled tx
+        let settled = vec![
+            TxIdType::from_bytes(&[0u8; 32]),
+            TxIdType::from_bytes(&[1u8; 32]),
+        ];
+        storage.remove_tx_traces(settled).await.unwrap();
+        let last = TxIdType::from_bytes(&[2u8; 32]);
+        storage
+            .remove_transaction_delta(&last, DeltaStatus::Confirmed)
+            .await
+            .unwrap();
+        storage.remove_tx_traces(vec![last]).await.unwrap();
+        let detail = storage.get_balance_detail(&address, &token).await.unwrap();
+        assert_eq!(detail.confirmed, BalanceType::from(12));
+        assert_eq!(detail.pending, BalanceType::from(0));
+    }
+
+    #[tokio::test]
+    pub async fn test_list_deltas() {
+        let mut storage = KVStorageProcessor::new(MemoryDB::default());
+        let address = AddressType::from_bytes(&[0u8; 20]);
+        let token = TokenType::from_bytes(&[0u8; 20]);
+        for i in 0..5u8 {
+            let mut delta = HashMap::default();
+            delta.insert(address.clone(), vec![(token.clone(), BalanceType::from(1))]);
+            let delta = TransactionDelta {
+                tx_id: TxIdType::from_bytes(&[i; 32]),
+                deltas: delta,
+            };
+            storage.add_transaction_delta(&delta).await.unwrap();
+        }
+        storage
+            .remove_transaction_delta(&TxIdType::from_bytes(&[1u8; 32]), DeltaStatus::InActive)
+            .await
+            .unwrap();
+
+        let delta = storage
+            .get_transaction_delta(&TxIdType::from_bytes(&[1u8; 32]))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(delta.status, DeltaStatus::InActive.to_u8());
+        assert!(storage
+            .get_transaction_delta(&TxIdType::from_bytes(&[9u8; 32]))
+            .await
+            .unwrap()
+            .is_none());
+
+        let executed = storage
+            .list_deltas(
+                DeltaStatus::Executed,
+                &Pagination {
+                    offset: 1,
+                    limit: 2,
+                },
+            )
+            .await
+            .unwrap();
+        let ids: Vec<TxIdType> = executed.into_iter().map(|v| v.data.tx_id).collect();
+        assert_eq!(
+            ids,
+            vec![
+                TxIdType::from_bytes(&[2u8; 32]),
+                TxIdType::from_bytes(&[3u8; 32])
+            ]
+        );
+        let inactive = storage
+            .list_deltas(DeltaStatus::InActive, &Pagination::default())
+            .await
+            .unwrap();
+        assert_eq!(inactive.len(), 1);
+    }
+
+    #[tokio::test]
+    pub async fn test_gc_inactive_deltas() {
+        let mut storage = KVStorageProcessor::new(MemoryDB::default());
+        let address = AddressType::from_bytes(&[0u8; 20]);
+        let token = TokenType::from_bytes(&[0u8; 20]);
+        for i in 0..3u8 {
+            let mut delta = HashMap::default();
+            delta.insert(address.clone(), vec![(token.clone(), BalanceType::from(1))]);
+            let delta = TransactionDelta {
+                tx_id: TxIdType::from_bytes(&[i; 32]),
+                deltas: delta,
+            };
+            storage.add_transaction_delta(&delta).await.unwrap();
+        }
+        for i in [2u8, 0u8] {
+            storage
+                .remove_transaction_delta(&TxIdType::from_bytes(&[i; 32]), DeltaStatus::InActive)
+                .await
+                .unwrap();
+        }
+
+        assert_eq!(storage.gc_inactive_deltas().await.unwrap(), 2);
+        assert_eq!(storage.gc_inactive_deltas().await.unwrap(), 0);
+        assert!(storage
+            .get_transaction_delta(&TxIdType::from_bytes(&[0u8; 32]))
+            .await
+            .unwrap()
+            .is_none());
+        assert!(storage
+            .get_transaction_delta(&TxIdType::from_bytes(&[1u8; 32]))
+            .await
+            .unwrap()
+            .is_some());
+
+        let log = storage.get_audit_log(&Pagination::default()).await.unwrap();
+        let ids: Vec<TxIdType> = log.into_iter().map(|v| v.data.tx_id).collect();
+        assert_eq!(
+            ids,
+            vec![
+                TxIdType::from_bytes(&[0u8; 32]),
+                TxIdType::from_bytes(&[2u8; 32])
+            ]
+        );
+    }
+
+    #[tokio::test]
+    pub async fn test_seen_metadata() {
+        let mut storage = KVStorageProcessor::new(MemoryDB::default());
+        let tx = Transaction {
+            version: 1,
+            lock_time: LockTime::ZERO,
+            input: vec![],
+            output: vec![],
         };
-        storage.add_transaction_delta(&delta).await.unwrap();
+        let tx_id: TxIdType = tx.txid().into();
 
-        let bal = storage
-            .get_balance(&address, &TokenType::from_bytes(&[0u8; 20]))
+        let seen = storage.seen_and_store_txs(&tx, true).await.unwrap();
+        assert!(!seen.is_seen());
+        let first_seen = seen.first_seen();
+        for i in 1..=3 {
+            let seen = storage.seen_and_store_txs(&tx, false).await.unwrap();
+            assert!(seen.is_seen());
+            assert_eq!(seen.re_announced(), i);
+        }
+        let seen = storage.seen_tx(tx_id).await.unwrap();
+        assert_eq!(seen.first_seen(), first_seen);
+        assert_eq!(seen.re_announced(), 3);
+        assert!(seen.is_from_restore());
+        assert!(!seen.is_executed());
+
+        // legacy records carry no metadata
+        let legacy = DefaultCodec
+            .decode_seen(&[&7i64.to_le_bytes()[..], &[1u8]].concat())
+            .unwrap();
+        assert_eq!(legacy.timestamp, 7);
+        assert_eq!(legacy.re_announced, 0);
+        assert!(!legacy.from_restore);
+    }
+
+    #[tokio::test]
+    pub async fn test_pending_txs() {
+        let mut storage = KVStorageProcessor::new(MemoryDB::default());
+        let txs: Vec<Transaction> = (0..2)
+            .map(|i| Transaction {
+                version: i,
+                lock_time: LockTime::ZERO,
+                input: vec![],
+                output: vec![],
+            })
+            .collect();
+        for tx in &txs {
+            storage.seen_and_store_txs(tx, false).await.unwrap();
+        }
+        let executed: TxIdType = txs[0].txid().into();
+        assert_eq!(
+            storage.get_raw_tx(&executed).await.unwrap(),
+            Some(txs[0].clone())
+        );
+        assert_eq!(
+            storage
+                .get_raw_tx(&TxIdType::from_bytes(&[9u8; 32]))
+                .await
+                .unwrap(),
+            None
+        );
+        let mut deltas = HashMap::default();
+        deltas.insert(
+            AddressType::from_bytes(&[0u8; 20]),
+            vec![(TokenType::from_bytes(&[0u8; 20]), BalanceType::from(1))],
+        );
+        storage
+            .add_transaction_delta(&TransactionDelta {
+                tx_id: executed.clone(),
+                deltas,
+            })
             .await
             .unwrap();
-        println!("{:?}", bal);
-        assert_eq!(bal, BalanceType::from(11i32));
+
+        let pending = storage.get_pending_txs().await.unwrap();
+        assert_eq!(pending.len(), 2);
+        let first = pending.iter().find(|v| v.tx_id == executed).unwrap();
+        assert!(first.executed);
+        assert_eq!(first.delta_status, Some(DeltaStatus::Executed));
+        let second = pending.iter().find(|v| v.tx_id != executed).unwrap();
+        assert!(!second.executed);
+        assert_eq!(second.delta_status, None);
 
         storage
-            .remove_transaction_delta(&TxIdType::from_bytes(&tx_id), DeltaStatus::Confirmed)
+            .remove_transaction_delta(&executed, DeltaStatus::Confirmed)
             .await
             .unwrap();
-        let bal = storage
-            .get_balance(&address, &TokenType::from_bytes(&[0u8; 20]))
+        let pending = storage.get_pending_txs().await.unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_ne!(pending[0].tx_id, executed);
+    }
+
+    #[tokio::test]
+    pub async fn test_token_registry() {
+        let mut storage = KVStorageProcessor::new(MemoryDB::default());
+        let token = TokenType::from_bytes(b"ordi");
+        assert!(storage.get_token_meta(&token).await.unwrap().is_none());
+
+        let mut meta = TokenMeta {
+            decimals: 18,
+            protocol: "brc20".to_string(),
+            deploy_tx_id: TxIdType::from_bytes(&[1u8; 32]),
+        };
+        storage.register_token(&token, &meta).await.unwrap();
+        assert_eq!(
+            storage.get_token_meta(&token).await.unwrap(),
+            Some(meta.clone())
+        );
+
+        meta.decimals = 8;
+        storage.register_token(&token, &meta).await.unwrap();
+        assert_eq!(storage.get_token_meta(&token).await.unwrap(), Some(meta));
+        assert!(storage
+            .get_token_meta(&TokenType::from_bytes(b"sats"))
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    pub async fn test_dead_letters() {
+        let mut storage = KVStorageProcessor::new(MemoryDB::default());
+        let event = IndexerEvent::TxRemoved(TxIdType::from_bytes(&[1u8; 32]));
+        storage
+            .add_dead_letter(&event.to_bytes(), "first")
             .await
             .unwrap();
-        println!("{:?}", bal);
-        assert_eq!(bal, BalanceType::from(1i32));
+        storage
+            .add_dead_letter(&IndexerEvent::ReportHeight(7).to_bytes(), "second")
+            .await
+            .unwrap();
+
+        let letters = storage.get_dead_letters().await.unwrap();
+        assert_eq!(letters.len(), 2);
+        assert_eq!(letters[0].index, 0);
+        assert_eq!(letters[1].error, "second");
+        assert!(matches!(
+            IndexerEvent::from_bytes(&letters[0].event).unwrap(),
+            IndexerEvent::TxRemoved(v) if v == TxIdType::from_bytes(&[1u8; 32])
+        ));
+
+        storage.remove_dead_letter(0).await.unwrap();
+        let letters = storage.get_dead_letters().await.unwrap();
+        assert_eq!(letters.len(), 1);
+        assert_eq!(letters[0].index, 1);
+        storage.add_dead_letter(&[13], "third").await.unwrap();
+        assert_eq!(storage.get_dead_letters().await.unwrap()[1].index, 2);
+    }
+
+    #[tokio::test]
+    pub async fn test_held_events() {
+        let mut storage = KVStorageProcessor::new(MemoryDB::default());
+        let first = IndexerEvent::ReportHeight(7).to_bytes();
+        let second = IndexerEvent::TxRemoved(TxIdType::from_bytes(&[1u8; 32])).to_bytes();
+        assert_eq!(storage.hold_event(&first).await.unwrap(), 0);
+        assert_eq!(storage.hold_event(&second).await.unwrap(), 1);
+        assert_eq!(
+            storage.get_held_events().await.unwrap(),
+            vec![(0, first), (1, second.clone())]
+        );
+
+        storage.remove_held_event(0).await.unwrap();
+        assert_eq!(storage.get_held_events().await.unwrap(), vec![(1, second)]);
+        assert_eq!(storage.hold_event(&[13]).await.unwrap(), 2);
+    }
+
+    #[tokio::test]
+    pub async fn test_state_root() {
+        let address = AddressType::from_bytes(&[0u8; 20]);
+        let token = TokenType::from_bytes(&[0u8; 20]);
+        let deltas: Vec<TransactionDelta> = (0..3u8)
+            .map(|i| {
+                let mut delta = HashMap::default();
+                delta.insert(address.clone(), vec![(token.clone(), BalanceType::from(1))]);
+                delta.insert(
+                    AddressType::from_bytes(&[i + 1; 20]),
+                    vec![(token.clone(), BalanceType::from(-1))],
+                );
+                TransactionDelta {
+                    tx_id: TxIdType::from_bytes(&[i; 32]),
+                    deltas: delta,
+                }
+            })
+            .collect();
+        let mut a = KVStorageProcessor::new(MemoryDB::default());
+        let mut b = KVStorageProcessor::new(MemoryDB::default());
+        let empty = a.get_state_root().await.unwrap();
+        for delta in &deltas {
+            a.add_transaction_delta(delta).await.unwrap();
+            b.add_transaction_delta(delta).await.unwrap();
+        }
+        let root = a.get_state_root().await.unwrap();
+        assert_ne!(root, empty);
+        assert_eq!(root, b.get_state_root().await.unwrap());
+
+        b.remove_transaction_delta(&deltas[0].tx_id, DeltaStatus::Confirmed)
+            .await
+            .unwrap();
+        assert_ne!(root, b.get_state_root().await.unwrap());
+    }
+
+    #[tokio::test]
+    pub async fn test_event_journal() {
+        let mut storage = KVStorageProcessor::new(MemoryDB::default());
+        for i in 0..5u8 {
+            assert_eq!(storage.append_journal(&[i]).await.unwrap(), i as u64);
+        }
+        let journal = storage.get_journal(2, 2).await.unwrap();
+        assert_eq!(journal, vec![(2, vec![2]), (3, vec![3])]);
+        assert_eq!(storage.get_journal(3, 10).await.unwrap().len(), 2);
+        assert!(storage.get_journal(5, 10).await.unwrap().is_empty());
+
+        storage.truncate_journal(3).await.unwrap();
+        let journal = storage.get_journal(0, 10).await.unwrap();
+        assert_eq!(journal, vec![(3, vec![3]), (4, vec![4])]);
+        // the seqs keep counting after a truncation
+        assert_eq!(storage.append_journal(&[5]).await.unwrap(), 5);
+        storage.truncate_journal(2).await.unwrap();
+        assert_eq!(storage.get_journal(0, 10).await.unwrap().len(), 3);
+        storage.truncate_journal(100).await.unwrap();
+        assert!(storage.get_journal(0, 10).await.unwrap().is_empty());
+        assert_eq!(storage.append_journal(&[6]).await.unwrap(), 6);
+        assert_eq!(
+            storage.get_journal(0, 10).await.unwrap(),
+            vec![(6, vec![6])]
+        );
+
+        assert_eq!(storage.get_cursor(1).await.unwrap(), None);
+        storage.set_cursor(1, 3).await.unwrap();
+        storage.set_cursor(2, 4).await.unwrap();
+        assert_eq!(storage.get_cursor(1).await.unwrap(), Some(3));
+        assert_eq!(storage.get_cursor(2).await.unwrap(), Some(4));
     }
 }
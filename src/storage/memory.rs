@@ -1,3 +1,5 @@
+// MemoryStorageProcessor is retired: the in-memory wiring used by the factory is
+// KVStorageProcessor<ThreadSafeDB<MemoryDB>>,which implements get_all_un_consumed_txs
 // use crate::error::IndexerResult;
 // use crate::event::{AddressType, BalanceType, TokenType, TxIdType};
 // use crate::storage::StorageProcessor;
@@ -0,0 +1,1136 @@
+use crate::error::IndexerResult;
+use crate::event::{AddressType, BalanceType, TokenType, TxIdType};
+use crate::storage::kv::TransactionDeltaWrapper;
+use crate::storage::prefix::DeltaStatus;
+use crate::storage::prune::PruneRetention;
+use crate::storage::snapshot::Snapshot;
+use crate::storage::{SeenStatusResponse, StorageProcessor, StorageStats};
+use crate::types::dead_letter::DeadLetter;
+use crate::types::delta::TransactionDelta;
+use crate::types::request::Pagination;
+use crate::types::response::{AllBalanceResponse, BalanceDetailResponse, PendingTxResponse};
+use crate::types::token::TokenMeta;
+use crate::types::transaction::Utxo;
+use bitcoincore_rpc::bitcoin::Transaction;
+use std::collections::HashMap;
+use std::fmt::{Debug, Formatter};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+// wraps a storage with an extra layer,e.g. caching,metrics or validation
+pub trait StorageMiddleware: Send + Sync {
+    fn name(&self) -> &str;
+
+    fn layer(&self, inner: Box<dyn StorageProcessor>) -> Box<dyn StorageProcessor>;
+}
+
+// the first pushed middleware is the outermost layer
+#[derive(Clone, Default)]
+pub struct MiddlewareStack {
+    middlewares: Vec<Arc<dyn StorageMiddleware>>,
+}
+
+impl MiddlewareStack {
+    pub fn push(mut self, middleware: Arc<dyn StorageMiddleware>) -> Self {
+        self.middlewares.push(middleware);
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.middlewares.is_empty()
+    }
+
+    pub fn apply<T: StorageProcessor + 'static>(&self, storage: T) -> LayeredStorageProcessor {
+        let mut ret: Box<dyn StorageProcessor> = Box::new(storage);
+        for middleware in self.middlewares.iter().rev() {
+            ret = middleware.layer(ret);
+        }
+        LayeredStorageProcessor {
+            internal: Arc::new(Mutex::new(ret)),
+        }
+    }
+}
+
+impl Debug for MiddlewareStack {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_list()
+            .entries(self.middlewares.iter().map(|v| v.name()))
+            .finish()
+    }
+}
+
+// base of a layer: it overrides the calls it intercepts,every other call goes to the inner storage
+#[async_trait::async_trait]
+pub trait StorageLayer: Send + Sync {
+    fn inner(&mut self) -> &mut dyn StorageProcessor;
+    async fn get_balance(
+        &mut self,
+        address: &AddressType,
+        token_type: &TokenType,
+    ) -> IndexerResult<BalanceType> {
+        self.inner().get_balance(address, token_type).await
+    }
+
+    async fn add_transaction_delta(&mut self, transaction: &TransactionDelta) -> IndexerResult<()> {
+        self.inner().add_transaction_delta(transaction).await
+    }
+
+    async fn add_transaction_deltas(
+        &mut self,
+        transactions: &[TransactionDelta],
+    ) -> IndexerResult<()> {
+        self.inner().add_transaction_deltas(transactions).await
+    }
+
+    async fn remove_transaction_delta(
+        &mut self,
+        tx_id: &TxIdType,
+        status: DeltaStatus,
+    ) -> IndexerResult<()> {
+        self.inner().remove_transaction_delta(tx_id, status).await
+    }
+
+    async fn seen_and_store_txs(
+        &mut self,
+        tx: &Transaction,
+        from_restore: bool,
+    ) -> IndexerResult<SeenStatusResponse> {
+        self.inner().seen_and_store_txs(tx, from_restore).await
+    }
+
+    async fn seen_tx(&mut self, tx_id: TxIdType) -> IndexerResult<SeenStatusResponse> {
+        self.inner().seen_tx(tx_id).await
+    }
+
+    async fn get_all_un_consumed_txs(&mut self) -> IndexerResult<HashMap<TxIdType, i64>> {
+        self.inner().get_all_un_consumed_txs().await
+    }
+
+    async fn is_tx_executed(&mut self, tx_id: &TxIdType) -> IndexerResult<bool> {
+        self.inner().is_tx_executed(tx_id).await
+    }
+
+    async fn get_all_balance(
+        &mut self,
+        address: &AddressType,
+    ) -> IndexerResult<Vec<AllBalanceResponse>> {
+        self.inner().get_all_balance(address).await
+    }
+
+    async fn simple_set(
+        &mut self,
+        tx_id: &TxIdType,
+        key: &[u8],
+        value: Vec<u8>,
+    ) -> IndexerResult<()> {
+        self.inner().simple_set(tx_id, key, value).await
+    }
+
+    async fn simple_get(&mut self, key: &[u8]) -> IndexerResult<Option<Vec<u8>>> {
+        self.inner().simple_get(key).await
+    }
+
+    async fn save_height_tx(&mut self, height: u32, tx_id: TxIdType) -> IndexerResult<()> {
+        self.inner().save_height_tx(height, tx_id).await
+    }
+
+    async fn remove_height_traces(&mut self, height: u32) -> IndexerResult<()> {
+        self.inner().remove_height_traces(height).await
+    }
+
+    async fn remove_tx_traces(&mut self, tx_id: Vec<TxIdType>) -> IndexerResult<()> {
+        self.inner().remove_tx_traces(tx_id).await
+    }
+
+    async fn take_snapshot(&mut self, height: u32) -> IndexerResult<Snapshot> {
+        self.inner().take_snapshot(height).await
+    }
+
+    async fn restore_snapshot(&mut self, snapshot: &Snapshot) -> IndexerResult<()> {
+        self.inner().restore_snapshot(snapshot).await
+    }
+
+    async fn update_indexed_height(&mut self, height: u32) -> IndexerResult<()> {
+        self.inner().update_indexed_height(height).await
+    }
+
+    async fn stats(&mut self) -> IndexerResult<StorageStats> {
+        self.inner().stats().await
+    }
+
+    async fn prune_deltas(&mut self, retention: &PruneRetention) -> IndexerResult<u32> {
+        self.inner().prune_deltas(retention).await
+    }
+
+    async fn get_address_history(
+        &mut self,
+        address: &AddressType,
+        pagination: &Pagination,
+    ) -> IndexerResult<Vec<TxIdType>> {
+        self.inner().get_address_history(address, pagination).await
+    }
+
+    async fn apply_utxos(
+        &mut self,
+        tx_id: &TxIdType,
+        created: Vec<Utxo>,
+        spent: Vec<Utxo>,
+    ) -> IndexerResult<()> {
+        self.inner().apply_utxos(tx_id, created, spent).await
+    }
+
+    async fn settle_utxos(&mut self, tx_id: &TxIdType, revert: bool) -> IndexerResult<()> {
+        self.inner().settle_utxos(tx_id, revert).await
+    }
+
+    async fn get_utxo(&mut self, tx_id: &TxIdType, vout: u32) -> IndexerResult<Option<Utxo>> {
+        self.inner().get_utxo(tx_id, vout).await
+    }
+
+    async fn get_utxos(&mut self, address: &AddressType) -> IndexerResult<Vec<Utxo>> {
+        self.inner().get_utxos(address).await
+    }
+
+    async fn get_balance_at_height(
+        &mut self,
+        address: &AddressType,
+        token_type: &TokenType,
+        height: u32,
+    ) -> IndexerResult<BalanceType> {
+        self.inner()
+            .get_balance_at_height(address, token_type, height)
+            .await
+    }
+
+    async fn get_balance_detail(
+        &mut self,
+        address: &AddressType,
+        token_type: &TokenType,
+    ) -> IndexerResult<BalanceDetailResponse> {
+        self.inner().get_balance_detail(address, token_type).await
+    }
+
+    async fn get_transaction_delta(
+        &mut self,
+        tx_id: &TxIdType,
+    ) -> IndexerResult<Option<TransactionDeltaWrapper>> {
+        self.inner().get_transaction_delta(tx_id).await
+    }
+
+    async fn list_deltas(
+        &mut self,
+        status: DeltaStatus,
+        pagination: &Pagination,
+    ) -> IndexerResult<Vec<TransactionDeltaWrapper>> {
+        self.inner().list_deltas(status, pagination).await
+    }
+
+    async fn gc_inactive_deltas(&mut self) -> IndexerResult<u32> {
+        self.inner().gc_inactive_deltas().await
+    }
+
+    async fn get_audit_log(
+        &mut self,
+        pagination: &Pagination,
+    ) -> IndexerResult<Vec<TransactionDeltaWrapper>> {
+        self.inner().get_audit_log(pagination).await
+    }
+
+    async fn register_token(
+        &mut self,
+        token_type: &TokenType,
+        meta: &TokenMeta,
+    ) -> IndexerResult<()> {
+        self.inner().register_token(token_type, meta).await
+    }
+
+    async fn get_token_meta(&mut self, token_type: &TokenType) -> IndexerResult<Option<TokenMeta>> {
+        self.inner().get_token_meta(token_type).await
+    }
+
+    async fn add_dead_letter(&mut self, event: &[u8], error: &str) -> IndexerResult<()> {
+        self.inner().add_dead_letter(event, error).await
+    }
+
+    async fn get_dead_letters(&mut self) -> IndexerResult<Vec<DeadLetter>> {
+        self.inner().get_dead_letters().await
+    }
+
+    async fn remove_dead_letter(&mut self, index: u64) -> IndexerResult<()> {
+        self.inner().remove_dead_letter(index).await
+    }
+
+    async fn append_journal(&mut self, event: &[u8]) -> IndexerResult<u64> {
+        self.inner().append_journal(event).await
+    }
+
+    async fn get_journal(
+        &mut self,
+        from_seq: u64,
+        limit: usize,
+    ) -> IndexerResult<Vec<(u64, Vec<u8>)>> {
+        self.inner().get_journal(from_seq, limit).await
+    }
+
+    async fn truncate_journal(&mut self, before_seq: u64) -> IndexerResult<()> {
+        self.inner().truncate_journal(before_seq).await
+    }
+
+    async fn get_state_root(&mut self) -> IndexerResult<String> {
+        self.inner().get_state_root().await
+    }
+
+    async fn get_pending_txs(&mut self) -> IndexerResult<Vec<PendingTxResponse>> {
+        self.inner().get_pending_txs().await
+    }
+
+    async fn set_cursor(&mut self, consumer: u32, seq: u64) -> IndexerResult<()> {
+        self.inner().set_cursor(consumer, seq).await
+    }
+
+    async fn get_cursor(&mut self, consumer: u32) -> IndexerResult<Option<u64>> {
+        self.inner().get_cursor(consumer).await
+    }
+
+    async fn get_raw_tx(&mut self, tx_id: &TxIdType) -> IndexerResult<Option<Transaction>> {
+        self.inner().get_raw_tx(tx_id).await
+    }
+
+    async fn hold_event(&mut self, event: &[u8]) -> IndexerResult<u64> {
+        self.inner().hold_event(event).await
+    }
+
+    async fn get_held_events(&mut self) -> IndexerResult<Vec<(u64, Vec<u8>)>> {
+        self.inner().get_held_events().await
+    }
+
+    async fn remove_held_event(&mut self, index: u64) -> IndexerResult<()> {
+        self.inner().remove_held_event(index).await
+    }
+
+    async fn set_chain_state(&mut self, state: &[u8]) -> IndexerResult<()> {
+        self.inner().set_chain_state(state).await
+    }
+
+    async fn get_chain_state(&mut self) -> IndexerResult<Option<Vec<u8>>> {
+        self.inner().get_chain_state().await
+    }
+
+    async fn get_balances(
+        &mut self,
+        keys: &[(AddressType, TokenType)],
+    ) -> IndexerResult<Vec<BalanceType>> {
+        self.inner().get_balances(keys).await
+    }
+
+    async fn begin_tx(&mut self) -> IndexerResult<()> {
+        self.inner().begin_tx().await
+    }
+
+    async fn commit(&mut self) -> IndexerResult<()> {
+        self.inner().commit().await
+    }
+
+    async fn rollback(&mut self) -> IndexerResult<()> {
+        self.inner().rollback().await
+    }
+
+    async fn export(&mut self, path: &str) -> IndexerResult<()> {
+        self.inner().export(path).await
+    }
+
+    async fn import(&mut self, path: &str) -> IndexerResult<()> {
+        self.inner().import(path).await
+    }
+}
+
+#[async_trait::async_trait]
+impl<T: StorageLayer> StorageProcessor for T {
+    async fn get_balance(
+        &mut self,
+        address: &AddressType,
+        token_type: &TokenType,
+    ) -> IndexerResult<BalanceType> {
+        StorageLayer::get_balance(self, address, token_type).await
+    }
+
+    async fn add_transaction_delta(&mut self, transaction: &TransactionDelta) -> IndexerResult<()> {
+        StorageLayer::add_transaction_delta(self, transaction).await
+    }
+
+    async fn add_transaction_deltas(
+        &mut self,
+        transactions: &[TransactionDelta],
+    ) -> IndexerResult<()> {
+        StorageLayer::add_transaction_deltas(self, transactions).await
+    }
+
+    async fn remove_transaction_delta(
+        &mut self,
+        tx_id: &TxIdType,
+        status: DeltaStatus,
+    ) -> IndexerResult<()> {
+        StorageLayer::remove_transaction_delta(self, tx_id, status).await
+    }
+
+    async fn seen_and_store_txs(
+        &mut self,
+        tx: &Transaction,
+        from_restore: bool,
+    ) -> IndexerResult<SeenStatusResponse> {
+        StorageLayer::seen_and_store_txs(self, tx, from_restore).await
+    }
+
+    async fn seen_tx(&mut self, tx_id: TxIdType) -> IndexerResult<SeenStatusResponse> {
+        StorageLayer::seen_tx(self, tx_id).await
+    }
+
+    async fn get_all_un_consumed_txs(&mut self) -> IndexerResult<HashMap<TxIdType, i64>> {
+        StorageLayer::get_all_un_consumed_txs(self).await
+    }
+
+    async fn is_tx_executed(&mut self, tx_id: &TxIdType) -> IndexerResult<bool> {
+        StorageLayer::is_tx_executed(self, tx_id).await
+    }
+
+    async fn get_all_balance(
+        &mut self,
+        address: &AddressType,
+    ) -> IndexerResult<Vec<AllBalanceResponse>> {
+        StorageLayer::get_all_balance(self, address).await
+    }
+
+    async fn simple_set(
+        &mut self,
+        tx_id: &TxIdType,
+        key: &[u8],
+        value: Vec<u8>,
+    ) -> IndexerResult<()> {
+        StorageLayer::simple_set(self, tx_id, key, value).await
+    }
+
+    async fn simple_get(&mut self, key: &[u8]) -> IndexerResult<Option<Vec<u8>>> {
+        StorageLayer::simple_get(self, key).await
+    }
+
+    async fn save_height_tx(&mut self, height: u32, tx_id: TxIdType) -> IndexerResult<()> {
+        StorageLayer::save_height_tx(self, height, tx_id).await
+    }
+
+    async fn remove_height_traces(&mut self, height: u32) -> IndexerResult<()> {
+        StorageLayer::remove_height_traces(self, height).await
+    }
+
+    async fn remove_tx_traces(&mut self, tx_id: Vec<TxIdType>) -> IndexerResult<()> {
+        StorageLayer::remove_tx_traces(self, tx_id).await
+    }
+
+    async fn take_snapshot(&mut self, height: u32) -> IndexerResult<Snapshot> {
+        StorageLayer::take_snapshot(self, height).await
+    }
+
+    async fn restore_snapshot(&mut self, snapshot: &Snapshot) -> IndexerResult<()> {
+        StorageLayer::restore_snapshot(self, snapshot).await
+    }
+
+    async fn update_indexed_height(&mut self, height: u32) -> IndexerResult<()> {
+        StorageLayer::update_indexed_height(self, height).await
+    }
+
+    async fn stats(&mut self) -> IndexerResult<StorageStats> {
+        StorageLayer::stats(self).await
+    }
+
+    async fn prune_deltas(&mut self, retention: &PruneRetention) -> IndexerResult<u32> {
+        StorageLayer::prune_deltas(self, retention).await
+    }
+
+    async fn get_address_history(
+        &mut self,
+        address: &AddressType,
+        pagination: &Pagination,
+    ) -> IndexerResult<Vec<TxIdType>> {
+        StorageLayer::get_address_history(self, address, pagination).await
+    }
+
+    async fn apply_utxos(
+        &mut self,
+        tx_id: &TxIdType,
+        created: Vec<Utxo>,
+        spent: Vec<Utxo>,
+    ) -> IndexerResult<()> {
+        StorageLayer::apply_utxos(self, tx_id, created, spent).await
+    }
+
+    async fn settle_utxos(&mut self, tx_id: &TxIdType, revert: bool) -> IndexerResult<()> {
+        StorageLayer::settle_utxos(self, tx_id, revert).await
+    }
+
+    async fn get_utxo(&mut self, tx_id: &TxIdType, vout: u32) -> IndexerResult<Option<Utxo>> {
+        StorageLayer::get_utxo(self, tx_id, vout).await
+    }
+
+    async fn get_utxos(&mut self, address: &AddressType) -> IndexerResult<Vec<Utxo>> {
+        StorageLayer::get_utxos(self, address).await
+    }
+
+    async fn get_balance_at_height(
+        &mut self,
+        address: &AddressType,
+        token_type: &TokenType,
+        height: u32,
+    ) -> IndexerResult<BalanceType> {
+        StorageLayer::get_balance_at_height(self, address, token_type, height).await
+    }
+
+    async fn get_balance_detail(
+        &mut self,
+        address: &AddressType,
+        token_type: &TokenType,
+    ) -> IndexerResult<BalanceDetailResponse> {
+        StorageLayer::get_balance_detail(self, address, token_type).await
+    }
+
+    async fn get_transaction_delta(
+        &mut self,
+        tx_id: &TxIdType,
+    ) -> IndexerResult<Option<TransactionDeltaWrapper>> {
+        StorageLayer::get_transaction_delta(self, tx_id).await
+    }
+
+    async fn list_deltas(
+        &mut self,
+        status: DeltaStatus,
+        pagination: &Pagination,
+    ) -> IndexerResult<Vec<TransactionDeltaWrapper>> {
+        StorageLayer::list_deltas(self, status, pagination).await
+    }
+
+    async fn gc_inactive_deltas(&mut self) -> IndexerResult<u32> {
+        StorageLayer::gc_inactive_deltas(self).await
+    }
+
+    async fn get_audit_log(
+        &mut self,
+        pagination: &Pagination,
+    ) -> IndexerResult<Vec<TransactionDeltaWrapper>> {
+        StorageLayer::get_audit_log(self, pagination).await
+    }
+
+    async fn register_token(
+        &mut self,
+        token_type: &TokenType,
+        meta: &TokenMeta,
+    ) -> IndexerResult<()> {
+        StorageLayer::register_token(self, token_type, meta).await
+    }
+
+    async fn get_token_meta(&mut self, token_type: &TokenType) -> IndexerResult<Option<TokenMeta>> {
+        StorageLayer::get_token_meta(self, token_type).await
+    }
+
+    async fn add_dead_letter(&mut self, event: &[u8], error: &str) -> IndexerResult<()> {
+        StorageLayer::add_dead_letter(self, event, error).await
+    }
+
+    async fn get_dead_letters(&mut self) -> IndexerResult<Vec<DeadLetter>> {
+        StorageLayer::get_dead_letters(self).await
+    }
+
+    async fn remove_dead_letter(&mut self, index: u64) -> IndexerResult<()> {
+        StorageLayer::remove_dead_letter(self, index).await
+    }
+
+    async fn append_journal(&mut self, event: &[u8]) -> IndexerResult<u64> {
+        StorageLayer::append_journal(self, event).await
+    }
+
+    async fn get_journal(
+        &mut self,
+        from_seq: u64,
+        limit: usize,
+    ) -> IndexerResult<Vec<(u64, Vec<u8>)>> {
+        StorageLayer::get_journal(self, from_seq, limit).await
+    }
+
+    async fn truncate_journal(&mut self, before_seq: u64) -> IndexerResult<()> {
+        StorageLayer::truncate_journal(self, before_seq).await
+    }
+
+    async fn get_state_root(&mut self) -> IndexerResult<String> {
+        StorageLayer::get_state_root(self).await
+    }
+
+    async fn get_pending_txs(&mut self) -> IndexerResult<Vec<PendingTxResponse>> {
+        StorageLayer::get_pending_txs(self).await
+    }
+
+    async fn set_cursor(&mut self, consumer: u32, seq: u64) -> IndexerResult<()> {
+        StorageLayer::set_cursor(self, consumer, seq).await
+    }
+
+    async fn get_cursor(&mut self, consumer: u32) -> IndexerResult<Option<u64>> {
+        StorageLayer::get_cursor(self, consumer).await
+    }
+
+    async fn get_raw_tx(&mut self, tx_id: &TxIdType) -> IndexerResult<Option<Transaction>> {
+        StorageLayer::get_raw_tx(self, tx_id).await
+    }
+
+    async fn hold_event(&mut self, event: &[u8]) -> IndexerResult<u64> {
+        StorageLayer::hold_event(self, event).await
+    }
+
+    async fn get_held_events(&mut self) -> IndexerResult<Vec<(u64, Vec<u8>)>> {
+        StorageLayer::get_held_events(self).await
+    }
+
+    async fn remove_held_event(&mut self, index: u64) -> IndexerResult<()> {
+        StorageLayer::remove_held_event(self, index).await
+    }
+
+    async fn set_chain_state(&mut self, state: &[u8]) -> IndexerResult<()> {
+        StorageLayer::set_chain_state(self, state).await
+    }
+
+    async fn get_chain_state(&mut self) -> IndexerResult<Option<Vec<u8>>> {
+        StorageLayer::get_chain_state(self).await
+    }
+
+    async fn get_balances(
+        &mut self,
+        keys: &[(AddressType, TokenType)],
+    ) -> IndexerResult<Vec<BalanceType>> {
+        StorageLayer::get_balances(self, keys).await
+    }
+
+    async fn begin_tx(&mut self) -> IndexerResult<()> {
+        StorageLayer::begin_tx(self).await
+    }
+
+    async fn commit(&mut self) -> IndexerResult<()> {
+        StorageLayer::commit(self).await
+    }
+
+    async fn rollback(&mut self) -> IndexerResult<()> {
+        StorageLayer::rollback(self).await
+    }
+
+    async fn export(&mut self, path: &str) -> IndexerResult<()> {
+        StorageLayer::export(self, path).await
+    }
+
+    async fn import(&mut self, path: &str) -> IndexerResult<()> {
+        StorageLayer::import(self, path).await
+    }
+}
+
+// shared handle of the layered storage
+#[derive(Clone)]
+pub struct LayeredStorageProcessor {
+    internal: Arc<Mutex<Box<dyn StorageProcessor>>>,
+}
+
+#[async_trait::async_trait]
+impl StorageProcessor for LayeredStorageProcessor {
+    async fn get_balance(
+        &mut self,
+        address: &AddressType,
+        token_type: &TokenType,
+    ) -> IndexerResult<BalanceType> {
+        self.internal
+            .lock()
+            .await
+            .get_balance(address, token_type)
+            .await
+    }
+
+    async fn add_transaction_delta(&mut self, transaction: &TransactionDelta) -> IndexerResult<()> {
+        self.internal
+            .lock()
+            .await
+            .add_transaction_delta(transaction)
+            .await
+    }
+
+    async fn add_transaction_deltas(
+        &mut self,
+        transactions: &[TransactionDelta],
+    ) -> IndexerResult<()> {
+        self.internal
+            .lock()
+            .await
+            .add_transaction_deltas(transactions)
+            .await
+    }
+
+    async fn remove_transaction_delta(
+        &mut self,
+        tx_id: &TxIdType,
+        status: DeltaStatus,
+    ) -> IndexerResult<()> {
+        self.internal
+            .lock()
+            .await
+            .remove_transaction_delta(tx_id, status)
+            .await
+    }
+
+    async fn seen_and_store_txs(
+        &mut self,
+        tx: &Transaction,
+        from_restore: bool,
+    ) -> IndexerResult<SeenStatusResponse> {
+        self.internal
+            .lock()
+            .await
+            .seen_and_store_txs(tx, from_restore)
+            .await
+    }
+
+    async fn seen_tx(&mut self, tx_id: TxIdType) -> IndexerResult<SeenStatusResponse> {
+        self.internal.lock().await.seen_tx(tx_id).await
+    }
+
+    async fn get_all_un_consumed_txs(&mut self) -> IndexerResult<HashMap<TxIdType, i64>> {
+        self.internal.lock().await.get_all_un_consumed_txs().await
+    }
+
+    async fn is_tx_executed(&mut self, tx_id: &TxIdType) -> IndexerResult<bool> {
+        self.internal.lock().await.is_tx_executed(tx_id).await
+    }
+
+    async fn get_all_balance(
+        &mut self,
+        address: &AddressType,
+    ) -> IndexerResult<Vec<AllBalanceResponse>> {
+        self.internal.lock().await.get_all_balance(address).await
+    }
+
+    async fn simple_set(
+        &mut self,
+        tx_id: &TxIdType,
+        key: &[u8],
+        value: Vec<u8>,
+    ) -> IndexerResult<()> {
+        self.internal
+            .lock()
+            .await
+            .simple_set(tx_id, key, value)
+            .await
+    }
+
+    async fn simple_get(&mut self, key: &[u8]) -> IndexerResult<Option<Vec<u8>>> {
+        self.internal.lock().await.simple_get(key).await
+    }
+
+    async fn save_height_tx(&mut self, height: u32, tx_id: TxIdType) -> IndexerResult<()> {
+        self.internal
+            .lock()
+            .await
+            .save_height_tx(height, tx_id)
+            .await
+    }
+
+    async fn remove_height_traces(&mut self, height: u32) -> IndexerResult<()> {
+        self.internal
+            .lock()
+            .await
+            .remove_height_traces(height)
+            .await
+    }
+
+    async fn remove_tx_traces(&mut self, tx_id: Vec<TxIdType>) -> IndexerResult<()> {
+        self.internal.lock().await.remove_tx_traces(tx_id).await
+    }
+
+    async fn take_snapshot(&mut self, height: u32) -> IndexerResult<Snapshot> {
+        self.internal.lock().await.take_snapshot(height).await
+    }
+
+    async fn restore_snapshot(&mut self, snapshot: &Snapshot) -> IndexerResult<()> {
+        self.internal.lock().await.restore_snapshot(snapshot).await
+    }
+
+    async fn update_indexed_height(&mut self, height: u32) -> IndexerResult<()> {
+        self.internal
+            .lock()
+            .await
+            .update_indexed_height(height)
+            .await
+    }
+
+    async fn stats(&mut self) -> IndexerResult<StorageStats> {
+        self.internal.lock().await.stats().await
+    }
+
+    async fn prune_deltas(&mut self, retention: &PruneRetention) -> IndexerResult<u32> {
+        self.internal.lock().await.prune_deltas(retention).await
+    }
+
+    async fn get_address_history(
+        &mut self,
+        address: &AddressType,
+        pagination: &Pagination,
+    ) -> IndexerResult<Vec<TxIdType>> {
+        self.internal
+            .lock()
+            .await
+            .get_address_history(address, pagination)
+            .await
+    }
+
+    async fn apply_utxos(
+        &mut self,
+        tx_id: &TxIdType,
+        created: Vec<Utxo>,
+        spent: Vec<Utxo>,
+    ) -> IndexerResult<()> {
+        self.internal
+            .lock()
+            .await
+            .apply_utxos(tx_id, created, spent)
+            .await
+    }
+
+    async fn settle_utxos(&mut self, tx_id: &TxIdType, revert: bool) -> IndexerResult<()> {
+        self.internal.lock().await.settle_utxos(tx_id, revert).await
+    }
+
+    async fn get_utxo(&mut self, tx_id: &TxIdType, vout: u32) -> IndexerResult<Option<Utxo>> {
+        self.internal.lock().await.get_utxo(tx_id, vout).await
+    }
+
+    async fn get_utxos(&mut self, address: &AddressType) -> IndexerResult<Vec<Utxo>> {
+        self.internal.lock().await.get_utxos(address).await
+    }
+
+    async fn get_balance_at_height(
+        &mut self,
+        address: &AddressType,
+        token_type: &TokenType,
+        height: u32,
+    ) -> IndexerResult<BalanceType> {
+        self.internal
+            .lock()
+            .await
+            .get_balance_at_height(address, token_type, height)
+            .await
+    }
+
+    async fn get_balance_detail(
+        &mut self,
+        address: &AddressType,
+        token_type: &TokenType,
+    ) -> IndexerResult<BalanceDetailResponse> {
+        self.internal
+            .lock()
+            .await
+            .get_balance_detail(address, token_type)
+            .await
+    }
+
+    async fn get_transaction_delta(
+        &mut self,
+        tx_id: &TxIdType,
+    ) -> IndexerResult<Option<TransactionDeltaWrapper>> {
+        self.internal
+            .lock()
+            .await
+            .get_transaction_delta(tx_id)
+            .await
+    }
+
+    async fn list_deltas(
+        &mut self,
+        status: DeltaStatus,
+        pagination: &Pagination,
+    ) -> IndexerResult<Vec<TransactionDeltaWrapper>> {
+        self.internal
+            .lock()
+            .await
+            .list_deltas(status, pagination)
+            .await
+    }
+
+    async fn gc_inactive_deltas(&mut self) -> IndexerResult<u32> {
+        self.internal.lock().await.gc_inactive_deltas().await
+    }
+
+    async fn get_audit_log(
+        &mut self,
+        pagination: &Pagination,
+    ) -> IndexerResult<Vec<TransactionDeltaWrapper>> {
+        self.internal.lock().await.get_audit_log(pagination).await
+    }
+
+    async fn register_token(
+        &mut self,
+        token_type: &TokenType,
+        meta: &TokenMeta,
+    ) -> IndexerResult<()> {
+        self.internal
+            .lock()
+            .await
+            .register_token(token_type, meta)
+            .await
+    }
+
+    async fn get_token_meta(&mut self, token_type: &TokenType) -> IndexerResult<Option<TokenMeta>> {
+        self.internal.lock().await.get_token_meta(token_type).await
+    }
+
+    async fn add_dead_letter(&mut self, event: &[u8], error: &str) -> IndexerResult<()> {
+        self.internal
+            .lock()
+            .await
+            .add_dead_letter(event, error)
+            .await
+    }
+
+    async fn get_dead_letters(&mut self) -> IndexerResult<Vec<DeadLetter>> {
+        self.internal.lock().await.get_dead_letters().await
+    }
+
+    async fn remove_dead_letter(&mut self, index: u64) -> IndexerResult<()> {
+        self.internal.lock().await.remove_dead_letter(index).await
+    }
+
+    async fn append_journal(&mut self, event: &[u8]) -> IndexerResult<u64> {
+        self.internal.lock().await.append_journal(event).await
+    }
+
+    async fn get_journal(
+        &mut self,
+        from_seq: u64,
+        limit: usize,
+    ) -> IndexerResult<Vec<(u64, Vec<u8>)>> {
+        self.internal
+            .lock()
+            .await
+            .get_journal(from_seq, limit)
+            .await
+    }
+
+    async fn truncate_journal(&mut self, before_seq: u64) -> IndexerResult<()> {
+        self.internal
+            .lock()
+            .await
+            .truncate_journal(before_seq)
+            .await
+    }
+
+    async fn get_state_root(&mut self) -> IndexerResult<String> {
+        self.internal.lock().await.get_state_root().await
+    }
+
+    async fn get_pending_txs(&mut self) -> IndexerResult<Vec<PendingTxResponse>> {
+        self.internal.lock().await.get_pending_txs().await
+    }
+
+    async fn set_cursor(&mut self, consumer: u32, seq: u64) -> IndexerResult<()> {
+        self.internal.lock().await.set_cursor(consumer, seq).await
+    }
+
+    async fn get_cursor(&mut self, consumer: u32) -> IndexerResult<Option<u64>> {
+        self.internal.lock().await.get_cursor(consumer).await
+    }
+
+    async fn get_raw_tx(&mut self, tx_id: &TxIdType) -> IndexerResult<Option<Transaction>> {
+        self.internal.lock().await.get_raw_tx(tx_id).await
+    }
+
+    async fn hold_event(&mut self, event: &[u8]) -> IndexerResult<u64> {
+        self.internal.lock().await.hold_event(event).await
+    }
+
+    async fn get_held_events(&mut self) -> IndexerResult<Vec<(u64, Vec<u8>)>> {
+        self.internal.lock().await.get_held_events().await
+    }
+
+    async fn remove_held_event(&mut self, index: u64) -> IndexerResult<()> {
+        self.internal.lock().await.remove_held_event(index).await
+    }
+
+    async fn set_chain_state(&mut self, state: &[u8]) -> IndexerResult<()> {
+        self.internal.lock().await.set_chain_state(state).await
+    }
+
+    async fn get_chain_state(&mut self) -> IndexerResult<Option<Vec<u8>>> {
+        self.internal.lock().await.get_chain_state().await
+    }
+
+    async fn get_balances(
+        &mut self,
+        keys: &[(AddressType, TokenType)],
+    ) -> IndexerResult<Vec<BalanceType>> {
+        self.internal.lock().await.get_balances(keys).await
+    }
+
+    async fn begin_tx(&mut self) -> IndexerResult<()> {
+        self.internal.lock().await.begin_tx().await
+    }
+
+    async fn commit(&mut self) -> IndexerResult<()> {
+        self.internal.lock().await.commit().await
+    }
+
+    async fn rollback(&mut self) -> IndexerResult<()> {
+        self.internal.lock().await.rollback().await
+    }
+
+    async fn export(&mut self, path: &str) -> IndexerResult<()> {
+        self.internal.lock().await.export(path).await
+    }
+
+    async fn import(&mut self, path: &str) -> IndexerResult<()> {
+        self.internal.lock().await.import(path).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::db::memory::MemoryDB;
+    use crate::storage::kv::KVStorageProcessor;
+    use crate::storage::thread_safe::ThreadSafeStorageProcessor;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    struct ThreadSafeLayer {
+        applied: Arc<AtomicU32>,
+    }
+
+    impl StorageMiddleware for ThreadSafeLayer {
+        fn name(&self) -> &str {
+            "thread_safe"
+        }
+
+        fn layer(&self, inner: Box<dyn StorageProcessor>) -> Box<dyn StorageProcessor> {
+            self.applied.fetch_add(1, Ordering::SeqCst);
+            Box::new(ThreadSafeStorageProcessor::new(inner))
+        }
+    }
+
+    // records the balance lookups it sees,answers them itself when fixed is set
+    struct RecordingLayer {
+        name: &'static str,
+        calls: Arc<std::sync::Mutex<Vec<&'static str>>>,
+        fixed: Option<i32>,
+    }
+
+    struct Recording {
+        name: &'static str,
+        calls: Arc<std::sync::Mutex<Vec<&'static str>>>,
+        fixed: Option<i32>,
+        inner: Box<dyn StorageProcessor>,
+    }
+
+    impl StorageMiddleware for RecordingLayer {
+        fn name(&self) -> &str {
+            self.name
+        }
+
+        fn layer(&self, inner: Box<dyn StorageProcessor>) -> Box<dyn StorageProcessor> {
+            Box::new(Recording {
+                name: self.name,
+                calls: self.calls.clone(),
+                fixed: self.fixed,
+                inner,
+            })
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl StorageLayer for Recording {
+        fn inner(&mut self) -> &mut dyn StorageProcessor {
+            self.inner.as_mut()
+        }
+
+        async fn get_balance(
+            &mut self,
+            address: &AddressType,
+            token_type: &TokenType,
+        ) -> IndexerResult<BalanceType> {
+            self.calls.lock().unwrap().push(self.name);
+            match self.fixed {
+                Some(v) => Ok(BalanceType::from(v)),
+                None => self.inner.get_balance(address, token_type).await,
+            }
+        }
+    }
+
+    #[tokio::test]
+    pub async fn test_middleware_order() {
+        let calls = Arc::new(std::sync::Mutex::new(vec![]));
+        let layer = |name, fixed| {
+            Arc::new(RecordingLayer {
+                name,
+                calls: calls.clone(),
+                fixed,
+            })
+        };
+        let address = AddressType::from_bytes(&[0u8; 20]);
+        let token = TokenType::from_bytes(&[0u8; 20]);
+        let mut deltas = HashMap::default();
+        deltas.insert(address.clone(), vec![(token.clone(), BalanceType::from(7))]);
+        let delta = TransactionDelta {
+            tx_id: TxIdType::from_bytes(&[0u8; 32]),
+            deltas,
+        };
+
+        // the first pushed sees the call first,the calls it does not override pass through
+        let mut storage = MiddlewareStack::default()
+            .push(layer("outer", None))
+            .push(layer("inner", None))
+            .apply(KVStorageProcessor::new(MemoryDB::default()));
+        storage.add_transaction_delta(&delta).await.unwrap();
+        assert_eq!(
+            storage.get_balance(&address, &token).await.unwrap(),
+            BalanceType::from(7)
+        );
+        assert_eq!(*calls.lock().unwrap(), vec!["outer", "inner"]);
+
+        // an intercepted call never reaches the inner layers
+        calls.lock().unwrap().clear();
+        let mut storage = MiddlewareStack::default()
+            .push(layer("outer", Some(42)))
+            .push(layer("inner", None))
+            .apply(KVStorageProcessor::new(MemoryDB::default()));
+        assert_eq!(
+            storage.get_balance(&address, &token).await.unwrap(),
+            BalanceType::from(42)
+        );
+        assert_eq!(*calls.lock().unwrap(), vec!["outer"]);
+    }
+
+    #[tokio::test]
+    pub async fn test_middleware_stack() {
+        let applied = Arc::new(AtomicU32::new(0));
+        let stack = MiddlewareStack::default()
+            .push(Arc::new(ThreadSafeLayer {
+                applied: applied.clone(),
+            }))
+            .push(Arc::new(ThreadSafeLayer {
+                applied: applied.clone(),
+            }));
+        assert_eq!(format!("{:?}", stack), r#"["thread_safe", "thread_safe"]"#);
+
+        let mut storage = stack.apply(KVStorageProcessor::new(MemoryDB::default()));
+        assert_eq!(applied.load(Ordering::SeqCst), 2);
+
+        let address = AddressType::from_bytes(&[0u8; 20]);
+        let token = TokenType::from_bytes(&[0u8; 20]);
+        let mut deltas = HashMap::default();
+        deltas.insert(address.clone(), vec![(token.clone(), BalanceType::from(7))]);
+        let delta = TransactionDelta {
+            tx_id: TxIdType::from_bytes(&[0u8; 32]),
+            deltas,
+        };
+        storage.add_transaction_delta(&delta).await.unwrap();
+        let mut shared = storage.clone();
+        assert_eq!(
+            shared.get_balance(&address, &token).await.unwrap(),
+            BalanceType::from(7)
+        );
+    }
+}
@@ -0,0 +1,272 @@
+use crate::error::{IndexerError, IndexerResult};
+use crate::storage::codec::{Codec, DefaultCodec};
+use crate::storage::db::batch::Batch;
+use crate::storage::db::DB;
+use crate::storage::prefix::{DeltaStatus, KeyPrefix};
+use chrono::Local;
+use log::info;
+
+// bump this whenever the key layout in storage::prefix or the stored records change,
+// and register a migration for it
+pub const CURRENT_SCHEMA_VERSION: u32 = 4;
+
+pub trait Migration<T: DB> {
+    // upgrades the db from `source_version` to `source_version + 1`
+    fn source_version(&self) -> u32;
+
+    // the changes,written in one batch with the new version
+    fn migrate(&self, db: &mut T) -> IndexerResult<Batch>;
+}
+
+pub struct Migrator<T: DB> {
+    migrations: Vec<Box<dyn Migration<T>>>,
+}
+
+impl<T: DB> Default for Migrator<T> {
+    fn default() -> Self {
+        Self::with_codec(DefaultCodec)
+    }
+}
+
+impl<T: DB> Migrator<T> {
+    pub fn new() -> Self {
+        Self { migrations: vec![] }
+    }
+
+    // the migrations of the sdk,records end up encoded with codec
+    pub fn with_codec<C: Codec + 'static>(codec: C) -> Self {
+        let mut ret = Self::new();
+        ret.register(Box::new(LegacyMigration));
+        ret.register(Box::new(HeightStampMigration));
+        ret.register(Box::new(CodecMigration {
+            codec: codec.clone(),
+        }));
+        ret.register(Box::new(SeenRecordMigration { codec }));
+        ret
+    }
+
+    pub fn register(&mut self, migration: Box<dyn Migration<T>>) {
+        self.migrations.push(migration);
+    }
+
+    pub fn get_version(db: &mut T) -> IndexerResult<Option<u32>> {
+        let key = KeyPrefix::build_schema_version_key();
+        let ret = db
+            .get(key.as_slice())?
+            .map(|v| u32::from_le_bytes(v.as_slice().try_into().unwrap()));
+        Ok(ret)
+    }
+
+    fn set_version(db: &mut T, version: u32) -> IndexerResult<()> {
+        let key = KeyPrefix::build_schema_version_key();
+        db.set(None, key.as_slice(), version.to_le_bytes().as_slice())
+    }
+
+    // upgrades the db to CURRENT_SCHEMA_VERSION,returns the version the db was at before
+    pub fn run(&self, db: &mut T) -> IndexerResult<u32> {
+        let stored = Self::get_version(db)?;
+        let mut version = match stored {
+            Some(v) => v,
+            None => {
+                let empty = db.iter_all_mut(&[], |_| (), |_| Some(()))?.is_empty();
+                if empty {
+                    info!("empty db,init schema version:{}", CURRENT_SCHEMA_VERSION);
+                    Self::set_version(db, CURRENT_SCHEMA_VERSION)?;
+                    return Ok(CURRENT_SCHEMA_VERSION);
+                }
+                // data written before schema versioning existed
+                0
+            }
+        };
+        let origin = version;
+        if version > CURRENT_SCHEMA_VERSION {
+            return Err(IndexerError::SchemaVersionTooNew(
+                version,
+                CURRENT_SCHEMA_VERSION,
+            ));
+        }
+        while version < CURRENT_SCHEMA_VERSION {
+            let migration = self
+                .migrations
+                .iter()
+                .find(|v| v.source_version() == version)
+                .ok_or(IndexerError::MissingMigration(version))?;
+            info!("migrate schema from {} to {}", version, version + 1);
+            let mut batch = migration.migrate(db)?;
+            version += 1;
+            let key = KeyPrefix::build_schema_version_key();
+            batch.put(key.as_slice(), version.to_le_bytes().as_slice());
+            db.write_batch(None, batch, true)?;
+        }
+        Ok(origin)
+    }
+}
+
+// v0 -> v1: the layout is unchanged,only the version key is introduced
+struct LegacyMigration;
+
+impl<T: DB> Migration<T> for LegacyMigration {
+    fn source_version(&self) -> u32 {
+        0
+    }
+
+    fn migrate(&self, _: &mut T) -> IndexerResult<Batch> {
+        Ok(Batch::new())
+    }
+}
+
+// v1 -> v2: deltas carry the heights they were executed and settled at. the old deltas are
+// stamped with the current indexed height,so pruning does not drop them at once
+struct HeightStampMigration;
+
+impl<T: DB> Migration<T> for HeightStampMigration {
+    fn source_version(&self) -> u32 {
+        1
+    }
+
+    fn migrate(&self, db: &mut T) -> IndexerResult<Batch> {
+        let height = db
+            .get(KeyPrefix::build_indexed_height_key().as_slice())?
+            .map_or(0, |v| u32::from_le_bytes(v.as_slice().try_into().unwrap()));
+        let now = Local::now().timestamp();
+        let codec = DefaultCodec;
+        let mut batch = Batch::new();
+        for (k, v) in db.iter_all_mut(KeyPrefix::TransactionDelta.get_prefix(), |k| k, Some)? {
+            let mut wrapper = codec.decode_delta(v.as_slice())?;
+            let executed = DeltaStatus::Executed.to_u8();
+            if wrapper.status != executed && wrapper.settled_height == 0 {
+                wrapper.settled_height = height;
+                wrapper.settled_at = now;
+            }
+            batch.put(k.as_slice(), codec.encode_delta(&wrapper)?.as_slice());
+        }
+        Ok(batch)
+    }
+}
+
+// v2 -> v3: the records were json and legacy seen records,they are encoded with the codec now
+struct CodecMigration<C: Codec> {
+    codec: C,
+}
+
+impl<T: DB, C: Codec> Migration<T> for CodecMigration<C> {
+    fn source_version(&self) -> u32 {
+        2
+    }
+
+    fn migrate(&self, db: &mut T) -> IndexerResult<Batch> {
+        let legacy = DefaultCodec;
+        let mut batch = Batch::new();
+        for (k, v) in db.iter_all_mut(KeyPrefix::TransactionDelta.get_prefix(), |k| k, Some)? {
+            let wrapper = legacy.decode_delta(v.as_slice())?;
+            batch.put(k.as_slice(), self.codec.encode_delta(&wrapper)?.as_slice());
+        }
+        for (k, v) in db.iter_all_mut(KeyPrefix::SeenTx.get_prefix(), |k| k, Some)? {
+            let record = legacy.decode_seen(v.as_slice())?;
+            batch.put(k.as_slice(), self.codec.encode_seen(&record)?.as_slice());
+        }
+        Ok(batch)
+    }
+}
+
+// v3 -> v4: seen records grew the first seen,re-announce and restore metadata
+struct SeenRecordMigration<C: Codec> {
+    codec: C,
+}
+
+impl<T: DB, C: Codec> Migration<T> for SeenRecordMigration<C> {
+    fn source_version(&self) -> u32 {
+        3
+    }
+
+    fn migrate(&self, db: &mut T) -> IndexerResult<Batch> {
+        let mut batch = Batch::new();
+        for (k, v) in db.iter_all_mut(KeyPrefix::SeenTx.get_prefix(), |k| k, Some)? {
+            let record = self.codec.decode_seen(v.as_slice())?;
+            batch.put(k.as_slice(), self.codec.encode_seen(&record)?.as_slice());
+        }
+        Ok(batch)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::TxIdType;
+    use crate::storage::codec::BincodeCodec;
+    use crate::storage::db::memory::MemoryDB;
+    use crate::storage::kv::KVStorageProcessor;
+    use crate::storage::StorageProcessor;
+    use serde_json::json;
+
+    #[test]
+    pub fn test_migrate() {
+        let mut db = MemoryDB::default();
+        let migrator = Migrator::default();
+        assert_eq!(migrator.run(&mut db).unwrap(), CURRENT_SCHEMA_VERSION);
+
+        let mut legacy = MemoryDB::default();
+        legacy.set(None, b"a", &1u32.to_le_bytes()).unwrap();
+        assert_eq!(migrator.run(&mut legacy).unwrap(), 0);
+        assert_eq!(
+            Migrator::get_version(&mut legacy).unwrap(),
+            Some(CURRENT_SCHEMA_VERSION)
+        );
+
+        Migrator::set_version(&mut legacy, CURRENT_SCHEMA_VERSION + 1).unwrap();
+        assert!(migrator.run(&mut legacy).is_err());
+    }
+
+    #[tokio::test]
+    pub async fn test_open_v1_db() {
+        // a json delta without heights and a seen record without metadata,as written by v1
+        let mut db = MemoryDB::default();
+        let tx_id = TxIdType::from_bytes(&[1u8; 32]);
+        let delta = json!({
+            "data": {"tx_id": tx_id, "deltas": {}},
+            "status": DeltaStatus::Confirmed.to_u8(),
+        });
+        let mut batch = Batch::new();
+        batch.put(
+            KeyPrefix::build_schema_version_key().as_slice(),
+            &1u32.to_le_bytes(),
+        );
+        batch.put(
+            KeyPrefix::build_indexed_height_key().as_slice(),
+            &7u32.to_le_bytes(),
+        );
+        batch.put(
+            KeyPrefix::build_transaction_data_key(1).as_slice(),
+            serde_json::to_vec(&delta).unwrap().as_slice(),
+        );
+        batch.put(
+            KeyPrefix::build_transaction_index_map_key(&tx_id).as_slice(),
+            &1u32.to_le_bytes(),
+        );
+        batch.put(
+            KeyPrefix::build_seen_tx_key(&tx_id).as_slice(),
+            &[&5i64.to_le_bytes()[..], &[1u8]].concat(),
+        );
+        db.write_batch(None, batch, true).unwrap();
+
+        let mut storage = KVStorageProcessor::with_codec(db.clone(), BincodeCodec);
+        assert_eq!(storage.migrate().unwrap(), 1);
+        assert_eq!(
+            Migrator::get_version(&mut db).unwrap(),
+            Some(CURRENT_SCHEMA_VERSION)
+        );
+        let delta = storage
+            .get_transaction_delta(&tx_id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(delta.settled_height, 7);
+        assert!(delta.settled_at > 0);
+        let seen = storage.seen_tx(tx_id.clone()).await.unwrap();
+        assert!(seen.is_executed());
+        assert_eq!(seen.first_seen(), 5);
+        assert_eq!(seen.re_announced(), 0);
+        // nothing left to migrate
+        assert_eq!(storage.migrate().unwrap(), CURRENT_SCHEMA_VERSION);
+    }
+}
@@ -1,14 +1,31 @@
+pub mod block_cache;
+pub mod codec;
 pub mod db;
+pub mod export;
 pub mod kv;
 pub mod memory;
+pub mod middleware;
+pub mod migration;
 pub mod prefix;
+pub mod prune;
+pub mod read_only;
+pub mod snapshot;
 pub mod thread_safe;
+pub mod tiered;
 
 use crate::error::IndexerResult;
 use crate::event::{AddressType, BalanceType, TokenType, TxIdType};
+use crate::storage::codec::SeenRecord;
+use crate::storage::kv::TransactionDeltaWrapper;
 use crate::storage::prefix::{DeltaStatus, SeenStatus};
+use crate::storage::prune::PruneRetention;
+use crate::storage::snapshot::Snapshot;
+use crate::types::dead_letter::DeadLetter;
 use crate::types::delta::TransactionDelta;
-use crate::types::response::AllBalanceResponse;
+use crate::types::request::Pagination;
+use crate::types::response::{AllBalanceResponse, BalanceDetailResponse, PendingTxResponse};
+use crate::types::token::TokenMeta;
+use crate::types::transaction::Utxo;
 use bitcoincore_rpc::bitcoin::Transaction;
 use std::collections::HashMap;
 
@@ -26,13 +43,23 @@ pub trait StorageProcessor: Send + Sync {
     ) -> IndexerResult<Vec<AllBalanceResponse>>;
 
     async fn add_transaction_delta(&mut self, transaction: &TransactionDelta) -> IndexerResult<()>;
+    // all or nothing,persisted with a single write
+    async fn add_transaction_deltas(
+        &mut self,
+        transactions: &[TransactionDelta],
+    ) -> IndexerResult<()>;
     async fn remove_transaction_delta(
         &mut self,
         tx_id: &TxIdType,
         status: DeltaStatus,
     ) -> IndexerResult<()>;
 
-    async fn seen_and_store_txs(&mut self, tx: &Transaction) -> IndexerResult<SeenStatusResponse>;
+    // a seen tx is not stored again,only its re-announce count grows
+    async fn seen_and_store_txs(
+        &mut self,
+        tx: &Transaction,
+        from_restore: bool,
+    ) -> IndexerResult<SeenStatusResponse>;
 
     async fn seen_tx(&mut self, tx_id: TxIdType) -> IndexerResult<SeenStatusResponse>;
 
@@ -54,20 +81,193 @@ pub trait StorageProcessor: Send + Sync {
     async fn remove_height_traces(&mut self, height: u32) -> IndexerResult<()>;
 
     async fn remove_tx_traces(&mut self, tx_id: Vec<TxIdType>) -> IndexerResult<()>;
+
+    async fn take_snapshot(&mut self, height: u32) -> IndexerResult<Snapshot>;
+
+    async fn restore_snapshot(&mut self, snapshot: &Snapshot) -> IndexerResult<()>;
+
+    async fn update_indexed_height(&mut self, height: u32) -> IndexerResult<()>;
+
+    async fn stats(&mut self) -> IndexerResult<StorageStats>;
+
+    // drops settled deltas older than the retention,returns how many were removed
+    async fn prune_deltas(&mut self, retention: &PruneRetention) -> IndexerResult<u32>;
+
+    // txs touching the address,oldest first
+    async fn get_address_history(
+        &mut self,
+        address: &AddressType,
+        pagination: &Pagination,
+    ) -> IndexerResult<Vec<TxIdType>>;
+
+    async fn apply_utxos(
+        &mut self,
+        tx_id: &TxIdType,
+        created: Vec<Utxo>,
+        spent: Vec<Utxo>,
+    ) -> IndexerResult<()>;
+
+    // revert:true undoes the changes of a dropped tx
+    async fn settle_utxos(&mut self, tx_id: &TxIdType, revert: bool) -> IndexerResult<()>;
+
+    async fn get_utxo(&mut self, tx_id: &TxIdType, vout: u32) -> IndexerResult<Option<Utxo>>;
+
+    async fn get_utxos(&mut self, address: &AddressType) -> IndexerResult<Vec<Utxo>>;
+
+    // sum of the deltas active at height,pruned deltas are not counted
+    async fn get_balance_at_height(
+        &mut self,
+        address: &AddressType,
+        token_type: &TokenType,
+        height: u32,
+    ) -> IndexerResult<BalanceType>;
+
+    async fn get_balance_detail(
+        &mut self,
+        address: &AddressType,
+        token_type: &TokenType,
+    ) -> IndexerResult<BalanceDetailResponse>;
+
+    async fn get_transaction_delta(
+        &mut self,
+        tx_id: &TxIdType,
+    ) -> IndexerResult<Option<TransactionDeltaWrapper>>;
+
+    // ordered by execution
+    async fn list_deltas(
+        &mut self,
+        status: DeltaStatus,
+        pagination: &Pagination,
+    ) -> IndexerResult<Vec<TransactionDeltaWrapper>>;
+
+    // moves inactive deltas into the audit log,returns how many were moved
+    async fn gc_inactive_deltas(&mut self) -> IndexerResult<u32>;
+
+    async fn get_audit_log(
+        &mut self,
+        pagination: &Pagination,
+    ) -> IndexerResult<Vec<TransactionDeltaWrapper>>;
+
+    // overwrites the meta of an already registered token
+    async fn register_token(
+        &mut self,
+        token_type: &TokenType,
+        meta: &TokenMeta,
+    ) -> IndexerResult<()>;
+
+    async fn get_token_meta(&mut self, token_type: &TokenType) -> IndexerResult<Option<TokenMeta>>;
+
+    // persists an event that failed to be handled
+    async fn add_dead_letter(&mut self, event: &[u8], error: &str) -> IndexerResult<()>;
+
+    async fn get_dead_letters(&mut self) -> IndexerResult<Vec<DeadLetter>>;
+
+    async fn remove_dead_letter(&mut self, index: u64) -> IndexerResult<()>;
+
+    // appends a dispatched client event,returns its sequence number
+    async fn append_journal(&mut self, event: &[u8]) -> IndexerResult<u64>;
+
+    async fn get_journal(
+        &mut self,
+        from_seq: u64,
+        limit: usize,
+    ) -> IndexerResult<Vec<(u64, Vec<u8>)>>;
+
+    // drops the journal records before the seq
+    async fn truncate_journal(&mut self, before_seq: u64) -> IndexerResult<()>;
+
+    // sha256 over the stored deltas and their statuses,in index order
+    async fn get_state_root(&mut self) -> IndexerResult<String>;
+
+    // seen txs which are not settled yet,oldest first
+    async fn get_pending_txs(&mut self) -> IndexerResult<Vec<PendingTxResponse>>;
+
+    // the journal seq the consumer processed up to
+    async fn set_cursor(&mut self, consumer: u32, seq: u64) -> IndexerResult<()>;
+
+    async fn get_cursor(&mut self, consumer: u32) -> IndexerResult<Option<u64>>;
+
+    // the txs seen by the sdk,removed with the traces of the tx
+    async fn get_raw_tx(&mut self, tx_id: &TxIdType) -> IndexerResult<Option<Transaction>>;
+
+    // persists an event that arrived while the processor was paused,returns its index
+    async fn hold_event(&mut self, event: &[u8]) -> IndexerResult<u64>;
+
+    async fn get_held_events(&mut self) -> IndexerResult<Vec<(u64, Vec<u8>)>>;
+
+    async fn remove_held_event(&mut self, index: u64) -> IndexerResult<()>;
+
+    // the processor state which outlives a restart,opaque to the storage
+    async fn set_chain_state(&mut self, state: &[u8]) -> IndexerResult<()>;
+
+    async fn get_chain_state(&mut self) -> IndexerResult<Option<Vec<u8>>>;
+
+    // the balances in the order of keys,read at once
+    async fn get_balances(
+        &mut self,
+        keys: &[(AddressType, TokenType)],
+    ) -> IndexerResult<Vec<BalanceType>>;
+
+    async fn begin_tx(&mut self) -> IndexerResult<()>;
+
+    async fn commit(&mut self) -> IndexerResult<()>;
+
+    async fn rollback(&mut self) -> IndexerResult<()>;
+
+    async fn export(&mut self, path: &str) -> IndexerResult<()>;
+
+    async fn import(&mut self, path: &str) -> IndexerResult<()>;
 }
 
 #[derive(Clone, Debug)]
 pub struct SeenStatusResponse {
     seen: bool,
     status: SeenStatus,
+    first_seen: i64,
+    re_announced: u32,
+    from_restore: bool,
 }
 impl SeenStatusResponse {
+    pub(crate) fn new(seen: bool, status: SeenStatus, record: &SeenRecord) -> Self {
+        Self {
+            seen,
+            status,
+            first_seen: record.timestamp,
+            re_announced: record.re_announced,
+            from_restore: record.from_restore,
+        }
+    }
     pub fn is_seen(&self) -> bool {
         self.seen
     }
     pub fn is_executed(&self) -> bool {
         self.status == SeenStatus::Executed
     }
+    // unix timestamp of the first announcement
+    pub fn first_seen(&self) -> i64 {
+        self.first_seen
+    }
+    // announcements after the first one,restores included
+    pub fn re_announced(&self) -> u32 {
+        self.re_announced
+    }
+    // first seen by a restore pass rather than zmq
+    pub fn is_from_restore(&self) -> bool {
+        self.from_restore
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct StorageStats {
+    pub seen_txs: u64,
+    // deltas still executed,waiting for confirm or drop
+    pub unconsumed_deltas: u64,
+    pub total_keys: u64,
+    // keys and values
+    pub total_bytes: u64,
+    // write operations since the processor was created
+    pub write_ops: u64,
+    pub write_ops_per_sec: f64,
 }
 
 #[async_trait::async_trait]
@@ -84,6 +284,13 @@ impl StorageProcessor for Box<dyn StorageProcessor> {
         self.as_mut().add_transaction_delta(transaction).await
     }
 
+    async fn add_transaction_deltas(
+        &mut self,
+        transactions: &[TransactionDelta],
+    ) -> IndexerResult<()> {
+        self.as_mut().add_transaction_deltas(transactions).await
+    }
+
     async fn remove_transaction_delta(
         &mut self,
         tx_id: &TxIdType,
@@ -92,8 +299,12 @@ impl StorageProcessor for Box<dyn StorageProcessor> {
         self.as_mut().remove_transaction_delta(tx_id, status).await
     }
 
-    async fn seen_and_store_txs(&mut self, tx: &Transaction) -> IndexerResult<SeenStatusResponse> {
-        self.as_mut().seen_and_store_txs(tx).await
+    async fn seen_and_store_txs(
+        &mut self,
+        tx: &Transaction,
+        from_restore: bool,
+    ) -> IndexerResult<SeenStatusResponse> {
+        self.as_mut().seen_and_store_txs(tx, from_restore).await
     }
 
     async fn seen_tx(&mut self, tx_id: TxIdType) -> IndexerResult<SeenStatusResponse> {
@@ -139,4 +350,205 @@ impl StorageProcessor for Box<dyn StorageProcessor> {
     async fn remove_tx_traces(&mut self, tx_id: Vec<TxIdType>) -> IndexerResult<()> {
         self.as_mut().remove_tx_traces(tx_id).await
     }
+
+    async fn take_snapshot(&mut self, height: u32) -> IndexerResult<Snapshot> {
+        self.as_mut().take_snapshot(height).await
+    }
+
+    async fn restore_snapshot(&mut self, snapshot: &Snapshot) -> IndexerResult<()> {
+        self.as_mut().restore_snapshot(snapshot).await
+    }
+
+    async fn update_indexed_height(&mut self, height: u32) -> IndexerResult<()> {
+        self.as_mut().update_indexed_height(height).await
+    }
+
+    async fn stats(&mut self) -> IndexerResult<StorageStats> {
+        self.as_mut().stats().await
+    }
+
+    async fn prune_deltas(&mut self, retention: &PruneRetention) -> IndexerResult<u32> {
+        self.as_mut().prune_deltas(retention).await
+    }
+
+    async fn get_address_history(
+        &mut self,
+        address: &AddressType,
+        pagination: &Pagination,
+    ) -> IndexerResult<Vec<TxIdType>> {
+        self.as_mut().get_address_history(address, pagination).await
+    }
+
+    async fn apply_utxos(
+        &mut self,
+        tx_id: &TxIdType,
+        created: Vec<Utxo>,
+        spent: Vec<Utxo>,
+    ) -> IndexerResult<()> {
+        self.as_mut().apply_utxos(tx_id, created, spent).await
+    }
+
+    async fn settle_utxos(&mut self, tx_id: &TxIdType, revert: bool) -> IndexerResult<()> {
+        self.as_mut().settle_utxos(tx_id, revert).await
+    }
+
+    async fn get_utxo(&mut self, tx_id: &TxIdType, vout: u32) -> IndexerResult<Option<Utxo>> {
+        self.as_mut().get_utxo(tx_id, vout).await
+    }
+
+    async fn get_utxos(&mut self, address: &AddressType) -> IndexerResult<Vec<Utxo>> {
+        self.as_mut().get_utxos(address).await
+    }
+
+    async fn get_balance_at_height(
+        &mut self,
+        address: &AddressType,
+        token_type: &TokenType,
+        height: u32,
+    ) -> IndexerResult<BalanceType> {
+        self.as_mut()
+            .get_balance_at_height(address, token_type, height)
+            .await
+    }
+
+    async fn get_balance_detail(
+        &mut self,
+        address: &AddressType,
+        token_type: &TokenType,
+    ) -> IndexerResult<BalanceDetailResponse> {
+        self.as_mut().get_balance_detail(address, token_type).await
+    }
+
+    async fn get_transaction_delta(
+        &mut self,
+        tx_id: &TxIdType,
+    ) -> IndexerResult<Option<TransactionDeltaWrapper>> {
+        self.as_mut().get_transaction_delta(tx_id).await
+    }
+
+    async fn list_deltas(
+        &mut self,
+        status: DeltaStatus,
+        pagination: &Pagination,
+    ) -> IndexerResult<Vec<TransactionDeltaWrapper>> {
+        self.as_mut().list_deltas(status, pagination).await
+    }
+
+    async fn gc_inactive_deltas(&mut self) -> IndexerResult<u32> {
+        self.as_mut().gc_inactive_deltas().await
+    }
+
+    async fn get_audit_log(
+        &mut self,
+        pagination: &Pagination,
+    ) -> IndexerResult<Vec<TransactionDeltaWrapper>> {
+        self.as_mut().get_audit_log(pagination).await
+    }
+
+    async fn register_token(
+        &mut self,
+        token_type: &TokenType,
+        meta: &TokenMeta,
+    ) -> IndexerResult<()> {
+        self.as_mut().register_token(token_type, meta).await
+    }
+
+    async fn get_token_meta(&mut self, token_type: &TokenType) -> IndexerResult<Option<TokenMeta>> {
+        self.as_mut().get_token_meta(token_type).await
+    }
+
+    async fn add_dead_letter(&mut self, event: &[u8], error: &str) -> IndexerResult<()> {
+        self.as_mut().add_dead_letter(event, error).await
+    }
+
+    async fn get_dead_letters(&mut self) -> IndexerResult<Vec<DeadLetter>> {
+        self.as_mut().get_dead_letters().await
+    }
+
+    async fn remove_dead_letter(&mut self, index: u64) -> IndexerResult<()> {
+        self.as_mut().remove_dead_letter(index).await
+    }
+
+    async fn append_journal(&mut self, event: &[u8]) -> IndexerResult<u64> {
+        self.as_mut().append_journal(event).await
+    }
+
+    async fn get_journal(
+        &mut self,
+        from_seq: u64,
+        limit: usize,
+    ) -> IndexerResult<Vec<(u64, Vec<u8>)>> {
+        self.as_mut().get_journal(from_seq, limit).await
+    }
+
+    async fn truncate_journal(&mut self, before_seq: u64) -> IndexerResult<()> {
+        self.as_mut().truncate_journal(before_seq).await
+    }
+
+    async fn get_state_root(&mut self) -> IndexerResult<String> {
+        self.as_mut().get_state_root().await
+    }
+
+    async fn get_pending_txs(&mut self) -> IndexerResult<Vec<PendingTxResponse>> {
+        self.as_mut().get_pending_txs().await
+    }
+
+    async fn set_cursor(&mut self, consumer: u32, seq: u64) -> IndexerResult<()> {
+        self.as_mut().set_cursor(consumer, seq).await
+    }
+
+    async fn get_cursor(&mut self, consumer: u32) -> IndexerResult<Option<u64>> {
+        self.as_mut().get_cursor(consumer).await
+    }
+
+    async fn get_raw_tx(&mut self, tx_id: &TxIdType) -> IndexerResult<Option<Transaction>> {
+        self.as_mut().get_raw_tx(tx_id).await
+    }
+
+    async fn hold_event(&mut self, event: &[u8]) -> IndexerResult<u64> {
+        self.as_mut().hold_event(event).await
+    }
+
+    async fn get_held_events(&mut self) -> IndexerResult<Vec<(u64, Vec<u8>)>> {
+        self.as_mut().get_held_events().await
+    }
+
+    async fn remove_held_event(&mut self, index: u64) -> IndexerResult<()> {
+        self.as_mut().remove_held_event(index).await
+    }
+
+    async fn set_chain_state(&mut self, state: &[u8]) -> IndexerResult<()> {
+        self.as_mut().set_chain_state(state).await
+    }
+
+    async fn get_chain_state(&mut self) -> IndexerResult<Option<Vec<u8>>> {
+        self.as_mut().get_chain_state().await
+    }
+
+    async fn get_balances(
+        &mut self,
+        keys: &[(AddressType, TokenType)],
+    ) -> IndexerResult<Vec<BalanceType>> {
+        self.as_mut().get_balances(keys).await
+    }
+
+    async fn begin_tx(&mut self) -> IndexerResult<()> {
+        self.as_mut().begin_tx().await
+    }
+
+    async fn commit(&mut self) -> IndexerResult<()> {
+        self.as_mut().commit().await
+    }
+
+    async fn rollback(&mut self) -> IndexerResult<()> {
+        self.as_mut().rollback().await
+    }
+
+    async fn export(&mut self, path: &str) -> IndexerResult<()> {
+        self.as_mut().export(path).await
+    }
+
+    async fn import(&mut self, path: &str) -> IndexerResult<()> {
+        self.as_mut().import(path).await
+    }
 }
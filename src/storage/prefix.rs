@@ -10,7 +10,38 @@ pub enum KeyPrefix {
     HeightTxSet, // height -> tx_id set
 
     TxKeyTrace, // tx_id+key -> {}
+
+    SchemaVersion, // -> u32
+
+    IndexedHeight, // -> u32
+
+    AddressHistory, // address|index -> tx_id
+
+    Utxo,        // address|tx_id|vout -> value
+    UtxoOwner,   // tx_id|vout -> address
+    UtxoChanges, // tx_id -> created and spent utxos
+
+    ConfirmedBalance, // address|token -> balance
+
+    AuditLog, // index -> TransactionWrapper,inactive deltas only
+
+    TokenMeta, // token -> TokenMeta
+
+    DeadLetter, // index -> DeadLetter
+
+    EventJournal, // seq -> ClientEvent bytes
+    JournalHead,  // -> next seq
+    JournalTail,  // -> first retained seq
+
+    ConsumerCursor, // consumer -> last acknowledged journal seq
+
+    RawTx, // tx_id -> consensus encoded tx
+
+    HeldEvent, // index -> IndexerEvent bytes,received while paused
+
+    ChainState, // -> blocks and coinbases of the processor waiting for their depth
 }
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum DeltaStatus {
     Default,
     Executed,
@@ -47,6 +78,23 @@ impl KeyPrefix {
             // KeyPrefix::PureSet => b"f",
             KeyPrefix::HeightTxSet => b"g",
             KeyPrefix::TxKeyTrace => b"h",
+            KeyPrefix::SchemaVersion => b"i",
+            KeyPrefix::IndexedHeight => b"j",
+            KeyPrefix::AddressHistory => b"k",
+            KeyPrefix::Utxo => b"l",
+            KeyPrefix::UtxoOwner => b"m",
+            KeyPrefix::UtxoChanges => b"n",
+            KeyPrefix::ConfirmedBalance => b"o",
+            KeyPrefix::AuditLog => b"p",
+            KeyPrefix::TokenMeta => b"q",
+            KeyPrefix::DeadLetter => b"r",
+            KeyPrefix::EventJournal => b"s",
+            KeyPrefix::JournalHead => b"t",
+            KeyPrefix::ConsumerCursor => b"u",
+            KeyPrefix::RawTx => b"v",
+            KeyPrefix::HeldEvent => b"w",
+            KeyPrefix::JournalTail => b"x",
+            KeyPrefix::ChainState => b"y",
         }
     }
     pub fn get_suffix<'a>(&self, key: &'a [u8]) -> &'a [u8] {
@@ -72,12 +120,105 @@ impl KeyPrefix {
     pub fn build_state_key() -> Vec<u8> {
         Self::State.get_prefix().to_vec()
     }
+    pub fn build_schema_version_key() -> Vec<u8> {
+        Self::SchemaVersion.get_prefix().to_vec()
+    }
+    pub fn build_indexed_height_key() -> Vec<u8> {
+        Self::IndexedHeight.get_prefix().to_vec()
+    }
+    // length prefixed,so an address is never the prefix of another one
+    pub fn build_address_history_prefix(address: &AddressType) -> Vec<u8> {
+        let mut ret = Self::AddressHistory.get_prefix().to_vec();
+        let address = address.to_bytes();
+        ret.push(address.len() as u8);
+        ret.extend_from_slice(address.as_slice());
+        ret
+    }
+    // big endian index keeps the history ordered
+    pub fn build_address_history_key(address: &AddressType, index: u32) -> Vec<u8> {
+        let mut ret = Self::build_address_history_prefix(address);
+        ret.extend_from_slice(&index.to_be_bytes());
+        ret
+    }
+    pub fn build_utxo_prefix(address: &AddressType) -> Vec<u8> {
+        let mut ret = Self::Utxo.get_prefix().to_vec();
+        let address = address.to_bytes();
+        ret.push(address.len() as u8);
+        ret.extend_from_slice(address.as_slice());
+        ret
+    }
+    pub fn build_utxo_key(address: &AddressType, tx_id: &TxIdType, vout: u32) -> Vec<u8> {
+        let mut ret = Self::build_utxo_prefix(address);
+        ret.extend_from_slice(tx_id.to_bytes().as_slice());
+        ret.extend_from_slice(&vout.to_be_bytes());
+        ret
+    }
+    pub fn build_utxo_owner_key(tx_id: &TxIdType, vout: u32) -> Vec<u8> {
+        let mut ret = Self::UtxoOwner.get_prefix().to_vec();
+        ret.extend_from_slice(tx_id.to_bytes().as_slice());
+        ret.extend_from_slice(&vout.to_be_bytes());
+        ret
+    }
+    pub fn build_utxo_changes_key(tx_id: &TxIdType) -> Vec<u8> {
+        Self::UtxoChanges.build_prefix(tx_id.to_bytes().as_slice())
+    }
     pub fn build_address_token_key(address: &AddressType, token_type: &TokenType) -> Vec<u8> {
         let mut ret = Self::AddressTokenBalance.get_prefix().to_vec();
         ret.extend_from_slice(address.to_bytes().as_slice());
         ret.extend_from_slice(token_type.to_bytes().as_slice());
         ret
     }
+    pub fn build_confirmed_balance_key(address: &AddressType, token_type: &TokenType) -> Vec<u8> {
+        let mut ret = Self::ConfirmedBalance.get_prefix().to_vec();
+        ret.extend_from_slice(address.to_bytes().as_slice());
+        ret.extend_from_slice(token_type.to_bytes().as_slice());
+        ret
+    }
+    pub fn build_token_meta_key(token_type: &TokenType) -> Vec<u8> {
+        let mut ret = Self::TokenMeta.get_prefix().to_vec();
+        ret.extend_from_slice(token_type.to_bytes().as_slice());
+        ret
+    }
+    // big endian index keeps the log in insertion order
+    pub fn build_audit_log_key(index: u32) -> Vec<u8> {
+        let mut ret = Self::AuditLog.get_prefix().to_vec();
+        ret.extend_from_slice(&index.to_be_bytes());
+        ret
+    }
+    pub fn build_dead_letter_key(index: u64) -> Vec<u8> {
+        let mut ret = Self::DeadLetter.get_prefix().to_vec();
+        ret.extend_from_slice(&index.to_be_bytes());
+        ret
+    }
+    pub fn build_event_journal_key(seq: u64) -> Vec<u8> {
+        let mut ret = Self::EventJournal.get_prefix().to_vec();
+        ret.extend_from_slice(&seq.to_be_bytes());
+        ret
+    }
+    pub fn build_journal_head_key() -> Vec<u8> {
+        Self::JournalHead.get_prefix().to_vec()
+    }
+    pub fn build_journal_tail_key() -> Vec<u8> {
+        Self::JournalTail.get_prefix().to_vec()
+    }
+    pub fn build_held_event_key(index: u64) -> Vec<u8> {
+        let mut ret = Self::HeldEvent.get_prefix().to_vec();
+        ret.extend_from_slice(&index.to_be_bytes());
+        ret
+    }
+    pub fn build_raw_tx_key(tx_id: &TxIdType) -> Vec<u8> {
+        let mut ret = Self::RawTx.get_prefix().to_vec();
+        ret.extend_from_slice(&tx_id.to_bytes());
+        ret
+    }
+    pub fn build_chain_state_key() -> Vec<u8> {
+        Self::ChainState.get_prefix().to_vec()
+    }
+    pub fn build_consumer_cursor_key(consumer: u32) -> Vec<u8> {
+        let mut ret = Self::ConsumerCursor.get_prefix().to_vec();
+        ret.extend_from_slice(&consumer.to_be_bytes());
+        ret
+    }
     pub fn build_address_balance_prefix_key(address: &AddressType) -> Vec<u8> {
         let mut ret = Self::AddressTokenBalance.get_prefix().to_vec();
         ret.extend_from_slice(address.to_bytes().as_slice());
@@ -0,0 +1,53 @@
+use crate::configuration::base::PruneConfiguration;
+use crate::storage::StorageProcessor;
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+
+// how long settled (confirmed or inactive) deltas are kept before being pruned
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PruneRetention {
+    // keep deltas settled within the latest n indexed blocks
+    Height(u32),
+    // keep deltas settled within the latest n seconds
+    Age(i64),
+}
+
+pub fn start_pruner<T: StorageProcessor + Clone + 'static>(
+    storage: T,
+    cfg: PruneConfiguration,
+    mut exit: watch::Receiver<()>,
+) -> JoinHandle<()> {
+    let mut storage = storage;
+    tokio::task::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(cfg.interval_secs));
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {
+                    // gc first,so inactive deltas reach the audit log before the retention drops them
+                    if cfg.gc_inactive {
+                        match storage.gc_inactive_deltas().await {
+                            Ok(0) => {}
+                            Ok(n) => info!("moved {} inactive deltas into the audit log", n),
+                            Err(e) => error!("gc inactive deltas error:{:?}", e),
+                        }
+                    }
+                    if let Some(retention) = &cfg.retention {
+                        match storage.prune_deltas(retention).await {
+                            Ok(0) => {}
+                            Ok(n) => info!("pruned {} settled deltas,retention:{:?}", n, retention),
+                            Err(e) => error!("prune deltas error:{:?}", e),
+                        }
+                    }
+                }
+                _ = exit.changed() => {
+                    info!("pruner exit");
+                    return;
+                }
+            }
+        }
+    })
+}
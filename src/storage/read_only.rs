@@ -0,0 +1,268 @@
+use crate::error::{IndexerError, IndexerResult};
+use crate::event::{AddressType, BalanceType, TokenType, TxIdType};
+use crate::storage::codec::{Codec, DefaultCodec};
+use crate::storage::db::level_db::LevelDB;
+use crate::storage::db::DB;
+use crate::storage::kv::{KVStorageProcessor, TransactionDeltaWrapper};
+use crate::storage::prefix::DeltaStatus;
+use crate::storage::{SeenStatusResponse, StorageProcessor, StorageStats};
+use crate::types::dead_letter::DeadLetter;
+use crate::types::request::Pagination;
+use crate::types::response::{AllBalanceResponse, BalanceDetailResponse, PendingTxResponse};
+use crate::types::token::TokenMeta;
+use crate::types::transaction::Utxo;
+use log::warn;
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+// query only view of the kv storage,for reporting jobs that must never write indexer state
+#[derive(Clone)]
+pub struct ReadOnlyStorageProcessor<T: DB + Send + Sync + Clone, C: Codec = DefaultCodec> {
+    internal: KVStorageProcessor<T, C>,
+}
+
+impl<T: DB + Send + Sync + Clone> ReadOnlyStorageProcessor<T> {
+    pub fn new(db: T) -> Self {
+        Self::with_codec(db, DefaultCodec)
+    }
+}
+
+impl<T: DB + Send + Sync + Clone, C: Codec> ReadOnlyStorageProcessor<T, C> {
+    pub fn with_codec(db: T, codec: C) -> Self {
+        Self {
+            internal: KVStorageProcessor::with_codec(db, codec),
+        }
+    }
+
+    pub async fn get_balance(
+        &mut self,
+        address: &AddressType,
+        token_type: &TokenType,
+    ) -> IndexerResult<BalanceType> {
+        self.internal.get_balance(address, token_type).await
+    }
+
+    pub async fn get_balance_at_height(
+        &mut self,
+        address: &AddressType,
+        token_type: &TokenType,
+        height: u32,
+    ) -> IndexerResult<BalanceType> {
+        self.internal
+            .get_balance_at_height(address, token_type, height)
+            .await
+    }
+
+    pub async fn get_balance_detail(
+        &mut self,
+        address: &AddressType,
+        token_type: &TokenType,
+    ) -> IndexerResult<BalanceDetailResponse> {
+        self.internal.get_balance_detail(address, token_type).await
+    }
+
+    pub async fn get_all_balance(
+        &mut self,
+        address: &AddressType,
+    ) -> IndexerResult<Vec<AllBalanceResponse>> {
+        self.internal.get_all_balance(address).await
+    }
+
+    pub async fn seen_tx(&mut self, tx_id: TxIdType) -> IndexerResult<SeenStatusResponse> {
+        self.internal.seen_tx(tx_id).await
+    }
+
+    pub async fn is_tx_executed(&mut self, tx_id: &TxIdType) -> IndexerResult<bool> {
+        self.internal.is_tx_executed(tx_id).await
+    }
+
+    pub async fn get_all_un_consumed_txs(&mut self) -> IndexerResult<HashMap<TxIdType, i64>> {
+        self.internal.get_all_un_consumed_txs().await
+    }
+
+    pub async fn get_address_history(
+        &mut self,
+        address: &AddressType,
+        pagination: &Pagination,
+    ) -> IndexerResult<Vec<TxIdType>> {
+        self.internal.get_address_history(address, pagination).await
+    }
+
+    pub async fn get_utxos(&mut self, address: &AddressType) -> IndexerResult<Vec<Utxo>> {
+        self.internal.get_utxos(address).await
+    }
+
+    pub async fn get_transaction_delta(
+        &mut self,
+        tx_id: &TxIdType,
+    ) -> IndexerResult<Option<TransactionDeltaWrapper>> {
+        self.internal.get_transaction_delta(tx_id).await
+    }
+
+    pub async fn list_deltas(
+        &mut self,
+        status: DeltaStatus,
+        pagination: &Pagination,
+    ) -> IndexerResult<Vec<TransactionDeltaWrapper>> {
+        self.internal.list_deltas(status, pagination).await
+    }
+
+    pub async fn get_audit_log(
+        &mut self,
+        pagination: &Pagination,
+    ) -> IndexerResult<Vec<TransactionDeltaWrapper>> {
+        self.internal.get_audit_log(pagination).await
+    }
+
+    pub async fn get_journal(
+        &mut self,
+        from_seq: u64,
+        limit: usize,
+    ) -> IndexerResult<Vec<(u64, Vec<u8>)>> {
+        self.internal.get_journal(from_seq, limit).await
+    }
+
+    pub async fn get_pending_txs(&mut self) -> IndexerResult<Vec<PendingTxResponse>> {
+        self.internal.get_pending_txs().await
+    }
+
+    pub async fn get_state_root(&mut self) -> IndexerResult<String> {
+        self.internal.get_state_root().await
+    }
+
+    pub async fn get_dead_letters(&mut self) -> IndexerResult<Vec<DeadLetter>> {
+        self.internal.get_dead_letters().await
+    }
+
+    pub async fn get_token_meta(
+        &mut self,
+        token_type: &TokenType,
+    ) -> IndexerResult<Option<TokenMeta>> {
+        self.internal.get_token_meta(token_type).await
+    }
+
+    pub async fn simple_get(&mut self, key: &[u8]) -> IndexerResult<Option<Vec<u8>>> {
+        self.internal.simple_get(key).await
+    }
+
+    pub async fn stats(&mut self) -> IndexerResult<StorageStats> {
+        self.internal.stats().await
+    }
+
+    pub async fn export(&mut self, path: &str) -> IndexerResult<()> {
+        self.internal.export(path).await
+    }
+
+    pub fn get_indexed_height(&mut self) -> IndexerResult<u32> {
+        self.internal.get_indexed_height()
+    }
+}
+
+// marks a directory written by open,only those are replaced.
+// the db files go to a sub dir,leveldb refuses unknown files next to them
+const COPY_MARKER: &str = "READ_ONLY_COPY";
+const COPY_DB_DIR: &str = "db";
+const COPY_ATTEMPTS: usize = 5;
+
+impl ReadOnlyStorageProcessor<LevelDB> {
+    // leveldb holds an exclusive lock on db_path while the writer runs,
+    // so the files are copied below copy_path and the copy is opened instead.
+    // a write during the copy changes the files,the copy is then retried
+    pub fn open(db_path: &str, copy_path: &str) -> IndexerResult<Self> {
+        let copy_db = Path::new(copy_path).join(COPY_DB_DIR);
+        prepare_copy_path(copy_path)?;
+        for attempt in 1..=COPY_ATTEMPTS {
+            let before = db_files(db_path)?;
+            for (name, _, _) in &before {
+                fs::copy(Path::new(db_path).join(name), copy_db.join(name))?;
+            }
+            if db_files(db_path)? == before {
+                return Ok(Self::new(LevelDB::new(copy_db.to_str().unwrap())?));
+            }
+            warn!(
+                "{} changed while it was copied,attempt:{}",
+                db_path, attempt
+            );
+            prepare_copy_path(copy_path)?;
+            std::thread::sleep(Duration::from_millis(100));
+        }
+        Err(IndexerError::UnstableCopy(db_path.to_string()))
+    }
+}
+
+// name,len and modify time of every db file but the lock
+fn db_files(db_path: &str) -> IndexerResult<Vec<(OsString, u64, SystemTime)>> {
+    let mut ret = vec![];
+    for entry in fs::read_dir(db_path)? {
+        let entry = entry?;
+        if entry.file_name() == "LOCK" || !entry.file_type()?.is_file() {
+            continue;
+        }
+        let meta = entry.metadata()?;
+        ret.push((entry.file_name(), meta.len(), meta.modified()?));
+    }
+    ret.sort();
+    Ok(ret)
+}
+
+// an empty dir or a previous copy,anything else is refused instead of wiped
+fn prepare_copy_path(copy_path: &str) -> IndexerResult<()> {
+    let path = Path::new(copy_path);
+    if path.exists() {
+        if path.join(COPY_MARKER).exists() {
+            fs::remove_dir_all(path)?;
+        } else if fs::read_dir(path)?.next().is_some() {
+            return Err(IndexerError::InvalidCopyPath(copy_path.to_string()));
+        }
+    }
+    fs::create_dir_all(path.join(COPY_DB_DIR))?;
+    fs::write(path.join(COPY_MARKER), [])?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::delta::TransactionDelta;
+
+    #[tokio::test]
+    pub async fn test_read_only_open() {
+        let address = AddressType::from_bytes(&[0u8; 20]);
+        let token = TokenType::from_bytes(&[0u8; 20]);
+        let dir = std::env::temp_dir().join("test_read_only_open");
+        let _ = fs::remove_dir_all(&dir);
+        let db_path = dir.join("db");
+        let copy_path = dir.join("copy");
+        let (db_path, copy_path) = (db_path.to_str().unwrap(), copy_path.to_str().unwrap());
+        let mut writer = KVStorageProcessor::new(LevelDB::new(db_path).unwrap());
+        let mut delta = HashMap::default();
+        delta.insert(address.clone(), vec![(token.clone(), BalanceType::from(3))]);
+        writer
+            .add_transaction_delta(&TransactionDelta {
+                tx_id: TxIdType::from_bytes(&[0u8; 32]),
+                deltas: delta,
+            })
+            .await
+            .unwrap();
+
+        let mut reader = ReadOnlyStorageProcessor::open(db_path, copy_path).unwrap();
+        let bal = reader.get_balance(&address, &token).await.unwrap();
+        assert_eq!(bal, BalanceType::from(3));
+        drop(reader);
+        // a previous copy is replaced
+        assert!(ReadOnlyStorageProcessor::open(db_path, copy_path).is_ok());
+
+        // anything else is left alone
+        let foreign = dir.join("foreign");
+        fs::create_dir_all(&foreign).unwrap();
+        fs::write(foreign.join("data"), b"keep").unwrap();
+        assert!(ReadOnlyStorageProcessor::open(db_path, foreign.to_str().unwrap()).is_err());
+        assert_eq!(fs::read(foreign.join("data")).unwrap(), b"keep");
+
+        drop(writer);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,176 @@
+pub mod remote;
+
+use crate::error::IndexerResult;
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::fs;
+use std::path::Path;
+
+const SNAPSHOT_FILE_PREFIX: &str = "snapshot-";
+const SNAPSHOT_FILE_SUFFIX: &str = ".json";
+
+// full copy of the kv store (seen txs, deltas, balances, state) at a processed height
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub height: u32,
+    pub timestamp: i64,
+    // hex encoded key/value pairs
+    entries: Vec<(String, String)>,
+}
+
+impl Snapshot {
+    pub fn new(height: u32, entries: Vec<(Vec<u8>, Vec<u8>)>) -> Self {
+        let entries = entries
+            .into_iter()
+            .map(|(k, v)| (hex::encode(k), hex::encode(v)))
+            .collect();
+        Self {
+            height,
+            timestamp: Local::now().timestamp(),
+            entries,
+        }
+    }
+
+    pub fn entries(&self) -> IndexerResult<Vec<(Vec<u8>, Vec<u8>)>> {
+        let mut ret = vec![];
+        for (k, v) in &self.entries {
+            ret.push((hex::decode(k)?, hex::decode(v)?));
+        }
+        Ok(ret)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn save(&self, dir: &str) -> IndexerResult<String> {
+        fs::create_dir_all(dir)?;
+        let file_name = format!(
+            "{}{:010}{}",
+            SNAPSHOT_FILE_PREFIX, self.height, SNAPSHOT_FILE_SUFFIX
+        );
+        let path = Path::new(dir).join(file_name);
+        let data = serde_json::to_vec(self)?;
+        // write to a temp file first,so a crash never leaves a half written snapshot
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, data)?;
+        fs::rename(&tmp, &path)?;
+        Ok(path.to_string_lossy().to_string())
+    }
+
+    pub fn load(path: &str) -> IndexerResult<Self> {
+        let data = fs::read(path)?;
+        let ret: Snapshot = serde_json::from_slice(data.as_slice())?;
+        Ok(ret)
+    }
+
+    // drops the snapshots at or above height,they were taken on a branch that got reorged away
+    pub fn remove_from(dir: &str, height: u32) -> IndexerResult<usize> {
+        if !Path::new(dir).exists() {
+            return Ok(0);
+        }
+        let mut ret = 0;
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().to_string();
+            if snapshot_height(&name).is_some_and(|v| v >= height) {
+                fs::remove_file(entry.path())?;
+                ret += 1;
+            }
+        }
+        Ok(ret)
+    }
+
+    // drops all but the keep highest snapshots
+    pub fn retain_latest(dir: &str, keep: usize) -> IndexerResult<usize> {
+        if !Path::new(dir).exists() {
+            return Ok(0);
+        }
+        let mut snapshots = vec![];
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            if let Some(height) = snapshot_height(&entry.file_name().to_string_lossy()) {
+                snapshots.push((height, entry.path()));
+            }
+        }
+        snapshots.sort_by_key(|(height, _)| Reverse(*height));
+        let mut ret = 0;
+        for (_, path) in snapshots.iter().skip(keep) {
+            fs::remove_file(path)?;
+            ret += 1;
+        }
+        Ok(ret)
+    }
+
+    // snapshot with the highest height in dir
+    pub fn latest(dir: &str) -> IndexerResult<Option<Self>> {
+        if !Path::new(dir).exists() {
+            return Ok(None);
+        }
+        let mut latest = None;
+        for entry in fs::read_dir(dir)? {
+            let name = entry?.file_name().to_string_lossy().to_string();
+            if !name.starts_with(SNAPSHOT_FILE_PREFIX) || !name.ends_with(SNAPSHOT_FILE_SUFFIX) {
+                continue;
+            }
+            if latest.as_ref().is_none_or(|v: &String| name > *v) {
+                latest = Some(name);
+            }
+        }
+        match latest {
+            None => Ok(None),
+            Some(name) => {
+                let path = Path::new(dir).join(name);
+                Ok(Some(Self::load(path.to_string_lossy().as_ref())?))
+            }
+        }
+    }
+}
+
+fn snapshot_height(file_name: &str) -> Option<u32> {
+    file_name
+        .strip_prefix(SNAPSHOT_FILE_PREFIX)
+        .and_then(|v| v.strip_suffix(SNAPSHOT_FILE_SUFFIX))
+        .and_then(|v| v.parse::<u32>().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_remove_from() {
+        let dir = "./test_snapshot_remove_from";
+        for height in [10, 20, 30] {
+            Snapshot::new(height, vec![]).save(dir).unwrap();
+        }
+        assert_eq!(Snapshot::remove_from(dir, 20).unwrap(), 2);
+        assert_eq!(Snapshot::latest(dir).unwrap().unwrap().height, 10);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    pub fn test_retain_latest() {
+        let dir = "./test_snapshot_retain_latest";
+        for height in [5, 30, 10, 20] {
+            Snapshot::new(height, vec![]).save(dir).unwrap();
+        }
+        assert_eq!(Snapshot::retain_latest(dir, 2).unwrap(), 2);
+        assert_eq!(Snapshot::retain_latest(dir, 2).unwrap(), 0);
+        let mut names: Vec<_> = fs::read_dir(dir)
+            .unwrap()
+            .map(|v| v.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        names.sort();
+        assert_eq!(
+            names,
+            vec!["snapshot-0000000020.json", "snapshot-0000000030.json"]
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
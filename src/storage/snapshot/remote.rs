@@ -0,0 +1,197 @@
+use crate::configuration::base::SnapshotConfiguration;
+use crate::error::{IndexerError, IndexerResult};
+use crate::storage::snapshot::{SNAPSHOT_FILE_PREFIX, SNAPSHOT_FILE_SUFFIX};
+use bitcoincore_rpc::bitcoin::hashes::{sha256, Hash};
+use log::{info, warn};
+use std::fs;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+const CHECKSUM_SUFFIX: &str = ".sha256";
+
+// minimal object storage api,only LocalObjectStore ships.
+// implement it on top of an s3/gcs client and pass it to the builder to upload snapshots to a bucket directly
+#[async_trait::async_trait]
+pub trait ObjectStore: Send + Sync {
+    async fn put(&self, key: &str, data: Vec<u8>) -> IndexerResult<()>;
+    async fn get(&self, key: &str) -> IndexerResult<Option<Vec<u8>>>;
+    async fn list(&self, prefix: &str) -> IndexerResult<Vec<String>>;
+}
+
+// object store backed by a directory,e.g. a bucket mounted with s3fs or gcsfuse
+pub struct LocalObjectStore {
+    root: PathBuf,
+}
+
+impl LocalObjectStore {
+    pub fn new(root: &str) -> Self {
+        Self {
+            root: PathBuf::from(root),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl ObjectStore for LocalObjectStore {
+    async fn put(&self, key: &str, data: Vec<u8>) -> IndexerResult<()> {
+        fs::create_dir_all(&self.root)?;
+        let path = self.root.join(key);
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, data)?;
+        fs::rename(&tmp, &path)?;
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> IndexerResult<Option<Vec<u8>>> {
+        let path = self.root.join(key);
+        if !path.exists() {
+            return Ok(None);
+        }
+        Ok(Some(fs::read(path)?))
+    }
+
+    async fn list(&self, prefix: &str) -> IndexerResult<Vec<String>> {
+        if !self.root.exists() {
+            return Ok(vec![]);
+        }
+        let mut ret = vec![];
+        for entry in fs::read_dir(&self.root)? {
+            let name = entry?.file_name().to_string_lossy().to_string();
+            if name.starts_with(prefix) {
+                ret.push(name);
+            }
+        }
+        Ok(ret)
+    }
+}
+
+// uploads checkpoints with a sha256 checksum object next to them,and verifies it on download
+#[derive(Clone)]
+pub struct RemoteSnapshotStore {
+    store: Arc<dyn ObjectStore>,
+    max_retries: u32,
+    backoff: Duration,
+}
+
+impl RemoteSnapshotStore {
+    pub fn new(store: Arc<dyn ObjectStore>, max_retries: u32) -> Self {
+        Self {
+            store,
+            max_retries,
+            backoff: Duration::from_millis(500),
+        }
+    }
+
+    // the mounted bucket of upload_dir,none without one
+    pub fn from_configuration(cfg: &SnapshotConfiguration) -> Option<Self> {
+        let dir = cfg.upload_dir.as_ref()?;
+        Some(Self::new(
+            Arc::new(LocalObjectStore::new(dir)),
+            cfg.upload_retries,
+        ))
+    }
+
+    pub fn with_backoff(mut self, backoff: Duration) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    pub async fn upload(&self, snapshot_path: &str) -> IndexerResult<String> {
+        let data = fs::read(snapshot_path)?;
+        let key = Path::new(snapshot_path)
+            .file_name()
+            .map(|v| v.to_string_lossy().to_string())
+            .ok_or(IndexerError::InvalidSnapshotPath(snapshot_path.to_string()))?;
+        let checksum = sha256::Hash::hash(data.as_slice()).to_string();
+        // data first,a checksum without data is never visible
+        self.retry(|| self.store.put(&key, data.clone())).await?;
+        let checksum_key = format!("{}{}", key, CHECKSUM_SUFFIX);
+        self.retry(|| self.store.put(&checksum_key, checksum.clone().into_bytes()))
+            .await?;
+        info!("snapshot {} uploaded,sha256:{}", key, checksum);
+        Ok(key)
+    }
+
+    // downloads the highest snapshot that has a checksum into dir,returns the local path
+    pub async fn download_latest(&self, dir: &str) -> IndexerResult<Option<String>> {
+        let keys = self.retry(|| self.store.list(SNAPSHOT_FILE_PREFIX)).await?;
+        let latest = keys
+            .iter()
+            .filter(|k| k.ends_with(SNAPSHOT_FILE_SUFFIX))
+            .filter(|k| keys.contains(&format!("{}{}", k, CHECKSUM_SUFFIX)))
+            .max();
+        let key = match latest {
+            None => return Ok(None),
+            Some(key) => key.clone(),
+        };
+        let data = self.retry(|| self.store.get(&key)).await?;
+        let checksum_key = format!("{}{}", key, CHECKSUM_SUFFIX);
+        let checksum = self.retry(|| self.store.get(&checksum_key)).await?;
+        let (data, checksum) = match (data, checksum) {
+            (Some(data), Some(checksum)) => (data, String::from_utf8_lossy(&checksum).to_string()),
+            _ => return Ok(None),
+        };
+        let actual = sha256::Hash::hash(data.as_slice()).to_string();
+        if actual != checksum.trim() {
+            return Err(IndexerError::ChecksumMismatch(key));
+        }
+        fs::create_dir_all(dir)?;
+        let path = Path::new(dir).join(&key);
+        fs::write(&path, data)?;
+        info!("snapshot {} downloaded to {:?}", key, path);
+        Ok(Some(path.to_string_lossy().to_string()))
+    }
+
+    async fn retry<R, F, Fut>(&self, f: F) -> IndexerResult<R>
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = IndexerResult<R>>,
+    {
+        let mut attempt = 0;
+        loop {
+            match f().await {
+                Ok(v) => return Ok(v),
+                Err(e) if attempt < self.max_retries => {
+                    attempt += 1;
+                    warn!("object store error:{:?},retry {}", e, attempt);
+                    tokio::time::sleep(self.backoff * attempt).await;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::snapshot::Snapshot;
+
+    #[tokio::test]
+    pub async fn test_upload_download() {
+        let dir = std::env::temp_dir().join("test_upload_download");
+        let _ = fs::remove_dir_all(&dir);
+        let (local, bucket, download) =
+            (dir.join("local"), dir.join("bucket"), dir.join("download"));
+        let (local, bucket, download) = (
+            local.to_str().unwrap(),
+            bucket.to_str().unwrap(),
+            download.to_str().unwrap(),
+        );
+        let snapshot = Snapshot::new(7, vec![(b"k".to_vec(), b"v".to_vec())]);
+        let path = snapshot.save(local).unwrap();
+        let remote = RemoteSnapshotStore::new(Arc::new(LocalObjectStore::new(bucket)), 1);
+        remote.upload(&path).await.unwrap();
+
+        let path = remote.download_latest(download).await.unwrap().unwrap();
+        let loaded = Snapshot::load(&path).unwrap();
+        assert_eq!(loaded.height, 7);
+
+        // tampered data must be rejected
+        fs::write(Path::new(bucket).join("snapshot-0000000007.json"), b"{}").unwrap();
+        assert!(remote.download_latest(download).await.is_err());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -1,9 +1,16 @@
 use crate::error::IndexerResult;
 use crate::event::{AddressType, BalanceType, TokenType, TxIdType};
+use crate::storage::kv::TransactionDeltaWrapper;
 use crate::storage::prefix::DeltaStatus;
-use crate::storage::{SeenStatusResponse, StorageProcessor};
+use crate::storage::prune::PruneRetention;
+use crate::storage::snapshot::Snapshot;
+use crate::storage::{SeenStatusResponse, StorageProcessor, StorageStats};
+use crate::types::dead_letter::DeadLetter;
 use crate::types::delta::TransactionDelta;
-use crate::types::response::AllBalanceResponse;
+use crate::types::request::Pagination;
+use crate::types::response::{AllBalanceResponse, BalanceDetailResponse, PendingTxResponse};
+use crate::types::token::TokenMeta;
+use crate::types::transaction::Utxo;
 use bitcoincore_rpc::bitcoin::Transaction;
 use log::debug;
 use std::collections::HashMap;
@@ -43,6 +50,16 @@ impl<T: StorageProcessor> StorageProcessor for ThreadSafeStorageProcessor<T> {
         Ok(())
     }
 
+    async fn add_transaction_deltas(
+        &mut self,
+        transactions: &[TransactionDelta],
+    ) -> IndexerResult<()> {
+        let mut write = self.rw_lock.write().await;
+        self.internal.add_transaction_deltas(transactions).await?;
+        *write += 1;
+        Ok(())
+    }
+
     async fn remove_transaction_delta(
         &mut self,
         tx_id: &TxIdType,
@@ -56,9 +73,13 @@ impl<T: StorageProcessor> StorageProcessor for ThreadSafeStorageProcessor<T> {
         Ok(())
     }
 
-    async fn seen_and_store_txs(&mut self, tx: &Transaction) -> IndexerResult<SeenStatusResponse> {
+    async fn seen_and_store_txs(
+        &mut self,
+        tx: &Transaction,
+        from_restore: bool,
+    ) -> IndexerResult<SeenStatusResponse> {
         let write = self.rw_lock.write().await;
-        let ret = self.internal.seen_and_store_txs(tx).await?;
+        let ret = self.internal.seen_and_store_txs(tx, from_restore).await?;
         drop(write);
         Ok(ret)
     }
@@ -133,4 +154,326 @@ impl<T: StorageProcessor> StorageProcessor for ThreadSafeStorageProcessor<T> {
         *write += 1;
         Ok(())
     }
+
+    async fn take_snapshot(&mut self, height: u32) -> IndexerResult<Snapshot> {
+        let read = self.rw_lock.write().await;
+        let ret = self.internal.take_snapshot(height).await;
+        drop(read);
+        ret
+    }
+
+    async fn restore_snapshot(&mut self, snapshot: &Snapshot) -> IndexerResult<()> {
+        let mut write = self.rw_lock.write().await;
+        self.internal.restore_snapshot(snapshot).await?;
+        *write += 1;
+        Ok(())
+    }
+
+    async fn update_indexed_height(&mut self, height: u32) -> IndexerResult<()> {
+        let mut write = self.rw_lock.write().await;
+        self.internal.update_indexed_height(height).await?;
+        *write += 1;
+        Ok(())
+    }
+
+    async fn stats(&mut self) -> IndexerResult<StorageStats> {
+        let read = self.rw_lock.read().await;
+        let ret = self.internal.stats().await;
+        drop(read);
+        ret
+    }
+
+    async fn prune_deltas(&mut self, retention: &PruneRetention) -> IndexerResult<u32> {
+        let mut write = self.rw_lock.write().await;
+        let ret = self.internal.prune_deltas(retention).await?;
+        *write += 1;
+        Ok(ret)
+    }
+
+    async fn get_address_history(
+        &mut self,
+        address: &AddressType,
+        pagination: &Pagination,
+    ) -> IndexerResult<Vec<TxIdType>> {
+        let read = self.rw_lock.read().await;
+        let ret = self.internal.get_address_history(address, pagination).await;
+        drop(read);
+        ret
+    }
+
+    async fn apply_utxos(
+        &mut self,
+        tx_id: &TxIdType,
+        created: Vec<Utxo>,
+        spent: Vec<Utxo>,
+    ) -> IndexerResult<()> {
+        let mut write = self.rw_lock.write().await;
+        self.internal.apply_utxos(tx_id, created, spent).await?;
+        *write += 1;
+        Ok(())
+    }
+
+    async fn settle_utxos(&mut self, tx_id: &TxIdType, revert: bool) -> IndexerResult<()> {
+        let mut write = self.rw_lock.write().await;
+        self.internal.settle_utxos(tx_id, revert).await?;
+        *write += 1;
+        Ok(())
+    }
+
+    async fn get_utxo(&mut self, tx_id: &TxIdType, vout: u32) -> IndexerResult<Option<Utxo>> {
+        let read = self.rw_lock.read().await;
+        let ret = self.internal.get_utxo(tx_id, vout).await;
+        drop(read);
+        ret
+    }
+
+    async fn get_utxos(&mut self, address: &AddressType) -> IndexerResult<Vec<Utxo>> {
+        let read = self.rw_lock.read().await;
+        let ret = self.internal.get_utxos(address).await;
+        drop(read);
+        ret
+    }
+
+    async fn get_balance_at_height(
+        &mut self,
+        address: &AddressType,
+        token_type: &TokenType,
+        height: u32,
+    ) -> IndexerResult<BalanceType> {
+        let read = self.rw_lock.read().await;
+        let ret = self
+            .internal
+            .get_balance_at_height(address, token_type, height)
+            .await;
+        drop(read);
+        ret
+    }
+
+    async fn get_balance_detail(
+        &mut self,
+        address: &AddressType,
+        token_type: &TokenType,
+    ) -> IndexerResult<BalanceDetailResponse> {
+        let read = self.rw_lock.read().await;
+        let ret = self.internal.get_balance_detail(address, token_type).await;
+        drop(read);
+        ret
+    }
+
+    async fn get_transaction_delta(
+        &mut self,
+        tx_id: &TxIdType,
+    ) -> IndexerResult<Option<TransactionDeltaWrapper>> {
+        let read = self.rw_lock.read().await;
+        let ret = self.internal.get_transaction_delta(tx_id).await;
+        drop(read);
+        ret
+    }
+
+    async fn list_deltas(
+        &mut self,
+        status: DeltaStatus,
+        pagination: &Pagination,
+    ) -> IndexerResult<Vec<TransactionDeltaWrapper>> {
+        let read = self.rw_lock.read().await;
+        let ret = self.internal.list_deltas(status, pagination).await;
+        drop(read);
+        ret
+    }
+
+    async fn gc_inactive_deltas(&mut self) -> IndexerResult<u32> {
+        let mut write = self.rw_lock.write().await;
+        let ret = self.internal.gc_inactive_deltas().await?;
+        *write += 1;
+        Ok(ret)
+    }
+
+    async fn get_audit_log(
+        &mut self,
+        pagination: &Pagination,
+    ) -> IndexerResult<Vec<TransactionDeltaWrapper>> {
+        let read = self.rw_lock.read().await;
+        let ret = self.internal.get_audit_log(pagination).await;
+        drop(read);
+        ret
+    }
+
+    async fn register_token(
+        &mut self,
+        token_type: &TokenType,
+        meta: &TokenMeta,
+    ) -> IndexerResult<()> {
+        let mut write = self.rw_lock.write().await;
+        self.internal.register_token(token_type, meta).await?;
+        *write += 1;
+        Ok(())
+    }
+
+    async fn get_token_meta(&mut self, token_type: &TokenType) -> IndexerResult<Option<TokenMeta>> {
+        let read = self.rw_lock.read().await;
+        let ret = self.internal.get_token_meta(token_type).await;
+        drop(read);
+        ret
+    }
+
+    async fn add_dead_letter(&mut self, event: &[u8], error: &str) -> IndexerResult<()> {
+        let mut write = self.rw_lock.write().await;
+        self.internal.add_dead_letter(event, error).await?;
+        *write += 1;
+        Ok(())
+    }
+
+    async fn get_dead_letters(&mut self) -> IndexerResult<Vec<DeadLetter>> {
+        let read = self.rw_lock.read().await;
+        let ret = self.internal.get_dead_letters().await;
+        drop(read);
+        ret
+    }
+
+    async fn remove_dead_letter(&mut self, index: u64) -> IndexerResult<()> {
+        let mut write = self.rw_lock.write().await;
+        self.internal.remove_dead_letter(index).await?;
+        *write += 1;
+        Ok(())
+    }
+
+    async fn append_journal(&mut self, event: &[u8]) -> IndexerResult<u64> {
+        let mut write = self.rw_lock.write().await;
+        let ret = self.internal.append_journal(event).await?;
+        *write += 1;
+        Ok(ret)
+    }
+
+    async fn get_journal(
+        &mut self,
+        from_seq: u64,
+        limit: usize,
+    ) -> IndexerResult<Vec<(u64, Vec<u8>)>> {
+        let read = self.rw_lock.read().await;
+        let ret = self.internal.get_journal(from_seq, limit).await;
+        drop(read);
+        ret
+    }
+
+    async fn truncate_journal(&mut self, before_seq: u64) -> IndexerResult<()> {
+        let mut write = self.rw_lock.write().await;
+        self.internal.truncate_journal(before_seq).await?;
+        *write += 1;
+        Ok(())
+    }
+
+    async fn get_state_root(&mut self) -> IndexerResult<String> {
+        let read = self.rw_lock.read().await;
+        let ret = self.internal.get_state_root().await;
+        drop(read);
+        ret
+    }
+
+    async fn get_pending_txs(&mut self) -> IndexerResult<Vec<PendingTxResponse>> {
+        let read = self.rw_lock.read().await;
+        let ret = self.internal.get_pending_txs().await;
+        drop(read);
+        ret
+    }
+
+    async fn set_cursor(&mut self, consumer: u32, seq: u64) -> IndexerResult<()> {
+        let mut write = self.rw_lock.write().await;
+        self.internal.set_cursor(consumer, seq).await?;
+        *write += 1;
+        Ok(())
+    }
+
+    async fn get_cursor(&mut self, consumer: u32) -> IndexerResult<Option<u64>> {
+        let read = self.rw_lock.read().await;
+        let ret = self.internal.get_cursor(consumer).await;
+        drop(read);
+        ret
+    }
+
+    async fn get_raw_tx(&mut self, tx_id: &TxIdType) -> IndexerResult<Option<Transaction>> {
+        let read = self.rw_lock.read().await;
+        let ret = self.internal.get_raw_tx(tx_id).await;
+        drop(read);
+        ret
+    }
+
+    async fn hold_event(&mut self, event: &[u8]) -> IndexerResult<u64> {
+        let mut write = self.rw_lock.write().await;
+        let ret = self.internal.hold_event(event).await?;
+        *write += 1;
+        Ok(ret)
+    }
+
+    async fn get_held_events(&mut self) -> IndexerResult<Vec<(u64, Vec<u8>)>> {
+        let read = self.rw_lock.read().await;
+        let ret = self.internal.get_held_events().await;
+        drop(read);
+        ret
+    }
+
+    async fn remove_held_event(&mut self, index: u64) -> IndexerResult<()> {
+        let mut write = self.rw_lock.write().await;
+        self.internal.remove_held_event(index).await?;
+        *write += 1;
+        Ok(())
+    }
+
+    async fn set_chain_state(&mut self, state: &[u8]) -> IndexerResult<()> {
+        let mut write = self.rw_lock.write().await;
+        self.internal.set_chain_state(state).await?;
+        *write += 1;
+        Ok(())
+    }
+
+    async fn get_chain_state(&mut self) -> IndexerResult<Option<Vec<u8>>> {
+        let read = self.rw_lock.read().await;
+        let ret = self.internal.get_chain_state().await;
+        drop(read);
+        ret
+    }
+
+    async fn get_balances(
+        &mut self,
+        keys: &[(AddressType, TokenType)],
+    ) -> IndexerResult<Vec<BalanceType>> {
+        let read = self.rw_lock.read().await;
+        let ret = self.internal.get_balances(keys).await;
+        drop(read);
+        ret
+    }
+
+    async fn begin_tx(&mut self) -> IndexerResult<()> {
+        let write = self.rw_lock.write().await;
+        let ret = self.internal.begin_tx().await;
+        drop(write);
+        ret
+    }
+
+    async fn commit(&mut self) -> IndexerResult<()> {
+        let mut write = self.rw_lock.write().await;
+        self.internal.commit().await?;
+        *write += 1;
+        Ok(())
+    }
+
+    async fn rollback(&mut self) -> IndexerResult<()> {
+        let write = self.rw_lock.write().await;
+        let ret = self.internal.rollback().await;
+        drop(write);
+        ret
+    }
+
+    async fn export(&mut self, path: &str) -> IndexerResult<()> {
+        let read = self.rw_lock.write().await;
+        let ret = self.internal.export(path).await;
+        drop(read);
+        ret
+    }
+
+    async fn import(&mut self, path: &str) -> IndexerResult<()> {
+        let mut write = self.rw_lock.write().await;
+        self.internal.import(path).await?;
+        *write += 1;
+        Ok(())
+    }
 }
@@ -0,0 +1,718 @@
+use crate::error::IndexerResult;
+use crate::event::{AddressType, BalanceType, TokenType, TxIdType};
+use crate::storage::kv::TransactionDeltaWrapper;
+use crate::storage::prefix::{DeltaStatus, SeenStatus};
+use crate::storage::prune::PruneRetention;
+use crate::storage::snapshot::Snapshot;
+use crate::storage::{SeenStatusResponse, StorageProcessor, StorageStats};
+use crate::types::dead_letter::DeadLetter;
+use crate::types::delta::TransactionDelta;
+use crate::types::request::Pagination;
+use crate::types::response::{AllBalanceResponse, BalanceDetailResponse, PendingTxResponse};
+use crate::types::token::TokenMeta;
+use crate::types::transaction::Utxo;
+use bitcoincore_rpc::bitcoin::Transaction;
+use chrono::Local;
+use log::{error, info};
+use lru::LruCache;
+use std::collections::HashMap;
+use std::num::NonZeroUsize;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+
+const DEFAULT_FLUSH_THRESHOLD: usize = 1024;
+
+struct TieredCache {
+    seen: LruCache<TxIdType, SeenStatusResponse>,
+    balances: LruCache<(AddressType, TokenType), BalanceType>,
+    // only stored deltas,a miss always goes to the persistent layer
+    deltas: LruCache<TxIdType, TransactionDeltaWrapper>,
+    // announcements which only live in memory yet
+    pending_seen: Vec<(Transaction, bool)>,
+}
+
+// hot in-memory layer over a persistent storage processor:
+// seen-tx flags,balances and deltas are served from a lru cache,new seen txs are buffered and flushed
+// in batches (by threshold,before any other write,or by the background flusher)
+pub struct TieredStorageProcessor<T: StorageProcessor> {
+    internal: Arc<tokio::sync::Mutex<T>>,
+    cache: Arc<Mutex<TieredCache>>,
+    flush_threshold: usize,
+}
+
+impl<T: StorageProcessor> Clone for TieredStorageProcessor<T> {
+    fn clone(&self) -> Self {
+        Self {
+            internal: self.internal.clone(),
+            cache: self.cache.clone(),
+            flush_threshold: self.flush_threshold,
+        }
+    }
+}
+
+impl<T: StorageProcessor + 'static> TieredStorageProcessor<T> {
+    pub fn new(internal: T, capacity: usize) -> Self {
+        let capacity = NonZeroUsize::new(capacity).unwrap_or(NonZeroUsize::new(1).unwrap());
+        Self {
+            internal: Arc::new(tokio::sync::Mutex::new(internal)),
+            cache: Arc::new(Mutex::new(TieredCache {
+                seen: LruCache::new(capacity),
+                balances: LruCache::new(capacity),
+                deltas: LruCache::new(capacity),
+                pending_seen: vec![],
+            })),
+            flush_threshold: DEFAULT_FLUSH_THRESHOLD,
+        }
+    }
+
+    pub fn with_flush_threshold(mut self, flush_threshold: usize) -> Self {
+        self.flush_threshold = flush_threshold;
+        self
+    }
+
+    pub fn start_flusher(&self, interval: Duration) -> JoinHandle<()> {
+        let node = self.clone();
+        tokio::task::spawn(async move {
+            let mut interval = tokio::time::interval(interval);
+            loop {
+                interval.tick().await;
+                if let Err(e) = node.flush().await {
+                    error!("tiered storage flush error:{:?}", e);
+                }
+            }
+        })
+    }
+
+    pub async fn flush(&self) -> IndexerResult<()> {
+        let pending = {
+            let mut cache = self.cache.lock().unwrap();
+            std::mem::take(&mut cache.pending_seen)
+        };
+        if pending.is_empty() {
+            return Ok(());
+        }
+        info!("tiered storage flush {} seen txs", pending.len());
+        let mut internal = self.internal.lock().await;
+        for (tx, from_restore) in &pending {
+            internal.seen_and_store_txs(tx, *from_restore).await?;
+        }
+        Ok(())
+    }
+
+    fn on_delta_added(&self, transaction: &TransactionDelta) {
+        let mut cache = self.cache.lock().unwrap();
+        if let Some(seen) = cache.seen.get_mut(&transaction.tx_id) {
+            seen.status = SeenStatus::Executed;
+        }
+        cache.deltas.pop(&transaction.tx_id);
+        for (address, deltas) in &transaction.deltas {
+            for (token, _) in deltas {
+                cache.balances.pop(&(address.clone(), token.clone()));
+            }
+        }
+    }
+
+    fn clear_cache(&self) {
+        let mut cache = self.cache.lock().unwrap();
+        cache.seen.clear();
+        cache.balances.clear();
+        cache.deltas.clear();
+    }
+
+    // deltas removed by pruning are unknown here
+    fn clear_deltas(&self) {
+        self.cache.lock().unwrap().deltas.clear();
+    }
+}
+
+#[async_trait::async_trait]
+impl<T: StorageProcessor + 'static> StorageProcessor for TieredStorageProcessor<T> {
+    async fn get_balance(
+        &mut self,
+        address: &AddressType,
+        token_type: &TokenType,
+    ) -> IndexerResult<BalanceType> {
+        let key = (address.clone(), token_type.clone());
+        if let Some(v) = self.cache.lock().unwrap().balances.get(&key) {
+            return Ok(v.clone());
+        }
+        let ret = self
+            .internal
+            .lock()
+            .await
+            .get_balance(address, token_type)
+            .await?;
+        self.cache.lock().unwrap().balances.put(key, ret.clone());
+        Ok(ret)
+    }
+
+    async fn get_all_balance(
+        &mut self,
+        address: &AddressType,
+    ) -> IndexerResult<Vec<AllBalanceResponse>> {
+        self.internal.lock().await.get_all_balance(address).await
+    }
+
+    async fn add_transaction_delta(&mut self, transaction: &TransactionDelta) -> IndexerResult<()> {
+        self.flush().await?;
+        self.internal
+            .lock()
+            .await
+            .add_transaction_delta(transaction)
+            .await?;
+        self.on_delta_added(transaction);
+        Ok(())
+    }
+
+    async fn add_transaction_deltas(
+        &mut self,
+        transactions: &[TransactionDelta],
+    ) -> IndexerResult<()> {
+        self.flush().await?;
+        self.internal
+            .lock()
+            .await
+            .add_transaction_deltas(transactions)
+            .await?;
+        for transaction in transactions {
+            self.on_delta_added(transaction);
+        }
+        Ok(())
+    }
+
+    async fn remove_transaction_delta(
+        &mut self,
+        tx_id: &TxIdType,
+        status: DeltaStatus,
+    ) -> IndexerResult<()> {
+        self.flush().await?;
+        self.internal
+            .lock()
+            .await
+            .remove_transaction_delta(tx_id, status)
+            .await?;
+        let mut cache = self.cache.lock().unwrap();
+        cache.seen.pop(tx_id);
+        cache.deltas.pop(tx_id);
+        // the removed delta is unknown here,drop every cached balance
+        cache.balances.clear();
+        Ok(())
+    }
+
+    async fn seen_and_store_txs(
+        &mut self,
+        tx: &Transaction,
+        from_restore: bool,
+    ) -> IndexerResult<SeenStatusResponse> {
+        let tx_id: TxIdType = tx.txid().into();
+        let mut seen = self.seen_tx(tx_id.clone()).await?;
+        let flush = {
+            let mut cache = self.cache.lock().unwrap();
+            if seen.is_seen() {
+                seen.re_announced += 1;
+                cache.seen.put(tx_id, seen.clone());
+            } else {
+                seen.first_seen = Local::now().timestamp();
+                seen.from_restore = from_restore;
+                let mut cached = seen.clone();
+                cached.seen = true;
+                cache.seen.put(tx_id, cached);
+            }
+            cache.pending_seen.push((tx.clone(), from_restore));
+            cache.pending_seen.len() >= self.flush_threshold
+        };
+        if flush {
+            self.flush().await?;
+        }
+        Ok(seen)
+    }
+
+    async fn seen_tx(&mut self, tx_id: TxIdType) -> IndexerResult<SeenStatusResponse> {
+        if let Some(v) = self.cache.lock().unwrap().seen.get(&tx_id) {
+            return Ok(v.clone());
+        }
+        let ret = self.internal.lock().await.seen_tx(tx_id.clone()).await?;
+        if ret.is_seen() {
+            self.cache.lock().unwrap().seen.put(tx_id, ret.clone());
+        }
+        Ok(ret)
+    }
+
+    async fn is_tx_executed(&mut self, tx_id: &TxIdType) -> IndexerResult<bool> {
+        let ret = self.seen_tx(tx_id.clone()).await?;
+        Ok(ret.is_executed())
+    }
+
+    async fn get_all_un_consumed_txs(&mut self) -> IndexerResult<HashMap<TxIdType, i64>> {
+        self.flush().await?;
+        self.internal.lock().await.get_all_un_consumed_txs().await
+    }
+
+    async fn simple_set(
+        &mut self,
+        tx_id: &TxIdType,
+        key: &[u8],
+        value: Vec<u8>,
+    ) -> IndexerResult<()> {
+        self.internal
+            .lock()
+            .await
+            .simple_set(tx_id, key, value)
+            .await
+    }
+
+    async fn simple_get(&mut self, key: &[u8]) -> IndexerResult<Option<Vec<u8>>> {
+        self.internal.lock().await.simple_get(key).await
+    }
+
+    async fn save_height_tx(&mut self, height: u32, tx_id: TxIdType) -> IndexerResult<()> {
+        self.internal
+            .lock()
+            .await
+            .save_height_tx(height, tx_id)
+            .await
+    }
+
+    async fn remove_height_traces(&mut self, height: u32) -> IndexerResult<()> {
+        self.flush().await?;
+        self.internal
+            .lock()
+            .await
+            .remove_height_traces(height)
+            .await?;
+        self.clear_cache();
+        Ok(())
+    }
+
+    async fn remove_tx_traces(&mut self, tx_id: Vec<TxIdType>) -> IndexerResult<()> {
+        self.flush().await?;
+        self.internal.lock().await.remove_tx_traces(tx_id).await?;
+        self.clear_cache();
+        Ok(())
+    }
+
+    async fn take_snapshot(&mut self, height: u32) -> IndexerResult<Snapshot> {
+        self.flush().await?;
+        self.internal.lock().await.take_snapshot(height).await
+    }
+
+    async fn restore_snapshot(&mut self, snapshot: &Snapshot) -> IndexerResult<()> {
+        self.cache.lock().unwrap().pending_seen.clear();
+        self.internal
+            .lock()
+            .await
+            .restore_snapshot(snapshot)
+            .await?;
+        self.clear_cache();
+        Ok(())
+    }
+
+    async fn update_indexed_height(&mut self, height: u32) -> IndexerResult<()> {
+        self.internal
+            .lock()
+            .await
+            .update_indexed_height(height)
+            .await
+    }
+
+    async fn stats(&mut self) -> IndexerResult<StorageStats> {
+        self.flush().await?;
+        self.internal.lock().await.stats().await
+    }
+
+    async fn prune_deltas(&mut self, retention: &PruneRetention) -> IndexerResult<u32> {
+        let ret = self.internal.lock().await.prune_deltas(retention).await?;
+        self.clear_deltas();
+        Ok(ret)
+    }
+
+    async fn get_address_history(
+        &mut self,
+        address: &AddressType,
+        pagination: &Pagination,
+    ) -> IndexerResult<Vec<TxIdType>> {
+        self.internal
+            .lock()
+            .await
+            .get_address_history(address, pagination)
+            .await
+    }
+
+    async fn apply_utxos(
+        &mut self,
+        tx_id: &TxIdType,
+        created: Vec<Utxo>,
+        spent: Vec<Utxo>,
+    ) -> IndexerResult<()> {
+        self.flush().await?;
+        self.internal
+            .lock()
+            .await
+            .apply_utxos(tx_id, created, spent)
+            .await
+    }
+
+    async fn settle_utxos(&mut self, tx_id: &TxIdType, revert: bool) -> IndexerResult<()> {
+        self.flush().await?;
+        self.internal.lock().await.settle_utxos(tx_id, revert).await
+    }
+
+    async fn get_utxo(&mut self, tx_id: &TxIdType, vout: u32) -> IndexerResult<Option<Utxo>> {
+        self.internal.lock().await.get_utxo(tx_id, vout).await
+    }
+
+    async fn get_utxos(&mut self, address: &AddressType) -> IndexerResult<Vec<Utxo>> {
+        self.internal.lock().await.get_utxos(address).await
+    }
+
+    async fn get_balance_at_height(
+        &mut self,
+        address: &AddressType,
+        token_type: &TokenType,
+        height: u32,
+    ) -> IndexerResult<BalanceType> {
+        self.internal
+            .lock()
+            .await
+            .get_balance_at_height(address, token_type, height)
+            .await
+    }
+
+    async fn get_balance_detail(
+        &mut self,
+        address: &AddressType,
+        token_type: &TokenType,
+    ) -> IndexerResult<BalanceDetailResponse> {
+        self.internal
+            .lock()
+            .await
+            .get_balance_detail(address, token_type)
+            .await
+    }
+
+    async fn get_transaction_delta(
+        &mut self,
+        tx_id: &TxIdType,
+    ) -> IndexerResult<Option<TransactionDeltaWrapper>> {
+        if let Some(v) = self.cache.lock().unwrap().deltas.get(tx_id) {
+            return Ok(Some(v.clone()));
+        }
+        let ret = self
+            .internal
+            .lock()
+            .await
+            .get_transaction_delta(tx_id)
+            .await?;
+        if let Some(v) = &ret {
+            self.cache
+                .lock()
+                .unwrap()
+                .deltas
+                .put(tx_id.clone(), v.clone());
+        }
+        Ok(ret)
+    }
+
+    async fn list_deltas(
+        &mut self,
+        status: DeltaStatus,
+        pagination: &Pagination,
+    ) -> IndexerResult<Vec<TransactionDeltaWrapper>> {
+        self.internal
+            .lock()
+            .await
+            .list_deltas(status, pagination)
+            .await
+    }
+
+    async fn gc_inactive_deltas(&mut self) -> IndexerResult<u32> {
+        self.flush().await?;
+        let ret = self.internal.lock().await.gc_inactive_deltas().await?;
+        self.clear_deltas();
+        Ok(ret)
+    }
+
+    async fn get_audit_log(
+        &mut self,
+        pagination: &Pagination,
+    ) -> IndexerResult<Vec<TransactionDeltaWrapper>> {
+        self.internal.lock().await.get_audit_log(pagination).await
+    }
+
+    async fn register_token(
+        &mut self,
+        token_type: &TokenType,
+        meta: &TokenMeta,
+    ) -> IndexerResult<()> {
+        self.flush().await?;
+        self.internal
+            .lock()
+            .await
+            .register_token(token_type, meta)
+            .await
+    }
+
+    async fn get_token_meta(&mut self, token_type: &TokenType) -> IndexerResult<Option<TokenMeta>> {
+        self.internal.lock().await.get_token_meta(token_type).await
+    }
+
+    async fn add_dead_letter(&mut self, event: &[u8], error: &str) -> IndexerResult<()> {
+        self.flush().await?;
+        self.internal
+            .lock()
+            .await
+            .add_dead_letter(event, error)
+            .await
+    }
+
+    async fn get_dead_letters(&mut self) -> IndexerResult<Vec<DeadLetter>> {
+        self.internal.lock().await.get_dead_letters().await
+    }
+
+    async fn remove_dead_letter(&mut self, index: u64) -> IndexerResult<()> {
+        self.flush().await?;
+        self.internal.lock().await.remove_dead_letter(index).await
+    }
+
+    async fn append_journal(&mut self, event: &[u8]) -> IndexerResult<u64> {
+        self.flush().await?;
+        self.internal.lock().await.append_journal(event).await
+    }
+
+    async fn get_journal(
+        &mut self,
+        from_seq: u64,
+        limit: usize,
+    ) -> IndexerResult<Vec<(u64, Vec<u8>)>> {
+        self.internal
+            .lock()
+            .await
+            .get_journal(from_seq, limit)
+            .await
+    }
+
+    async fn truncate_journal(&mut self, before_seq: u64) -> IndexerResult<()> {
+        self.flush().await?;
+        self.internal
+            .lock()
+            .await
+            .truncate_journal(before_seq)
+            .await
+    }
+
+    async fn get_state_root(&mut self) -> IndexerResult<String> {
+        self.internal.lock().await.get_state_root().await
+    }
+
+    async fn get_pending_txs(&mut self) -> IndexerResult<Vec<PendingTxResponse>> {
+        self.flush().await?;
+        self.internal.lock().await.get_pending_txs().await
+    }
+
+    async fn set_cursor(&mut self, consumer: u32, seq: u64) -> IndexerResult<()> {
+        self.flush().await?;
+        self.internal.lock().await.set_cursor(consumer, seq).await
+    }
+
+    async fn get_cursor(&mut self, consumer: u32) -> IndexerResult<Option<u64>> {
+        self.internal.lock().await.get_cursor(consumer).await
+    }
+
+    async fn get_raw_tx(&mut self, tx_id: &TxIdType) -> IndexerResult<Option<Transaction>> {
+        self.internal.lock().await.get_raw_tx(tx_id).await
+    }
+
+    async fn hold_event(&mut self, event: &[u8]) -> IndexerResult<u64> {
+        self.flush().await?;
+        self.internal.lock().await.hold_event(event).await
+    }
+
+    async fn get_held_events(&mut self) -> IndexerResult<Vec<(u64, Vec<u8>)>> {
+        self.internal.lock().await.get_held_events().await
+    }
+
+    async fn remove_held_event(&mut self, index: u64) -> IndexerResult<()> {
+        self.flush().await?;
+        self.internal.lock().await.remove_held_event(index).await
+    }
+
+    async fn set_chain_state(&mut self, state: &[u8]) -> IndexerResult<()> {
+        self.flush().await?;
+        self.internal.lock().await.set_chain_state(state).await
+    }
+
+    async fn get_chain_state(&mut self) -> IndexerResult<Option<Vec<u8>>> {
+        self.internal.lock().await.get_chain_state().await
+    }
+
+    async fn get_balances(
+        &mut self,
+        keys: &[(AddressType, TokenType)],
+    ) -> IndexerResult<Vec<BalanceType>> {
+        let mut ret: Vec<Option<BalanceType>> = {
+            let mut cache = self.cache.lock().unwrap();
+            keys.iter()
+                .map(|k| cache.balances.get(k).cloned())
+                .collect()
+        };
+        let missing: Vec<_> = keys
+            .iter()
+            .zip(ret.iter())
+            .filter(|(_, v)| v.is_none())
+            .map(|(k, _)| k.clone())
+            .collect();
+        if missing.is_empty() {
+            return Ok(ret.into_iter().flatten().collect());
+        }
+        let mut fetched = self
+            .internal
+            .lock()
+            .await
+            .get_balances(&missing)
+            .await?
+            .into_iter();
+        let mut cache = self.cache.lock().unwrap();
+        for (key, v) in keys.iter().zip(ret.iter_mut()) {
+            if v.is_none() {
+                let balance = fetched.next().unwrap();
+                cache.balances.put(key.clone(), balance.clone());
+                *v = Some(balance);
+            }
+        }
+        Ok(ret.into_iter().flatten().collect())
+    }
+
+    async fn begin_tx(&mut self) -> IndexerResult<()> {
+        self.flush().await?;
+        self.internal.lock().await.begin_tx().await
+    }
+
+    async fn commit(&mut self) -> IndexerResult<()> {
+        self.internal.lock().await.commit().await
+    }
+
+    async fn rollback(&mut self) -> IndexerResult<()> {
+        self.internal.lock().await.rollback().await?;
+        // cached values may come from the discarded writes
+        self.clear_cache();
+        Ok(())
+    }
+
+    async fn export(&mut self, path: &str) -> IndexerResult<()> {
+        self.flush().await?;
+        self.internal.lock().await.export(path).await
+    }
+
+    async fn import(&mut self, path: &str) -> IndexerResult<()> {
+        self.cache.lock().unwrap().pending_seen.clear();
+        self.internal.lock().await.import(path).await?;
+        self.clear_cache();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::db::memory::MemoryDB;
+    use crate::storage::db::thread_safe::ThreadSafeDB;
+    use crate::storage::kv::KVStorageProcessor;
+    use bitcoincore_rpc::bitcoin::absolute::LockTime;
+
+    #[tokio::test]
+    pub async fn test_tiered_seen_flush() {
+        let db = ThreadSafeDB::new(MemoryDB::default());
+        let mut persistent = KVStorageProcessor::new(db.clone());
+        let mut storage =
+            TieredStorageProcessor::new(KVStorageProcessor::new(db), 16).with_flush_threshold(10);
+        let tx = Transaction {
+            version: 1,
+            lock_time: LockTime::ZERO,
+            input: vec![],
+            output: vec![],
+        };
+        let tx_id: TxIdType = tx.txid().into();
+
+        assert!(!storage
+            .seen_and_store_txs(&tx, true)
+            .await
+            .unwrap()
+            .is_seen());
+        let seen = storage.seen_and_store_txs(&tx, false).await.unwrap();
+        assert!(seen.is_seen());
+        assert_eq!(seen.re_announced(), 1);
+        assert!(seen.is_from_restore());
+        assert!(!persistent.seen_tx(tx_id.clone()).await.unwrap().is_seen());
+
+        storage.flush().await.unwrap();
+        let seen = persistent.seen_tx(tx_id).await.unwrap();
+        assert!(seen.is_seen());
+        assert_eq!(seen.re_announced(), 1);
+        assert!(seen.is_from_restore());
+    }
+
+    #[tokio::test]
+    pub async fn test_tiered_delta_cache() {
+        let db = ThreadSafeDB::new(MemoryDB::default());
+        let mut storage = TieredStorageProcessor::new(KVStorageProcessor::new(db), 16);
+        let tx_id = TxIdType::from_bytes(&[0u8; 32]);
+        let mut deltas = HashMap::default();
+        deltas.insert(
+            AddressType::from_bytes(&[0u8; 20]),
+            vec![(TokenType::from_bytes(&[0u8; 20]), BalanceType::from(1))],
+        );
+        let delta = TransactionDelta {
+            tx_id: tx_id.clone(),
+            deltas,
+        };
+        assert!(storage
+            .get_transaction_delta(&tx_id)
+            .await
+            .unwrap()
+            .is_none());
+
+        storage.add_transaction_delta(&delta).await.unwrap();
+        let cached = storage
+            .get_transaction_delta(&tx_id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(cached.status, DeltaStatus::Executed.to_u8());
+        assert!(storage.cache.lock().unwrap().deltas.contains(&tx_id));
+
+        storage
+            .remove_transaction_delta(&tx_id, DeltaStatus::Confirmed)
+            .await
+            .unwrap();
+        let settled = storage
+            .get_transaction_delta(&tx_id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(settled.status, DeltaStatus::Confirmed.to_u8());
+
+        storage
+            .get_balance(
+                &AddressType::from_bytes(&[0u8; 20]),
+                &TokenType::from_bytes(&[0u8; 20]),
+            )
+            .await
+            .unwrap();
+        assert!(!storage.cache.lock().unwrap().balances.is_empty());
+
+        // the traces go,the settled delta stays. what the inner storage removed along with them
+        // is unknown,so no cached entry survives
+        storage.remove_tx_traces(vec![tx_id.clone()]).await.unwrap();
+        {
+            let cache = storage.cache.lock().unwrap();
+            assert!(cache.seen.is_empty() && cache.balances.is_empty() && cache.deltas.is_empty());
+        }
+        let settled = storage
+            .get_transaction_delta(&tx_id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(settled.status, DeltaStatus::Confirmed.to_u8());
+    }
+}
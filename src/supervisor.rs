@@ -0,0 +1,149 @@
+use crate::configuration::base::RestartPolicy;
+use crate::error::IndexerResult;
+use crate::Event;
+use async_channel::Sender;
+use log::{error, info};
+use std::future::Future;
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+
+// delay before the restart after attempt failures in a row,none when it is not restarted
+pub fn restart_delay(policy: &RestartPolicy, attempt: u32) -> Option<Duration> {
+    match policy {
+        RestartPolicy::Never => None,
+        RestartPolicy::Always => Some(Duration::ZERO),
+        RestartPolicy::Backoff {
+            base_delay_ms,
+            max_delay_ms,
+        } => Some(Duration::from_millis(
+            base_delay_ms
+                .saturating_mul(1 << attempt.min(32))
+                .min(*max_delay_ms),
+        )),
+    }
+}
+
+// runs the task until it returns ok or the exit signal,failed or panicked runs are reported
+// and restarted as the policy says
+pub(crate) fn supervise<E, F, Fut>(
+    name: String,
+    policy: RestartPolicy,
+    report: Option<Sender<E>>,
+    mut exit: watch::Receiver<()>,
+    mut run: F,
+) -> JoinHandle<()>
+where
+    E: Event + 'static,
+    F: FnMut() -> Fut + Send + 'static,
+    Fut: Future<Output = IndexerResult<()>> + Send + 'static,
+{
+    tokio::task::spawn(async move {
+        let mut attempt = 0;
+        loop {
+            let reason = match tokio::task::spawn(run()).await {
+                Ok(Ok(())) => return,
+                Ok(Err(e)) => e.to_string(),
+                Err(e) if e.is_panic() => format!("panicked:{:?}", e),
+                Err(_) => return,
+            };
+            error!("component {} failed:{}", name, reason);
+            if let (Some(report), Some(event)) = (&report, E::component_failed(&name, &reason)) {
+                let _ = report.send(event).await;
+            }
+            let Some(delay) = restart_delay(&policy, attempt) else {
+                return;
+            };
+            tokio::select! {
+                _ = exit.changed() => return,
+                _ = tokio::time::sleep(delay) => {}
+            }
+            attempt += 1;
+            info!("restart component {},attempt:{}", name, attempt);
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::IndexerError;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::Arc;
+
+    #[derive(Debug)]
+    struct Failed(String);
+
+    impl Event for Failed {
+        fn component_failed(component: &str, reason: &str) -> Option<Self> {
+            Some(Failed(format!("{}:{}", component, reason)))
+        }
+    }
+
+    #[test]
+    pub fn test_restart_delay() {
+        let policy = RestartPolicy::Backoff {
+            base_delay_ms: 100,
+            max_delay_ms: 1000,
+        };
+        assert_eq!(restart_delay(&policy, 0), Some(Duration::from_millis(100)));
+        assert_eq!(restart_delay(&policy, 3), Some(Duration::from_millis(800)));
+        assert_eq!(
+            restart_delay(&policy, 40),
+            Some(Duration::from_millis(1000))
+        );
+        assert_eq!(restart_delay(&RestartPolicy::Never, 0), None);
+        assert_eq!(
+            restart_delay(&RestartPolicy::Always, 5),
+            Some(Duration::ZERO)
+        );
+    }
+
+    #[tokio::test]
+    pub async fn test_supervise() {
+        let (_exit_tx, exit) = watch::channel(());
+        let (tx, rx) = async_channel::unbounded();
+        let runs = Arc::new(AtomicU32::new(0));
+        let counter = runs.clone();
+        // panics,fails,then stops
+        supervise(
+            "test".to_string(),
+            RestartPolicy::Always,
+            Some(tx.clone()),
+            exit.clone(),
+            move || {
+                let run = counter.fetch_add(1, Ordering::SeqCst);
+                async move {
+                    match run {
+                        0 => panic!("bad message"),
+                        1 => Err(IndexerError::ChannelClosed),
+                        _ => Ok(()),
+                    }
+                }
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(runs.load(Ordering::SeqCst), 3);
+        let Failed(first) = rx.recv().await.unwrap();
+        assert!(first.starts_with("test:panicked"));
+        let Failed(second) = rx.recv().await.unwrap();
+        assert_eq!(second, "test:channel closed");
+
+        runs.store(0, Ordering::SeqCst);
+        let counter = runs.clone();
+        supervise::<Failed, _, _>(
+            "test".to_string(),
+            RestartPolicy::Never,
+            None,
+            exit,
+            move || {
+                counter.fetch_add(1, Ordering::SeqCst);
+                async { Err(IndexerError::ChannelClosed) }
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+    }
+}
@@ -0,0 +1,11 @@
+use serde::{Deserialize, Serialize};
+
+// an indexer event that failed to be handled,kept until it is replayed
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DeadLetter {
+    pub index: u64,
+    // IndexerEvent::to_bytes
+    pub event: Vec<u8>,
+    pub error: String,
+    pub timestamp: i64,
+}
@@ -1,4 +1,6 @@
+pub mod dead_letter;
 pub mod delta;
 pub mod request;
 pub mod response;
+pub mod token;
 pub mod transaction;
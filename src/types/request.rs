@@ -1 +1,65 @@
+use crate::event::TxIdType;
+use bitcoincore_rpc::bitcoin::BlockHash;
+use serde::{Deserialize, Serialize};
 
+#[derive(Clone, Debug, PartialEq)]
+pub struct Pagination {
+    pub offset: usize,
+    pub limit: usize,
+}
+
+impl Default for Pagination {
+    fn default() -> Self {
+        Self {
+            offset: 0,
+            limit: 100,
+        }
+    }
+}
+
+// a block on the best chain by its height,or any block by its hash
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BlockId {
+    Height(u32),
+    Hash(BlockHash),
+}
+
+impl From<u32> for BlockId {
+    fn from(value: u32) -> Self {
+        BlockId::Height(value)
+    }
+}
+
+impl From<BlockHash> for BlockId {
+    fn from(value: BlockHash) -> Self {
+        BlockId::Hash(value)
+    }
+}
+
+// a block by its height and hash
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BlockRef {
+    pub height: u32,
+    pub hash: BlockHash,
+}
+
+// reported by the executor once it rolled its own state back to the fork
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ReorgReport {
+    pub old_tip: BlockRef,
+    pub new_tip: BlockRef,
+    pub disconnected_blocks: Vec<BlockRef>,
+    // txs of the disconnected blocks whose deltas the executor reverted
+    pub affected_txs: Vec<TxIdType>,
+}
+
+impl ReorgReport {
+    // lowest height that was replaced
+    pub fn fork_height(&self) -> u32 {
+        self.disconnected_blocks
+            .iter()
+            .map(|v| v.height)
+            .min()
+            .unwrap_or(self.new_tip.height.min(self.old_tip.height))
+    }
+}
@@ -1,4 +1,5 @@
-use crate::event::{BalanceType, TokenType};
+use crate::event::{AddressType, BalanceType, TokenType, TxIdType};
+use crate::storage::prefix::DeltaStatus;
 use serde::{Deserialize, Serialize};
 
 #[derive(Clone, Debug)]
@@ -30,3 +31,77 @@ pub struct AllBalanceResponse {
     pub balance: BalanceType,
     pub token: TokenType,
 }
+
+// confirmed: deltas of txs that were mined,pending: deltas of mempool txs
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct BalanceDetailResponse {
+    pub confirmed: BalanceType,
+    pub pending: BalanceType,
+}
+
+// a seen tx the indexer still tracks,the delta status is none until the client pushes its delta
+#[derive(Debug, Clone, PartialEq)]
+pub struct PendingTxResponse {
+    pub tx_id: TxIdType,
+    pub first_seen: i64,
+    pub executed: bool,
+    pub delta_status: Option<DeltaStatus>,
+    pub re_announced: u32,
+    pub from_restore: bool,
+}
+
+// chain_height is none when bitcoind can not be reached,indexed_height before the first block
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct StatusResponse {
+    pub chain_height: Option<u32>,
+    pub indexed_height: Option<u32>,
+    pub restoring: bool,
+    pub paused: bool,
+    // events waiting for the processor,and events the clients did not take yet
+    pub pending_events: usize,
+    pub undelivered_events: usize,
+    pub zmq_connected: bool,
+    // the sections the processor runs with,see ProcessorConfiguration::redacted
+    pub configuration: serde_json::Value,
+}
+
+impl StatusResponse {
+    pub fn is_caught_up(&self) -> bool {
+        !self.restoring && self.chain_height.is_some() && self.indexed_height >= self.chain_height
+    }
+}
+
+// balances after a simulated delta,violations are the ones that would go negative
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SimulationResult {
+    pub balances: Vec<(AddressType, TokenType, BalanceType)>,
+    pub violations: Vec<(AddressType, TokenType, BalanceType)>,
+}
+
+impl SimulationResult {
+    pub fn is_valid(&self) -> bool {
+        self.violations.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_status_caught_up() {
+        let mut status = StatusResponse {
+            chain_height: Some(10),
+            indexed_height: Some(9),
+            ..Default::default()
+        };
+        assert!(!status.is_caught_up());
+        status.indexed_height = Some(10);
+        assert!(status.is_caught_up());
+        status.restoring = true;
+        assert!(!status.is_caught_up());
+        status.restoring = false;
+        status.chain_height = None;
+        assert!(!status.is_caught_up());
+    }
+}
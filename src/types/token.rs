@@ -0,0 +1,10 @@
+use crate::event::TxIdType;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct TokenMeta {
+    pub decimals: u8,
+    // e.g. brc20,runes
+    pub protocol: String,
+    pub deploy_tx_id: TxIdType,
+}
@@ -1,2 +1,192 @@
+use crate::error::{IndexerError, IndexerResult};
+use crate::event::{AddressType, TxIdType};
+use bitcoincore_rpc::bitcoin::consensus::{deserialize_partial, serialize};
+use bitcoincore_rpc::bitcoin::{Transaction, Txid};
+use serde::{Deserialize, Serialize};
+
 #[derive(Clone, Debug)]
 pub struct BitCoinTransaction {}
+
+// what the node knows of a tx in its mempool
+#[derive(Clone, Debug, PartialEq)]
+pub struct MempoolEntry {
+    pub vsize: u64,
+    // sats
+    pub fee: u64,
+    // unix timestamp
+    pub time: u64,
+}
+
+// a tx of the mempool listing,depends are its parents still in the mempool
+#[derive(Clone, Debug, PartialEq)]
+pub struct MempoolListing {
+    pub txid: Txid,
+    // unix timestamp
+    pub time: u64,
+    pub depends: Vec<Txid>,
+}
+
+// a dispatched tx with its mempool entry,fee and entry time are unknown for txs
+// which never reached the mempool
+#[derive(Clone, Debug, PartialEq)]
+pub struct MempoolTransaction {
+    pub tx: Transaction,
+    pub vsize: u64,
+    // sats
+    pub fee: Option<u64>,
+    // unix timestamp
+    pub entry_time: Option<u64>,
+}
+
+impl MempoolTransaction {
+    pub fn new(tx: Transaction) -> Self {
+        Self {
+            vsize: tx.vsize() as u64,
+            tx,
+            fee: None,
+            entry_time: None,
+        }
+    }
+
+    pub fn with_entry(tx: Transaction, entry: Option<MempoolEntry>) -> Self {
+        let mut ret = Self::new(tx);
+        if let Some(entry) = entry {
+            ret.vsize = entry.vsize;
+            ret.fee = Some(entry.fee);
+            ret.entry_time = Some(entry.time);
+        }
+        ret
+    }
+
+    // sat/vB
+    pub fn fee_rate(&self) -> Option<f64> {
+        if self.vsize == 0 {
+            return None;
+        }
+        self.fee.map(|fee| fee as f64 / self.vsize as f64)
+    }
+
+    // tx|vsize(le)|fee(le,u64::MAX when unknown)|entry_time(le,0 when unknown)
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut ret = serialize(&self.tx);
+        ret.extend_from_slice(&self.vsize.to_le_bytes());
+        ret.extend_from_slice(&self.fee.unwrap_or(u64::MAX).to_le_bytes());
+        ret.extend_from_slice(&self.entry_time.unwrap_or_default().to_le_bytes());
+        ret
+    }
+
+    pub fn from_bytes(data: &[u8]) -> IndexerResult<Self> {
+        let (tx, l): (Transaction, usize) = deserialize_partial(data)?;
+        let meta = &data[l..];
+        if meta.len() != 24 {
+            return Err(IndexerError::CodecError(format!(
+                "invalid transaction meta length:{}",
+                meta.len()
+            )));
+        }
+        let read = |i: usize| u64::from_le_bytes(meta[i * 8..(i + 1) * 8].try_into().unwrap());
+        Ok(Self {
+            tx,
+            vsize: read(0),
+            fee: Some(read(1)).filter(|v| *v != u64::MAX),
+            entry_time: Some(read(2)).filter(|v| *v != 0),
+        })
+    }
+}
+
+// an output owned by address (its script pubkey),value in sats
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Utxo {
+    pub tx_id: TxIdType,
+    pub vout: u32,
+    pub address: AddressType,
+    pub value: u64,
+}
+
+// a tx with the outputs its inputs spend,coinbase inputs have none
+#[derive(Clone, Debug, PartialEq)]
+pub struct RichTransaction {
+    pub tx: MempoolTransaction,
+    pub prevouts: Vec<Utxo>,
+}
+
+impl RichTransaction {
+    // tx length(le)|tx|json prevouts
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let tx = self.tx.to_bytes();
+        let mut ret = (tx.len() as u32).to_le_bytes().to_vec();
+        ret.extend_from_slice(&tx);
+        ret.extend_from_slice(&serde_json::to_vec(&self.prevouts).unwrap());
+        ret
+    }
+
+    pub fn from_bytes(data: &[u8]) -> IndexerResult<Self> {
+        if data.len() < 4 {
+            return Err(IndexerError::CodecError(
+                "invalid rich transaction".to_string(),
+            ));
+        }
+        let l = u32::from_le_bytes(data[..4].try_into().unwrap()) as usize;
+        if data.len() < 4 + l {
+            return Err(IndexerError::CodecError(
+                "invalid rich transaction".to_string(),
+            ));
+        }
+        Ok(Self {
+            tx: MempoolTransaction::from_bytes(&data[4..4 + l])?,
+            prevouts: serde_json::from_slice(&data[4 + l..])?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoincore_rpc::bitcoin::absolute::LockTime;
+
+    #[test]
+    pub fn test_mempool_transaction_bytes() {
+        let tx = Transaction {
+            version: 1,
+            lock_time: LockTime::ZERO,
+            input: vec![],
+            output: vec![],
+        };
+        let mut data = MempoolTransaction::new(tx);
+        data.vsize = 200;
+        assert_eq!(data.fee_rate(), None);
+        assert_eq!(
+            MempoolTransaction::from_bytes(&data.to_bytes()).unwrap(),
+            data
+        );
+
+        data.fee = Some(1000);
+        data.entry_time = Some(1700000000);
+        assert_eq!(data.fee_rate(), Some(5.0));
+        assert_eq!(
+            MempoolTransaction::from_bytes(&data.to_bytes()).unwrap(),
+            data
+        );
+    }
+
+    #[test]
+    pub fn test_rich_transaction_bytes() {
+        let tx = Transaction {
+            version: 1,
+            lock_time: LockTime::ZERO,
+            input: vec![],
+            output: vec![],
+        };
+        let data = RichTransaction {
+            tx: MempoolTransaction::new(tx),
+            prevouts: vec![Utxo {
+                tx_id: TxIdType::from_bytes(&[1u8; 32]),
+                vout: 2,
+                address: AddressType::from_bytes(&[3u8; 22]),
+                value: 1000,
+            }],
+        };
+        assert_eq!(RichTransaction::from_bytes(&data.to_bytes()).unwrap(), data);
+        assert!(RichTransaction::from_bytes(&[1, 0]).is_err());
+    }
+}